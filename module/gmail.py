@@ -9,32 +9,253 @@ CHANGELOG:
 01/13/2026 - Created PyO3-compatible module for warm connections (Claude)
 """
 
+import argparse
 import base64
+import datetime
+import json
+import logging
 import mimetypes
+import os
 import pickle
+import sys
+import threading
+import time
 from email import encoders
 from email.mime.base import MIMEBase
 from email.mime.multipart import MIMEMultipart
 from email.mime.text import MIMEText
+from email.utils import make_msgid
 from pathlib import Path
-from typing import Dict, Any, List
+from typing import Dict, Any, List, Optional, Tuple
 
+from google.auth.exceptions import RefreshError
 from google.auth.transport.requests import Request
 from google.oauth2.credentials import Credentials
 from google_auth_oauthlib.flow import InstalledAppFlow
 from googleapiclient.discovery import build
 
+# Make the sibling gmail_lib package importable when loaded by the daemon
+sys.path.insert(0, str(Path(__file__).resolve().parent))
+
+from gmail_lib.accounts import DEFAULT_ACCOUNT, Account, AccountRegistry, AuthExpired, signin_hint  # noqa: E402
+from gmail_lib.audit import DEFAULT_TAIL as DEFAULT_AUDIT_TAIL  # noqa: E402
+from gmail_lib.audit import AuditLog  # noqa: E402
+from gmail_lib.auth_monitor import DEFAULT_INTERVAL_SECS as DEFAULT_AUTH_CHECK_INTERVAL_SECS  # noqa: E402
+from gmail_lib.auth_monitor import AuthMonitor, AuthRequired  # noqa: E402
+from gmail_lib.backend import (  # noqa: E402
+    ApiBackend,
+    NotFound,
+    ReplayBackend,
+    authorized_http_factory,
+    env_flag,
+    http_status,
+    open_recording,
+)
+from gmail_lib.cache import DEFAULT_MAX_ENTRIES, DEFAULT_TTL_SECS, MISSING, ResultCache  # noqa: E402
+from gmail_lib.concurrency import DEFAULT_DRAIN_SECS, CallSlots, DaemonBusy, InFlight  # noqa: E402
+from gmail_lib.contacts import ContactBook  # noqa: E402
+from gmail_lib.device_auth import DeviceAuthError, DeviceLogin, OAuthClient  # noqa: E402
+from gmail_lib.filters import ACTION_FIELDS, CRITERIA_FIELDS, build_filter, describe_filter  # noqa: E402
+from gmail_lib.forward import forward_attachments, forward_bodies, forward_subject  # noqa: E402
+from gmail_lib.grouping import DEFAULT_MAX_PER_GROUP, GROUP_BY, group_summaries  # noqa: E402
+from gmail_lib.health import DEGRADED, DISABLED, FAILED, OK, HealthReport, SubsystemHealth  # noqa: E402
+from gmail_lib.history import (  # noqa: E402
+    FullSyncRequired,
+    compact_changes,
+    history_changes,
+    history_id_param,
+    history_types_param,
+    push_status,
+    validate_watch,
+)
+from gmail_lib.idempotency import IdempotencyStore, fingerprint  # noqa: E402
+from gmail_lib.idempotency import parse_key as parse_idempotency_key  # noqa: E402
+from gmail_lib.markdown import to_html_document  # noqa: E402
+from gmail_lib.mime import (  # noqa: E402
+    attachment_metadata,
+    decode_raw,
+    extract_content,
+    extract_email_content,
+    header_map,
+    parse_eml,
+    parse_raw,
+    payload_from_email,
+    sniff_mime_type,
+)
+from gmail_lib.metrics import Metrics  # noqa: E402
+from gmail_lib.outbox import Outbox, queueable  # noqa: E402
+from gmail_lib.prefetch import DEFAULT_METHODS as DEFAULT_PREFETCH_METHODS  # noqa: E402
+from gmail_lib.prefetch import DEFAULT_PAGES as DEFAULT_PREFETCH_PAGES  # noqa: E402
+from gmail_lib.prefetch import Prefetcher, Skip  # noqa: E402
+from gmail_lib.policy import CallTimeouts, RetryPolicy  # noqa: E402
+from gmail_lib.ratelimit import RateLimited, RateLimiter  # noqa: E402
+from gmail_lib.params import (  # noqa: E402
+    DEFAULT_LIMIT,
+    DEFAULT_MAX_LIMIT,
+    SEARCH_PARAMS,
+    age_terms,
+    compose_query,
+    parse_char_limit,
+    parse_limit,
+)
+from gmail_lib.responses import (  # noqa: E402
+    ResponseTooLarge,
+    charge,
+    check_response_size,
+    max_response_bytes,
+    response_budget,
+)
+from gmail_lib.settings import build_vacation, describe_send_as, describe_vacation, resolve_from  # noqa: E402
+from gmail_lib.restricted import detect_restriction, restriction_note  # noqa: E402
+from gmail_lib.spans import call_span, configure_logging  # noqa: E402
+from gmail_lib.tokens import token_lock, write_token  # noqa: E402
+from gmail_lib.types import (  # noqa: E402
+    SUMMARY_HEADERS,
+    THREAD_HEADERS,
+    Draft,
+    EmailSummary,
+    Profile,
+    SendResult,
+    Thread,
+    UnexpectedOutput,
+    truncate_text,
+)
+from gmail_lib.watch import DEFAULT_INTERVAL_SECS, CommandSink, QueueSink, Watch, WebhookSink  # noqa: E402
+
+log = logging.getLogger("fgp_gmail")
+
 # Gmail API scopes
 SCOPES = [
     'https://www.googleapis.com/auth/gmail.readonly',
     'https://www.googleapis.com/auth/gmail.send',
-    'https://www.googleapis.com/auth/gmail.modify'
+    'https://www.googleapis.com/auth/gmail.modify',
+    'https://www.googleapis.com/auth/gmail.settings.basic'
 ]
 
 # Auth paths
 FGP_AUTH_DIR = Path.home() / ".fgp" / "auth" / "google"
 LEGACY_AUTH_DIR = Path.home() / ".wolfie-gateway" / "auth" / "google"
 
+# Optional param accepted by every account-scoped method
+ACCOUNT_PARAM = {
+    "name": "account",
+    "type": "string",
+    "required": False,
+    "description": "Account name (defaults to the configured default account)"
+}
+
+# Methods that honor `dry_run`: everything that writes to the mailbox except
+# send_queued, whose work depends on the outbox rather than its params
+DRY_RUN_METHODS = frozenset({
+    "gmail.send", "gmail.forward", "gmail.create_draft", "gmail.send_draft", "gmail.filter_create",
+    "gmail.filter_delete", "gmail.bulk_modify", "gmail.archive", "gmail.unarchive", "gmail.import_raw",
+    "gmail.vacation_set",
+})
+
+# Methods that don't operate on a single account
+ACCOUNTLESS_METHODS = frozenset({
+    "gmail.accounts", "gmail.audit_tail", "gmail.batch", "gmail.config", "gmail.drain", "gmail.health",
+    "gmail.outbox", "gmail.send_queued", "gmail.stats",
+})
+
+# Daemon-control methods gmail.batch refuses to run: drain would wait on the
+# batch running it and then stop the daemon's workers from inside a call.
+# Nested batches are refused separately.
+UNBATCHABLE_METHODS = frozenset({
+    "gmail.audit_tail", "gmail.config", "gmail.drain", "gmail.stats",
+})
+
+# Methods that must keep working while an account's token is broken
+AUTH_METHODS = frozenset({
+    "gmail.auth_status", "gmail.auth_login", "gmail.auth_login_status", "gmail.auth_login_cancel",
+})
+
+# Methods left out of call metrics: reading stats shouldn't skew them, and a
+# batch's operations are recorded individually
+UNMETERED_METHODS = frozenset({"gmail.audit_tail", "gmail.batch", "gmail.drain", "gmail.stats"})
+
+# Read-only methods whose results are cached
+CACHEABLE_METHODS = frozenset({
+    "gmail.inbox", "gmail.unread", "gmail.search", "gmail.thread", "gmail.message",
+})
+
+# Methods that change mailbox state, and the cached methods they make stale.
+# Any of them can change labels, and so what a search matches, so a write
+# drops every cached result for its account. Queued sends are accountless
+# and invalidate in _outbox_send instead
+MUTATING_METHODS = frozenset({
+    "gmail.send", "gmail.forward", "gmail.create_draft", "gmail.send_draft", "gmail.filter_create",
+    "gmail.filter_delete", "gmail.bulk_modify", "gmail.archive", "gmail.unarchive", "gmail.import_raw",
+})
+INVALIDATED_BY_WRITES = CACHEABLE_METHODS
+
+# Upper bound on the health-check API probe so a hung API can't wedge health
+DEFAULT_PROBE_TIMEOUT_SECS = 3.0
+
+FRESH_PARAM = {
+    "name": "fresh",
+    "type": "boolean",
+    "required": False,
+    "default": False,
+    "description": "Bypass the result cache for this call"
+}
+
+DRY_RUN_PARAM = {
+    "name": "dry_run",
+    "type": "boolean",
+    "required": False,
+    "default": False,
+    "description": "Validate everything and return the Gmail API calls this would make (would_call) without making them"
+}
+
+PAGE_TOKEN_PARAM = {
+    "name": "page_token",
+    "type": "string",
+    "required": False,
+    "description": "next_page_token from the previous page"
+}
+
+# gmail.bulk_modify actions, their label changes, and its safety limits
+BULK_ACTIONS = {
+    "archive": ([], ["INBOX"]),
+    "mark_read": ([], ["UNREAD"]),
+    "trash": None,
+    "add_label": None,
+    "remove_label": None,
+}
+BULK_DEFAULT_MAX_MESSAGES = 100
+BULK_HARD_MAX_MESSAGES = 1000
+BULK_BATCH_SIZE = 100
+BULK_DEFAULT_TIMEOUT_SECS = 120
+BULK_SAMPLE_SIZE = 10
+
+# Most operations one gmail.batch call may carry
+BATCH_MAX_OPERATIONS = 50
+
+# Most messages one gmail.archive/gmail.unarchive call may name
+ARCHIVE_MAX_MESSAGES = 100
+
+# gmail.export_raw returns content inline only up to this size
+EXPORT_INLINE_MAX_BYTES = 1024 * 1024
+# Gmail's limit for messages.import (35 MB)
+IMPORT_MAX_BYTES = 36_700_160
+
+# Formats accepted by gmail.message
+MESSAGE_FORMATS = ('full', 'metadata', 'raw')
+
+# Service state directory
+SERVICE_DIR = Path.home() / ".fgp" / "services" / "gmail"
+RECORDINGS_DIR = SERVICE_DIR / "recordings"
+EVENTS_DIR = SERVICE_DIR / "events"
+OUTBOX_DIR = SERVICE_DIR / "outbox"
+EXPORTS_DIR = SERVICE_DIR / "exports"
+AUDIT_LOG = SERVICE_DIR / "audit.log"
+IDEMPOTENCY_FILE = SERVICE_DIR / "idempotency.json"
+CONTACTS_FILE = SERVICE_DIR / "contacts.json"
+
+# Consecutive failed polls before a watch reports itself degraded
+WATCH_DEGRADED_AFTER = 3
+
 
 class GmailModule:
     """Gmail service module following FGP PyO3 interface."""
@@ -43,52 +264,173 @@ class GmailModule:
     name = "gmail"
     version = "1.0.0"
 
-    def __init__(self):
-        """Initialize Gmail service - this runs ONCE at daemon startup."""
-        self.service = None
-        self._init_service()
-
-    def _get_credentials(self) -> Credentials:
-        """Get OAuth2 credentials, refreshing if needed."""
-        creds = None
+    def __init__(self, backend=None, accounts: AccountRegistry = None):
+        """Initialize Gmail service - this runs ONCE at daemon startup.
 
-        # Try FGP auth first
-        token_file = FGP_AUTH_DIR / "gmail_token.pickle"
-        credentials_file = FGP_AUTH_DIR / "credentials.json"
-
-        # Fallback to legacy
-        if not credentials_file.exists():
-            token_file = LEGACY_AUTH_DIR / "gmail_token.pickle"
-            credentials_file = LEGACY_AUTH_DIR / "credentials.json"
-
-        # Try to load existing token
-        if token_file.exists():
-            with open(token_file, 'rb') as f:
-                creds = pickle.load(f)
+        The daemon constructs the module with no arguments; tests pass a
+        `backend` directly to skip OAuth and API discovery. A backend passed
+        here (or the replay backend) serves every account.
+        """
+        configure_logging(os.environ.get("FGP_GMAIL_LOG_FILTER"))
+        self.accounts = accounts or AccountRegistry(
+            FGP_AUTH_DIR,
+            LEGACY_AUTH_DIR,
+            default_account=os.environ.get("FGP_GMAIL_DEFAULT_ACCOUNT") or None,
+        )
+        self.backend = backend
+        self.cache = ResultCache(
+            ttl=float(os.environ.get("FGP_GMAIL_CACHE_TTL", DEFAULT_TTL_SECS)),
+            max_entries=int(os.environ.get("FGP_GMAIL_CACHE_MAX_ENTRIES", DEFAULT_MAX_ENTRIES)),
+        )
+        self.metrics = Metrics()
+        self.audit = AuditLog.from_env(os.environ, AUDIT_LOG)
+        self.idempotency = IdempotencyStore.from_env(os.environ, IDEMPOTENCY_FILE)
+        self.contacts = ContactBook.from_env(os.environ, CONTACTS_FILE)
+        self.prefetch = Prefetcher(
+            enabled=env_flag(os.environ.get("FGP_GMAIL_PREFETCH")) and self.cache.enabled,
+            pages=int(os.environ.get("FGP_GMAIL_PREFETCH_PAGES", DEFAULT_PREFETCH_PAGES)),
+            methods=os.environ.get("FGP_GMAIL_PREFETCH_METHODS", ",".join(DEFAULT_PREFETCH_METHODS)).split(","),
+            gate=self._prefetch_gate,
+        )
+        self._account_backends: Dict[str, Any] = {}
+        self._backend_lock = threading.Lock()
+        self._local = threading.local()
+        self.max_limit = int(os.environ.get("FGP_GMAIL_MAX_LIMIT", DEFAULT_MAX_LIMIT))
+        self.max_response_bytes = max_response_bytes(os.environ)
+        self.probe_timeout = float(os.environ.get("FGP_GMAIL_PROBE_TIMEOUT", DEFAULT_PROBE_TIMEOUT_SECS))
+        self.retry = RetryPolicy.from_env(os.environ)
+        self.rate_limiter = RateLimiter.from_env(os.environ)
+        self.call_slots = CallSlots.from_env(os.environ)
+        self.in_flight = InFlight()
+        self.auth_monitor = AuthMonitor(
+            check_token, self.accounts.discover,
+            interval=float(os.environ.get("FGP_GMAIL_AUTH_CHECK_INTERVAL", DEFAULT_AUTH_CHECK_INTERVAL_SECS)),
+        )
+        self.timeouts = CallTimeouts.from_env(os.environ, [m["name"] for m in self.method_list()])
+        self._probe: Optional[Tuple[threading.Thread, Dict[str, Any]]] = None
+        self._probe_lock = threading.Lock()
+        self._logins: Dict[str, DeviceLogin] = {}
+        self._login_lock = threading.Lock()
+        self._watches: Dict[str, Watch] = {}
+        # account -> push watch registered through gmail.watch's `topic`
+        self._push_watches: Dict[str, Dict[str, Any]] = {}
+        self._watch_lock = threading.Lock()
+        self.outbox_dir = Path(os.environ.get("FGP_GMAIL_OUTBOX_DIR") or OUTBOX_DIR)
+        # gmail.get_attachment only writes below this directory
+        self.attachments_root = Path(os.environ.get("FGP_GMAIL_ATTACHMENTS_ROOT") or Path.home()).expanduser()
+        self._outbox: Optional[Outbox] = None
+        self._outbox_lock = threading.Lock()
+        if self.backend is None:
+            self._init_service()
+
+    def _get_credentials(self, account: Account) -> Credentials:
+        """Get OAuth2 credentials for an account, refreshing if needed.
+
+        Raises AuthExpired instead of starting an interactive sign-in.
+        """
+        token_file = account.token_file
+        creds = load_credentials(account)
 
-        # Refresh or get new credentials
-        if not creds or not creds.valid:
-            if creds and creds.expired and creds.refresh_token:
-                creds.refresh(Request())
-            elif credentials_file.exists():
-                flow = InstalledAppFlow.from_client_secrets_file(str(credentials_file), SCOPES)
-                creds = flow.run_local_server(port=0)
-            else:
+        if creds and creds.valid:
+            return creds
+        if not creds:
+            if not account.credentials_file.exists():
                 raise FileNotFoundError(
-                    f"No credentials found. Place credentials.json in {FGP_AUTH_DIR}"
+                    f"No credentials found for account '{account.name}'. "
+                    f"Place credentials.json in {account.directory}"
                 )
-
-            # Save refreshed token
-            token_file.parent.mkdir(parents=True, exist_ok=True)
-            with open(token_file, 'wb') as f:
-                pickle.dump(creds, f)
-
+            raise AuthExpired(account, f"no cached token at {token_file}")
+
+        with token_lock(token_file):
+            # Whoever held the lock before us may have refreshed it already
+            creds = load_credentials(account) or creds
+            if creds.valid:
+                return creds
+            if not (creds.expired and creds.refresh_token):
+                raise AuthExpired(account, "cached token is invalid and can't be refreshed")
+            try:
+                creds.refresh(Request())
+            except RefreshError as e:
+                raise AuthExpired(account, f"token refresh was rejected ({e})") from None
+            save_credentials(account, creds)
         return creds
 
     def _init_service(self):
-        """Build Gmail API service (runs once at startup)."""
-        creds = self._get_credentials()
-        self.service = build('gmail', 'v1', credentials=creds, cache_discovery=False)
+        """Build the Gmail API backend (runs once at startup).
+
+        `FGP_GMAIL_BACKEND=replay` serves responses from `FGP_GMAIL_REPLAY_FILE`
+        instead of calling Gmail. `FGP_GMAIL_RECORD=1` records every API call
+        into a new session file under ~/.fgp/services/gmail/recordings/,
+        redacted unless `FGP_GMAIL_RECORD_UNSAFE=1`.
+        """
+        if os.environ.get("FGP_GMAIL_BACKEND") == "replay":
+            replay_file = os.environ.get("FGP_GMAIL_REPLAY_FILE")
+            if not replay_file:
+                raise ValueError("FGP_GMAIL_REPLAY_FILE is required for the replay backend")
+            self.backend = ReplayBackend(Path(replay_file).expanduser())
+            return
+
+        # Accounts connect on first use; on_start warms up the default one
+
+    def _backend_for(self, account: Account):
+        """Get (building on first use) the API backend for an account."""
+        with self._backend_lock:
+            backend = self._account_backends.get(account.name)
+            if backend is not None:
+                return backend
+
+            creds = self._get_credentials(account)
+            service = build('gmail', 'v1', credentials=creds, cache_discovery=False)
+            backend = ApiBackend(service, http_factory=authorized_http_factory(creds))
+
+            if env_flag(os.environ.get("FGP_GMAIL_RECORD")):
+                session = time.strftime("session-%Y%m%d-%H%M%S") + f"-{os.getpid()}-{account.name}"
+                backend = open_recording(
+                    backend,
+                    RECORDINGS_DIR,
+                    session,
+                    unsafe_keep_content=env_flag(os.environ.get("FGP_GMAIL_RECORD_UNSAFE")),
+                )
+
+            self._account_backends[account.name] = backend
+            return backend
+
+    def _current_account(self) -> Account:
+        """Account selected by the in-flight call, or the default account."""
+        return getattr(self._local, "account", None) or self.accounts.resolve(None)
+
+    def _current_backend(self):
+        """Backend for the account selected by the in-flight call."""
+        if self.backend is not None:
+            return self.backend
+        account = getattr(self._local, "account", None) or self.accounts.resolve(None)
+        return self._backend_for(account)
+
+    def _api_for(self, account: Optional[Account]):
+        """An `_api` equivalent bound to one account, for background threads."""
+        def call(name: str, **params) -> Dict[str, Any]:
+            backend = self.backend if self.backend is not None else self._backend_for(account)
+            return self.retry.run(name, lambda: backend.call(name, userId='me', **params))
+        return call
+
+    def _api(self, name: str, **params) -> Dict[str, Any]:
+        """Call a Gmail API method (e.g. 'messages.list') for the current user.
+
+        Transient errors are retried per the retry policy, and the call fails
+        with TimeoutError once the in-flight method's time budget is spent.
+        Time spent here is attributed to the in-flight call's API time.
+        """
+        deadline = getattr(self._local, "deadline", None)
+        if deadline is not None and time.monotonic() >= deadline:
+            method, secs = self._local.timeout
+            raise TimeoutError(f"{method} exceeded its {secs:g}s timeout")
+
+        started = time.monotonic()
+        try:
+            backend = self._current_backend()
+            return self.retry.run(name, lambda: backend.call(name, userId='me', **params), deadline)
+        finally:
+            self._local.api_ms = getattr(self._local, "api_ms", 0.0) + (time.monotonic() - started) * 1000
 
     def dispatch(self, method: str, params: Dict[str, Any]) -> Dict[str, Any]:
         """
@@ -96,42 +438,296 @@ class GmailModule:
 
         This is called by the Rust daemon for each request.
         The service is already warm, so we just execute the method.
+        Each call runs in a log span, and its response carries the span's
+        `request_id`. With auditing on, every call is also appended to the
+        audit log, failures included. Once `gmail.drain` has run, calls fail
+        with `ShuttingDown`. Results over `max_response_bytes` fail with
+        `ResponseTooLarge`, as soon as the bodies decoded so far pass it.
         """
+        request_id = None
+        error = None
+        started = time.monotonic()
+        try:
+            with call_span(method, params or {}) as request_id, self.in_flight.call(method), \
+                    response_budget(method, self.max_response_bytes):
+                result = self._dispatch_call(method, params)
+                check_response_size(method, result, self.max_response_bytes)
+        except Exception as e:
+            error = e
+            raise
+        finally:
+            self.audit.record(method, params or {}, (time.monotonic() - started) * 1000, error, request_id)
+        if isinstance(result, dict):
+            result = dict(result, request_id=request_id)
+        return result
+
+    def _dispatch_call(self, method: str, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Look up the handler and run it with metrics and a time budget."""
         handlers = {
+            "gmail.accounts": self._cmd_accounts,
+            "gmail.profile": self._cmd_profile,
+            "gmail.config": self._cmd_config,
+            "gmail.health": self._cmd_health,
+            "gmail.stats": self._cmd_stats,
+            "gmail.audit_tail": self._cmd_audit_tail,
+            "gmail.drain": self._cmd_drain,
             "gmail.inbox": self._cmd_inbox,
             "gmail.unread": self._cmd_unread,
             "gmail.search": self._cmd_search,
+            "gmail.contacts": self._cmd_contacts,
             "gmail.send": self._cmd_send,
+            "gmail.forward": self._cmd_forward,
+            "gmail.outbox": self._cmd_outbox,
+            "gmail.send_queued": self._cmd_send_queued,
+            "gmail.create_draft": self._cmd_create_draft,
+            "gmail.send_draft": self._cmd_send_draft,
+            "gmail.list_drafts": self._cmd_list_drafts,
+            "gmail.filters": self._cmd_filters,
+            "gmail.filter_create": self._cmd_filter_create,
+            "gmail.filter_delete": self._cmd_filter_delete,
+            "gmail.vacation_get": self._cmd_vacation_get,
+            "gmail.vacation_set": self._cmd_vacation_set,
+            "gmail.sendas_list": self._cmd_sendas_list,
+            "gmail.bulk_modify": self._cmd_bulk_modify,
+            "gmail.archive": self._cmd_archive,
+            "gmail.unarchive": self._cmd_unarchive,
+            "gmail.batch": self._cmd_batch,
             "gmail.thread": self._cmd_thread,
             "gmail.read": self._cmd_read,
+            "gmail.message": self._cmd_message,
+            "gmail.export_raw": self._cmd_export_raw,
+            "gmail.import_raw": self._cmd_import_raw,
             "gmail.download_attachment": self._cmd_download_attachment,
+            "gmail.get_attachment": self._cmd_get_attachment,
+            "gmail.auth_status": self._cmd_auth_status,
+            "gmail.auth_login": self._cmd_auth_login,
+            "gmail.auth_login_status": self._cmd_auth_login_status,
+            "gmail.auth_login_cancel": self._cmd_auth_login_cancel,
+            "gmail.watch": self._cmd_watch,
+            "gmail.unwatch": self._cmd_unwatch,
+            "gmail.watch_status": self._cmd_watch_status,
+            "gmail.history": self._cmd_history,
         }
 
         handler = handlers.get(method)
         if handler is None:
             raise ValueError(f"Unknown method: {method}")
-
-        return handler(params)
+        if method in UNMETERED_METHODS:
+            return handler(dict(params or {}))
+
+        started = time.monotonic()
+        self._local.api_ms = 0.0
+        timeout = self.timeouts.for_method(method)
+        self._local.timeout = (method, timeout)
+        self._local.deadline = started + timeout if timeout is not None else None
+        error = None
+        try:
+            return self._dispatch_account(method, handler, params)
+        except Exception as e:
+            error = f"{type(e).__name__}: {e}"
+            raise
+        finally:
+            self._local.deadline = None
+            self.metrics.record(method, (time.monotonic() - started) * 1000, self._local.api_ms, error)
+
+    def _dispatch_account(self, method: str, handler, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Resolve the `account` param and run the call as that account."""
+        params = dict(params or {})
+        account_name = params.pop("account", None)
+        if method in ACCOUNTLESS_METHODS:
+            return handler(params)
+
+        # A shared backend serves all accounts, so only validate names the
+        # caller passed explicitly
+        account = None
+        if self.backend is None or account_name is not None:
+            account = self.accounts.resolve(account_name)
+            if method not in AUTH_METHODS:
+                # Fail before spending an API call on a token known to be broken
+                self.auth_monitor.require(account)
+
+        self._local.account = account
+        try:
+            return self._dispatch_cached(method, handler, params, account)
+        finally:
+            self._local.account = None
+
+    def _dispatch_cached(self, method: str, handler, params: Dict[str, Any],
+                         account: Optional[Account]) -> Dict[str, Any]:
+        """Serve read-only methods from the cache and invalidate it on writes."""
+        fresh = bool(params.pop("fresh", False))
+        account_name = account.name if account else None
+        deadline = getattr(self._local, "deadline", None)
+
+        if method not in CACHEABLE_METHODS:
+            self.rate_limiter.acquire(method, account_name, deadline)
+            with self.call_slots.slot(method, deadline):
+                result = handler(params)
+            if method in MUTATING_METHODS and not result.get('dry_run'):
+                self.cache.invalidate(INVALIDATED_BY_WRITES, account_name)
+            return result
+
+        key = self.cache.key(method, account_name, params)
+        if not fresh:
+            cached = self.cache.get(key)
+            if cached is not MISSING:
+                self.prefetch.note_hit(key)
+                return cached
+        self.rate_limiter.acquire(method, account_name, deadline)
+        with self.call_slots.slot(method, deadline):
+            result = handler(params)
+        check_response_size(method, result, self.max_response_bytes)
+        self.cache.put(key, result)
+        if self.prefetch.wants(method):
+            self._prefetch_next(method, handler, params, account, result, depth=1)
+        return result
+
+    def _prefetch_next(self, method: str, handler, params: Dict[str, Any],
+                       account: Optional[Account], result: Dict[str, Any], depth: int):
+        """Fetch the page after `result` in the background and cache it.
+
+        The fetch counts against the rate limiter and takes a call slot like
+        any other call, but only if both are free right now: a prefetch is
+        skipped rather than made to wait.
+        """
+        page_token = result.get('next_page_token')
+        if not page_token:
+            return
+        next_params = dict(params, page_token=page_token)
+        account_name = account.name if account else None
+        key = self.cache.key(method, account_name, next_params)
+
+        def fetch():
+            try:
+                self.rate_limiter.acquire(method, account_name, wait=False)
+            except RateLimited:
+                raise Skip("rate_limited")
+            try:
+                with self.call_slots.slot(method, time.monotonic()):
+                    self._local.account = account
+                    try:
+                        return handler(dict(next_params))
+                    finally:
+                        self._local.account = None
+            except DaemonBusy:
+                raise Skip("busy")
+
+        self.prefetch.submit(
+            key, fetch,
+            store=lambda page: self._store_prefetched(method, key, page),
+            depth=depth,
+            chain=lambda page, next_depth: self._prefetch_next(
+                method, handler, next_params, account, page, next_depth),
+        )
+
+    def _store_prefetched(self, method: str, key, page: Dict[str, Any]):
+        """Cache a prefetched page, unless it's too large to ever be served."""
+        try:
+            check_response_size(method, page, self.max_response_bytes)
+        except ResponseTooLarge:
+            log.debug("Not caching an oversized %s prefetch", method)
+            return
+        self.cache.put(key, page)
+
+    def _prefetch_gate(self) -> Optional[str]:
+        """Reason to skip a speculative prefetch right now, or None.
+
+        Checks against quota and API backoff state belong here. Prefetches
+        only spend rate-limit headroom, so they stop once a bucket is half
+        empty and never take tokens a real call would have to wait for.
+        """
+        if self.rate_limiter.headroom() < 0.5:
+            return "rate_limited"
+        return None
 
     def method_list(self) -> List[Dict[str, Any]]:
         """Return list of available methods."""
-        return [
+        methods = [
+            {
+                "name": "gmail.accounts",
+                "description": "List configured accounts and whether each has a valid cached token",
+                "params": []
+            },
+            {
+                "name": "gmail.profile",
+                "description": "Signed-in email address, message and thread totals, and current history id",
+                "params": []
+            },
+            {
+                "name": "gmail.config",
+                "description": "Effective daemon configuration (config file, env overrides, and defaults); no secrets",
+                "params": []
+            },
+            {
+                "name": "gmail.health",
+                "description": "Structured health for every subsystem with an overall rollup status",
+                "params": []
+            },
+            {
+                "name": "gmail.stats",
+                "description": "Per-method call counts, error rates, and latency percentiles (total and Gmail API time)",
+                "params": [{"name": "reset", "type": "boolean", "required": False, "default": False, "description": "Clear counters after reading them"}]
+            },
+            {
+                "name": "gmail.audit_tail",
+                "description": "Newest audit log entries (redacted params, outcome, duration), oldest first",
+                "params": [
+                    {"name": "limit", "type": "integer", "required": False, "default": 20, "description": "Entries to return, clamped to max_limit"},
+                    {"name": "method", "type": "string", "required": False, "description": "Only entries for this method"}
+                ]
+            },
+            {
+                "name": "gmail.drain",
+                "description": "Stop taking calls, wait for running ones, and stop watches and the outbox; the daemon calls this on SIGTERM",
+                "params": [
+                    {"name": "timeout_secs", "type": "number", "required": False, "default": 10, "description": "Longest wait for running calls ([daemon] drain_timeout_secs)"}
+                ]
+            },
             {
                 "name": "gmail.inbox",
                 "description": "List recent inbox emails",
-                "params": [{"name": "limit", "type": "integer", "required": False, "default": 10}]
+                "params": [
+                    {"name": "limit", "type": "integer", "required": False, "default": 10, "description": "Positive integer, clamped to max_limit (default 100)"},
+                    {"name": "include_attachments", "type": "boolean", "required": False, "default": False, "description": "Add has_attachments, attachment_count, and attachments ({filename, mime_type, size, attachment_id}) to each email; fetches whole messages, so it's slower"},
+                    PAGE_TOKEN_PARAM
+                ]
             },
             {
                 "name": "gmail.unread",
-                "description": "Get accurate unread count and summaries",
-                "params": [{"name": "limit", "type": "integer", "required": False, "default": 10}]
+                "description": "Get accurate unread count and summaries, optionally grouped",
+                "params": [
+                    {"name": "limit", "type": "integer", "required": False, "default": 10, "description": "Positive integer, clamped to max_limit (default 100); defaults to max_limit when grouping"},
+                    {"name": "group_by", "type": "string", "required": False, "description": "Group summaries by sender, domain, or label"},
+                    {"name": "max_per_group", "type": "integer", "required": False, "default": 3, "description": "Most recent summaries kept per group"},
+                    {"name": "older_than", "type": "string", "required": False, "description": "Only messages older than this age (e.g. 7d, 1h, 2w, 3m, 1y)"},
+                    {"name": "newer_than", "type": "string", "required": False, "description": "Only messages newer than this age (e.g. 7d, 1h, 2w, 3m, 1y)"}
+                ]
             },
             {
                 "name": "gmail.search",
-                "description": "Search emails by query",
+                "description": "Search emails by Gmail query and/or structured params (ANDed together)",
+                "params": [
+                    {"name": "query", "type": "string", "required": False, "description": "Raw Gmail search query"},
+                    {"name": "from", "type": "string", "required": False, "description": "Sender address or name"},
+                    {"name": "to", "type": "string", "required": False, "description": "Recipient address or name"},
+                    {"name": "subject", "type": "string", "required": False, "description": "Words in the subject"},
+                    {"name": "after", "type": "string", "required": False, "description": "Received on or after this date (YYYY-MM-DD)"},
+                    {"name": "before", "type": "string", "required": False, "description": "Received before this date (YYYY-MM-DD)"},
+                    {"name": "has_attachment", "type": "boolean", "required": False},
+                    {"name": "is_unread", "type": "boolean", "required": False},
+                    {"name": "label", "type": "string", "required": False, "description": "Label name"},
+                    {"name": "limit", "type": "integer", "required": False, "default": 10, "description": "Positive integer, clamped to max_limit (default 100)"},
+                    {"name": "include_attachments", "type": "boolean", "required": False, "default": False, "description": "Add has_attachments, attachment_count, and attachments ({filename, mime_type, size, attachment_id}) to each email; fetches whole messages, so it's slower"},
+                    PAGE_TOKEN_PARAM
+                ]
+            },
+            {
+                "name": "gmail.contacts",
+                "description": "Autocomplete recipients: addresses from recent mail whose name or address starts with query, best match first",
                 "params": [
-                    {"name": "query", "type": "string", "required": True},
-                    {"name": "limit", "type": "integer", "required": False, "default": 10}
+                    {"name": "query", "type": "string", "required": True, "description": "Prefix of a name, a word of it, or an address; case-insensitive"},
+                    {"name": "limit", "type": "integer", "required": False, "default": 10, "description": "Positive integer, clamped to max_limit (default 100)"},
+                    {"name": "refresh", "type": "boolean", "required": False, "default": False, "description": "Rebuild the index from Gmail before answering"}
                 ]
             },
             {
@@ -139,18 +735,188 @@ class GmailModule:
                 "description": "Read full email with body and attachment info",
                 "params": [{"name": "message_id", "type": "string", "required": True}]
             },
+            {
+                "name": "gmail.message",
+                "description": "Get a single message by ID with headers, snippet, and decoded body",
+                "params": [
+                    {"name": "message_id", "type": "string", "required": True},
+                    {"name": "format", "type": "string", "required": False, "default": "full", "description": "One of: full, metadata, raw"},
+                    {"name": "include_attachments", "type": "boolean", "required": False, "default": False, "description": "Return attachments as {filename, mime_type, size, attachment_id} with has_attachments and attachment_count; needs format full or raw"},
+                    {"name": "truncate_body", "type": "integer", "required": False, "description": "Cut body_text and body_html to this many characters; sets body_truncated"}
+                ]
+            },
+            {
+                "name": "gmail.export_raw",
+                "description": "Save a message's exact RFC 822 source as a .eml file, or return it inline as base64",
+                "params": [
+                    {"name": "message_id", "type": "string", "required": True},
+                    {"name": "output_path", "type": "string", "required": False, "description": "File or directory to write <message_id>.eml to (default ~/.fgp/services/gmail/exports/)"},
+                    {"name": "inline", "type": "boolean", "required": False, "default": False, "description": "Return the content as base64 instead of writing a file (messages up to 1 MB)"}
+                ]
+            },
+            {
+                "name": "gmail.import_raw",
+                "description": "Insert an .eml file into the mailbox without sending it",
+                "params": [
+                    {"name": "path", "type": "string", "required": True, "description": "RFC 822 file to import (max 35 MB)"},
+                    {"name": "labels", "type": "array", "required": False, "description": "Label names or ids to apply (default none, so the message is only in All Mail)"},
+                    {"name": "internal_date_from_header", "type": "boolean", "required": False, "default": False, "description": "Date the message by its Date header instead of the import time"}
+                ]
+            },
             {
                 "name": "gmail.send",
                 "description": "Send an email with optional attachments",
+                "params": [
+                    {"name": "to", "type": "string", "required": True, "description": "Comma-separated addresses, or an array of them"},
+                    {"name": "subject", "type": "string", "required": True},
+                    {"name": "body", "type": "string", "required": False, "description": "Plain-text body; required unless body_markdown is given"},
+                    {"name": "body_html", "type": "string", "required": False, "description": "HTML alternative to body"},
+                    {"name": "body_markdown", "type": "string", "required": False, "description": "Markdown to render as the HTML alternative; also the plain-text body if body is omitted. Not with body_html"},
+                    {"name": "from", "type": "string", "required": False, "description": "Send-as address (see gmail.sendas_list), optionally as \"Name <address>\"; defaults to the primary address"},
+                    {"name": "cc", "type": "string", "required": False},
+                    {"name": "bcc", "type": "string", "required": False},
+                    {"name": "attachments", "type": "array", "required": False, "description": "List of {filename, data (base64)} or {path}"},
+                    {"name": "queue_on_failure", "type": "boolean", "required": False, "default": False, "description": "If the send fails with a network error, 429, or 5xx, keep it in the outbox and retry later"},
+                    {"name": "idempotency_key", "type": "string", "required": False, "description": "Repeating the send with this key within 24h returns the first result with duplicate: true instead of sending again"}
+                ]
+            },
+            {
+                "name": "gmail.forward",
+                "description": "Forward a message with the standard forwarded-message header block",
+                "params": [
+                    {"name": "message_id", "type": "string", "required": True},
+                    {"name": "to", "type": "string", "required": True, "description": "Comma-separated addresses, or an array of them"},
+                    {"name": "from", "type": "string", "required": False, "description": "As for gmail.send"},
+                    {"name": "cc", "type": "string", "required": False},
+                    {"name": "bcc", "type": "string", "required": False},
+                    {"name": "comment", "type": "string", "required": False, "description": "Text to put above the forwarded message"},
+                    {"name": "include_attachments", "type": "boolean", "required": False, "default": True, "description": "Attach the original's attachments"},
+                    {"name": "queue_on_failure", "type": "boolean", "required": False, "default": False, "description": "As for gmail.send"},
+                    {"name": "idempotency_key", "type": "string", "required": False, "description": "As for gmail.send"}
+                ]
+            },
+            {
+                "name": "gmail.outbox",
+                "description": "List queued and given-up sends with their attempts and last error",
+                "params": []
+            },
+            {
+                "name": "gmail.send_queued",
+                "description": "Retry queued sends now instead of waiting for their backoff",
+                "params": [
+                    {"name": "id", "type": "string", "required": False, "description": "One outbox item to retry (re-queues it if it was given up); all pending items if omitted"}
+                ]
+            },
+            {
+                "name": "gmail.create_draft",
+                "description": "Create a draft for review instead of sending; same params as gmail.send",
                 "params": [
                     {"name": "to", "type": "string", "required": True},
                     {"name": "subject", "type": "string", "required": True},
-                    {"name": "body", "type": "string", "required": True},
+                    {"name": "body", "type": "string", "required": False, "description": "Plain-text body; required unless body_markdown is given"},
+                    {"name": "body_html", "type": "string", "required": False, "description": "HTML alternative to body"},
+                    {"name": "body_markdown", "type": "string", "required": False, "description": "Markdown to render as the HTML alternative; also the plain-text body if body is omitted. Not with body_html"},
+                    {"name": "from", "type": "string", "required": False, "description": "Send-as address (see gmail.sendas_list), optionally as \"Name <address>\"; defaults to the primary address"},
                     {"name": "cc", "type": "string", "required": False},
                     {"name": "bcc", "type": "string", "required": False},
                     {"name": "attachments", "type": "array", "required": False, "description": "List of {filename, data (base64)} or {path}"}
                 ]
             },
+            {
+                "name": "gmail.send_draft",
+                "description": "Send a previously created draft",
+                "params": [{"name": "draft_id", "type": "string", "required": True}]
+            },
+            {
+                "name": "gmail.list_drafts",
+                "description": "List drafts with their headers",
+                "params": [{"name": "limit", "type": "integer", "required": False, "default": 10, "description": "Positive integer, clamped to max_limit (default 100)"}]
+            },
+            {
+                "name": "gmail.filters",
+                "description": "List filters with their criteria and actions",
+                "params": []
+            },
+            {
+                "name": "gmail.filter_create",
+                "description": "Create a filter; needs at least one criterion and one action",
+                "params": [
+                    {"name": "from", "type": "string", "required": False},
+                    {"name": "to", "type": "string", "required": False},
+                    {"name": "subject", "type": "string", "required": False},
+                    {"name": "query", "type": "string", "required": False, "description": "Gmail search query"},
+                    {"name": "has_attachment", "type": "boolean", "required": False},
+                    {"name": "add_label", "type": "string", "required": False, "description": "Label name (or list of names) to apply"},
+                    {"name": "remove_label", "type": "string", "required": False, "description": "Label name (or list of names) to remove"},
+                    {"name": "archive", "type": "boolean", "required": False, "description": "Skip the inbox"},
+                    {"name": "mark_read", "type": "boolean", "required": False},
+                    {"name": "forward_to", "type": "string", "required": False, "description": "Verified forwarding address"},
+                    {"name": "create_label_if_missing", "type": "boolean", "required": False, "default": False, "description": "Create labels that don't exist yet instead of failing"}
+                ]
+            },
+            {
+                "name": "gmail.filter_delete",
+                "description": "Delete a filter by ID",
+                "params": [{"name": "filter_id", "type": "string", "required": True}]
+            },
+            {
+                "name": "gmail.vacation_get",
+                "description": "Get the vacation auto-reply setting",
+                "params": []
+            },
+            {
+                "name": "gmail.vacation_set",
+                "description": "Turn the vacation auto-reply on or off; fields not passed keep their current values",
+                "params": [
+                    {"name": "enabled", "type": "boolean", "required": True},
+                    {"name": "subject", "type": "string", "required": False},
+                    {"name": "body", "type": "string", "required": False, "description": "Plain-text reply body"},
+                    {"name": "start", "type": "string", "required": False, "description": "ISO 8601 date or datetime to start replying (UTC if no offset); null clears it"},
+                    {"name": "end", "type": "string", "required": False, "description": "ISO 8601 date or datetime to stop replying; must be after start"},
+                    {"name": "restrict_to_contacts", "type": "boolean", "required": False, "description": "Only reply to people in your contacts"},
+                    {"name": "restrict_to_domain", "type": "boolean", "required": False, "description": "Only reply to people in your Workspace domain"}
+                ]
+            },
+            {
+                "name": "gmail.sendas_list",
+                "description": "List send-as aliases usable as gmail.send's from",
+                "params": []
+            },
+            {
+                "name": "gmail.bulk_modify",
+                "description": "Apply an action to every message matching a search, in batches",
+                "params": [
+                    {"name": "query", "type": "string", "required": True, "description": "Gmail search query selecting the messages"},
+                    {"name": "action", "type": "string", "required": True, "description": "One of: archive, trash, mark_read, add_label, remove_label"},
+                    {"name": "label", "type": "string", "required": False, "description": "Label name for add_label/remove_label"},
+                    {"name": "max_messages", "type": "integer", "required": False, "default": BULK_DEFAULT_MAX_MESSAGES, "description": f"Safety cap on messages touched (max {BULK_HARD_MAX_MESSAGES})"},
+                    {"name": "dry_run", "type": "boolean", "required": False, "default": False, "description": "Only count matches and return a sample of subjects"},
+                    {"name": "timeout", "type": "integer", "required": False, "default": BULK_DEFAULT_TIMEOUT_SECS, "description": "Stop and report progress after this many seconds"}
+                ]
+            },
+            {
+                "name": "gmail.archive",
+                "description": "Remove messages from the inbox; returns each message's labels, or its error",
+                "params": [
+                    {"name": "message_id", "type": "string", "required": False, "description": "One message to archive"},
+                    {"name": "message_ids", "type": "array", "required": False, "description": f"Several messages to archive (max {ARCHIVE_MAX_MESSAGES}); one of message_id/message_ids is required"}
+                ]
+            },
+            {
+                "name": "gmail.unarchive",
+                "description": "Move archived messages back to the inbox; returns each message's labels, or its error",
+                "params": [
+                    {"name": "message_id", "type": "string", "required": False, "description": "One message to move back"},
+                    {"name": "message_ids", "type": "array", "required": False, "description": f"Several messages to move back (max {ARCHIVE_MAX_MESSAGES}); one of message_id/message_ids is required"}
+                ]
+            },
+            {
+                "name": "gmail.batch",
+                "description": "Run several methods in order in one call; each operation succeeds or fails on its own",
+                "params": [
+                    {"name": "operations", "type": "array", "required": True, "description": f"List of {{method, params}} objects (max {BATCH_MAX_OPERATIONS}); pass account inside each operation's params"}
+                ]
+            },
             {
                 "name": "gmail.download_attachment",
                 "description": "Download an attachment from an email",
@@ -160,179 +926,1257 @@ class GmailModule:
                     {"name": "save_path", "type": "string", "required": False, "description": "Path to save file (returns base64 if not specified)"}
                 ]
             },
+            {
+                "name": "gmail.get_attachment",
+                "description": "Get an attachment as base64 with its MIME type, or save it to a file",
+                "params": [
+                    {"name": "message_id", "type": "string", "required": True},
+                    {"name": "attachment_id", "type": "string", "required": True},
+                    {"name": "save_path", "type": "string", "required": False, "description": "New file in an existing, writable directory under the attachments root (home by default); returns base64 data if omitted"}
+                ]
+            },
             {
                 "name": "gmail.thread",
-                "description": "Get email thread by ID",
-                "params": [{"name": "thread_id", "type": "string", "required": True}]
+                "description": "Get a thread as a conversation: ordered messages with de-quoted bodies, plus its participants",
+                "params": [
+                    {"name": "thread_id", "type": "string", "required": True},
+                    {"name": "include_bodies", "type": "boolean", "required": False, "default": True, "description": "Fetch each message's plain-text body with quoted trails removed"},
+                    {"name": "max_messages", "type": "integer", "required": False, "description": "Return only the newest N messages; sets truncated when messages were dropped"},
+                    {"name": "include_attachments", "type": "boolean", "required": False, "default": False, "description": "Add each message's attachments ({filename, mime_type, size, attachment_id}), has_attachments, and attachment_count"},
+                    {"name": "truncate_body", "type": "integer", "required": False, "description": "Cut each message's body to this many characters; sets body_truncated on every message"}
+                ]
+            },
+            {
+                "name": "gmail.auth_status",
+                "description": "Whether the account has a valid cached token, when it expires, and how to sign in if not",
+                "params": []
+            },
+            {
+                "name": "gmail.auth_login",
+                "description": "Start a device-code OAuth login; returns a verification URL and user code",
+                "params": []
+            },
+            {
+                "name": "gmail.auth_login_status",
+                "description": "State of the account's device-code login (pending, complete, expired, denied, cancelled, error)",
+                "params": []
+            },
+            {
+                "name": "gmail.auth_login_cancel",
+                "description": "Cancel the account's pending device-code login",
+                "params": []
+            },
+            {
+                "name": "gmail.watch",
+                "description": "Start polling for new inbox mail (events go to a file queue, a shell command, or a webhook), or with topic, register Gmail push notifications to Pub/Sub",
+                "params": [
+                    {"name": "interval", "type": "integer", "required": False, "default": DEFAULT_INTERVAL_SECS, "description": "Seconds between polls"},
+                    {"name": "command", "type": "string", "required": False, "description": "Shell command run per event with the event JSON on stdin"},
+                    {"name": "webhook", "type": "string", "required": False, "description": "URL each event is POSTed to as JSON"},
+                    {"name": "topic", "type": "string", "required": False, "description": "Pub/Sub topic (projects/<project>/topics/<name>) for push notifications instead of polling; expires after about 7 days, call again to renew"},
+                    {"name": "labels", "type": "array", "required": False, "description": "Push only for changes to these labels (names or ids)"},
+                    {"name": "label_filter_behavior", "type": "string", "required": False, "default": "include", "description": "include: only the given labels; exclude: all but them"}
+                ]
+            },
+            {
+                "name": "gmail.unwatch",
+                "description": "Stop the account's new-mail watch and any push watch registered through the daemon",
+                "params": [
+                    {"name": "push", "type": "boolean", "required": False, "default": False, "description": "Stop push notifications even if this daemon didn't register them"}
+                ]
+            },
+            {
+                "name": "gmail.watch_status",
+                "description": "Whether a watch is active, its last poll time, how many events have fired, and the push watch's expiration",
+                "params": []
+            },
+            {
+                "name": "gmail.history",
+                "description": "Message changes since a history id, for incremental sync",
+                "params": [
+                    {"name": "start_history_id", "type": "string", "required": True, "description": "From gmail.profile, a push notification, or the previous call's history_id"},
+                    {"name": "history_types", "type": "array", "required": False, "description": "Only these changes: message_added, message_deleted, labels_added, labels_removed"},
+                    {"name": "label_filter", "type": "string", "required": False, "description": "Only changes to messages with this label (name or id), e.g. INBOX"},
+                    {"name": "limit", "type": "integer", "required": False, "default": 100, "description": "History records per page, clamped to max_limit"},
+                    PAGE_TOKEN_PARAM
+                ]
             }
         ]
+        for method in methods:
+            if method["name"] in CACHEABLE_METHODS:
+                method["params"].append(FRESH_PARAM)
+            if method["name"] in DRY_RUN_METHODS and not any(p["name"] == "dry_run" for p in method["params"]):
+                method["params"].append(DRY_RUN_PARAM)
+            if method["name"] not in ACCOUNTLESS_METHODS:
+                method["params"].append(ACCOUNT_PARAM)
+        return methods
 
     def on_start(self):
-        """Called when daemon starts."""
-        # Service already initialized in __init__
-        pass
+        """Called when daemon starts.
+
+        Connects the default account so a missing or expired token fails
+        startup with instructions, rather than failing the first call. With
+        several accounts and no default there's nothing to warm.
+        """
+        if self.backend is not None:
+            return
+        default = self.accounts.default_name()
+        if default is not None or not self.accounts.names():
+            account = self.accounts.resolve(default)
+            self._backend_for(account)
+            try:
+                profile = Profile.from_api(self._api_for(account)('getProfile'))
+                log.info("Account '%s' is signed in as %s", account.name, profile.email_address)
+            except Exception as e:
+                log.warning("Couldn't fetch the profile for account '%s': %s", account.name, e)
+        self.auth_monitor.start()
+        self._get_outbox().start()
 
     def on_stop(self):
         """Called when daemon stops."""
-        pass
+        self.auth_monitor.stop()
+        self.contacts.stop()
+        if self._outbox is not None:
+            self._outbox.stop()
+        with self._watch_lock:
+            watches = list(self._watches.values())
+            self._watches.clear()
+        for watch in watches:
+            watch.stop()
 
     def health_check(self) -> Dict[str, Any]:
-        """Return health status."""
-        return {
-            "gmail_service": {
-                "ok": self.service is not None,
-                "message": "Gmail service initialized" if self.service else "Service not initialized"
-            }
-        }
+        """Return health status flattened for the FGP framework."""
+        return self._health_report().to_framework()
+
+    def _health_report(self) -> HealthReport:
+        """Collect structured health from every subsystem."""
+        report = HealthReport()
+
+        if self.backend is not None:
+            report.add(SubsystemHealth(
+                "gmail_service", OK, "backend_ready",
+                f"Gmail service initialized ({self.backend.name} backend)", core=True,
+            ))
+        else:
+            connected = sorted(self._account_backends)
+            if connected or not self.accounts.names():
+                report.add(SubsystemHealth(
+                    "gmail_service", OK, "backend_ready",
+                    f"Gmail service initialized (accounts: {', '.join(connected) or 'none connected'})",
+                    core=True,
+                ))
+            else:
+                report.add(SubsystemHealth(
+                    "gmail_service", FAILED, "backend_not_initialized",
+                    "No account has connected to Gmail yet", core=True,
+                    remediation="Pass 'account' or set FGP_GMAIL_DEFAULT_ACCOUNT",
+                ))
+
+        report.add(self._probe_api())
+
+        default = self.accounts.default_name()
+        for name, account in self.accounts.discover().items():
+            report.add(self._account_health(name, account, is_default=name == default))
+        report.add(self._auth_health(default))
+
+        for name, watch in sorted(self._watches.items()):
+            report.add(self._watch_health(name, watch))
+
+        for method, stats in self.metrics.snapshot().items():
+            report.add(SubsystemHealth(
+                f"method.{method.split('.', 1)[-1]}", OK, "method_stats",
+                f"{stats['count']} calls, {stats['errors']} errors; "
+                f"{stats['window_error_rate']:.0%} errors and p95 {stats['p95_ms']:.0f}ms "
+                f"over last {stats['window']}",
+                latency_ms=stats['p95_ms'],
+                details=stats,
+            ))
+
+        stats = self.cache.stats()
+        if self.cache.enabled:
+            report.add(SubsystemHealth(
+                "cache", OK, "cache_active",
+                f"{stats['hits']} hits, {stats['misses']} misses, {stats['entries']} entries "
+                f"(ttl {stats['ttl_secs']:g}s)",
+                details=stats,
+            ))
+        else:
+            report.add(SubsystemHealth("cache", DISABLED, "cache_disabled", "Result cache disabled"))
+        return report
+
+    def _probe_api(self) -> SubsystemHealth:
+        """Round-trip a cheap API call (getProfile) to check Gmail is reachable.
+
+        The call runs on a worker thread bounded by `probe_timeout`. A probe
+        that hangs is left to finish in the background and no new probe is
+        started until it does, so repeated health checks can't pile up
+        threads. Concurrent health checks share the one probe: checking for
+        it and starting a new one happen under `_probe_lock`.
+        """
+        if self.backend is not None and self.backend.name == "replay":
+            return SubsystemHealth("gmail_api", DISABLED, "probe_skipped",
+                                   "API probe skipped for replay backend", core=True)
+
+        outcome: Dict[str, Any] = {}
+
+        def probe():
+            started = time.monotonic()
+            try:
+                outcome["profile"] = self._fetch_profile()
+            except Exception as e:
+                outcome["error"] = e
+            outcome["latency_ms"] = (time.monotonic() - started) * 1000
+
+        with self._probe_lock:
+            running = self._probe
+            if running is not None and running[0].is_alive():
+                # Share the probe in flight (another health check's, or a
+                # hung one) instead of starting a second
+                thread, outcome = running
+                started_here = False
+            else:
+                thread = threading.Thread(target=probe, name="gmail-health-probe", daemon=True)
+                self._probe = (thread, outcome)
+                thread.start()
+                started_here = True
+        thread.join(self.probe_timeout)
+
+        if thread.is_alive() and not started_here:
+            return SubsystemHealth(
+                "gmail_api", FAILED, "probe_hung",
+                "Previous API probe still hasn't returned", core=True,
+                remediation="Check network connectivity to gmail.googleapis.com",
+            )
+        if thread.is_alive():
+            return SubsystemHealth(
+                "gmail_api", FAILED, "probe_timeout",
+                f"Gmail API did not respond within {self.probe_timeout:g}s", core=True,
+                latency_ms=self.probe_timeout * 1000,
+                remediation="Check network connectivity to gmail.googleapis.com",
+            )
+
+        latency_ms = round(outcome["latency_ms"], 1)
+        error = outcome.get("error")
+        if isinstance(error, AuthExpired):
+            return SubsystemHealth(
+                "gmail_api", FAILED, "auth_expired", f"Gmail API probe failed: {error.reason}",
+                core=True, latency_ms=latency_ms, remediation=signin_hint(error.account),
+            )
+        if error is not None:
+            return SubsystemHealth(
+                "gmail_api", FAILED, "probe_failed", f"Gmail API probe failed: {error}",
+                core=True, latency_ms=latency_ms,
+                remediation="Check the account's OAuth token and network connectivity",
+            )
+        return SubsystemHealth(
+            "gmail_api", OK, "api_reachable",
+            f"Gmail API reachable as {outcome['profile'].email_address}", core=True,
+            latency_ms=latency_ms,
+        )
+
+    def _watch_health(self, name: str, watch: Watch) -> SubsystemHealth:
+        """Health of one account's new-mail poller."""
+        status = watch.status()
+        if not watch.active:
+            return SubsystemHealth(f"watch.{name}", DISABLED, "watch_stopped", "Watch stopped",
+                                   details=status)
+        if watch.consecutive_failures >= WATCH_DEGRADED_AFTER:
+            return SubsystemHealth(
+                f"watch.{name}", DEGRADED, "watch_poll_failing",
+                f"{watch.consecutive_failures} polls failed in a row: {watch.last_error}",
+                remediation="Check network connectivity and the account's token",
+                details=status,
+            )
+        return SubsystemHealth(f"watch.{name}", OK, "watch_active",
+                               f"{watch.events_fired} events fired", details=status)
+
+    def _auth_health(self, default: Optional[str]) -> SubsystemHealth:
+        """Rollup of the auth monitor's latest token checks."""
+        states = self.auth_monitor.states()
+        if not states:
+            return SubsystemHealth("auth", DISABLED, "auth_unchecked", "No token checks have run")
+
+        broken = [state for state in states.values() if not state["usable"]]
+        if broken:
+            first = broken[0]
+            names = ", ".join(state["account"] for state in broken)
+            return SubsystemHealth(
+                "auth", FAILED if default in {state["account"] for state in broken} else DEGRADED,
+                "auth_required", f"Can't authenticate {names}: {first['reason']}",
+                core=True, remediation=signin_hint(first["account"]), details=states,
+            )
+
+        unscoped = [state for state in states.values() if state["missing_scopes"]]
+        if unscoped:
+            first = unscoped[0]
+            return SubsystemHealth(
+                "auth", DEGRADED, "scopes_missing",
+                f"Account '{first['account']}' token lacks {', '.join(first['missing_scopes'])}",
+                remediation=f"Sign in again to grant them. {signin_hint(first['account'])}",
+                details=states,
+            )
+
+        return SubsystemHealth(
+            "auth", OK, "auth_ok",
+            f"Tokens usable for {len(states)} account(s), checked every {self.auth_monitor.interval:g}s",
+            details=states,
+        )
+
+    def _account_health(self, name: str, account: Account, is_default: bool) -> SubsystemHealth:
+        """Auth health for one account. Only the default account is core."""
+        token = token_status(account)
+        suffix = " (default)" if is_default else ""
+        reauth = signin_hint(name)
+
+        if token.get("token_error"):
+            status, reason, message, remediation = FAILED, "token_unreadable", token["token_error"], reauth
+        elif not token["token_cached"]:
+            status, reason, message, remediation = (
+                FAILED, "token_missing", "No cached token", reauth)
+        elif token["token_valid"]:
+            status, reason, message, remediation = OK, "token_valid", "Token valid", None
+        elif token["token_refreshable"]:
+            status, reason, message, remediation = (
+                OK, "token_refreshable", "Token expired; will refresh on next call", None)
+        else:
+            status, reason, message, remediation = (
+                FAILED, "token_invalid", "Token invalid", reauth)
+
+        return SubsystemHealth(
+            f"account.{name}", status, reason, message + suffix,
+            core=is_default, remediation=remediation,
+            details={"token_expiry": token["token_expiry"]},
+        )
 
     # =========================================================================
     # Method Handlers
     # =========================================================================
 
+    def _cmd_accounts(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """List configured accounts with their cached-token status."""
+        default = self.accounts.default_name()
+        accounts = []
+        for name, account in self.accounts.discover().items():
+            entry = {
+                'name': name,
+                'default': name == default,
+                'directory': str(account.directory),
+            }
+            entry.update(token_status(account))
+            accounts.append(entry)
+
+        return {
+            'accounts': accounts,
+            'default': default,
+            'count': len(accounts)
+        }
+
+    def _fetch_profile(self) -> Profile:
+        """The current account's profile.
+
+        A rejected token surfaces as AuthExpired, like a failed sign-in at
+        connect time, rather than as whatever google-auth raised mid-call.
+        """
+        try:
+            return Profile.from_api(self._api('getProfile'))
+        except RefreshError as e:
+            reason = f"token refresh was rejected ({e})"
+        except Exception as e:
+            if http_status(e) != 401:
+                raise
+            reason = "Gmail rejected the token (HTTP 401)"
+        account = getattr(self._local, "account", None) or Account(DEFAULT_ACCOUNT, self.accounts.auth_dir)
+        raise AuthExpired(account, reason) from None
+
+    def _cmd_profile(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """The signed-in address, mailbox totals, and current history id."""
+        account = getattr(self._local, "account", None)
+        result = {'account': account.name if account else None}
+        result.update(self._fetch_profile().to_dict())
+        return result
+
+    def _cmd_config(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Effective configuration as loaded.
+
+        Only settings and file locations are reported; credentials and tokens
+        never leave their files.
+        """
+        return {
+            'source': os.environ.get("FGP_GMAIL_CONFIG_FILE") or None,
+            'daemon': {
+                'socket': os.environ.get("FGP_GMAIL_SOCKET") or None,
+                'log_filter': os.environ.get("FGP_GMAIL_LOG_FILTER") or None,
+                'drain_timeout_secs': float(os.environ.get("FGP_GMAIL_DRAIN_TIMEOUT") or DEFAULT_DRAIN_SECS),
+                'python': os.environ.get("FGP_GMAIL_PYTHON") or None,
+                'module': str(Path(__file__).resolve()),
+                'backend': self.backend.name if self.backend is not None else "api",
+            },
+            'accounts': {
+                'auth_dir': str(self.accounts.auth_dir),
+                'default': self.accounts.default_name(),
+            },
+            'max_limit': self.max_limit,
+            'max_response_bytes': self.max_response_bytes,
+            'auth_check_interval_secs': self.auth_monitor.interval,
+            'rate_limit': self.rate_limiter.to_dict(),
+            'concurrency': {
+                'max_concurrent_calls': self.call_slots.max_concurrent,
+                'busy_wait_secs': self.call_slots.max_wait_secs,
+            },
+            'idempotency': self.idempotency.to_dict(),
+            'contacts': self.contacts.to_dict(),
+            'cache': {
+                'ttl_secs': self.cache.ttl,
+                'max_entries': self.cache.max_entries,
+            },
+            'timeouts': {
+                'probe_secs': self.probe_timeout,
+                'methods': self.timeouts.to_dict(),
+            },
+            'retry': self.retry.to_dict(),
+            'prefetch': {
+                'enabled': self.prefetch.enabled,
+                'pages': self.prefetch.pages,
+                'methods': sorted(self.prefetch.methods),
+            },
+            'audit': self.audit.to_dict(),
+        }
+
+    def _cmd_audit_tail(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """The newest audit log entries, oldest first."""
+        limit = parse_limit(params.get("limit"), default=DEFAULT_AUDIT_TAIL, max_limit=self.max_limit)
+        method = params.get("method")
+        if method is not None and not isinstance(method, str):
+            raise ValueError("method must be a string")
+        entries = self.audit.tail(limit, method)
+        return {
+            'enabled': self.audit.enabled,
+            'path': str(self.audit.path),
+            'entries': entries,
+            'count': len(entries)
+        }
+
+    def _cmd_drain(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Stop taking calls, let running ones finish, and stop background work.
+
+        The daemon calls this when it gets SIGTERM or SIGINT, then exits.
+        Calls still running after `timeout_secs` are abandoned; the outbox and
+        the watches (and any sink commands they're running) are stopped
+        either way, so queued sends and journal writes are on disk.
+        """
+        timeout = params.get("timeout_secs", float(os.environ.get("FGP_GMAIL_DRAIN_TIMEOUT") or DEFAULT_DRAIN_SECS))
+        if isinstance(timeout, bool) or not isinstance(timeout, (int, float)) or timeout < 0:
+            raise ValueError("timeout_secs must be a non-negative number")
+        result = self.in_flight.drain(timeout)
+        if not result['drained']:
+            log.warning("Shutting down with %d call(s) still running after %gs", result['abandoned'], timeout)
+        self.on_stop()
+        return result
+
+    def _cmd_health(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Full structured health document."""
+        return self._health_report().to_dict()
+
+    def _cmd_stats(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Structured call metrics, optionally resetting them."""
+        result = {
+            'since': self.metrics.since,
+            'window': self.metrics.window,
+            'methods': self.metrics.snapshot(),
+            'cache': self.cache.stats(),
+            'prefetch': self.prefetch.stats(),
+            'concurrency': dict(self.call_slots.to_dict(), **self.in_flight.to_dict()),
+        }
+        if params.get("reset"):
+            self.metrics.reset()
+            result['reset'] = True
+        return result
+
     def _cmd_inbox(self, params: Dict[str, Any]) -> Dict[str, Any]:
         """List recent emails from inbox."""
-        limit = params.get("limit", 10)
+        limit = parse_limit(params.get("limit"), max_limit=self.max_limit)
+        include_attachments = self._include_attachments(params)
 
-        results = self.service.users().messages().list(
-            userId='me',
+        results = self._api(
+            'messages.list',
             labelIds=['INBOX'],
-            maxResults=limit
-        ).execute()
+            maxResults=limit,
+            **self._page_param(params)
+        )
 
         messages = results.get('messages', [])
-        emails = []
-
-        for msg in messages:
-            detail = self.service.users().messages().get(
-                userId='me',
-                id=msg['id'],
-                format='metadata',
-                metadataHeaders=['From', 'Subject', 'Date']
-            ).execute()
-
-            headers = {h['name']: h['value'] for h in detail.get('payload', {}).get('headers', [])}
-            emails.append({
-                'id': msg['id'],
-                'thread_id': detail.get('threadId'),
-                'from': headers.get('From', ''),
-                'subject': headers.get('Subject', ''),
-                'date': headers.get('Date', ''),
-                'snippet': detail.get('snippet', '')[:100]
-            })
+        emails = [self._fetch_summary(msg['id'], include_attachments).to_dict() for msg in messages]
 
         return {
             'emails': emails,
-            'count': len(emails)
+            'count': len(emails),
+            'next_page_token': results.get('nextPageToken')
         }
 
     def _cmd_unread(self, params: Dict[str, Any]) -> Dict[str, Any]:
         """Get ACCURATE unread count and summaries."""
-        limit = params.get("limit", 10)
+        group_by = params.get("group_by")
+        if group_by is not None and group_by not in GROUP_BY:
+            raise ValueError(f"group_by must be one of: {', '.join(GROUP_BY)}")
+        max_per_group = parse_limit(params.get("max_per_group"), default=DEFAULT_MAX_PER_GROUP,
+                                    max_limit=self.max_limit, name="max_per_group")
+        # Grouping is only useful over many messages, so it scans as many as allowed
+        limit = parse_limit(params.get("limit"), default=self.max_limit if group_by else DEFAULT_LIMIT,
+                            max_limit=self.max_limit)
+        query = " ".join(age_terms(params)) or None
 
         # Get ACCURATE unread count from labels API (not estimate!)
-        label_info = self.service.users().labels().get(
-            userId='me',
+        label_info = self._api(
+            'labels.get',
             id='UNREAD'
-        ).execute()
+        )
         accurate_unread_count = label_info.get('messagesUnread', 0)
 
         # Get recent unread messages for summaries
-        results = self.service.users().messages().list(
-            userId='me',
+        list_params = {'q': query} if query else {}
+        results = self._api(
+            'messages.list',
             labelIds=['INBOX', 'UNREAD'],
-            maxResults=limit
-        ).execute()
+            maxResults=limit,
+            **list_params
+        )
 
         messages = results.get('messages', [])
         emails = []
 
         for msg in messages:
-            detail = self.service.users().messages().get(
-                userId='me',
+            detail = self._api(
+                'messages.get',
                 id=msg['id'],
                 format='metadata',
-                metadataHeaders=['From', 'Subject']
-            ).execute()
-
-            headers = {h['name']: h['value'] for h in detail.get('payload', {}).get('headers', [])}
-            emails.append({
-                'id': msg['id'],
-                'from': headers.get('From', ''),
-                'subject': headers.get('Subject', ''),
-                'snippet': detail.get('snippet', '')[:80]
-            })
+                metadataHeaders=SUMMARY_HEADERS
+            )
+            emails.append(EmailSummary.from_api(detail).to_dict())
 
-        return {
+        result = {
             'unread_count': accurate_unread_count,  # Accurate, not estimate!
-            'emails': emails
+            'count': len(emails)
         }
+        if query:
+            result['query'] = query
+        if not group_by:
+            result['emails'] = emails
+            return result
+
+        label_names = {label['id']: label['name'] for label in self._labels()} if group_by == 'label' else None
+        result.update(
+            group_by=group_by,
+            groups=group_summaries(emails, group_by, max_per_group, label_names),
+            truncated='nextPageToken' in results,
+        )
+        return result
 
     def _cmd_search(self, params: Dict[str, Any]) -> Dict[str, Any]:
-        """Search emails by query."""
-        query = params.get("query")
+        """Search emails by query and/or structured search params."""
+        query = compose_query(params)
         if not query:
-            raise ValueError("query parameter is required")
+            raise ValueError(f"query or at least one of {', '.join(SEARCH_PARAMS)} is required")
 
-        limit = params.get("limit", 10)
+        limit = parse_limit(params.get("limit"), max_limit=self.max_limit)
+        include_attachments = self._include_attachments(params)
 
-        results = self.service.users().messages().list(
-            userId='me',
+        results = self._api(
+            'messages.list',
             q=query,
-            maxResults=limit
-        ).execute()
+            maxResults=limit,
+            **self._page_param(params)
+        )
 
         messages = results.get('messages', [])
-        emails = []
-
-        for msg in messages:
-            detail = self.service.users().messages().get(
-                userId='me',
-                id=msg['id'],
-                format='metadata',
-                metadataHeaders=['From', 'Subject', 'Date']
-            ).execute()
-
-            headers = {h['name']: h['value'] for h in detail.get('payload', {}).get('headers', [])}
-            emails.append({
-                'id': msg['id'],
-                'thread_id': detail.get('threadId'),
-                'from': headers.get('From', ''),
-                'subject': headers.get('Subject', ''),
-                'date': headers.get('Date', ''),
-                'snippet': detail.get('snippet', '')[:100]
-            })
+        emails = [self._fetch_summary(msg['id'], include_attachments).to_dict() for msg in messages]
 
         return {
             'query': query,
             'emails': emails,
-            'count': len(emails)
+            'count': len(emails),
+            'next_page_token': results.get('nextPageToken')
         }
 
+    def _cmd_contacts(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Addresses from recent mail matching a name or address prefix.
+
+        See gmail_lib.contacts for how the index is built and kept fresh.
+        """
+        query = params.get("query")
+        if not isinstance(query, str):
+            raise ValueError("query must be a string")
+        limit = parse_limit(params.get("limit"), max_limit=self.max_limit)
+        refresh = params.get("refresh", False)
+        if not isinstance(refresh, bool):
+            raise ValueError("refresh must be a boolean")
+        account = getattr(self._local, "account", None)
+        return self.contacts.lookup(
+            account.name if account else DEFAULT_ACCOUNT,
+            query, limit,
+            api=self._api,
+            background_api=self._api_for(account),
+            refresh=refresh,
+        )
+
+    def _fetch_summary(self, message_id: str, include_attachments: bool = False) -> EmailSummary:
+        if include_attachments:
+            # Only the full format carries the MIME tree attachments are listed in
+            detail = self._api('messages.get', id=message_id, format='full')
+        else:
+            detail = self._api('messages.get', id=message_id, format='metadata', metadataHeaders=SUMMARY_HEADERS)
+        return EmailSummary.from_api(detail, include_attachments=include_attachments)
+
+    @staticmethod
+    def _include_attachments(params: Dict[str, Any]) -> bool:
+        include_attachments = params.get("include_attachments", False)
+        if not isinstance(include_attachments, bool):
+            raise ValueError("include_attachments must be a boolean")
+        return include_attachments
+
+    @staticmethod
+    def _page_param(params: Dict[str, Any]) -> Dict[str, Any]:
+        """`pageToken` kwarg for list calls, only when the caller passed one."""
+        page_token = params.get("page_token")
+        if page_token is None:
+            return {}
+        if not isinstance(page_token, str) or not page_token:
+            raise ValueError("page_token must be a non-empty string")
+        return {'pageToken': page_token}
+
     def _cmd_send(self, params: Dict[str, Any]) -> Dict[str, Any]:
-        """Send an email with optional attachments."""
-        to = params.get("to")
+        """Send an email with optional attachments.
+
+        With `queue_on_failure`, a send that fails for a transient reason is
+        handed to the outbox; the message then carries its own Message-ID
+        so a later retry can tell whether it already went out. With an
+        `idempotency_key`, repeats return the first send's result instead.
+        """
+        key = parse_idempotency_key(params.get("idempotency_key"))
+        if key is not None and not self._dry_run(params):
+            return self._send_once(key, params)
+        rfc822_id = make_msgid(domain="fgp-gmail.local") if params.get("queue_on_failure") else None
+        raw, attached_files = self._build_message(params, message_id=rfc822_id)
+        if self._dry_run(params):
+            return self._planned([('messages.send', {'body': {'raw': raw}})], attachments=attached_files or None)
+        return self._send_raw(params, raw, attached_files, rfc822_id)
+
+    def _send_once(self, key: str, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Send unless `key` already sent this message; see gmail_lib.idempotency."""
+        account = getattr(self._local, "account", None)
+        account_name = account.name if account else ""
+        with self.idempotency.hold(account_name, key, fingerprint(params)) as entry:
+            if entry.result is not None:
+                return dict(entry.result, duplicate=True)
+            if entry.attempted:
+                # An earlier attempt's outcome is unknown; Gmail may have the message
+                found = self._find_sent(entry.rfc822_id)
+                if found is not None:
+                    result = SendResult.from_api(found).to_dict()
+                    self.idempotency.finish(entry, result)
+                    return dict(result, duplicate=True)
+            raw, attached_files = self._build_message(params, message_id=entry.rfc822_id)
+            self.idempotency.attempt(entry)
+            result = self._send_raw(params, raw, attached_files, entry.rfc822_id)
+            self.idempotency.finish(entry, result)
+        return dict(result, duplicate=False)
+
+    def _send_raw(self, params: Dict[str, Any], raw: str, attached_files: List[Dict[str, Any]],
+                  rfc822_id: Optional[str]) -> Dict[str, Any]:
+        """Send a built message, queueing it on failure if asked to."""
+        queue = bool(params.get("queue_on_failure"))
+        try:
+            result = self._api(
+                'messages.send',
+                body={'raw': raw}
+            )
+        except Exception as e:
+            if not queue or not queueable(e):
+                raise
+            account = getattr(self._local, "account", None)
+            item = self._get_outbox().enqueue(
+                account.name if account else None, raw, rfc822_id, e,
+                to=self._recipients(params, "to") or "", subject=params.get("subject", ""))
+            return {'sent': False, 'queued': True, 'outbox_id': item['id'], 'error': str(e),
+                    'next_attempt_at': item['next_attempt_at']}
+
+        return SendResult.from_api(result, attached_files).to_dict()
+
+    def _cmd_forward(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Forward a message, carrying its attachments over unless told not to.
+
+        The forward is built from the original's raw source and then goes
+        through `_cmd_send`, so it validates, queues, and dry-runs exactly
+        like a send.
+        """
+        message_id = params.get("message_id")
+        if not message_id:
+            raise ValueError("message_id parameter is required")
+        for name in ("to", "cc", "bcc"):
+            self._recipients(params, name)
+        if not self._recipients(params, "to"):
+            raise ValueError("to parameter is required")
+        comment = params.get("comment") or ""
+        if not isinstance(comment, str):
+            raise ValueError("comment must be a string")
+        include_attachments = params.get("include_attachments", True)
+        if not isinstance(include_attachments, bool):
+            raise ValueError("include_attachments must be a boolean")
+
+        msg = self._api('messages.get', id=message_id, format='raw')
+        raw = msg.get('raw')
+        if not isinstance(raw, str):
+            raise UnexpectedOutput(f"unexpected API output: missing field `raw` in message {message_id}")
+        original = parse_raw(raw)
+        restriction = detect_restriction({
+            'labelIds': msg.get('labelIds', []),
+            'payload': payload_from_email(original),
+        })
+        if restriction:
+            # Gmail only gives us placeholder content for these
+            raise ValueError(f"Message {message_id} can't be forwarded: {restriction_note(restriction)}")
+
+        body, body_html = forward_bodies(original, comment)
+        send_params = {name: params[name] for name in ("from", "to", "cc", "bcc", "queue_on_failure", "dry_run",
+                                                       "idempotency_key") if name in params}
+        send_params.update(
+            subject=forward_subject(str(original['Subject'] or '')),
+            body=body,
+            attachments=forward_attachments(original) if include_attachments else [],
+        )
+        if body_html:
+            send_params['body_html'] = body_html
+
+        result = self._cmd_send(send_params)
+        result['forwarded_id'] = msg.get('id', message_id)
+        return result
+
+    def _cmd_outbox(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """List queued and given-up sends."""
+        items = self._get_outbox().items()
+        return {'items': items, 'count': len(items)}
+
+    def _cmd_send_queued(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Retry one or all queued sends now."""
+        item_id = params.get("id")
+        if item_id is not None and (not isinstance(item_id, str) or not item_id):
+            raise ValueError("id must be an outbox item id")
+        try:
+            results = self._get_outbox().retry(item_id)
+        except KeyError as e:
+            raise NotFound(e.args[0]) from None
+        sent = sum(1 for result in results if result['state'] == 'sent')
+        return {'results': results, 'count': len(results), 'sent': sent}
+
+    def _get_outbox(self) -> Outbox:
+        """The outbox, opened on first use."""
+        with self._outbox_lock:
+            if self._outbox is None:
+                self._outbox = Outbox(self.outbox_dir, self._outbox_send, self._outbox_find)
+            return self._outbox
+
+    def _outbox_api(self, account_name: Optional[str]):
+        return self._api_for(self.accounts.resolve(account_name) if account_name is not None else None)
+
+    def _outbox_send(self, account_name: Optional[str], raw: str) -> Dict[str, Any]:
+        """Send a queued message under the same rate limit and call slots as
+        `gmail.send`, whether `gmail.send_queued` or the retry loop asked."""
+        deadline = getattr(self._local, "deadline", None)
+        self.rate_limiter.acquire("gmail.send", account_name, deadline)
+        with self.call_slots.slot("gmail.send", deadline):
+            sent = self._outbox_api(account_name)('messages.send', body={'raw': raw})
+        self.cache.invalidate(INVALIDATED_BY_WRITES, account_name)
+        return sent
+
+    def _outbox_find(self, account_name: Optional[str], rfc822_id: str) -> Optional[str]:
+        """Gmail id of the already-sent copy of a queued message, if any."""
+        found = self._find_sent(rfc822_id, self._outbox_api(account_name))
+        return found['id'] if found else None
+
+    def _find_sent(self, rfc822_id: str, api=None) -> Optional[Dict[str, Any]]:
+        """The message ({id, threadId}) with this Message-ID header, if Gmail has one."""
+        found = (api or self._api)(
+            'messages.list', q=f"rfc822msgid:{rfc822_id.strip('<>')}", includeSpamTrash=True, maxResults=1)
+        messages = found.get('messages') or []
+        return messages[0] if messages else None
+
+    def _cmd_create_draft(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Stage an email as a draft, taking the same params as send."""
+        raw, attached_files = self._build_message(params)
+        if self._dry_run(params):
+            return self._planned([('drafts.create', {'body': {'message': {'raw': raw}}})],
+                                 attachments=attached_files or None)
+
+        draft = self._api(
+            'drafts.create',
+            body={'message': {'raw': raw}}
+        )
+
+        result = Draft.from_api(draft).to_dict()
+        result['attachments'] = attached_files or None
+        return result
+
+    def _cmd_send_draft(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Send a previously created draft."""
+        draft_id = params.get("draft_id")
+        if not draft_id:
+            raise ValueError("draft_id parameter is required")
+
+        if self._dry_run(params):
+            try:
+                self._api('drafts.get', id=draft_id, format='minimal')
+            except Exception as e:
+                if http_status(e) == 404:
+                    raise NotFound(f"Draft not found: {draft_id} (already sent or deleted?)") from None
+                raise
+            return self._planned([('drafts.send', {'body': {'id': draft_id}})], draft_id=draft_id)
+
+        try:
+            result = self._api(
+                'drafts.send',
+                body={'id': draft_id}
+            )
+        except Exception as e:
+            if http_status(e) == 404:
+                raise NotFound(f"Draft not found: {draft_id} (already sent or deleted?)") from None
+            raise
+
+        return dict(SendResult.from_api(result).to_dict(), draft_id=draft_id)
+
+    def _cmd_list_drafts(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """List drafts, newest first, with their headers."""
+        limit = parse_limit(params.get("limit"), max_limit=self.max_limit)
+
+        results = self._api(
+            'drafts.list',
+            maxResults=limit
+        )
+
+        drafts = []
+        for entry in results.get('drafts', []):
+            draft = self._api(
+                'drafts.get',
+                id=entry['id'],
+                format='metadata'
+            )
+            drafts.append(Draft.from_api(draft).to_dict())
+
+        return {
+            'drafts': drafts,
+            'count': len(drafts)
+        }
+
+    def _cmd_filters(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """List filters, with label ids shown as names."""
+        results = self._api('settings.filters.list')
+        names = {label['id']: label['name'] for label in self._labels()}
+        filters = [describe_filter(f, names) for f in results.get('filter', [])]
+        return {
+            'filters': filters,
+            'count': len(filters)
+        }
+
+    def _cmd_filter_create(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Create a filter from criteria and action params."""
+        unknown = set(params) - set(CRITERIA_FIELDS) - set(ACTION_FIELDS) - {"create_label_if_missing", "dry_run"}
+        if unknown:
+            raise ValueError(f"Unknown filter params: {', '.join(sorted(unknown))}")
+
+        labels = {label['name'].lower(): label['id'] for label in self._labels()}
+        create_missing = bool(params.get("create_label_if_missing"))
+        dry_run = self._dry_run(params)
+        created_labels = []
+        planned = []
+
+        def label_id(name: str) -> str:
+            found = labels.get(name.lower())
+            if found:
+                return found
+            if not create_missing:
+                raise NotFound(f"Label not found: {name!r} (pass create_label_if_missing: true to create it)")
+            body = {'name': name, 'labelListVisibility': 'labelShow', 'messageListVisibility': 'show'}
+            if dry_run:
+                planned.append(('labels.create', {'body': body}))
+                label = {'id': f"<new label {name}>"}
+            else:
+                label = self._api('labels.create', body=body)
+            labels[name.lower()] = label['id']
+            created_labels.append(name)
+            return label['id']
+
+        # Validate everything before creating any labels
+        build_filter(params, lambda name: name)
+        body = build_filter(params, label_id)
+        if dry_run:
+            planned.append(('settings.filters.create', {'body': body}))
+            return self._planned(planned, created_labels=created_labels)
+
+        result = self._api(
+            'settings.filters.create',
+            body=body
+        )
+
+        return {
+            'filter_id': result['id'],
+            'criteria': body['criteria'],
+            'action': body['action'],
+            'created_labels': created_labels
+        }
+
+    def _cmd_filter_delete(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Delete a filter by ID."""
+        filter_id = params.get("filter_id")
+        if not filter_id:
+            raise ValueError("filter_id parameter is required")
+        dry_run = self._dry_run(params)
+
+        try:
+            if dry_run:
+                self._api('settings.filters.get', id=filter_id)
+            else:
+                self._api(
+                    'settings.filters.delete',
+                    id=filter_id
+                )
+        except Exception as e:
+            if http_status(e) == 404:
+                raise NotFound(f"Filter not found: {filter_id}") from None
+            raise
+        if dry_run:
+            return self._planned([('settings.filters.delete', {'id': filter_id})], filter_id=filter_id)
+
+        return {
+            'deleted': True,
+            'filter_id': filter_id
+        }
+
+    def _cmd_vacation_get(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Get the vacation responder setting."""
+        return describe_vacation(self._api('settings.getVacation'))
+
+    def _cmd_vacation_set(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Update the vacation responder; fields that aren't passed keep their
+        current values."""
+        # Validate everything against an empty setting before reading the real one
+        build_vacation(params, {})
+        dry_run = self._dry_run(params)
+        body = build_vacation(params, self._api('settings.getVacation'))
+        if dry_run:
+            return self._planned([('settings.updateVacation', {'body': body})], vacation=describe_vacation(body))
+
+        return describe_vacation(self._api('settings.updateVacation', body=body))
+
+    def _cmd_sendas_list(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """List the addresses mail can be sent from."""
+        aliases = self._send_as()
+        return {
+            'aliases': aliases,
+            'count': len(aliases)
+        }
+
+    def _send_as(self) -> List[Dict[str, Any]]:
+        results = self._api('settings.sendAs.list')
+        return [describe_send_as(alias) for alias in results.get('sendAs', [])]
+
+    @staticmethod
+    def _dry_run(params: Dict[str, Any]) -> bool:
+        """Whether the call is a dry run. Check it after validating params and
+        before the first write."""
+        dry_run = params.get("dry_run", False)
+        if not isinstance(dry_run, bool):
+            raise ValueError("dry_run must be a boolean")
+        return dry_run
+
+    @staticmethod
+    def _planned(calls: List[Tuple[str, Dict[str, Any]]], **fields) -> Dict[str, Any]:
+        """A dry run's response: the Gmail API calls that would have been made."""
+        return dict(fields, dry_run=True, would_call=[
+            {'method': name, 'params': dict(params, userId='me')} for name, params in calls
+        ])
+
+    def _labels(self) -> List[Dict[str, Any]]:
+        """All system and user labels."""
+        return self._api('labels.list').get('labels', [])
+
+    def _label_id(self, name: str) -> str:
+        """Resolve a label name (case-insensitive) or system label id."""
+        for label in self._labels():
+            if name.lower() in (label['name'].lower(), label['id'].lower()):
+                return label['id']
+        raise NotFound(f"Label not found: {name!r}")
+
+    def _cmd_batch(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Run operations in order, isolating each one's failure.
+
+        Every operation goes through `dispatch`, so it gets the same account
+        resolution, caching, metrics, and timeout as a standalone call.
+        Results come back in request order as
+        `{index, method, ok, result}` or `{index, method, ok, error, error_type}`.
+        """
+        operations = params.get("operations")
+        if not isinstance(operations, list) or not operations:
+            raise ValueError("operations must be a non-empty list of {method, params} objects")
+        if len(operations) > BATCH_MAX_OPERATIONS:
+            raise ValueError(f"A batch can hold at most {BATCH_MAX_OPERATIONS} operations (got {len(operations)})")
+
+        results = []
+        for index, operation in enumerate(operations):
+            method = operation.get("method") if isinstance(operation, dict) else None
+            entry: Dict[str, Any] = {'index': index, 'method': method}
+            try:
+                if not isinstance(method, str) or not method:
+                    raise ValueError("operation needs a method name")
+                if method == "gmail.batch":
+                    raise ValueError("gmail.batch can't be nested")
+                if method in UNBATCHABLE_METHODS:
+                    raise ValueError(f"{method} can't run inside gmail.batch")
+                op_params = operation.get("params", {})
+                if op_params is None:
+                    op_params = {}
+                if not isinstance(op_params, dict):
+                    raise ValueError("operation params must be an object")
+                entry.update(ok=True, result=self.dispatch(method, op_params))
+            except Exception as e:
+                entry.update(ok=False, error=str(e), error_type=type(e).__name__)
+            results.append(entry)
+
+        succeeded = sum(1 for entry in results if entry['ok'])
+        return {
+            'results': results,
+            'count': len(results),
+            'succeeded': succeeded,
+            'failed': len(results) - succeeded
+        }
+
+    def _cmd_bulk_modify(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Apply one action to every message matching `query`, in batches.
+
+        Stops at the first failed batch, or when `timeout` runs out, and
+        reports how far it got; messages after that point are `skipped`.
+        """
+        query = params.get("query")
+        if not query or not isinstance(query, str):
+            raise ValueError("query parameter is required")
+        action = params.get("action")
+        if action not in BULK_ACTIONS:
+            raise ValueError(f"action must be one of: {', '.join(BULK_ACTIONS)} (got {action!r})")
+        label = params.get("label")
+        if action in ("add_label", "remove_label") and not label:
+            raise ValueError(f"label parameter is required for {action}")
+        max_messages = parse_limit(params.get("max_messages"), default=BULK_DEFAULT_MAX_MESSAGES,
+                                   max_limit=BULK_HARD_MAX_MESSAGES, name="max_messages")
+        timeout = params.get("timeout", BULK_DEFAULT_TIMEOUT_SECS)
+        if isinstance(timeout, bool) or not isinstance(timeout, (int, float)) or timeout <= 0:
+            raise ValueError("timeout must be a positive number of seconds")
+        deadline = time.monotonic() + timeout
+
+        if action == "trash":
+            add, remove = None, None
+        elif action == "add_label":
+            add, remove = [self._label_id(label)], []
+        elif action == "remove_label":
+            add, remove = [], [self._label_id(label)]
+        else:
+            add, remove = BULK_ACTIONS[action]
+
+        dry_run = self._dry_run(params)
+        ids, truncated, timed_out = self._matching_ids(query, max_messages, deadline)
+        result = {
+            'query': query,
+            'action': action,
+            'label': label,
+            'dry_run': dry_run,
+            'matched': len(ids),
+            'truncated': truncated,
+        }
+
+        if dry_run:
+            sample = []
+            for message_id in ids[:BULK_SAMPLE_SIZE]:
+                detail = self._api('messages.get', id=message_id, format='metadata',
+                                   metadataHeaders=SUMMARY_HEADERS)
+                summary = EmailSummary.from_api(detail)
+                sample.append({'id': summary.id, 'from': summary.from_, 'subject': summary.subject})
+            if action == "trash":
+                calls = [('messages.trash', {'id': message_id}) for message_id in ids]
+            else:
+                calls = [('messages.batchModify', {'body': {'ids': ids[start:start + BULK_BATCH_SIZE],
+                                                            'addLabelIds': add, 'removeLabelIds': remove}})
+                         for start in range(0, len(ids), BULK_BATCH_SIZE)]
+            return self._planned(calls, **result, sample=sample, timed_out=timed_out)
+
+        succeeded = failed = 0
+        error = None
+        for start in range(0, len(ids), BULK_BATCH_SIZE):
+            if time.monotonic() >= deadline:
+                timed_out = True
+                break
+            batch = ids[start:start + BULK_BATCH_SIZE]
+            try:
+                if action == "trash":
+                    for message_id in batch:
+                        self._api('messages.trash', id=message_id)
+                        succeeded += 1
+                else:
+                    self._api('messages.batchModify',
+                              body={'ids': batch, 'addLabelIds': add, 'removeLabelIds': remove})
+                    succeeded += len(batch)
+            except Exception as e:
+                done_in_batch = succeeded - start
+                failed = len(batch) - done_in_batch
+                error = f"Batch starting at message {start} failed: {e}"
+                break
+
+        result.update(
+            succeeded=succeeded,
+            failed=failed,
+            skipped=len(ids) - succeeded - failed,
+            stopped_early=error is not None or timed_out,
+            timed_out=timed_out,
+            error=error,
+        )
+        return result
+
+    def _cmd_archive(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Remove messages from the inbox."""
+        return self._modify_each(params, 'archive', remove=['INBOX'])
+
+    def _cmd_unarchive(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Add messages back to the inbox."""
+        return self._modify_each(params, 'unarchive', add=['INBOX'])
+
+    def _modify_each(self, params: Dict[str, Any], action: str,
+                     add: Optional[List[str]] = None, remove: Optional[List[str]] = None) -> Dict[str, Any]:
+        """Apply one label change to each named message on its own.
+
+        Unlike bulk_modify, a failure doesn't stop the rest: every id gets
+        `{id, ok, labels}` or `{id, ok, error, error_type}`.
+        """
+        ids = self._message_ids_param(params)
+        body = {'addLabelIds': add or [], 'removeLabelIds': remove or []}
+        if self._dry_run(params):
+            return self._planned([('messages.modify', {'id': message_id, 'body': body}) for message_id in ids],
+                                 action=action, count=len(ids))
+
+        results = []
+        for message_id in ids:
+            entry: Dict[str, Any] = {'id': message_id}
+            try:
+                msg = self._api('messages.modify', id=message_id, body=body)
+                entry.update(ok=True, labels=msg.get('labelIds', []))
+            except Exception as e:
+                entry.update(ok=False, error=str(e), error_type=type(e).__name__)
+            results.append(entry)
+
+        succeeded = sum(1 for entry in results if entry['ok'])
+        return {
+            'action': action,
+            'results': results,
+            'count': len(results),
+            'succeeded': succeeded,
+            'failed': len(results) - succeeded
+        }
+
+    @staticmethod
+    def _message_ids_param(params: Dict[str, Any]) -> List[str]:
+        """The ids named by `message_id` and/or `message_ids`, deduplicated in order."""
+        ids = []
+        if params.get("message_id") is not None:
+            ids.append(params["message_id"])
+        many = params.get("message_ids")
+        if many is not None:
+            if not isinstance(many, list):
+                raise ValueError("message_ids must be a list of message ids")
+            ids.extend(many)
+        if not ids:
+            raise ValueError("message_id or message_ids parameter is required")
+        if not all(isinstance(message_id, str) and message_id for message_id in ids):
+            raise ValueError("message ids must be non-empty strings")
+        ids = list(dict.fromkeys(ids))
+        if len(ids) > ARCHIVE_MAX_MESSAGES:
+            raise ValueError(f"At most {ARCHIVE_MAX_MESSAGES} messages per call (got {len(ids)})")
+        return ids
+
+    def _matching_ids(self, query: str, cap: int, deadline: float):
+        """Page through search results for up to `cap` ids.
+
+        Returns `(ids, truncated, timed_out)`; `truncated` means more
+        messages matched than the cap allowed.
+        """
+        ids: List[str] = []
+        page_token = None
+        while len(ids) < cap:
+            if time.monotonic() >= deadline:
+                return ids, False, True
+            kwargs = {'pageToken': page_token} if page_token else {}
+            results = self._api('messages.list', q=query, maxResults=min(500, cap - len(ids)), **kwargs)
+            ids.extend(msg['id'] for msg in results.get('messages', []))
+            page_token = results.get('nextPageToken')
+            if not page_token:
+                return ids[:cap], False, False
+        return ids[:cap], bool(page_token), False
+
+    @staticmethod
+    def _recipients(params: Dict[str, Any], name: str) -> Optional[str]:
+        """A recipient param, given as a comma-separated string or a list of
+        addresses, as a header value."""
+        value = params.get(name)
+        if isinstance(value, list):
+            if not all(isinstance(address, str) and address.strip() for address in value):
+                raise ValueError(f"{name} must be a string or a list of addresses")
+            return ", ".join(address.strip() for address in value) or None
+        if value is not None and not isinstance(value, str):
+            raise ValueError(f"{name} must be a string or a list of addresses")
+        return value
+
+    def _build_message(self, params: Dict[str, Any], message_id: Optional[str] = None):
+        """Build a base64url-encoded RFC 2822 message from send-style params.
+
+        Returns `(raw, attached_files)`.
+        """
+        to = self._recipients(params, "to")
         subject = params.get("subject")
         body = params.get("body")
-        cc = params.get("cc")
-        bcc = params.get("bcc")
+        body_html = params.get("body_html")
+        body_markdown = params.get("body_markdown")
+        cc = self._recipients(params, "cc")
+        bcc = self._recipients(params, "bcc")
         attachments = params.get("attachments", [])
 
+        if body_html and body_markdown:
+            raise ValueError("Pass either body_html or body_markdown, not both")
+        if body_markdown:
+            # The markdown source doubles as the plain-text part
+            body = body or body_markdown
+            body_html = to_html_document(body_markdown)
         if not all([to, subject, body]):
-            raise ValueError("to, subject, and body parameters are required")
+            raise ValueError("to, subject, and body (or body_markdown) parameters are required")
+        sender = resolve_from(params["from"], self._send_as())[0] if params.get("from") is not None else None
+
+        # Plain text alone, or plain text and HTML as alternatives of each other
+        if body_html:
+            content = MIMEMultipart('alternative')
+            content.attach(MIMEText(body, 'plain'))
+            content.attach(MIMEText(body_html, 'html'))
+        else:
+            content = MIMEText(body, 'plain') if attachments else MIMEText(body)
 
-        # Build message - multipart if we have attachments
+        # Attachments go alongside the content in a multipart/mixed
         if attachments:
             message = MIMEMultipart()
-            message.attach(MIMEText(body, 'plain'))
+            message.attach(content)
         else:
-            message = MIMEText(body)
+            message = content
 
+        if sender:
+            message['from'] = sender
         message['to'] = to
         message['subject'] = subject
         if cc:
             message['cc'] = cc
         if bcc:
             message['bcc'] = bcc
+        if message_id:
+            message['Message-ID'] = message_id
 
         # Process attachments
         attached_files = []
@@ -360,7 +2204,9 @@ class GmailModule:
                     raise ValueError("Attachment must have 'filename' or 'name' field")
 
                 # Guess MIME type
-                mime_type, _ = mimetypes.guess_type(filename)
+                mime_type = attachment.get('mime_type')
+                if not mime_type or '/' not in mime_type:
+                    mime_type, _ = mimetypes.guess_type(filename)
                 if mime_type is None:
                     mime_type = 'application/octet-stream'
                 main_type, sub_type = mime_type.split('/', 1)
@@ -374,18 +2220,7 @@ class GmailModule:
                 attached_files.append({'filename': filename, 'size': len(file_data)})
 
         raw = base64.urlsafe_b64encode(message.as_bytes()).decode()
-
-        result = self.service.users().messages().send(
-            userId='me',
-            body={'raw': raw}
-        ).execute()
-
-        return {
-            'sent': True,
-            'message_id': result.get('id'),
-            'thread_id': result.get('threadId'),
-            'attachments': attached_files if attached_files else None
-        }
+        return raw, attached_files
 
     def _cmd_thread(self, params: Dict[str, Any]) -> Dict[str, Any]:
         """Get email thread by ID."""
@@ -393,29 +2228,27 @@ class GmailModule:
         if not thread_id:
             raise ValueError("thread_id parameter is required")
 
-        thread = self.service.users().threads().get(
-            userId='me',
-            id=thread_id,
-            format='metadata',
-            metadataHeaders=['From', 'Subject', 'Date']
-        ).execute()
-
-        messages = []
-        for msg in thread.get('messages', []):
-            headers = {h['name']: h['value'] for h in msg.get('payload', {}).get('headers', [])}
-            messages.append({
-                'id': msg['id'],
-                'from': headers.get('From', ''),
-                'subject': headers.get('Subject', ''),
-                'date': headers.get('Date', ''),
-                'snippet': msg.get('snippet', '')[:100]
-            })
+        include_bodies = params.get("include_bodies", True)
+        if not isinstance(include_bodies, bool):
+            raise ValueError("include_bodies must be a boolean")
+        max_messages = params.get("max_messages")
+        if max_messages is not None:
+            max_messages = parse_limit(max_messages, max_limit=self.max_limit, name="max_messages")
+        include_attachments = self._include_attachments(params)
+        body_limit = parse_char_limit(params.get("truncate_body"), "truncate_body")
+
+        if include_bodies or include_attachments:
+            thread = self._api('threads.get', id=thread_id, format='full')
+        else:
+            thread = self._api(
+                'threads.get',
+                id=thread_id,
+                format='metadata',
+                metadataHeaders=THREAD_HEADERS
+            )
 
-        return {
-            'thread_id': thread_id,
-            'messages': messages,
-            'count': len(messages)
-        }
+        return Thread.from_api(thread, include_bodies=include_bodies, max_messages=max_messages,
+                               include_attachments=include_attachments, body_limit=body_limit).to_dict()
 
     def _cmd_read(self, params: Dict[str, Any]) -> Dict[str, Any]:
         """Read full email with body and attachment info."""
@@ -424,62 +2257,28 @@ class GmailModule:
             raise ValueError("message_id parameter is required")
 
         # Get full message
-        msg = self.service.users().messages().get(
-            userId='me',
+        msg = self._api(
+            'messages.get',
             id=message_id,
             format='full'
-        ).execute()
-
-        # Extract headers
-        headers = {h['name']: h['value'] for h in msg.get('payload', {}).get('headers', [])}
-
-        # Extract body and attachments
-        body_text = None
-        body_html = None
-        attachments = []
-
-        def process_parts(parts, parent_mime_type=None):
-            nonlocal body_text, body_html
-            for part in parts:
-                mime_type = part.get('mimeType', '')
-                part_body = part.get('body', {})
-                filename = part.get('filename', '')
-
-                if mime_type == 'text/plain' and not filename:
-                    data = part_body.get('data')
-                    if data:
-                        body_text = base64.urlsafe_b64decode(data).decode('utf-8', errors='replace')
-                elif mime_type == 'text/html' and not filename:
-                    data = part_body.get('data')
-                    if data:
-                        body_html = base64.urlsafe_b64decode(data).decode('utf-8', errors='replace')
-                elif filename or part_body.get('attachmentId'):
-                    # This is an attachment
-                    attachments.append({
-                        'id': part_body.get('attachmentId'),
-                        'filename': filename or 'untitled',
-                        'mime_type': mime_type,
-                        'size': part_body.get('size', 0)
-                    })
-
-                # Recursively process nested parts
-                if 'parts' in part:
-                    process_parts(part['parts'], mime_type)
+        )
 
         payload = msg.get('payload', {})
-
-        # Handle simple messages (no parts)
-        if payload.get('body', {}).get('data'):
-            mime_type = payload.get('mimeType', 'text/plain')
-            data = payload['body']['data']
-            if 'text/plain' in mime_type:
-                body_text = base64.urlsafe_b64decode(data).decode('utf-8', errors='replace')
-            elif 'text/html' in mime_type:
-                body_html = base64.urlsafe_b64decode(data).decode('utf-8', errors='replace')
-
-        # Handle multipart messages
-        if 'parts' in payload:
-            process_parts(payload['parts'])
+        headers = header_map(payload)
+        content = extract_content(payload)
+        body_text = content['body_text']
+        body_html = content['body_html']
+        attachments = content['attachments']
+
+        # Restricted messages only carry placeholder content; don't pass it
+        # off as the real body
+        restriction = detect_restriction(msg)
+        snippet = msg.get('snippet', '')
+        if restriction:
+            body_text = None
+            body_html = None
+            snippet = ''
+        charge(body_text, body_html)
 
         return {
             'id': msg['id'],
@@ -491,10 +2290,186 @@ class GmailModule:
             'date': headers.get('Date', ''),
             'body_text': body_text,
             'body_html': body_html,
-            'snippet': msg.get('snippet', ''),
+            'snippet': snippet,
+            'labels': msg.get('labelIds', []),
+            'attachments': attachments if attachments else None,
+            'has_attachments': len(attachments) > 0,
+            'content_restricted': restriction,
+            'content_note': restriction_note(restriction)
+        }
+
+    def _cmd_message(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Get a single message by ID in the requested format."""
+        message_id = params.get("message_id")
+        if not message_id:
+            raise ValueError("message_id parameter is required")
+
+        fmt = params.get("format", "full")
+        if fmt not in MESSAGE_FORMATS:
+            raise ValueError(f"format must be one of: {', '.join(MESSAGE_FORMATS)} (got {fmt!r})")
+        include_attachments = self._include_attachments(params)
+        if include_attachments and fmt not in ('full', 'raw'):
+            raise ValueError(f"include_attachments needs format full or raw (got {fmt!r})")
+        body_limit = parse_char_limit(params.get("truncate_body"), "truncate_body")
+
+        msg = self._api(
+            'messages.get',
+            id=message_id,
+            format=fmt
+        )
+
+        if fmt == 'raw':
+            parsed = parse_raw(msg.get('raw', ''))
+            payload = payload_from_email(parsed)
+            content = extract_email_content(parsed)
+        else:
+            payload = msg.get('payload', {})
+            content = extract_content(payload) if fmt == 'full' else {}
+
+        restriction = detect_restriction({'labelIds': msg.get('labelIds', []), 'payload': payload})
+        snippet = msg.get('snippet', '')
+        if restriction:
+            content = {}
+            snippet = ''
+
+        attachments = content.get('attachments') or []
+        result = {
+            'id': msg.get('id', message_id),
+            'thread_id': msg.get('threadId'),
+            'format': fmt,
+            'headers': header_map(payload),
+            'snippet': snippet,
             'labels': msg.get('labelIds', []),
+            'body_text': content.get('body_text'),
+            'body_html': content.get('body_html'),
             'attachments': attachments if attachments else None,
-            'has_attachments': len(attachments) > 0
+            'content_restricted': restriction,
+            'content_note': restriction_note(restriction)
+        }
+        if include_attachments and not restriction:
+            result.update(attachments=attachment_metadata(attachments), has_attachments=bool(attachments),
+                          attachment_count=len(attachments))
+        if body_limit is not None:
+            result['body_text'], text_cut = truncate_text(result['body_text'], body_limit)
+            result['body_html'], html_cut = truncate_text(result['body_html'], body_limit)
+            result['body_truncated'] = text_cut or html_cut
+        charge(result['body_text'], result['body_html'])
+        return result
+
+    def _cmd_export_raw(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Save a message's exact RFC 822 source as a .eml file.
+
+        With `inline`, the content comes back base64-encoded instead, for
+        messages up to EXPORT_INLINE_MAX_BYTES. The bytes are Gmail's
+        original, so restricted messages export as whatever Gmail stored
+        (the confidential-mode stub, or the encrypted S/MIME envelope).
+        """
+        message_id = params.get("message_id")
+        if not message_id:
+            raise ValueError("message_id parameter is required")
+        inline = params.get("inline", False)
+        if not isinstance(inline, bool):
+            raise ValueError("inline must be a boolean")
+        output_path = params.get("output_path")
+        if inline and output_path:
+            raise ValueError("Pass either output_path or inline, not both")
+
+        # Check the destination before spending an API call on the download
+        path = None
+        if not inline:
+            if output_path:
+                path = Path(output_path).expanduser().resolve()
+                if path.is_dir():
+                    path = path / f"{message_id}.eml"
+            else:
+                EXPORTS_DIR.mkdir(parents=True, exist_ok=True)
+                path = EXPORTS_DIR / f"{message_id}.eml"
+            if not path.parent.is_dir():
+                raise FileNotFoundError(f"Directory does not exist: {path.parent}")
+            if not os.access(path.parent, os.W_OK):
+                raise PermissionError(f"Directory is not writable: {path.parent}")
+
+        msg = self._api('messages.get', id=message_id, format='raw')
+        raw = msg.get('raw')
+        if not isinstance(raw, str):
+            raise UnexpectedOutput(f"unexpected API output: missing field `raw` in message {message_id}")
+        data = decode_raw(raw)
+        restriction = detect_restriction({
+            'labelIds': msg.get('labelIds', []),
+            'payload': payload_from_email(parse_raw(raw)),
+        })
+
+        result = {
+            'message_id': msg.get('id', message_id),
+            'thread_id': msg.get('threadId'),
+            'labels': msg.get('labelIds', []),
+            'size': len(data),
+            'content_restricted': restriction,
+            'content_note': restriction_note(restriction),
+        }
+        if path is None:
+            if len(data) > EXPORT_INLINE_MAX_BYTES:
+                raise ValueError(
+                    f"Message is {len(data)} bytes, over the {EXPORT_INLINE_MAX_BYTES}-byte inline limit; "
+                    f"pass output_path to save it to a file instead"
+                )
+            result['data'] = base64.b64encode(data).decode('ascii')
+            return result
+
+        with open(path, 'wb') as f:
+            f.write(data)
+        result.update(saved=True, path=str(path))
+        return result
+
+    def _cmd_import_raw(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Insert a .eml file into the mailbox as if it had been delivered.
+
+        Nothing is sent. The file is checked to parse as a message and to fit
+        Gmail's size limit before anything is uploaded. Without `labels` the
+        message lands in All Mail only.
+        """
+        path_param = params.get("path")
+        if not path_param:
+            raise ValueError("path parameter is required")
+        date_from_header = params.get("internal_date_from_header", False)
+        if not isinstance(date_from_header, bool):
+            raise ValueError("internal_date_from_header must be a boolean")
+        labels = params.get("labels") or []
+        if not isinstance(labels, list) or not all(isinstance(label, str) and label for label in labels):
+            raise ValueError("labels must be a list of label names or ids")
+
+        path = Path(path_param).expanduser().resolve()
+        if not path.is_file():
+            raise FileNotFoundError(f"File does not exist: {path}")
+        size = path.stat().st_size
+        if size > IMPORT_MAX_BYTES:
+            raise ValueError(f"{path.name} is {size} bytes; Gmail imports messages up to {IMPORT_MAX_BYTES} bytes (35 MB)")
+
+        data = path.read_bytes()
+        try:
+            message = parse_eml(data)
+        except ValueError as e:
+            raise ValueError(f"{path.name} is not a valid RFC 822 message: {e}") from None
+        if date_from_header and getattr(message['Date'], 'datetime', None) is None:
+            raise ValueError(
+                f"{path.name} has no parseable Date header; omit internal_date_from_header to use the import time"
+            )
+
+        label_ids = [self._label_id(label) for label in labels]
+        call = {
+            'body': {'raw': base64.urlsafe_b64encode(data).decode('ascii'), 'labelIds': label_ids},
+            'internalDateSource': 'dateHeader' if date_from_header else 'receivedTime',
+        }
+        if self._dry_run(params):
+            return self._planned([('messages.import_', call)], size=size, path=str(path))
+        result = self._api('messages.import_', **call)
+        return {
+            'imported': True,
+            'message_id': result.get('id'),
+            'thread_id': result.get('threadId'),
+            'labels': result.get('labelIds', label_ids),
+            'size': size,
+            'path': str(path),
         }
 
     def _cmd_download_attachment(self, params: Dict[str, Any]) -> Dict[str, Any]:
@@ -508,15 +2483,7 @@ class GmailModule:
         if not attachment_id:
             raise ValueError("attachment_id parameter is required")
 
-        # Get attachment data
-        attachment = self.service.users().messages().attachments().get(
-            userId='me',
-            messageId=message_id,
-            id=attachment_id
-        ).execute()
-
-        data = attachment.get('data', '')
-        file_data = base64.urlsafe_b64decode(data)
+        file_data = self._fetch_attachment(message_id, attachment_id)
         size = len(file_data)
 
         if save_path:
@@ -536,3 +2503,512 @@ class GmailModule:
                 'data': base64.b64encode(file_data).decode('ascii'),
                 'size': size
             }
+
+    def _cmd_get_attachment(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Get an attachment, either saved to `save_path` or inline as base64."""
+        message_id = params.get("message_id")
+        attachment_id = params.get("attachment_id")
+        save_path = params.get("save_path")
+
+        if not message_id:
+            raise ValueError("message_id parameter is required")
+        if not attachment_id:
+            raise ValueError("attachment_id parameter is required")
+
+        # Check the destination before spending an API call on the download
+        path = self._attachment_path(save_path) if save_path else None
+
+        file_data = self._fetch_attachment(message_id, attachment_id)
+
+        if path is not None:
+            # Exclusive create, in case the file appeared since the check
+            with open(path, 'xb') as f:
+                f.write(file_data)
+            return {
+                'saved': True,
+                'path': str(path),
+                'size': len(file_data)
+            }
+
+        return {
+            'data': base64.b64encode(file_data).decode('ascii'),
+            'mime_type': sniff_mime_type(file_data),
+            'size': len(file_data)
+        }
+
+    def _attachment_path(self, save_path: str) -> Path:
+        """Validate `save_path` for gmail.get_attachment.
+
+        The file must be new, and inside `attachments_root` without going
+        through `..` segments or symlinked directories on the way there.
+        """
+        path = Path(save_path).expanduser()
+        if '..' in path.parts:
+            raise ValueError(f"save_path must not contain '..' segments: {save_path}")
+        path = Path(os.path.abspath(path))
+        root = Path(os.path.abspath(self.attachments_root))
+        # The root itself may be reached through a symlink (/tmp on macOS)
+        for base in (root, root.resolve()):
+            try:
+                below_root = path.relative_to(base).parts
+                break
+            except ValueError:
+                continue
+        else:
+            raise PermissionError(f"save_path must be inside {root}: {path}")
+        if not below_root:
+            raise ValueError(f"save_path is a directory, expected a file path: {path}")
+
+        parent = path.parent
+        if not parent.is_dir():
+            raise FileNotFoundError(f"Directory does not exist: {parent}")
+        directory = base
+        for part in below_root[:-1]:
+            directory = directory / part
+            if directory.is_symlink():
+                raise PermissionError(f"save_path must not go through a symlinked directory: {directory}")
+        if not os.access(parent, os.W_OK):
+            raise PermissionError(f"Directory is not writable: {parent}")
+        if path.is_dir():
+            raise ValueError(f"save_path is a directory, expected a file path: {path}")
+        if os.path.lexists(path):
+            raise FileExistsError(f"File already exists, not overwriting: {path}")
+        return path
+
+    def _fetch_attachment(self, message_id: str, attachment_id: str) -> bytes:
+        """Download and decode an attachment's bytes."""
+        attachment = self._api(
+            'messages.attachments.get',
+            messageId=message_id,
+            id=attachment_id
+        )
+        return base64.urlsafe_b64decode(attachment.get('data', ''))
+
+    def _cmd_auth_status(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Full token metadata: validity, expiry, scopes, and the last probe.
+
+        Reads the token file only; never refreshes or signs in. A token that
+        looks refreshable still counts as broken if the last probe's refresh
+        was rejected.
+        """
+        account = self._current_account()
+        token = check_token(account, refresh=False)
+        probe = self.auth_monitor.current(account)
+        authenticated = token["usable"] and (probe is None or probe["usable"])
+        reason = token["reason"] or (probe["reason"] if probe else None)
+
+        expires_in = None
+        if token["token_expiry"]:
+            # google-auth stores expiry as naive UTC
+            expiry = datetime.datetime.fromisoformat(token["token_expiry"])
+            expires_in = round((expiry - datetime.datetime.utcnow()).total_seconds())
+
+        return {
+            'account': account.name,
+            'authenticated': authenticated,
+            'needs_auth': not authenticated,
+            'token_cached': token["token_cached"],
+            'token_valid': token["token_valid"],
+            'token_refreshable': token["token_refreshable"],
+            'token_expiry': token["token_expiry"],
+            'expires_in_secs': expires_in,
+            'scopes': token["token_scopes"],
+            'missing_scopes': token["missing_scopes"],
+            'reason': None if authenticated else reason,
+            'last_checked': probe["checked_at"] if probe else None,
+            'error': token.get("token_error"),
+            'remediation': None if authenticated else signin_hint(account.name),
+        }
+
+    def _cmd_auth_login(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Start (or return the pending) device-code login for an account."""
+        account = self._current_account()
+        with self._login_lock:
+            login = self._logins.get(account.name)
+            if login is None or login.finished:
+                login = start_device_login(account, on_saved=self._forget_backend)
+                self._logins[account.name] = login
+        return login.to_dict()
+
+    def _cmd_auth_login_status(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Report the state of an account's most recent device-code login."""
+        account = self._current_account()
+        login = self._logins.get(account.name)
+        if login is None:
+            raise ValueError(f"No device login started for account '{account.name}'; call gmail.auth_login")
+        return login.to_dict()
+
+    def _cmd_auth_login_cancel(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Cancel an account's pending device-code login."""
+        account = self._current_account()
+        login = self._logins.get(account.name)
+        cancelled = login.cancel() if login is not None else False
+        return {'account': account.name, 'cancelled': cancelled}
+
+    def _cmd_watch(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Start a new-mail poller for the account. At most one per account.
+
+        With `topic`, register for Gmail push notifications instead.
+        """
+        if params.get("topic") is not None:
+            return self._push_watch(params)
+        account = getattr(self._local, "account", None)
+        name = self._watch_name(account)
+        command = params.get("command")
+        webhook = params.get("webhook")
+        if command and webhook:
+            raise ValueError("Pass at most one of command and webhook")
+
+        interval = params.get("interval", DEFAULT_INTERVAL_SECS)
+        if isinstance(interval, bool) or not isinstance(interval, (int, float)) or interval <= 0:
+            raise ValueError(f"interval must be a positive number of seconds (got {interval!r})")
+
+        with self._watch_lock:
+            existing = self._watches.get(name)
+            if existing is not None and existing.active:
+                return dict(existing.status(), started=False)
+
+            if command:
+                sink = CommandSink(command)
+            elif webhook:
+                sink = WebhookSink(webhook)
+            else:
+                sink = QueueSink(EVENTS_DIR / name)
+            watch = Watch(name, self._api_for(account), sink, interval=interval)
+            # Take the baseline now so setup errors reach the caller
+            watch.poll_once()
+            if watch.last_error:
+                raise RuntimeError(f"Failed to start watch: {watch.last_error}")
+            self._watches[name] = watch.start()
+        return dict(watch.status(), started=True)
+
+    def _push_watch(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Register, or renew, push notifications to a Pub/Sub topic."""
+        if any(params.get(name) is not None for name in ("command", "webhook", "interval")):
+            raise ValueError("topic can't be combined with command, webhook, or interval")
+        labels = params.get("labels") or []
+        if not isinstance(labels, list) or not all(isinstance(label, str) for label in labels):
+            raise ValueError("labels must be a list of label names or ids")
+        behavior = params.get("label_filter_behavior", "include")
+        topic = params.get("topic")
+        validate_watch(topic, behavior)
+        body: Dict[str, Any] = {'topicName': topic}
+        if labels:
+            body.update(labelIds=[self._label_id(label) for label in labels], labelFilterBehavior=behavior)
+
+        name = self._watch_name(getattr(self._local, "account", None))
+        result = self._api('watch', body=body)
+        registration = {
+            'topic': topic,
+            'labels': labels,
+            'label_filter_behavior': behavior,
+            'history_id': str(result['historyId']),
+            'expiration_ms': result['expiration'],
+        }
+        with self._watch_lock:
+            renewed = name in self._push_watches
+            self._push_watches[name] = registration
+        return dict(push_status(registration), account=name, renewed=renewed)
+
+    def _cmd_unwatch(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Stop the account's poller, and its push watch if it has one."""
+        name = self._watch_name(getattr(self._local, "account", None))
+        push = params.get("push", False)
+        if not isinstance(push, bool):
+            raise ValueError("push must be a boolean")
+        with self._watch_lock:
+            watch = self._watches.pop(name, None)
+            registration = self._push_watches.pop(name, None)
+        if watch is not None:
+            watch.stop()
+        if registration is not None or push:
+            self._api('stop')
+        return {'account': name, 'stopped': watch is not None, 'push_stopped': registration is not None or push}
+
+    def _cmd_watch_status(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Report the account's poller, or `active: false` if there is none,
+        and its push watch, or `push: null`."""
+        name = self._watch_name(getattr(self._local, "account", None))
+        watch = self._watches.get(name)
+        registration = self._push_watches.get(name)
+        if watch is None:
+            status = {'account': name, 'active': False, 'events_fired': 0, 'last_poll': None}
+        else:
+            status = watch.status()
+        status['push'] = push_status(registration) if registration else None
+        return status
+
+    def _cmd_history(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Message changes since `start_history_id`, a page of records at a time.
+
+        `history_id` in the response is where the next call should start
+        once every page has been read. Label changes are compacted within
+        each page.
+        """
+        start = history_id_param(params)
+        history_types = history_types_param(params.get("history_types"))
+        limit = parse_limit(params.get("limit"), default=DEFAULT_MAX_LIMIT, max_limit=self.max_limit)
+        label_filter = params.get("label_filter")
+        if label_filter is not None and not isinstance(label_filter, str):
+            raise ValueError("label_filter must be a label name or id")
+
+        list_params = {'historyTypes': history_types} if history_types else {}
+        if label_filter:
+            list_params['labelId'] = self._label_id(label_filter)
+        try:
+            page = self._api('history.list', startHistoryId=start, maxResults=limit,
+                             **list_params, **self._page_param(params))
+        except Exception as e:
+            if http_status(e) == 404:
+                raise FullSyncRequired(start) from None
+            raise
+
+        changes = compact_changes(history_changes(page.get('history', [])))
+        return {
+            'start_history_id': start,
+            'history_id': str(page.get('historyId', start)),
+            'changes': changes,
+            'count': len(changes),
+            'next_page_token': page.get('nextPageToken'),
+        }
+
+    def _watch_name(self, account: Optional[Account]) -> str:
+        """Key for per-account watch state, also used as its events directory."""
+        if account is not None:
+            return account.name
+        return self.accounts.default_name() or DEFAULT_ACCOUNT
+
+    def _forget_backend(self, account: Account):
+        """Drop an account's connection so the next call picks up a new token."""
+        with self._backend_lock:
+            self._account_backends.pop(account.name, None)
+        self.auth_monitor.forget(account.name)
+
+
+def load_credentials(account: Account) -> Optional[Credentials]:
+    """An account's cached token, or None if it has none."""
+    if not account.token_file.exists():
+        return None
+    with open(account.token_file, 'rb') as f:
+        return pickle.load(f)
+
+
+def save_credentials(account: Account, creds: Credentials):
+    """Cache an account's token. Every auth flow writes it this way.
+
+    Written atomically and under the token's lock; see gmail_lib.tokens.
+    A JSON copy goes next to it for the daemon's native backend.
+    """
+    with token_lock(account.token_file):
+        write_token(account.token_file, pickle.dumps(creds))
+        write_token(account.token_json_file, token_json(creds))
+
+
+def token_json(creds: Credentials) -> bytes:
+    """The fields the native backend needs to use and refresh a token.
+
+    `expiry` is UTC, as google-auth keeps it.
+    """
+    expiry = getattr(creds, "expiry", None)
+    scopes = getattr(creds, "scopes", None)
+    return json.dumps({
+        "token": getattr(creds, "token", None),
+        "refresh_token": getattr(creds, "refresh_token", None),
+        "token_uri": getattr(creds, "token_uri", None),
+        "client_id": getattr(creds, "client_id", None),
+        "client_secret": getattr(creds, "client_secret", None),
+        "scopes": sorted(scopes) if scopes else None,
+        "expiry": expiry.replace(tzinfo=None).isoformat() + "Z" if expiry else None,
+    }).encode()
+
+
+def token_status(account: Account) -> Dict[str, Any]:
+    """Report whether an account has a usable cached token, without refreshing it."""
+    status = {"token_cached": account.token_file.exists(), "token_valid": False,
+              "token_refreshable": False, "token_expiry": None, "token_scopes": None}
+    if not status["token_cached"]:
+        return status
+    try:
+        with open(account.token_file, 'rb') as f:
+            creds = pickle.load(f)
+    except Exception as e:
+        status["token_error"] = f"Unreadable token file: {e}"
+        return status
+
+    status["token_valid"] = bool(getattr(creds, "valid", False))
+    status["token_refreshable"] = bool(
+        getattr(creds, "expired", False) and getattr(creds, "refresh_token", None)
+    )
+    expiry = getattr(creds, "expiry", None)
+    status["token_expiry"] = expiry.isoformat() if expiry else None
+    scopes = getattr(creds, "scopes", None)
+    status["token_scopes"] = sorted(scopes) if scopes else None
+    return status
+
+
+def check_token(account: Account, refresh: bool = True) -> Dict[str, Any]:
+    """Token status plus a verdict on whether calls can authenticate.
+
+    With `refresh`, an expired token is refreshed (and saved) so a revoked
+    grant shows up now rather than on the next real call.
+    """
+    status = token_status(account)
+    reason = None
+    if status.get("token_error"):
+        reason = status["token_error"]
+    elif not status["token_cached"]:
+        reason = f"no cached token at {account.token_file}"
+    elif not (status["token_valid"] or status["token_refreshable"]):
+        reason = "cached token is invalid and can't be refreshed"
+    elif refresh and not status["token_valid"]:
+        try:
+            with token_lock(account.token_file):
+                creds = load_credentials(account)
+                if not creds.valid:
+                    creds.refresh(Request())
+                    save_credentials(account, creds)
+        except RefreshError as e:
+            reason = f"token refresh was rejected ({e})"
+        else:
+            status = token_status(account)
+
+    granted = status["token_scopes"]
+    status.update(
+        account=account.name,
+        usable=reason is None,
+        reason=reason,
+        missing_scopes=[scope for scope in SCOPES if scope not in granted] if granted else [],
+        checked_at=time.time(),
+    )
+    return status
+
+
+def start_device_login(account: Account, on_saved=None) -> DeviceLogin:
+    """Request a device code and poll for the token in the background."""
+    if not account.credentials_file.exists():
+        raise FileNotFoundError(
+            f"No credentials found for account '{account.name}'. "
+            f"Place credentials.json in {account.directory}"
+        )
+    client = OAuthClient.from_file(account.credentials_file)
+
+    def on_token(reply: Dict[str, Any]):
+        save_credentials(account, credentials_from_token(client, reply))
+        if on_saved is not None:
+            on_saved(account)
+
+    return DeviceLogin(account.name, client, SCOPES, on_token).start()
+
+
+def credentials_from_token(client: OAuthClient, reply: Dict[str, Any]) -> Credentials:
+    """Build google-auth credentials from a token endpoint response."""
+    expiry = None
+    if reply.get('expires_in'):
+        # google-auth compares expiry against naive UTC timestamps
+        expiry = datetime.datetime.utcnow() + datetime.timedelta(seconds=int(reply['expires_in']))
+    scope = reply.get('scope')
+    return Credentials(
+        token=reply['access_token'],
+        refresh_token=reply.get('refresh_token'),
+        token_uri=client.token_uri,
+        client_id=client.client_id,
+        client_secret=client.client_secret,
+        scopes=scope.split() if scope else SCOPES,
+        expiry=expiry,
+    )
+
+
+def browser_login(account: Account):
+    """Run the installed-app flow in a local browser and cache the token."""
+    if not account.credentials_file.exists():
+        raise FileNotFoundError(
+            f"No credentials found for account '{account.name}'. "
+            f"Place credentials.json in {account.directory}"
+        )
+    flow = InstalledAppFlow.from_client_secrets_file(str(account.credentials_file), SCOPES)
+    save_credentials(account, flow.run_local_server(port=0))
+
+
+def _cli_registry() -> AccountRegistry:
+    return AccountRegistry(
+        FGP_AUTH_DIR,
+        LEGACY_AUTH_DIR,
+        default_account=os.environ.get("FGP_GMAIL_DEFAULT_ACCOUNT") or None,
+    )
+
+
+def _check_auth_main(args: argparse.Namespace) -> int:
+    """Print token status as JSON; exit 1 if any checked account can't authenticate."""
+    registry = _cli_registry()
+    try:
+        accounts = [registry.resolve(args.account)] if args.account else list(registry.discover().values())
+    except ValueError as e:
+        print(f"error: {e}", file=sys.stderr)
+        return 1
+    if not accounts:
+        print(f"error: No Gmail accounts configured under {FGP_AUTH_DIR}", file=sys.stderr)
+        return 1
+
+    results = [check_token(account, refresh=args.refresh) for account in accounts]
+    print(json.dumps({"accounts": results}, indent=2, default=str))
+    for result in results:
+        if not result["usable"]:
+            print(f"{result['account']}: {result['reason']}. {signin_hint(result['account'])}", file=sys.stderr)
+    return 0 if all(result["usable"] for result in results) else 1
+
+
+def _auth_main(args: argparse.Namespace) -> int:
+    """Interactive login, writing the token the daemon reads."""
+    registry = _cli_registry()
+    try:
+        account = registry.resolve(args.account)
+        if args.browser:
+            print(f"Signing in Gmail account '{account.name}' in your browser...")
+            browser_login(account)
+            print(f"Signed in. Token saved to {account.token_file}")
+            return 0
+        login = start_device_login(account)
+    except (ValueError, FileNotFoundError, DeviceAuthError) as e:
+        print(f"error: {e}", file=sys.stderr)
+        return 1
+
+    print(f"Signing in Gmail account '{account.name}'.")
+    print()
+    print(f"  1. On any device, open: {login.verification_url}")
+    print(f"  2. Enter the code:      {login.user_code}")
+    print()
+    print(f"Waiting for approval (code expires in {login.to_dict()['expires_in'] // 60} min, Ctrl-C to cancel)...")
+    try:
+        state = login.wait()
+    except KeyboardInterrupt:
+        login.cancel()
+        print("\nCancelled.", file=sys.stderr)
+        return 130
+
+    if state == "complete":
+        print(f"Signed in. Token saved to {account.token_file}")
+        return 0
+    print(f"Login {state}: {login.error}", file=sys.stderr)
+    return 1
+
+
+def main(argv: Optional[List[str]] = None) -> int:
+    parser = argparse.ArgumentParser(prog="gmail.py", description="Gmail module utilities")
+    commands = parser.add_subparsers(dest="command", required=True)
+    auth = commands.add_parser("auth", help="Sign in with the OAuth device-code flow")
+    auth.add_argument("--account", help="Account to sign in (defaults to the default account)")
+    auth.add_argument("--browser", action="store_true", help="Sign in with a local browser instead")
+    check = commands.add_parser("check-auth", help="Report whether cached tokens are usable")
+    check.add_argument("--account", help="Account to check (defaults to every account)")
+    check.add_argument("--refresh", action="store_true", help="Refresh expired tokens to confirm the grant")
+
+    args = parser.parse_args(argv)
+    if args.command == "auth":
+        return _auth_main(args)
+    if args.command == "check-auth":
+        return _check_auth_main(args)
+    return 2
+
+
+if __name__ == "__main__":
+    sys.exit(main())
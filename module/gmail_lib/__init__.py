@@ -0,0 +1,7 @@
+"""
+Support package for the Gmail FGP module.
+
+Pure-Python helpers that don't touch the Gmail API directly live here so
+`gmail.py` stays focused on request routing and API calls, and so the
+helpers can be unit tested without Google client libraries installed.
+"""
@@ -0,0 +1,146 @@
+"""
+Gmail account discovery.
+
+Accounts live under the FGP Google auth directory, one subdirectory per
+account:
+
+    ~/.fgp/auth/google/
+        credentials.json          <- single-account layout ("default")
+        gmail_token.pickle
+        work/
+            credentials.json
+            gmail_token.pickle
+        personal/
+            credentials.json
+            gmail_token.pickle
+
+The top-level files, when present, form the `default` account so existing
+single-account setups keep working unchanged. The default account used when
+a call omits `account` is `FGP_GMAIL_DEFAULT_ACCOUNT` if set, otherwise
+`default` if it exists, otherwise the only configured account.
+"""
+
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Dict, List, Optional
+
+CREDENTIALS_FILE = "credentials.json"
+TOKEN_FILE = "gmail_token.pickle"
+# The same token as JSON, for the daemon's native backend, which can't unpickle
+TOKEN_JSON_FILE = "gmail_token.json"
+DEFAULT_ACCOUNT = "default"
+
+# Any of these makes a directory an account. src/native/auth.rs keeps the
+# same list, so both backends find the same accounts.
+ACCOUNT_FILES = (CREDENTIALS_FILE, TOKEN_FILE, TOKEN_JSON_FILE)
+
+
+class UnknownAccount(ValueError):
+    """Raised when a call names an account that isn't configured."""
+
+
+class AuthExpired(RuntimeError):
+    """Raised when an account has no usable token and must sign in again.
+
+    The daemon never starts an interactive OAuth flow itself: it has no
+    terminal or browser to run one in, and waiting on one would block every
+    call behind it.
+    """
+
+    def __init__(self, account: "Account", reason: str):
+        self.account = account.name
+        self.reason = reason
+        super().__init__(f"Account '{account.name}' needs to sign in: {reason}. {signin_hint(account.name)}")
+
+
+def signin_hint(name: str) -> str:
+    """How to (re)authorize an account outside the daemon."""
+    flag = "" if name == DEFAULT_ACCOUNT else f" --account {name}"
+    return f"Run `fgp-gmail auth{flag}` (add --browser to use a local browser) or call gmail.auth_login"
+
+
+@dataclass(frozen=True)
+class Account:
+    """Where one account's OAuth client secrets and cached token live."""
+
+    name: str
+    directory: Path
+
+    @property
+    def credentials_file(self) -> Path:
+        return self.directory / CREDENTIALS_FILE
+
+    @property
+    def token_file(self) -> Path:
+        return self.directory / TOKEN_FILE
+
+    @property
+    def token_json_file(self) -> Path:
+        return self.directory / TOKEN_JSON_FILE
+
+
+def _is_account_dir(path: Path) -> bool:
+    return any((path / name).exists() for name in ACCOUNT_FILES)
+
+
+class AccountRegistry:
+    """Discovers configured accounts and resolves the `account` param."""
+
+    def __init__(self, auth_dir: Path, legacy_dir: Optional[Path] = None,
+                 default_account: Optional[str] = None):
+        self.auth_dir = Path(auth_dir)
+        self.legacy_dir = Path(legacy_dir) if legacy_dir else None
+        self.configured_default = default_account
+
+    def discover(self) -> Dict[str, Account]:
+        """Scan the auth directory for accounts. Re-run on every lookup so
+        accounts added after startup are picked up without a restart."""
+        accounts: Dict[str, Account] = {}
+
+        if _is_account_dir(self.auth_dir):
+            accounts[DEFAULT_ACCOUNT] = Account(DEFAULT_ACCOUNT, self.auth_dir)
+        elif self.legacy_dir and _is_account_dir(self.legacy_dir):
+            accounts[DEFAULT_ACCOUNT] = Account(DEFAULT_ACCOUNT, self.legacy_dir)
+
+        if self.auth_dir.is_dir():
+            for child in sorted(self.auth_dir.iterdir()):
+                if child.is_dir() and _is_account_dir(child) and child.name not in accounts:
+                    accounts[child.name] = Account(child.name, child)
+
+        return accounts
+
+    def names(self) -> List[str]:
+        return list(self.discover())
+
+    def default_name(self) -> Optional[str]:
+        """Name of the account used when a call omits `account`."""
+        if self.configured_default:
+            return self.configured_default
+        accounts = self.discover()
+        if DEFAULT_ACCOUNT in accounts:
+            return DEFAULT_ACCOUNT
+        if len(accounts) == 1:
+            return next(iter(accounts))
+        return None
+
+    def resolve(self, name: Optional[str]) -> Account:
+        """Look up an account by name, or the default account when None."""
+        accounts = self.discover()
+        known = ", ".join(accounts) or "none"
+
+        if name is None:
+            name = self.default_name()
+            if name is None:
+                if not accounts:
+                    raise UnknownAccount(
+                        f"No Gmail accounts configured. Place credentials.json in {self.auth_dir} "
+                        f"or {self.auth_dir}/<account>/"
+                    )
+                raise UnknownAccount(
+                    f"Multiple accounts configured ({known}); pass 'account' or set "
+                    f"FGP_GMAIL_DEFAULT_ACCOUNT"
+                )
+
+        if not isinstance(name, str) or name not in accounts:
+            raise UnknownAccount(f"Unknown account: {name!r}. Known accounts: {known}")
+        return accounts[name]
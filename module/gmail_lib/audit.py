@@ -0,0 +1,162 @@
+"""
+Opt-in audit log of every call.
+
+With `[audit] enabled = true`, each dispatch appends one JSON line to
+`audit.log`: when it ran, the method, how long it took, whether it
+succeeded (and the error type if not), and a redacted copy of its params.
+`gmail.audit_tail` reads the newest lines back.
+
+Redaction keeps calls recognizable without keeping their content:
+
+- email addresses anywhere in the params, nested lists and batch
+  operations included, become stable hashes, as in recordings;
+- `subject`, bodies, and `comment` are cut to `text_chars` characters, or
+  replaced with their sha256 when `text_chars` is 0;
+- raw messages and attachment data are replaced outright;
+- everything else, message and thread ids included, is kept as is.
+
+The log rotates by size: once a write would take `audit.log` past
+`max_bytes`, it becomes `audit.log.1`, older files move up one, and
+anything past `audit.log.<keep>` is deleted.
+"""
+
+import hashlib
+import json
+import logging
+import threading
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Mapping, Optional
+
+from .backend import CONTENT_KEYS, REDACTED, env_flag, redact
+
+log = logging.getLogger("fgp_gmail.audit")
+
+DEFAULT_MAX_BYTES = 10 * 1024 * 1024
+DEFAULT_KEEP = 5
+DEFAULT_TEXT_CHARS = 32
+DEFAULT_TAIL = 20
+
+TEXT_PARAMS = frozenset({"subject", "body", "body_html", "body_markdown", "comment"})
+
+
+def redact_text(text: str, text_chars: int) -> str:
+    if text_chars <= 0:
+        return "sha256:" + hashlib.sha256(text.encode()).hexdigest()
+    if len(text) <= text_chars:
+        return redact(text)
+    return redact(text[:text_chars]) + f"… <{len(text)} chars>"
+
+
+def redact_params(value: Any, text_chars: int = DEFAULT_TEXT_CHARS) -> Any:
+    """A copy of `value` (params, or anything nested in them) safe to audit."""
+    if isinstance(value, dict):
+        redacted = {}
+        for key, item in value.items():
+            if key in TEXT_PARAMS and isinstance(item, str):
+                redacted[key] = redact_text(item, text_chars)
+            elif key in CONTENT_KEYS and isinstance(item, str):
+                redacted[key] = REDACTED
+            else:
+                redacted[key] = redact_params(item, text_chars)
+        return redacted
+    if isinstance(value, list):
+        return [redact_params(item, text_chars) for item in value]
+    return redact(value)
+
+
+class AuditLog:
+    """Appends call records to a size-rotated JSON lines file."""
+
+    def __init__(self, path: Path, max_bytes: int = DEFAULT_MAX_BYTES, keep: int = DEFAULT_KEEP,
+                 text_chars: int = DEFAULT_TEXT_CHARS, enabled: bool = True):
+        self.path = Path(path)
+        self.max_bytes = max(1, int(max_bytes))
+        self.keep = max(0, int(keep))
+        self.text_chars = max(0, int(text_chars))
+        self.enabled = enabled
+        self._lock = threading.Lock()
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str], default_path: Path) -> "AuditLog":
+        return cls(
+            path=Path(environ.get("FGP_GMAIL_AUDIT_PATH") or default_path).expanduser(),
+            max_bytes=int(environ.get("FGP_GMAIL_AUDIT_MAX_BYTES") or DEFAULT_MAX_BYTES),
+            keep=int(environ.get("FGP_GMAIL_AUDIT_KEEP") or DEFAULT_KEEP),
+            text_chars=int(environ.get("FGP_GMAIL_AUDIT_TEXT_CHARS") or DEFAULT_TEXT_CHARS),
+            enabled=env_flag(environ.get("FGP_GMAIL_AUDIT")),
+        )
+
+    def record(self, method: str, params: Dict[str, Any], duration_ms: float,
+               error: Optional[BaseException] = None, request_id: Optional[str] = None):
+        """Append one call's record. Never raises: a full disk shouldn't fail calls."""
+        if not self.enabled:
+            return
+        entry = {
+            'ts': datetime.now(timezone.utc).isoformat(timespec='milliseconds').replace("+00:00", "Z"),
+            'request_id': request_id,
+            'method': method,
+            'duration_ms': round(duration_ms, 1),
+            'ok': error is None,
+            'error_code': type(error).__name__ if error is not None else None,
+            'params': redact_params(params, self.text_chars),
+        }
+        if error is not None:
+            entry['error'] = redact(str(error))
+        line = (json.dumps(entry, default=str) + "\n").encode()
+        try:
+            with self._lock:
+                self.path.parent.mkdir(parents=True, exist_ok=True)
+                if self.path.exists() and self.path.stat().st_size + len(line) > self.max_bytes:
+                    self._rotate()
+                with open(self.path, 'ab') as f:
+                    f.write(line)
+        except OSError as e:
+            log.warning("Failed to write audit log %s: %s", self.path, e)
+
+    def _rotated(self, index: int) -> Path:
+        return self.path.with_name(f"{self.path.name}.{index}")
+
+    def _rotate(self):
+        if self.keep == 0:
+            self.path.unlink()
+            return
+        oldest = self._rotated(self.keep)
+        if oldest.exists():
+            oldest.unlink()
+        for index in range(self.keep - 1, 0, -1):
+            if self._rotated(index).exists():
+                self._rotated(index).rename(self._rotated(index + 1))
+        self.path.rename(self._rotated(1))
+
+    def tail(self, limit: int = DEFAULT_TAIL, method: Optional[str] = None) -> List[Dict[str, Any]]:
+        """The newest `limit` records (of `method`, if given), oldest first."""
+        entries: List[Dict[str, Any]] = []
+        with self._lock:
+            files = [self.path] + [self._rotated(index) for index in range(1, self.keep + 1)]
+            for path in files:
+                if len(entries) >= limit:
+                    break
+                try:
+                    lines = path.read_bytes().splitlines()
+                except FileNotFoundError:
+                    continue
+                for line in reversed(lines):
+                    try:
+                        entry = json.loads(line)
+                    except ValueError:
+                        continue  # a write cut short by a crash
+                    if method is None or entry.get('method') == method:
+                        entries.append(entry)
+                        if len(entries) >= limit:
+                            break
+        return entries[::-1]
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'enabled': self.enabled,
+            'path': str(self.path),
+            'max_bytes': self.max_bytes,
+            'keep': self.keep,
+            'text_chars': self.text_chars,
+        }
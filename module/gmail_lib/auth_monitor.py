@@ -0,0 +1,125 @@
+"""
+Periodic OAuth token checks.
+
+`AuthMonitor` checks every configured account's cached token at startup and
+then every `interval` seconds (default 10 minutes) on a background thread,
+and remembers the outcome: whether the token is usable, when it expires,
+and which scopes it grants. The check itself is supplied by the caller (see
+`check_token` in gmail.py); it may refresh an expired token, which is what
+catches revoked grants before a real call does.
+
+The recorded state lets the module fail a call with `AuthRequired` before
+spending an API request on a token known to be broken. A broken state is
+re-checked as soon as the token file changes, so signing in with
+`fgp-gmail auth` takes effect without waiting for the next tick.
+"""
+
+import logging
+import threading
+import time
+from typing import Any, Callable, Dict, Optional
+
+from .accounts import Account, AuthExpired
+
+log = logging.getLogger("fgp_gmail.auth")
+
+DEFAULT_INTERVAL_SECS = 600
+MIN_INTERVAL_SECS = 30
+
+TokenCheck = Callable[[Account], Dict[str, Any]]
+
+
+class AuthRequired(AuthExpired):
+    """Raised before any API call when the account's token is known to be unusable."""
+
+
+def _token_mtime(account: Account) -> Optional[float]:
+    try:
+        return account.token_file.stat().st_mtime
+    except OSError:
+        return None
+
+
+class AuthMonitor:
+    """Per-account token state, refreshed on a timer. Thread-safe."""
+
+    def __init__(self, check: TokenCheck, accounts: Callable[[], Dict[str, Account]],
+                 interval: float = DEFAULT_INTERVAL_SECS):
+        self._check = check
+        self._accounts = accounts
+        self.interval = max(float(interval), MIN_INTERVAL_SECS)
+        self._lock = threading.Lock()
+        self._states: Dict[str, Dict[str, Any]] = {}
+        self._mtimes: Dict[str, Optional[float]] = {}
+        self._stop = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def start(self):
+        """Check every account now, then keep checking in the background."""
+        self.check_all()
+        self._stop.clear()
+        self._thread = threading.Thread(target=self._run, name="gmail-auth-monitor", daemon=True)
+        self._thread.start()
+
+    def stop(self):
+        self._stop.set()
+        if self._thread is not None:
+            self._thread.join(5)
+
+    def _run(self):
+        while not self._stop.wait(self.interval):
+            self.check_all()
+
+    def check_all(self):
+        for account in self._accounts().values():
+            self.check(account)
+
+    def check(self, account: Account) -> Dict[str, Any]:
+        try:
+            state = self._check(account)
+        except Exception as e:
+            state = {"account": account.name, "usable": False, "reason": f"token check failed: {e}",
+                     "checked_at": time.time()}
+        # Taken afterwards: the check may have saved a refreshed token
+        mtime = _token_mtime(account)
+        if not state["usable"]:
+            log.warning("Account %s can't authenticate: %s", account.name, state.get("reason"))
+        with self._lock:
+            self._states[account.name] = state
+            self._mtimes[account.name] = mtime
+        return state
+
+    def state(self, name: str) -> Optional[Dict[str, Any]]:
+        with self._lock:
+            state = self._states.get(name)
+            return dict(state) if state is not None else None
+
+    def states(self) -> Dict[str, Dict[str, Any]]:
+        with self._lock:
+            return {name: dict(state) for name, state in sorted(self._states.items())}
+
+    def forget(self, name: str):
+        """Drop an account's state, e.g. after a new token was saved."""
+        with self._lock:
+            self._states.pop(name, None)
+            self._mtimes.pop(name, None)
+
+    def current(self, account: Account) -> Optional[Dict[str, Any]]:
+        """The account's last check, redone first if the token file has changed.
+
+        None if the account hasn't been checked yet.
+        """
+        with self._lock:
+            state = self._states.get(account.name)
+            mtime = self._mtimes.get(account.name)
+        if state is None:
+            return None
+        if _token_mtime(account) != mtime:
+            return self.check(account)
+        return dict(state)
+
+    def require(self, account: Account):
+        """Raise AuthRequired if the account's token is known to be unusable."""
+        state = self.current(account)
+        if state is not None and not state["usable"]:
+            raise AuthRequired(account, state.get("reason") or "token unusable")
@@ -0,0 +1,255 @@
+"""
+Gmail API backends.
+
+Every Gmail API call made by the module goes through a backend's
+`call(name, **params)`, where `name` is the dotted resource path below
+`users()` (e.g. `"messages.list"`, `"messages.attachments.get"`). This keeps
+the handlers independent of how responses are produced:
+
+- `ApiBackend` talks to the real API through googleapiclient.
+- `RecordingBackend` wraps another backend and appends each request/response
+  pair to a replay file.
+- `ReplayBackend` serves responses from a replay file and fails loudly on
+  any request that wasn't recorded.
+
+Replay files are JSON lines, one `{"method", "params", "response"}` (or
+`"error"`) record per call. Recordings are redacted by default: message
+bodies, raw content, snippets, and email addresses are replaced with
+placeholders, and so are message header values other than the structural
+ones (`Date`, `Content-Type`, ...). Pass `unsafe_keep_content=True` to record
+them verbatim.
+"""
+
+import copy
+import hashlib
+import json
+import re
+import threading
+from pathlib import Path
+from typing import Any, Callable, Dict, List, Optional
+
+REPLAY_FORMAT_VERSION = 1
+
+REDACTED = "[redacted]"
+
+# Keys whose values carry message content (the `response*` ones are the
+# vacation responder's)
+CONTENT_KEYS = frozenset({
+    "data",
+    "raw",
+    "snippet",
+    "responseSubject",
+    "responseBodyPlainText",
+    "responseBodyHtml",
+})
+
+# Message headers kept as is; every other header value (Subject, From, To,
+# ...) is content
+STRUCTURAL_HEADERS = frozenset({
+    "content-transfer-encoding",
+    "content-type",
+    "date",
+    "in-reply-to",
+    "message-id",
+    "mime-version",
+    "references",
+})
+
+EMAIL_RE = re.compile(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}")
+
+
+class ReplayMismatch(RuntimeError):
+    """Raised when a replayed session has no recording for a request."""
+
+
+def _redact_address(match: "re.Match") -> str:
+    digest = hashlib.sha256(match.group(0).lower().encode()).hexdigest()[:10]
+    return f"user-{digest}@redacted.invalid"
+
+
+def _redact_header(header: Any) -> Any:
+    if not isinstance(header, dict) or str(header.get("name", "")).lower() in STRUCTURAL_HEADERS:
+        return redact(header)
+    return {key: REDACTED if key == "value" else redact(item) for key, item in header.items()}
+
+
+def _redact_field(key: str, item: Any) -> Any:
+    if key in CONTENT_KEYS and isinstance(item, str):
+        return REDACTED
+    if key == "headers" and isinstance(item, list):
+        return [_redact_header(header) for header in item]
+    return redact(item)
+
+
+def redact(value: Any) -> Any:
+    """Return a copy of `value` with content and addresses replaced."""
+    if isinstance(value, dict):
+        return {key: _redact_field(key, item) for key, item in value.items()}
+    if isinstance(value, list):
+        return [redact(item) for item in value]
+    if isinstance(value, str):
+        return EMAIL_RE.sub(_redact_address, value)
+    return value
+
+
+class NotFound(ValueError):
+    """Raised when a call names a resource (draft, filter, ...) that doesn't exist."""
+
+
+def http_status(error: Exception) -> Optional[int]:
+    """HTTP status of a googleapiclient `HttpError`, or None for other errors."""
+    return getattr(getattr(error, "resp", None), "status", None)
+
+
+class ApiBackend:
+    """Backend that executes calls against the Gmail API.
+
+    The service object is shared, but its httplib2 connection isn't
+    thread-safe. With `http_factory`, each thread executes requests over
+    its own authorized connection, built on first use.
+    """
+
+    name = "api"
+
+    def __init__(self, service, http_factory: Optional[Callable[[], Any]] = None):
+        self.service = service
+        self._http_factory = http_factory
+        self._local = threading.local()
+
+    def _http(self):
+        http = getattr(self._local, "http", None)
+        if http is None:
+            http = self._local.http = self._http_factory()
+        return http
+
+    def call(self, name: str, **params) -> Dict[str, Any]:
+        *resources, method = name.split(".")
+        node = self.service.users()
+        for resource in resources:
+            node = getattr(node, resource)()
+        request = getattr(node, method)(**params)
+        if self._http_factory is None:
+            return request.execute()
+        return request.execute(http=self._http())
+
+
+def authorized_http_factory(credentials) -> Callable[[], Any]:
+    """Connection factory for ApiBackend: a new authorized httplib2 client each call."""
+    import google_auth_httplib2
+    import httplib2
+
+    return lambda: google_auth_httplib2.AuthorizedHttp(credentials, http=httplib2.Http())
+
+
+class RecordingBackend:
+    """Backend that records every call made through an inner backend."""
+
+    def __init__(self, inner, path: Path, unsafe_keep_content: bool = False):
+        self.inner = inner
+        self.path = Path(path)
+        self.unsafe_keep_content = unsafe_keep_content
+        self._lock = threading.Lock()
+        self.path.parent.mkdir(parents=True, exist_ok=True)
+
+    @property
+    def name(self) -> str:
+        return f"{self.inner.name}+recording"
+
+    def _sanitize(self, value: Any) -> Any:
+        return copy.deepcopy(value) if self.unsafe_keep_content else redact(value)
+
+    def _append(self, record: Dict[str, Any]):
+        line = json.dumps(record, sort_keys=True)
+        with self._lock, open(self.path, "a") as f:
+            f.write(line + "\n")
+
+    def call(self, name: str, **params) -> Dict[str, Any]:
+        record = {"method": name, "params": self._sanitize(params)}
+        try:
+            response = self.inner.call(name, **params)
+        except Exception as e:
+            record["error"] = self._sanitize(str(e))
+            self._append(record)
+            raise
+        record["response"] = self._sanitize(response)
+        self._append(record)
+        return response
+
+
+class ReplayBackend:
+    """
+    Backend that serves recorded responses.
+
+    Requests are matched on method name and params. Identical requests are
+    served in recording order; a recording is never served twice. If the
+    file was recorded redacted, incoming params are redacted the same way
+    before matching.
+    """
+
+    name = "replay"
+
+    def __init__(self, path: Path):
+        self.path = Path(path)
+        self.records: List[Dict[str, Any]] = []
+        self.redacted = True
+        self._used: List[bool] = []
+        self._lock = threading.Lock()
+        self._load()
+
+    def _load(self):
+        if not self.path.exists():
+            raise FileNotFoundError(f"Replay file not found: {self.path}")
+        with open(self.path) as f:
+            for lineno, line in enumerate(f, start=1):
+                line = line.strip()
+                if not line:
+                    continue
+                try:
+                    record = json.loads(line)
+                except json.JSONDecodeError as e:
+                    raise ValueError(f"{self.path}:{lineno}: invalid replay record: {e}") from e
+                if "version" in record and "method" not in record:
+                    self.redacted = record.get("redacted", True)
+                    continue
+                self.records.append(record)
+        self._used = [False] * len(self.records)
+
+    def call(self, name: str, **params) -> Dict[str, Any]:
+        wanted = redact(params) if self.redacted else params
+        with self._lock:
+            for index, record in enumerate(self.records):
+                if self._used[index] or record.get("method") != name:
+                    continue
+                if record.get("params") != wanted:
+                    continue
+                self._used[index] = True
+                if "error" in record:
+                    raise RuntimeError(record["error"])
+                return copy.deepcopy(record.get("response", {}))
+        raise ReplayMismatch(
+            f"No recorded response for {name} with params {json.dumps(wanted, sort_keys=True)}"
+        )
+
+    def unused(self) -> List[Dict[str, Any]]:
+        """Recorded calls that haven't been replayed yet."""
+        with self._lock:
+            return [r for r, used in zip(self.records, self._used) if not used]
+
+
+def session_header(redacted: bool) -> str:
+    """First line written to a new recording."""
+    return json.dumps({"version": REPLAY_FORMAT_VERSION, "redacted": redacted})
+
+
+def open_recording(inner, directory: Path, session: str, unsafe_keep_content: bool = False) -> RecordingBackend:
+    """Start a new recording session file under `directory`."""
+    path = Path(directory) / f"{session}.jsonl"
+    backend = RecordingBackend(inner, path, unsafe_keep_content=unsafe_keep_content)
+    with open(path, "a") as f:
+        f.write(session_header(not unsafe_keep_content) + "\n")
+    return backend
+
+
+def env_flag(value: Optional[str]) -> bool:
+    """Interpret an environment variable as a boolean flag."""
+    return (value or "").strip().lower() in ("1", "true", "yes", "on")
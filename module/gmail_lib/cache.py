@@ -0,0 +1,99 @@
+"""
+In-memory TTL cache for read-only method results.
+
+Entries are keyed by (method, account, normalized params) and expire after
+`ttl` seconds. When more than `max_entries` are stored, the least recently
+used entry is evicted. Thread-safe; values are deep-copied in and out so
+callers can't mutate cached results.
+"""
+
+import copy
+import json
+import threading
+import time
+from collections import OrderedDict
+from typing import Any, Callable, Dict, Hashable, Iterable, Optional, Tuple
+
+DEFAULT_TTL_SECS = 30.0
+DEFAULT_MAX_ENTRIES = 256
+
+_MISSING = object()
+
+
+class ResultCache:
+    def __init__(self, ttl: float = DEFAULT_TTL_SECS, max_entries: int = DEFAULT_MAX_ENTRIES,
+                 clock: Callable[[], float] = time.monotonic):
+        self.ttl = ttl
+        self.max_entries = max_entries
+        self._clock = clock
+        self._entries: "OrderedDict[Hashable, Tuple[float, Any]]" = OrderedDict()
+        self._lock = threading.Lock()
+        self.hits = 0
+        self.misses = 0
+        self.evictions = 0
+        self.invalidations = 0
+
+    @property
+    def enabled(self) -> bool:
+        return self.ttl > 0 and self.max_entries > 0
+
+    @staticmethod
+    def key(method: str, account: Optional[str], params: Dict[str, Any]) -> Tuple[str, Optional[str], str]:
+        """Cache key with params normalized so key order doesn't matter."""
+        return (method, account, json.dumps(params, sort_keys=True, default=str))
+
+    def get(self, key: Hashable) -> Any:
+        """Cached value, or the `MISSING` sentinel on a miss."""
+        with self._lock:
+            entry = self._entries.get(key)
+            if entry is not None and entry[0] > self._clock():
+                self._entries.move_to_end(key)
+                self.hits += 1
+                return copy.deepcopy(entry[1])
+            if entry is not None:
+                del self._entries[key]
+            self.misses += 1
+            return _MISSING
+
+    def put(self, key: Hashable, value: Any):
+        if not self.enabled:
+            return
+        with self._lock:
+            self._entries[key] = (self._clock() + self.ttl, copy.deepcopy(value))
+            self._entries.move_to_end(key)
+            while len(self._entries) > self.max_entries:
+                self._entries.popitem(last=False)
+                self.evictions += 1
+
+    def invalidate(self, methods: Iterable[str], account: Optional[str] = None):
+        """Drop entries for `methods`, limited to one account if given."""
+        methods = set(methods)
+        with self._lock:
+            stale = [
+                k for k in self._entries
+                if k[0] in methods and (account is None or k[1] == account)
+            ]
+            for k in stale:
+                del self._entries[k]
+            self.invalidations += len(stale)
+
+    def clear(self):
+        with self._lock:
+            self._entries.clear()
+
+    def stats(self) -> Dict[str, Any]:
+        with self._lock:
+            lookups = self.hits + self.misses
+            return {
+                "entries": len(self._entries),
+                "hits": self.hits,
+                "misses": self.misses,
+                "hit_rate": round(self.hits / lookups, 3) if lookups else None,
+                "evictions": self.evictions,
+                "invalidations": self.invalidations,
+                "ttl_secs": self.ttl,
+                "max_entries": self.max_entries,
+            }
+
+
+MISSING = _MISSING
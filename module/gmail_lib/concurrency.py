@@ -0,0 +1,172 @@
+"""
+Bounding how many calls run at once.
+
+The daemon serves each connection on its own thread, and the Gmail client
+drops the GIL while it waits on the network, so a slow search doesn't hold
+up a cheap unread check. A burst of calls, though, would open as many
+connections to Gmail as there are callers. `CallSlots` lets `max_concurrent`
+calls run their handlers at a time; the rest queue for a slot for up to
+`max_wait_secs` (or their own deadline) and then fail with `DaemonBusy`
+rather than piling up forever.
+
+`InFlight` counts the calls being served so the daemon can shut down
+without cutting them off: once `drain` starts, new calls fail with
+`ShuttingDown` and the drain waits, up to a timeout, for the running ones.
+"""
+
+import threading
+import time
+from contextlib import contextmanager
+from typing import Any, Dict, Iterator, Mapping, Optional
+
+DEFAULT_MAX_CONCURRENT = 4
+DEFAULT_MAX_WAIT_SECS = 10.0
+DEFAULT_DRAIN_SECS = 10.0
+
+
+class DaemonBusy(RuntimeError):
+    """Raised when a call waited too long for a free slot."""
+
+    def __init__(self, method: str, limit: int, waited: float):
+        super().__init__(
+            f"Daemon busy: {method} waited {waited:.1f}s for one of {limit} call slots; retry shortly"
+        )
+        self.method = method
+        self.limit = limit
+        self.waited = waited
+
+
+class ShuttingDown(RuntimeError):
+    """Raised for calls that arrive while the daemon is shutting down."""
+
+    def __init__(self, method: str):
+        super().__init__(f"Daemon shutting down: {method} was not started; retry once it's back")
+        self.method = method
+
+
+class CallSlots:
+    """A counting semaphore with a bounded wait and usage counters."""
+
+    def __init__(self, max_concurrent: int = DEFAULT_MAX_CONCURRENT,
+                 max_wait_secs: float = DEFAULT_MAX_WAIT_SECS,
+                 clock=time.monotonic):
+        self.max_concurrent = max(1, int(max_concurrent))
+        self.max_wait_secs = max(0.0, float(max_wait_secs))
+        self._clock = clock
+        self._semaphore = threading.BoundedSemaphore(self.max_concurrent)
+        self._lock = threading.Lock()
+        self._running = 0
+        self._waiting = 0
+        self._peak = 0
+        self._rejected = 0
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str]) -> "CallSlots":
+        return cls(
+            max_concurrent=int(environ.get("FGP_GMAIL_MAX_CONCURRENT") or DEFAULT_MAX_CONCURRENT),
+            max_wait_secs=float(environ.get("FGP_GMAIL_BUSY_WAIT") or DEFAULT_MAX_WAIT_SECS),
+        )
+
+    @contextmanager
+    def slot(self, method: str, deadline: Optional[float] = None) -> Iterator[None]:
+        """Hold a slot for the duration of the block.
+
+        Waits at most `max_wait_secs`, and never past `deadline` (a
+        `time.monotonic()` value), before raising DaemonBusy.
+        """
+        started = self._clock()
+        wait = self.max_wait_secs
+        if deadline is not None:
+            wait = max(0.0, min(wait, deadline - started))
+
+        acquired = False
+        with self._lock:
+            self._waiting += 1
+        try:
+            acquired = self._semaphore.acquire(timeout=wait)
+        finally:
+            with self._lock:
+                self._waiting -= 1
+                if acquired:
+                    self._running += 1
+                    self._peak = max(self._peak, self._running)
+                else:
+                    self._rejected += 1
+        if not acquired:
+            raise DaemonBusy(method, self.max_concurrent, self._clock() - started)
+
+        try:
+            yield
+        finally:
+            with self._lock:
+                self._running -= 1
+            self._semaphore.release()
+
+    def to_dict(self) -> Dict[str, Any]:
+        with self._lock:
+            return {
+                "max_concurrent": self.max_concurrent,
+                "max_wait_secs": self.max_wait_secs,
+                "running": self._running,
+                "waiting": self._waiting,
+                "peak": self._peak,
+                "rejected": self._rejected,
+            }
+
+
+class InFlight:
+    """Counts calls in progress and turns new ones away once draining.
+
+    Only the outermost call on a thread counts: operations a batch runs
+    through `dispatch` are part of it, and are never rejected halfway.
+    """
+
+    def __init__(self, clock=time.monotonic):
+        self._clock = clock
+        self._cond = threading.Condition()
+        self._local = threading.local()
+        self._running = 0
+        self._draining = False
+
+    @property
+    def draining(self) -> bool:
+        return self._draining
+
+    @contextmanager
+    def call(self, method: str) -> Iterator[None]:
+        """Count the block as a call in progress, or raise ShuttingDown."""
+        depth = getattr(self._local, "depth", 0)
+        if depth == 0:
+            with self._cond:
+                if self._draining:
+                    raise ShuttingDown(method)
+                self._running += 1
+        self._local.depth = depth + 1
+        try:
+            yield
+        finally:
+            self._local.depth = depth
+            if depth == 0:
+                with self._cond:
+                    self._running -= 1
+                    self._cond.notify_all()
+
+    def drain(self, timeout: float = DEFAULT_DRAIN_SECS) -> Dict[str, Any]:
+        """Stop taking calls and wait up to `timeout` seconds for running ones.
+
+        Called from within a call (`gmail.drain`), that call isn't waited for.
+        """
+        own = 1 if getattr(self._local, "depth", 0) > 0 else 0
+        started = self._clock()
+        with self._cond:
+            self._draining = True
+            drained = self._cond.wait_for(lambda: self._running <= own, max(0.0, timeout))
+            return {
+                "drained": drained,
+                "abandoned": self._running - own,
+                "waited_secs": round(self._clock() - started, 3),
+            }
+
+    def to_dict(self) -> Dict[str, Any]:
+        with self._cond:
+            return {"in_flight": self._running, "draining": self._draining}
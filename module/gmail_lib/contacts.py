@@ -0,0 +1,327 @@
+"""
+Recipient autocomplete from message history.
+
+`gmail.contacts` answers from an index of the addresses in the From, To,
+and Cc headers of an account's most recent `lookback` messages (default
+500), sent and received. It is built on first use and kept in
+`contacts.json` under the service directory, one entry per account.
+
+Each contact is one address, lowercased, with the display name it appears
+with most often (the most recent one on a tie). Its score adds up every
+message it appears in, weighted by age: a message `RECENCY_HALF_LIFE_DAYS`
+old counts half as much as one from today, and the recipients of messages
+you sent count `SENT_WEIGHT` times. The signed-in address itself is left
+out.
+
+An index older than `max_age` seconds (default 6 hours) is still answered
+from, but triggers a rebuild on a background thread, so queries don't
+wait on Gmail. Only a missing index, or `refresh: true`, makes a call
+build it first.
+"""
+
+import json
+import logging
+import os
+import re
+import threading
+import time
+from collections import Counter
+from datetime import datetime, timezone
+from email.utils import getaddresses
+from pathlib import Path
+from typing import Any, Callable, Dict, List, Mapping, Optional
+
+log = logging.getLogger("fgp_gmail.contacts")
+
+DEFAULT_LOOKBACK = 500
+DEFAULT_MAX_AGE_SECS = 6 * 3600
+CONTACT_HEADERS = ['From', 'To', 'Cc']
+RECENCY_HALF_LIFE_DAYS = 30
+SENT_WEIGHT = 2
+
+# messages.list returns at most this many ids per page
+LIST_PAGE_SIZE = 500
+
+# Where a name or address can start a new word for prefix matching
+WORD_BREAK_RE = re.compile(r"[\s.,_+\-@\"'()]+")
+
+Api = Callable[..., Dict[str, Any]]
+
+
+class RebuildCancelled(Exception):
+    """The daemon stopped while an index was being built."""
+
+
+def normalize(name: str, address: str):
+    """A header's (name, address) pair cleaned up, or None to skip it."""
+    address = address.strip().strip("<>").lower()
+    if "@" not in address or address.startswith("@") or " " in address:
+        return None
+    name = " ".join(name.strip().strip("\"'").split())
+    if name.lower() == address:
+        name = ""
+    return name, address
+
+
+class _Tally:
+    """Addresses seen so far while building an index."""
+
+    def __init__(self, now: float, own_address: Optional[str]):
+        self.now = now
+        self.own_address = own_address
+        self.scores: Dict[str, float] = {}
+        self.counts: Counter = Counter()
+        self.last_seen: Dict[str, int] = {}
+        self.names: Dict[str, Counter] = {}
+        self.name_seen: Dict[str, Dict[str, int]] = {}
+
+    def add(self, msg: Dict[str, Any]):
+        headers = {h.get('name'): h.get('value') or '' for h in (msg.get('payload') or {}).get('headers', [])}
+        try:
+            date_ms = int(msg.get('internalDate', 0))
+        except (TypeError, ValueError):
+            date_ms = 0
+        age_days = max(0.0, self.now - date_ms / 1000) / 86400
+        weight = 0.5 ** (age_days / RECENCY_HALF_LIFE_DAYS)
+        sent = 'SENT' in (msg.get('labelIds') or [])
+
+        fields = [(headers.get('From', ''), False), (headers.get('To', ''), sent), (headers.get('Cc', ''), sent)]
+        seen = set()
+        for value, recipient_of_ours in fields:
+            for pair in getaddresses([value]):
+                cleaned = normalize(*pair)
+                if cleaned is None or cleaned[1] == self.own_address:
+                    continue
+                name, address = cleaned
+                if name:
+                    self.names.setdefault(address, Counter())[name] += 1
+                    names_seen = self.name_seen.setdefault(address, {})
+                    names_seen[name] = max(names_seen.get(name, 0), date_ms)
+                if address in seen:
+                    continue
+                seen.add(address)
+                boost = SENT_WEIGHT if recipient_of_ours else 1
+                self.scores[address] = self.scores.get(address, 0.0) + weight * boost
+                self.counts[address] += 1
+                self.last_seen[address] = max(self.last_seen.get(address, 0), date_ms)
+
+    def _name(self, address: str) -> str:
+        names = self.names.get(address)
+        if not names:
+            return ""
+        seen = self.name_seen[address]
+        return max(names, key=lambda name: (names[name], seen[name]))
+
+    def contacts(self) -> List[Dict[str, Any]]:
+        contacts = [
+            {
+                'email': address,
+                'name': self._name(address),
+                'score': round(score, 3),
+                'count': self.counts[address],
+                'last_seen': _rfc3339(self.last_seen[address]),
+            }
+            for address, score in self.scores.items()
+        ]
+        contacts.sort(key=lambda c: (-c['score'], c['email']))
+        return contacts
+
+
+def _rfc3339(ms: int) -> Optional[str]:
+    if not ms:
+        return None
+    return datetime.fromtimestamp(ms / 1000, timezone.utc).isoformat().replace('+00:00', 'Z')
+
+
+def build_index(api: Api, lookback: int, clock=time.time,
+                cancelled: Callable[[], bool] = lambda: False) -> Dict[str, Any]:
+    """Scan the newest `lookback` messages into a fresh index."""
+    try:
+        own_address = (api('getProfile').get('emailAddress') or '').lower() or None
+    except Exception as e:
+        log.warning("Couldn't fetch the profile to leave out your own address: %s", e)
+        own_address = None
+
+    ids: List[str] = []
+    page_token = None
+    while len(ids) < lookback:
+        page = {'pageToken': page_token} if page_token else {}
+        results = api('messages.list', maxResults=min(LIST_PAGE_SIZE, lookback - len(ids)), **page)
+        ids.extend(msg['id'] for msg in results.get('messages', []))
+        page_token = results.get('nextPageToken')
+        if not page_token:
+            break
+
+    built_at = clock()
+    tally = _Tally(built_at, own_address)
+    for message_id in ids[:lookback]:
+        if cancelled():
+            raise RebuildCancelled()
+        tally.add(api('messages.get', id=message_id, format='metadata', metadataHeaders=CONTACT_HEADERS))
+    return {'built_at': built_at, 'messages_scanned': len(ids[:lookback]), 'contacts': tally.contacts()}
+
+
+def matches(contact: Dict[str, Any], query: str) -> bool:
+    """Whether the name, the address, or a word of the name or of the
+    address's local part starts with `query`.
+
+    Case-insensitive; an empty query matches everyone.
+    """
+    query = query.strip().casefold()
+    if not query:
+        return True
+    name = contact['name'].casefold()
+    email = contact['email']
+    if name.startswith(query) or email.startswith(query):
+        return True
+    return any(word.startswith(query) for word in WORD_BREAK_RE.split(f"{name} {email.split('@')[0]}") if word)
+
+
+class ContactBook:
+    """Per-account contact indexes, persisted as one JSON file. Thread-safe."""
+
+    def __init__(self, path: Path, lookback: int = DEFAULT_LOOKBACK,
+                 max_age: float = DEFAULT_MAX_AGE_SECS, clock=time.time):
+        if lookback <= 0:
+            raise ValueError("contacts lookback must be a positive number of messages")
+        self.path = Path(path)
+        self.lookback = int(lookback)
+        self.max_age = max(0.0, float(max_age))
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._build_locks: Dict[str, threading.Lock] = {}
+        self._refreshing: Dict[str, threading.Thread] = {}
+        self._indexes: Optional[Dict[str, Dict[str, Any]]] = None
+        self._stopped = threading.Event()
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str], default_path: Path) -> "ContactBook":
+        return cls(
+            path=Path(environ.get("FGP_GMAIL_CONTACTS_FILE") or default_path).expanduser(),
+            lookback=int(environ.get("FGP_GMAIL_CONTACTS_LOOKBACK") or DEFAULT_LOOKBACK),
+            max_age=float(environ.get("FGP_GMAIL_CONTACTS_MAX_AGE") or DEFAULT_MAX_AGE_SECS),
+        )
+
+    def _load(self) -> Dict[str, Dict[str, Any]]:
+        if self._indexes is None:
+            self._indexes = {}
+            try:
+                data = json.loads(self.path.read_text())
+                self._indexes = dict(data.get("accounts", {}))
+            except FileNotFoundError:
+                pass
+            except (ValueError, TypeError, AttributeError) as e:
+                log.warning("Ignoring unreadable contacts file %s: %s", self.path, e)
+        return self._indexes
+
+    def _save(self):
+        self.path.parent.mkdir(parents=True, exist_ok=True)
+        tmp = self.path.with_name(f".{self.path.name}.tmp")
+        with open(tmp, "w") as f:
+            json.dump({"accounts": self._load()}, f)
+            f.flush()
+            os.fsync(f.fileno())
+        os.replace(tmp, self.path)
+
+    def _index(self, account: str) -> Optional[Dict[str, Any]]:
+        with self._lock:
+            return self._load().get(account)
+
+    def _is_stale(self, index: Dict[str, Any]) -> bool:
+        return self._clock() - index.get('built_at', 0) >= self.max_age
+
+    def rebuild(self, account: str, api: Api, only_if_stale: bool = False) -> Dict[str, Any]:
+        """Build `account`'s index now and save it.
+
+        Builds of one account are serialized. With `only_if_stale`, a build
+        that finds a fresh index (one finished while it waited) returns that.
+        """
+        with self._lock:
+            build_lock = self._build_locks.setdefault(account, threading.Lock())
+        with build_lock:
+            if only_if_stale:
+                index = self._index(account)
+                if index is not None and not self._is_stale(index):
+                    return index
+            index = build_index(api, self.lookback, self._clock, self._stopped.is_set)
+            with self._lock:
+                self._load()[account] = index
+                try:
+                    self._save()
+                except OSError as e:
+                    log.warning("Failed to save contacts file %s: %s", self.path, e)
+            log.info("Indexed %d contacts from %d messages for account '%s'",
+                     len(index['contacts']), index['messages_scanned'], account)
+            return index
+
+    def _refresh_in_background(self, account: str, api: Api) -> bool:
+        """Start rebuilding `account`'s index unless that's already running."""
+        with self._lock:
+            running = self._refreshing.get(account)
+            if running is not None and running.is_alive():
+                return True
+            if self._stopped.is_set():
+                return False
+
+            def run():
+                try:
+                    self.rebuild(account, api, only_if_stale=True)
+                except RebuildCancelled:
+                    pass
+                except Exception as e:
+                    log.warning("Contacts refresh for account '%s' failed: %s", account, e)
+
+            thread = threading.Thread(target=run, name=f"gmail-contacts-{account}", daemon=True)
+            self._refreshing[account] = thread
+            thread.start()
+            return True
+
+    def lookup(self, account: str, query: str, limit: int, api: Api, background_api: Api,
+               refresh: bool = False) -> Dict[str, Any]:
+        """Contacts of `account` matching `query`, best score first.
+
+        A missing index, or `refresh`, is built with `api` on the calling
+        thread; a stale one is rebuilt with `background_api` on another.
+        """
+        index = self._index(account)
+        refreshing = False
+        if index is None:
+            index = self.rebuild(account, api, only_if_stale=True)
+        elif refresh:
+            index = self.rebuild(account, api)
+        elif self._is_stale(index):
+            refreshing = self._refresh_in_background(account, background_api)
+
+        found = [contact for contact in index['contacts'] if matches(contact, query)]
+        return {
+            'query': query,
+            'contacts': found[:limit],
+            'count': min(len(found), limit),
+            'total_matches': len(found),
+            'index': {
+                'built_at': _rfc3339(int(index['built_at'] * 1000)),
+                'messages_scanned': index['messages_scanned'],
+                'contacts': len(index['contacts']),
+                'stale': self._is_stale(index),
+                'refreshing': refreshing,
+            },
+        }
+
+    def stop(self):
+        """Abandon background rebuilds; the saved indexes are kept."""
+        self._stopped.set()
+        with self._lock:
+            threads = list(self._refreshing.values())
+            self._refreshing.clear()
+        for thread in threads:
+            thread.join(timeout=5)
+
+    def to_dict(self) -> Dict[str, Any]:
+        with self._lock:
+            indexes = dict(self._load())
+        return {
+            'path': str(self.path),
+            'lookback': self.lookback,
+            'max_age_secs': self.max_age,
+            'accounts': sorted(indexes),
+        }
@@ -0,0 +1,216 @@
+"""
+OAuth device-code flow for machines without a browser.
+
+The installed-app flow opens a browser on the daemon's machine. The device
+flow (RFC 8628) instead hands back a verification URL and a short user code
+that can be entered on any other device, while we poll Google's token
+endpoint until the user approves, denies, or the code expires.
+
+The OAuth client in credentials.json must be of type "TVs and Limited Input
+devices" for Google to accept device-code requests.
+
+`DeviceLogin` runs one flow on a background thread. The token response is
+handed to an `on_token` callback, which is responsible for persisting it in
+the same format as the other flows (see `save_credentials` in gmail.py).
+"""
+
+import json
+import threading
+import time
+import urllib.error
+import urllib.parse
+import urllib.request
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Any, Callable, Dict, List, Optional
+
+DEVICE_CODE_URL = "https://oauth2.googleapis.com/device/code"
+TOKEN_URL = "https://oauth2.googleapis.com/token"
+DEVICE_GRANT_TYPE = "urn:ietf:params:oauth:grant-type:device_code"
+
+# Added to the poll interval each time the server answers `slow_down`
+SLOW_DOWN_STEP_SECS = 5
+
+PENDING = "pending"
+COMPLETE = "complete"
+EXPIRED = "expired"
+DENIED = "denied"
+CANCELLED = "cancelled"
+ERROR = "error"
+
+FINISHED_STATES = frozenset({COMPLETE, EXPIRED, DENIED, CANCELLED, ERROR})
+
+PostForm = Callable[[str, Dict[str, str]], Dict[str, Any]]
+
+
+class DeviceAuthError(RuntimeError):
+    """Raised when a device-code request is rejected outright."""
+
+
+@dataclass(frozen=True)
+class OAuthClient:
+    """Client id and secret read from an account's credentials.json."""
+
+    client_id: str
+    client_secret: str
+    token_uri: str = TOKEN_URL
+
+    @classmethod
+    def from_file(cls, path: Path) -> "OAuthClient":
+        with open(path) as f:
+            config = json.load(f)
+        section = config.get("installed") or config.get("web") or config
+        try:
+            return cls(
+                client_id=section["client_id"],
+                client_secret=section.get("client_secret", ""),
+                token_uri=section.get("token_uri", TOKEN_URL),
+            )
+        except KeyError:
+            raise DeviceAuthError(f"No client_id in {path}") from None
+
+
+def post_form(url: str, fields: Dict[str, str]) -> Dict[str, Any]:
+    """POST a form and return the JSON body, including for 4xx error replies."""
+    data = urllib.parse.urlencode(fields).encode()
+    request = urllib.request.Request(url, data=data, method="POST")
+    try:
+        with urllib.request.urlopen(request, timeout=30) as response:
+            return json.load(response)
+    except urllib.error.HTTPError as e:
+        try:
+            return json.load(e)
+        except ValueError:
+            raise DeviceAuthError(f"{url} returned HTTP {e.code}") from None
+
+
+class DeviceLogin:
+    """One device-code authorization and its background poller. Thread-safe."""
+
+    def __init__(self, account: str, client: OAuthClient, scopes: List[str],
+                 on_token: Callable[[Dict[str, Any]], None],
+                 post: PostForm = post_form, clock: Callable[[], float] = time.time):
+        self.account = account
+        self.client = client
+        self.scopes = scopes
+        self.on_token = on_token
+        self._post = post
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._cancelled = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+        self.state = PENDING
+        self.error: Optional[str] = None
+        self.completed_at: Optional[float] = None
+
+        reply = self._post(DEVICE_CODE_URL, {
+            "client_id": client.client_id,
+            "scope": " ".join(scopes),
+        })
+        if "error" in reply:
+            raise DeviceAuthError(_describe(reply))
+        self.device_code = reply["device_code"]
+        self.user_code = reply["user_code"]
+        self.verification_url = reply.get("verification_url") or reply["verification_uri"]
+        self.interval = float(reply.get("interval", 5))
+        self.expires_at = self._clock() + float(reply["expires_in"])
+
+    @property
+    def finished(self) -> bool:
+        return self.state in FINISHED_STATES
+
+    def start(self) -> "DeviceLogin":
+        """Start polling the token endpoint on a daemon thread."""
+        self._thread = threading.Thread(
+            target=self._run, name=f"gmail-device-login-{self.account}", daemon=True)
+        self._thread.start()
+        return self
+
+    def wait(self, timeout: Optional[float] = None) -> str:
+        """Block until the flow finishes (or `timeout` passes); returns the state."""
+        if self._thread is not None:
+            self._thread.join(timeout)
+        return self.state
+
+    def cancel(self) -> bool:
+        """Stop polling. Returns False if the flow had already finished."""
+        with self._lock:
+            if self.finished:
+                return False
+            self._finish(CANCELLED)
+        self._cancelled.set()
+        return True
+
+    def poll_once(self) -> str:
+        """Make one token request and update the state. Returns the state."""
+        if self._clock() >= self.expires_at:
+            with self._lock:
+                if not self.finished:
+                    self._finish(EXPIRED, "Device code expired before it was approved")
+            return self.state
+
+        try:
+            reply = self._post(self.client.token_uri, {
+                "client_id": self.client.client_id,
+                "client_secret": self.client.client_secret,
+                "device_code": self.device_code,
+                "grant_type": DEVICE_GRANT_TYPE,
+            })
+        except Exception as e:
+            # Transient network trouble; keep polling until the code expires
+            self.error = str(e)
+            return self.state
+
+        error = reply.get("error")
+        if error == "authorization_pending":
+            return self.state
+        if error == "slow_down":
+            self.interval += SLOW_DOWN_STEP_SECS
+            return self.state
+
+        with self._lock:
+            if self.finished:
+                return self.state
+            if error == "access_denied":
+                self._finish(DENIED, "Authorization was denied")
+            elif error == "expired_token":
+                self._finish(EXPIRED, "Device code expired before it was approved")
+            elif error:
+                self._finish(ERROR, _describe(reply))
+            else:
+                try:
+                    self.on_token(reply)
+                except Exception as e:
+                    self._finish(ERROR, f"Failed to save token: {e}")
+                else:
+                    self._finish(COMPLETE)
+        return self.state
+
+    def _run(self):
+        while not self.finished:
+            if self._cancelled.wait(self.interval):
+                return
+            self.poll_once()
+
+    def _finish(self, state: str, error: Optional[str] = None):
+        """Record the final state. Caller holds the lock."""
+        self.state = state
+        self.error = error
+        self.completed_at = self._clock()
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "account": self.account,
+            "state": self.state,
+            "verification_url": self.verification_url,
+            "user_code": self.user_code,
+            "expires_in": max(0, round(self.expires_at - self._clock())),
+            "interval": self.interval,
+            "error": self.error,
+        }
+
+
+def _describe(reply: Dict[str, Any]) -> str:
+    description = reply.get("error_description")
+    return f"{reply['error']}: {description}" if description else reply["error"]
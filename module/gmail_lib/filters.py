@@ -0,0 +1,103 @@
+"""
+Translation between method params and Gmail filter resources.
+
+Callers work in label names and flat flags; the API wants label ids and
+folds archive / mark-as-read into label removals:
+
+    params                          settings.filters resource
+    ------------------------------  -------------------------------------
+    from, to, subject, query        criteria.from / to / subject / query
+    has_attachment                  criteria.hasAttachment
+    add_label (name or list)        action.addLabelIds
+    remove_label (name or list)     action.removeLabelIds
+    archive                         action.removeLabelIds += INBOX
+    mark_read                       action.removeLabelIds += UNREAD
+    forward_to                      action.forward
+"""
+
+from typing import Any, Callable, Dict, List
+
+CRITERIA_FIELDS = {
+    "from": "from",
+    "to": "to",
+    "subject": "subject",
+    "query": "query",
+    "has_attachment": "hasAttachment",
+}
+
+ACTION_FIELDS = ("add_label", "remove_label", "archive", "mark_read", "forward_to")
+
+
+def _names(value: Any, field: str) -> List[str]:
+    if value is None:
+        return []
+    values = [value] if isinstance(value, str) else value
+    if not isinstance(values, list) or not all(isinstance(v, str) and v for v in values):
+        raise ValueError(f"{field} must be a label name or a list of label names")
+    return values
+
+
+def build_filter(params: Dict[str, Any], label_id: Callable[[str], str]) -> Dict[str, Any]:
+    """Build a filter resource from params, resolving label names via `label_id`.
+
+    Raises ValueError unless at least one criterion and one action are given.
+    """
+    criteria = {}
+    for param, key in CRITERIA_FIELDS.items():
+        value = params.get(param)
+        if value in (None, "", False):
+            continue
+        if param == "has_attachment":
+            if not isinstance(value, bool):
+                raise ValueError("has_attachment must be a boolean")
+        elif not isinstance(value, str):
+            raise ValueError(f"{param} must be a string")
+        criteria[key] = value
+    if not criteria:
+        raise ValueError(f"Filter needs at least one criterion: {', '.join(CRITERIA_FIELDS)}")
+
+    add = _names(params.get("add_label"), "add_label")
+    remove = _names(params.get("remove_label"), "remove_label")
+    forward_to = params.get("forward_to")
+    if forward_to is not None and not isinstance(forward_to, str):
+        raise ValueError("forward_to must be an email address")
+    if not (add or remove or params.get("archive") or params.get("mark_read") or forward_to):
+        raise ValueError(f"Filter needs at least one action: {', '.join(ACTION_FIELDS)}")
+
+    action: Dict[str, Any] = {}
+    if add:
+        action["addLabelIds"] = [label_id(name) for name in add]
+    remove_ids = [label_id(name) for name in remove]
+    if params.get("archive"):
+        remove_ids.append("INBOX")
+    if params.get("mark_read"):
+        remove_ids.append("UNREAD")
+    if remove_ids:
+        action["removeLabelIds"] = list(dict.fromkeys(remove_ids))
+    if forward_to:
+        action["forward"] = forward_to
+
+    return {"criteria": criteria, "action": action}
+
+
+def describe_filter(resource: Dict[str, Any], label_names: Dict[str, str]) -> Dict[str, Any]:
+    """Render a filter resource in the same vocabulary `build_filter` accepts."""
+    api_criteria = resource.get("criteria", {})
+    criteria = {
+        param: api_criteria[key] for param, key in CRITERIA_FIELDS.items() if key in api_criteria
+    }
+    # Criteria the params don't cover (size, negatedQuery, ...) pass through
+    criteria.update({
+        key: value for key, value in api_criteria.items() if key not in CRITERIA_FIELDS.values()
+    })
+
+    api_action = resource.get("action", {})
+    removed = list(api_action.get("removeLabelIds", []))
+    actions: Dict[str, Any] = {
+        "add_label": [label_names.get(i, i) for i in api_action.get("addLabelIds", [])],
+        "archive": "INBOX" in removed,
+        "mark_read": "UNREAD" in removed,
+        "remove_label": [label_names.get(i, i) for i in removed if i not in ("INBOX", "UNREAD")],
+        "forward_to": api_action.get("forward"),
+    }
+    return {"id": resource["id"], "criteria": criteria, "actions": actions}
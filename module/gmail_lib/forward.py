@@ -0,0 +1,96 @@
+"""
+Building a forward of an existing message for `gmail.forward`.
+
+The forward is an ordinary new message: a `Fwd:` subject, the sender's
+comment, the forwarded-message header block Gmail itself writes (From,
+Date, Subject, To, and Cc when present), then the original body, in plain
+text and, when the original had one, HTML. Attachments are copied from the
+original's decoded parts, so `gmail.send` can attach them as it would any
+other file.
+"""
+
+import base64
+import html
+import re
+from email.message import Message
+from typing import Any, Dict, List, Optional, Tuple
+
+from .mime import html_to_text
+
+FORWARD_SEPARATOR = "---------- Forwarded message ---------"
+FORWARDED_HEADERS = ("From", "Date", "Subject", "To", "Cc")
+
+SUBJECT_PREFIX_RE = re.compile(r"^\s*(fwd|fw)\s*:", re.IGNORECASE)
+# The inside of an HTML document's <body>, so the original nests in ours
+BODY_RE = re.compile(r"<body[^>]*>(.*)</body>", re.IGNORECASE | re.DOTALL)
+
+
+def forward_subject(subject: str) -> str:
+    """`Fwd: <subject>`, without stacking prefixes on an earlier forward."""
+    subject = (subject or "").strip()
+    if SUBJECT_PREFIX_RE.match(subject):
+        return subject
+    return f"Fwd: {subject}" if subject else "Fwd:"
+
+
+def _header_block(message: Message) -> List[Tuple[str, str]]:
+    return [(name, str(message[name])) for name in FORWARDED_HEADERS if message[name] is not None]
+
+
+def _original_bodies(message: Message) -> Tuple[str, Optional[str]]:
+    """The original's text and HTML bodies; text falls back to the HTML's text,
+    so an HTML-only original still forwards readably as plain text."""
+    text_part = message.get_body(preferencelist=("plain",))
+    html_part = message.get_body(preferencelist=("html",))
+    body_html = html_part.get_content() if html_part is not None else None
+    if body_html is not None:
+        inner = BODY_RE.search(body_html)
+        body_html = inner.group(1) if inner else body_html
+    if text_part is not None:
+        body_text = text_part.get_content()
+    elif body_html is not None:
+        body_text = html_to_text(body_html)
+    else:
+        body_text = ""
+    return body_text, body_html
+
+
+def forward_bodies(message: Message, comment: str = "") -> Tuple[str, Optional[str]]:
+    """Plain-text and HTML (or None) bodies for forwarding `message`."""
+    original_text, original_html = _original_bodies(message)
+    headers = _header_block(message)
+
+    text_lines = [comment.rstrip(), ""] if comment else []
+    text_lines.append(FORWARD_SEPARATOR)
+    text_lines.extend(f"{name}: {value}" for name, value in headers)
+    text_lines.extend(["", original_text.rstrip()])
+    body_text = "\n".join(text_lines) + "\n"
+
+    if original_html is None:
+        return body_text, None
+    comment_html = html.escape(comment.strip()).replace("\n", "<br>") if comment else ""
+    header_html = "<br>".join(f"{name}: {html.escape(value)}" for name, value in headers)
+    body_html = (
+        f'<div>{comment_html}</div><br>' if comment_html else ''
+    ) + (
+        f'<div class="gmail_quote">{FORWARD_SEPARATOR}<br>{header_html}<br><br>{original_html}</div>'
+    )
+    return body_text, body_html
+
+
+def forward_attachments(message: Message) -> List[Dict[str, Any]]:
+    """The original's attachments, as `gmail.read` lists them, in the shape
+    `gmail.send` takes."""
+    attachments = []
+    for part in message.walk():
+        if part.is_multipart():
+            continue
+        filename = part.get_filename()
+        if not filename and part.get_content_disposition() != 'attachment':
+            continue
+        attachments.append({
+            "filename": filename or "untitled",
+            "data": base64.b64encode(part.get_payload(decode=True) or b"").decode("ascii"),
+            "mime_type": part.get_content_type(),
+        })
+    return attachments
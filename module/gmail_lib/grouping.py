@@ -0,0 +1,71 @@
+"""
+Grouping message summaries into buckets for `gmail.unread`.
+
+A long flat list of unread mail is hard to act on; "41 from GitHub, 12
+from the CI bot" is not. `group_summaries` buckets summaries by sender
+address, sender domain, or label, counts each bucket, keeps its newest few
+summaries, and sorts the buckets largest first.
+"""
+
+from email.utils import parseaddr
+from typing import Any, Dict, List, Mapping, Optional
+
+GROUP_BY = ("sender", "label", "domain")
+DEFAULT_MAX_PER_GROUP = 3
+
+# Every unread inbox message has these, so they say nothing
+IGNORED_LABELS = frozenset({"INBOX", "UNREAD"})
+NO_LABEL = "(no label)"
+UNKNOWN_SENDER = "(unknown sender)"
+
+
+def sender_address(from_header: str) -> str:
+    """The lowercased address in a From header.
+
+    Handles bare addresses, `Name <addr>`, and quoted display names that
+    contain commas or angle brackets. Falls back to the header itself when
+    it holds no address.
+    """
+    _, address = parseaddr(from_header or "")
+    if "@" not in address:
+        return (from_header or "").strip().lower() or UNKNOWN_SENDER
+    return address.strip().lower()
+
+
+def sender_domain(from_header: str) -> str:
+    address = sender_address(from_header)
+    if "@" not in address:
+        return address
+    return address.rpartition("@")[2]
+
+
+def group_summaries(emails: List[Dict[str, Any]], group_by: str, max_per_group: int = DEFAULT_MAX_PER_GROUP,
+                    label_names: Optional[Mapping[str, str]] = None) -> List[Dict[str, Any]]:
+    """Bucket summary dicts, newest first, into `{key, count, emails}` groups.
+
+    Groups are sorted by count, largest first, then by key. By label, a
+    message counts toward each of its labels (other than INBOX and UNREAD);
+    `label_names` maps label ids to display names.
+    """
+    if group_by not in GROUP_BY:
+        raise ValueError(f"group_by must be one of: {', '.join(GROUP_BY)}")
+
+    groups: Dict[str, Dict[str, Any]] = {}
+    for summary in emails:
+        if group_by == "sender":
+            keys = [sender_address(summary.get("from", ""))]
+        elif group_by == "domain":
+            keys = [sender_domain(summary.get("from", ""))]
+        else:
+            labels = [label for label in summary.get("labels", []) if label not in IGNORED_LABELS]
+            keys = [(label_names or {}).get(label, label) for label in labels] or [NO_LABEL]
+
+        for key in keys:
+            group = groups.setdefault(key, {"key": key, "count": 0, "emails": []})
+            if group_by == "sender" and "name" not in group:
+                group["name"] = parseaddr(summary.get("from", ""))[0]
+            group["count"] += 1
+            if len(group["emails"]) < max_per_group:
+                group["emails"].append(summary)
+
+    return sorted(groups.values(), key=lambda group: (-group["count"], group["key"]))
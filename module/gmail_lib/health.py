@@ -0,0 +1,146 @@
+"""
+Structured health model.
+
+Every subsystem (service backend, auth, poller, cache, ...) reports a
+`SubsystemHealth`. `HealthReport` rolls them up into one overall status and
+can flatten itself into the `{name: {ok, message, latency_ms}}` map the FGP
+framework expects from `health_check()`.
+
+Statuses, best to worst: `ok`, `disabled`, `degraded`, `failed`.
+
+Subsystems are either *core* (the daemon can't serve requests without them:
+backend, auth, Python environment) or *auxiliary* (background features
+such as pollers or caches). Rollup precedence:
+
+1. Any core subsystem `failed`  -> overall `failed`
+2. Any auxiliary `failed`, or any subsystem `degraded` -> overall `degraded`
+3. Otherwise -> overall `ok` (`disabled` subsystems never affect the rollup)
+
+The overall `reason` and `remediation` come from the worst subsystem,
+ranked by status, then core before auxiliary, then severity. So an auth
+failure always outranks a degraded poller.
+"""
+
+import time
+from dataclasses import dataclass, field
+from typing import Any, Dict, List, Optional
+
+OK = "ok"
+DEGRADED = "degraded"
+FAILED = "failed"
+DISABLED = "disabled"
+
+STATUSES = (OK, DISABLED, DEGRADED, FAILED)
+
+INFO = "info"
+WARNING = "warning"
+ERROR = "error"
+CRITICAL = "critical"
+
+SEVERITIES = (INFO, WARNING, ERROR, CRITICAL)
+
+_STATUS_RANK = {status: rank for rank, status in enumerate(STATUSES)}
+_SEVERITY_RANK = {severity: rank for rank, severity in enumerate(SEVERITIES)}
+
+
+def default_severity(status: str, core: bool) -> str:
+    """Severity implied by a status when a subsystem doesn't set one."""
+    if status == FAILED:
+        return CRITICAL if core else ERROR
+    if status == DEGRADED:
+        return WARNING
+    return INFO
+
+
+@dataclass
+class SubsystemHealth:
+    """Health of one subsystem at one point in time."""
+
+    name: str
+    status: str
+    reason: str
+    message: str
+    core: bool = False
+    severity: Optional[str] = None
+    remediation: Optional[str] = None
+    latency_ms: Optional[float] = None
+    last_checked: float = field(default_factory=time.time)
+    details: Dict[str, Any] = field(default_factory=dict)
+
+    def __post_init__(self):
+        if self.status not in STATUSES:
+            raise ValueError(f"Unknown health status: {self.status}")
+        if self.severity is None:
+            self.severity = default_severity(self.status, self.core)
+        elif self.severity not in SEVERITIES:
+            raise ValueError(f"Unknown health severity: {self.severity}")
+
+    @property
+    def ok(self) -> bool:
+        return self.status in (OK, DISABLED)
+
+    def rank(self):
+        """Sort key: higher is worse."""
+        return (_STATUS_RANK[self.status], self.core, _SEVERITY_RANK[self.severity])
+
+    def to_dict(self) -> Dict[str, Any]:
+        result = {
+            "status": self.status,
+            "severity": self.severity,
+            "reason": self.reason,
+            "message": self.message,
+            "core": self.core,
+            "remediation": self.remediation,
+            "latency_ms": self.latency_ms,
+            "last_checked": self.last_checked,
+        }
+        if self.details:
+            result["details"] = self.details
+        return result
+
+    def to_framework(self) -> Dict[str, Any]:
+        """Entry in the FGP `health_check()` map."""
+        message = self.message if self.status == OK else f"[{self.status}] {self.message}"
+        if self.remediation and not self.ok:
+            message += f" -- {self.remediation}"
+        return {"ok": self.ok, "message": message, "latency_ms": self.latency_ms}
+
+
+@dataclass
+class HealthReport:
+    """All subsystem reports plus the computed rollup."""
+
+    subsystems: List[SubsystemHealth] = field(default_factory=list)
+
+    def add(self, subsystem: SubsystemHealth) -> SubsystemHealth:
+        self.subsystems.append(subsystem)
+        return subsystem
+
+    def worst(self) -> Optional[SubsystemHealth]:
+        active = [s for s in self.subsystems if s.status != DISABLED]
+        return max(active, key=SubsystemHealth.rank) if active else None
+
+    def overall_status(self) -> str:
+        if any(s.core and s.status == FAILED for s in self.subsystems):
+            return FAILED
+        if any(s.status in (FAILED, DEGRADED) for s in self.subsystems):
+            return DEGRADED
+        return OK
+
+    def to_dict(self) -> Dict[str, Any]:
+        status = self.overall_status()
+        worst = self.worst()
+        severity = worst.severity if worst and status != OK else INFO
+        return {
+            "status": status,
+            "ok": status == OK,
+            "severity": severity,
+            "reason": worst.reason if worst and status != OK else "all_ok",
+            "remediation": worst.remediation if worst and status != OK else None,
+            "checked_at": time.time(),
+            "subsystems": {s.name: s.to_dict() for s in self.subsystems},
+        }
+
+    def to_framework(self) -> Dict[str, Dict[str, Any]]:
+        """Flatten into the framework's `{name: HealthStatus}` map."""
+        return {s.name: s.to_framework() for s in self.subsystems}
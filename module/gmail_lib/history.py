@@ -0,0 +1,154 @@
+"""
+Mailbox history and push notifications.
+
+Gmail numbers every change to a mailbox with a history id. A client that
+remembers the last id it saw (from `gmail.profile`, a push notification, or
+its previous `gmail.history` call) can ask for only what changed since,
+instead of re-reading the inbox.
+
+`users.watch` makes Gmail publish a notification to a Cloud Pub/Sub topic
+whenever the mailbox changes, so the client knows when to ask. A watch
+lasts about seven days and has to be registered again before it expires;
+registering again simply extends it.
+
+History ids only go back about a week. An older one fails with
+`FullSyncRequired`: the client has to re-read the mailbox and start over
+from a fresh `gmail.profile` history id.
+"""
+
+import re
+import time
+from typing import Any, Dict, List, Optional
+
+from .backend import NotFound
+from .types import rfc3339_date
+
+# Param values -> history.list historyTypes, and the record fields they fill
+HISTORY_TYPES = {
+    "message_added": ("messageAdded", "messagesAdded"),
+    "message_deleted": ("messageDeleted", "messagesDeleted"),
+    "labels_added": ("labelAdded", "labelsAdded"),
+    "labels_removed": ("labelRemoved", "labelsRemoved"),
+}
+
+LABEL_CHANGES = ("labels_added", "labels_removed")
+
+LABEL_FILTER_BEHAVIORS = ("include", "exclude")
+
+TOPIC_RE = re.compile(r"projects/[^/\s]+/topics/[^/\s]+")
+HISTORY_ID_RE = re.compile(r"\d+")
+
+
+class FullSyncRequired(NotFound):
+    """Raised when a history id is older than the history Gmail keeps."""
+
+    code = "FullSyncRequired"
+
+    def __init__(self, start_history_id: str):
+        super().__init__(f"History id {start_history_id} is too old or invalid; re-read the mailbox and "
+                         f"start again from gmail.profile's history_id")
+        self.start_history_id = start_history_id
+
+
+def history_id_param(params: Dict[str, Any], name: str = "start_history_id") -> str:
+    """Validate a history id param; ints and digit strings are both accepted."""
+    value = params.get(name)
+    if isinstance(value, int) and not isinstance(value, bool) and value > 0:
+        return str(value)
+    if isinstance(value, str) and HISTORY_ID_RE.fullmatch(value):
+        return value
+    if value is None:
+        raise ValueError(f"{name} parameter is required")
+    raise ValueError(f"{name} must be a history id (got {value!r})")
+
+
+def history_types_param(value: Any) -> Optional[List[str]]:
+    """`history_types` as history.list historyTypes, or None for all."""
+    if value is None:
+        return None
+    if not isinstance(value, list) or not value or any(kind not in HISTORY_TYPES for kind in value):
+        raise ValueError(f"history_types must be a list of: {', '.join(HISTORY_TYPES)}")
+    return [HISTORY_TYPES[kind][0] for kind in value]
+
+
+def history_changes(records: List[Dict[str, Any]]) -> List[Dict[str, Any]]:
+    """Flatten history records into one change per message, oldest first.
+
+    Each change is `{history_id, type, message_id, thread_id, labels}`, with
+    `changed_labels` for label changes. `labels` are the message's labels
+    as of the change.
+    """
+    changes = []
+    for record in records:
+        for kind, (_, field) in HISTORY_TYPES.items():
+            for entry in record.get(field, []):
+                message = entry.get("message") or {}
+                change = {
+                    "history_id": record.get("id"),
+                    "type": kind,
+                    "message_id": message.get("id"),
+                    "thread_id": message.get("threadId"),
+                    "labels": message.get("labelIds", []),
+                }
+                if "labelIds" in entry:
+                    change["changed_labels"] = entry["labelIds"]
+                changes.append(change)
+    return changes
+
+
+def compact_changes(changes: List[Dict[str, Any]]) -> List[Dict[str, Any]]:
+    """Merge each message's label changes into its net effect.
+
+    A message whose labels changed several times gets at most one
+    `labels_added` and one `labels_removed`, in the place of its last label
+    change and with its labels as of then. A label added and removed again
+    (or the reverse) drops out, and so does every label change of a message
+    deleted in the same run of changes. Other changes are kept as they are.
+    """
+    deleted = {change["message_id"] for change in changes if change["type"] == "message_deleted"}
+    last = {change["message_id"]: index for index, change in enumerate(changes) if change["type"] in LABEL_CHANGES}
+    net: Dict[str, Dict[str, str]] = {}
+    compacted = []
+    for index, change in enumerate(changes):
+        if change["type"] not in LABEL_CHANGES:
+            compacted.append(change)
+            continue
+        message_id = change["message_id"]
+        if message_id in deleted:
+            continue
+        labels = net.setdefault(message_id, {})
+        for label in change.get("changed_labels", []):
+            if labels.get(label, change["type"]) != change["type"]:
+                del labels[label]  # undone
+            else:
+                labels[label] = change["type"]
+        if index != last[message_id]:
+            continue
+        for kind in LABEL_CHANGES:
+            changed = [label for label, label_kind in labels.items() if label_kind == kind]
+            if changed:
+                compacted.append(dict(change, type=kind, changed_labels=changed))
+    return compacted
+
+
+def validate_watch(topic: Any, behavior: Any):
+    """Check users.watch params before anything is registered."""
+    if not isinstance(topic, str) or not TOPIC_RE.fullmatch(topic):
+        raise ValueError(f"topic must be a Pub/Sub topic like projects/<project>/topics/<name> (got {topic!r})")
+    if behavior not in LABEL_FILTER_BEHAVIORS:
+        raise ValueError(f"label_filter_behavior must be one of: {', '.join(LABEL_FILTER_BEHAVIORS)}")
+
+
+def push_status(registration: Dict[str, Any], now: Optional[float] = None) -> Dict[str, Any]:
+    """A registered push watch as reported to clients."""
+    expiration_ms = int(registration["expiration_ms"])
+    remaining = expiration_ms / 1000 - (time.time() if now is None else now)
+    return {
+        "topic": registration["topic"],
+        "labels": registration["labels"],
+        "label_filter_behavior": registration["label_filter_behavior"],
+        "history_id": registration["history_id"],
+        "expiration": rfc3339_date("", expiration_ms),
+        "expires_in_secs": max(0, int(remaining)),
+        "expired": remaining <= 0,
+    }
@@ -0,0 +1,187 @@
+"""
+Idempotency keys for sends.
+
+A `gmail.send` (or `gmail.forward`) with an `idempotency_key` is recorded in
+`idempotency.json` under the service directory along with its result.
+Repeating the call with the same key, for the same account, within `ttl`
+seconds (default 24 hours) returns the recorded result with
+`duplicate: true` instead of sending again. Calls without a key are not
+affected.
+
+Two things make a retry safe even when the first attempt's outcome is
+unknown (a timeout or 5xx after Gmail accepted the message, or a daemon
+that died mid-send):
+
+- The key's entry, with a Message-ID for the message, is written before
+  the send. A retry that finds an entry without a result searches the
+  mailbox for that Message-ID. If Gmail has it, the retry returns it as
+  the duplicate. If not, the same message is sent again, with the same
+  Message-ID.
+- Calls with the same key are serialized, so a client that reconnects and
+  resends while the first call is still running waits for it and gets its
+  result.
+
+Reusing a key for a different message is rejected rather than silently
+answered with the old result. A send that fails before reaching Gmail
+(invalid params, say) leaves no entry, so the key can be used again.
+"""
+
+import hashlib
+import json
+import logging
+import os
+import threading
+import time
+from contextlib import contextmanager
+from dataclasses import asdict, dataclass
+from email.utils import make_msgid
+from pathlib import Path
+from typing import Any, Dict, Iterator, Mapping, Optional, Tuple
+
+log = logging.getLogger("fgp_gmail.idempotency")
+
+DEFAULT_TTL_SECS = 24 * 3600
+MAX_KEY_LENGTH = 200
+MSGID_DOMAIN = "fgp-gmail.local"
+
+# Params that don't change which message is sent
+UNFINGERPRINTED_PARAMS = frozenset({"idempotency_key", "account", "dry_run", "queue_on_failure"})
+
+
+def parse_key(value: Any) -> Optional[str]:
+    """Validate an `idempotency_key` param; missing means none."""
+    if value is None:
+        return None
+    if not isinstance(value, str) or not value.strip():
+        raise ValueError("idempotency_key must be a non-empty string")
+    if len(value) > MAX_KEY_LENGTH:
+        raise ValueError(f"idempotency_key must be at most {MAX_KEY_LENGTH} characters")
+    return value
+
+
+def fingerprint(params: Dict[str, Any]) -> str:
+    """A digest of the params that decide what gets sent."""
+    relevant = {key: value for key, value in params.items() if key not in UNFINGERPRINTED_PARAMS}
+    encoded = json.dumps(relevant, sort_keys=True, default=str).encode()
+    return hashlib.sha256(encoded).hexdigest()
+
+
+@dataclass
+class Entry:
+    account: str
+    key: str
+    fingerprint: str
+    rfc822_id: str
+    created_at: float
+    # False until the message has been handed to Gmail at least once
+    attempted: bool = False
+    result: Optional[Dict[str, Any]] = None
+
+
+class IdempotencyStore:
+    """Recently used keys and their results, persisted as one JSON file."""
+
+    def __init__(self, path: Path, ttl: float = DEFAULT_TTL_SECS, clock=time.time):
+        self.path = Path(path)
+        self.ttl = max(0.0, float(ttl))
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._key_locks: Dict[Tuple[str, str], Tuple[threading.Lock, int]] = {}
+        self._entries: Optional[Dict[Tuple[str, str], Entry]] = None
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str], default_path: Path) -> "IdempotencyStore":
+        return cls(
+            path=Path(environ.get("FGP_GMAIL_IDEMPOTENCY_FILE") or default_path).expanduser(),
+            ttl=float(environ.get("FGP_GMAIL_IDEMPOTENCY_TTL") or DEFAULT_TTL_SECS),
+        )
+
+    def _load(self) -> Dict[Tuple[str, str], Entry]:
+        if self._entries is None:
+            self._entries = {}
+            try:
+                data = json.loads(self.path.read_text())
+                for item in data.get("entries", []):
+                    entry = Entry(**item)
+                    self._entries[(entry.account, entry.key)] = entry
+            except FileNotFoundError:
+                pass
+            except (ValueError, TypeError) as e:
+                log.warning("Ignoring unreadable idempotency file %s: %s", self.path, e)
+        now = self._clock()
+        for ident in [ident for ident, entry in self._entries.items() if entry.created_at + self.ttl <= now]:
+            del self._entries[ident]
+        return self._entries
+
+    def _save(self):
+        entries = [asdict(entry) for entry in self._load().values()]
+        self.path.parent.mkdir(parents=True, exist_ok=True)
+        tmp = self.path.with_name(f".{self.path.name}.tmp")
+        with open(tmp, "w") as f:
+            json.dump({"entries": entries}, f)
+            f.flush()
+            os.fsync(f.fileno())
+        os.replace(tmp, self.path)
+
+    @contextmanager
+    def _key_lock(self, ident: Tuple[str, str]) -> Iterator[None]:
+        with self._lock:
+            lock, holders = self._key_locks.get(ident, (threading.Lock(), 0))
+            self._key_locks[ident] = (lock, holders + 1)
+        try:
+            with lock:
+                yield
+        finally:
+            with self._lock:
+                lock, holders = self._key_locks[ident]
+                if holders == 1:
+                    del self._key_locks[ident]
+                else:
+                    self._key_locks[ident] = (lock, holders - 1)
+
+    @contextmanager
+    def hold(self, account: str, key: str, digest: str) -> Iterator[Entry]:
+        """The entry for `key`, held against concurrent calls with it.
+
+        A new entry gets a fresh Message-ID and isn't stored until `attempt`
+        marks it sent to Gmail. Raises ValueError if the key was used for
+        different params.
+        """
+        ident = (account, key)
+        with self._key_lock(ident):
+            with self._lock:
+                entry = self._load().get(ident)
+            if entry is not None and entry.fingerprint != digest:
+                raise ValueError(f"idempotency_key {key!r} was already used for a different message")
+            if entry is None:
+                entry = Entry(account=account, key=key, fingerprint=digest,
+                              rfc822_id=make_msgid(domain=MSGID_DOMAIN), created_at=self._clock())
+            yield entry
+
+    def attempt(self, entry: Entry):
+        """Record, before sending, that `entry`'s message is going to Gmail."""
+        entry.attempted = True
+        with self._lock:
+            self._load()[(entry.account, entry.key)] = entry
+            self._save()
+
+    def finish(self, entry: Entry, result: Dict[str, Any]):
+        """Record the result to return for repeats of `entry`'s key."""
+        entry.result = result
+        with self._lock:
+            self._load()[(entry.account, entry.key)] = entry
+            try:
+                self._save()
+            except OSError as e:
+                # The message is out; failing the call now would invite a resend
+                log.warning("Failed to save idempotency file %s: %s", self.path, e)
+
+    def to_dict(self) -> Dict[str, Any]:
+        with self._lock:
+            entries = list(self._load().values())
+        return {
+            'path': str(self.path),
+            'ttl_secs': self.ttl,
+            'keys': len(entries),
+            'unfinished': sum(1 for entry in entries if entry.result is None),
+        }
@@ -0,0 +1,307 @@
+"""
+Durable "do X at time T" journal shared by timer-driven features.
+
+Outbox retries, scheduled sends, snoozes, and reminders all need the same
+thing: persist a job, find it again after a crash, and run it at most once.
+This module implements that once.
+
+Storage layout under the journal directory:
+
+    snapshot.json   compacted state, replaced atomically via os.replace()
+    wal.log         append-only log of changes since the snapshot
+
+Each WAL line is `<crc32 hex> <json>\\n`. On recovery the snapshot is loaded
+and WAL records newer than the snapshot's sequence number are replayed. A
+torn tail (missing newline or bad checksum) is a write that never returned
+to its caller, so it is discarded and the log truncated back to the last
+good record. A bad record with good ones after it isn't a torn write;
+recovery raises `JournalError` rather than throw the acknowledged records
+away.
+
+Jobs move through `pending -> claimed -> (completed | released)`. Callers
+must `claim()` a job before executing it and `complete()` it afterwards.
+A job whose claim survived a crash but whose completion didn't is reported
+by `in_doubt()` instead of `due()`, so it's never run twice; the owning
+feature decides how to reconcile it (e.g. the outbox checks whether Gmail
+already has the message).
+"""
+
+import json
+import os
+import threading
+import time
+import uuid
+import zlib
+from dataclasses import asdict, dataclass, field
+from pathlib import Path
+from typing import Any, Dict, List, Optional
+
+SNAPSHOT_FILE = "snapshot.json"
+WAL_FILE = "wal.log"
+
+PENDING = "pending"
+CLAIMED = "claimed"
+
+
+class JournalError(RuntimeError):
+    """Raised for invalid journal operations (unknown id, wrong state) and
+    for a WAL corrupted before its tail."""
+
+
+@dataclass
+class Job:
+    """A scheduled unit of work."""
+
+    id: str
+    kind: str
+    payload: Dict[str, Any]
+    at: float
+    state: str = PENDING
+    created_at: float = field(default_factory=time.time)
+
+
+def _encode(record: Dict[str, Any]) -> bytes:
+    body = json.dumps(record, sort_keys=True, separators=(",", ":")).encode()
+    return b"%08x " % zlib.crc32(body) + body + b"\n"
+
+
+def _decode(line: bytes) -> Optional[Dict[str, Any]]:
+    """Parse one WAL line, or None if it is torn or corrupt."""
+    if not line.endswith(b"\n") or len(line) < 10 or line[8:9] != b" ":
+        return None
+    body = line[9:-1]
+    try:
+        if int(line[:8], 16) != zlib.crc32(body):
+            return None
+        return json.loads(body)
+    except ValueError:
+        return None
+
+
+class Journal:
+    """Crash-safe job journal. Thread-safe."""
+
+    def __init__(self, directory: Path, compact_every: int = 1000, fsync: bool = True):
+        self.directory = Path(directory)
+        self.compact_every = compact_every
+        self.fsync = fsync
+        self._lock = threading.RLock()
+        self._jobs: Dict[str, Job] = {}
+        self._seq = 0
+        self._wal_records = 0
+        self._wal = None
+
+        self.directory.mkdir(parents=True, exist_ok=True)
+        self._recover()
+
+    # -- persistence ---------------------------------------------------------
+
+    @property
+    def snapshot_path(self) -> Path:
+        return self.directory / SNAPSHOT_FILE
+
+    @property
+    def wal_path(self) -> Path:
+        return self.directory / WAL_FILE
+
+    def _sync(self, f):
+        f.flush()
+        if self.fsync:
+            os.fsync(f.fileno())
+
+    def _recover(self):
+        """Load the snapshot, replay the WAL, and drop any torn tail.
+
+        Raises JournalError if an undecodable record is followed by a valid
+        one: truncating there would lose acknowledged changes.
+        """
+        if self.snapshot_path.exists():
+            with open(self.snapshot_path) as f:
+                snapshot = json.load(f)
+            self._seq = snapshot["seq"]
+            self._jobs = {job["id"]: Job(**job) for job in snapshot["jobs"]}
+
+        good_bytes = 0
+        if self.wal_path.exists():
+            with open(self.wal_path, "rb") as f:
+                lines = f.readlines()
+            for lineno, line in enumerate(lines, start=1):
+                record = _decode(line)
+                if record is None:
+                    if any(_decode(later) is not None for later in lines[lineno:]):
+                        raise JournalError(
+                            f"{self.wal_path}:{lineno}: corrupt record followed by valid ones; "
+                            "not truncating"
+                        )
+                    break
+                good_bytes += len(line)
+                if record["seq"] > self._seq:
+                    self._apply(record)
+                    self._seq = record["seq"]
+                self._wal_records += 1
+
+        self._wal = open(self.wal_path, "ab")
+        if self._wal.tell() != good_bytes:
+            self._wal.truncate(good_bytes)
+            self._sync(self._wal)
+
+    def _apply(self, record: Dict[str, Any]):
+        op = record["op"]
+        job_id = record["id"]
+        if op == "schedule":
+            self._jobs[job_id] = Job(
+                id=job_id,
+                kind=record["kind"],
+                payload=record["payload"],
+                at=record["at"],
+                created_at=record.get("created_at", record["at"]),
+            )
+            return
+
+        job = self._jobs.get(job_id)
+        if job is None:
+            return
+        if op in ("cancel", "complete"):
+            del self._jobs[job_id]
+        elif op == "claim":
+            job.state = CLAIMED
+        elif op == "release":
+            job.state = PENDING
+            if record.get("at") is not None:
+                job.at = record["at"]
+            if record.get("payload") is not None:
+                job.payload = record["payload"]
+
+    def _append(self, record: Dict[str, Any]):
+        """Durably log and apply one change. Caller holds the lock."""
+        record["seq"] = self._seq + 1
+        self._wal.write(_encode(record))
+        self._sync(self._wal)
+        self._seq = record["seq"]
+        self._apply(record)
+        self._wal_records += 1
+        if self.compact_every and self._wal_records >= self.compact_every:
+            self.compact()
+
+    def compact(self):
+        """Fold the WAL into a new snapshot and start an empty WAL."""
+        with self._lock:
+            snapshot = {"seq": self._seq, "jobs": [asdict(job) for job in self._jobs.values()]}
+            tmp = self.snapshot_path.with_suffix(".json.tmp")
+            with open(tmp, "w") as f:
+                json.dump(snapshot, f)
+                self._sync(f)
+            os.replace(tmp, self.snapshot_path)
+            self._sync_dir()
+
+            # A crash before this truncate is harmless: replay skips records
+            # at or below the snapshot's sequence number
+            self._wal.truncate(0)
+            self._sync(self._wal)
+            self._wal_records = 0
+
+    def _sync_dir(self):
+        if not self.fsync or not hasattr(os, "O_DIRECTORY"):
+            return
+        fd = os.open(self.directory, os.O_DIRECTORY)
+        try:
+            os.fsync(fd)
+        finally:
+            os.close(fd)
+
+    def close(self):
+        with self._lock:
+            if self._wal is not None:
+                self._wal.close()
+                self._wal = None
+
+    # -- API -----------------------------------------------------------------
+
+    def schedule(self, kind: str, payload: Dict[str, Any], at: float,
+                 job_id: Optional[str] = None) -> str:
+        """Persist a job to run at epoch time `at`. Returns its id.
+
+        `job_id` reuses an id the caller already handed out (e.g. to
+        re-queue finished work); it must not belong to a live job.
+        """
+        with self._lock:
+            if job_id is None:
+                job_id = uuid.uuid4().hex
+            elif job_id in self._jobs:
+                raise JournalError(f"Job {job_id} already exists")
+            self._append({
+                "op": "schedule",
+                "id": job_id,
+                "kind": kind,
+                "payload": payload,
+                "at": at,
+                "created_at": time.time(),
+            })
+            return job_id
+
+    def cancel(self, job_id: str) -> bool:
+        """Drop a pending job. Returns False if it doesn't exist or is claimed."""
+        with self._lock:
+            job = self._jobs.get(job_id)
+            if job is None or job.state != PENDING:
+                return False
+            self._append({"op": "cancel", "id": job_id})
+            return True
+
+    def due(self, now: Optional[float] = None, kind: Optional[str] = None) -> List[Job]:
+        """Pending jobs whose time has come, earliest first."""
+        now = time.time() if now is None else now
+        with self._lock:
+            jobs = [
+                job for job in self._jobs.values()
+                if job.state == PENDING and job.at <= now and (kind is None or job.kind == kind)
+            ]
+        return sorted(jobs, key=lambda job: (job.at, job.created_at))
+
+    def claim(self, job_id: str) -> Job:
+        """Mark a job as executing. Must return before the work starts."""
+        with self._lock:
+            job = self._require(job_id)
+            if job.state != PENDING:
+                raise JournalError(f"Job {job_id} is already {job.state}")
+            self._append({"op": "claim", "id": job_id})
+            return job
+
+    def complete(self, job_id: str):
+        """Record that a claimed job finished; it won't be seen again."""
+        with self._lock:
+            self._require(job_id)
+            self._append({"op": "complete", "id": job_id})
+
+    def release(self, job_id: str, at: Optional[float] = None,
+                payload: Optional[Dict[str, Any]] = None):
+        """Return a claimed job to pending, optionally rescheduled or updated.
+
+        Use when the work is known not to have happened (e.g. a retryable
+        failure) or after reconciling an in-doubt job.
+        """
+        with self._lock:
+            self._require(job_id)
+            self._append({"op": "release", "id": job_id, "at": at, "payload": payload})
+
+    def get(self, job_id: str) -> Optional[Job]:
+        with self._lock:
+            return self._jobs.get(job_id)
+
+    def pending(self, kind: Optional[str] = None) -> List[Job]:
+        """All pending jobs regardless of time, earliest first."""
+        return self.due(now=float("inf"), kind=kind)
+
+    def in_doubt(self, kind: Optional[str] = None) -> List[Job]:
+        """Claimed jobs with no recorded completion."""
+        with self._lock:
+            return [
+                job for job in self._jobs.values()
+                if job.state == CLAIMED and (kind is None or job.kind == kind)
+            ]
+
+    def _require(self, job_id: str) -> Job:
+        job = self._jobs.get(job_id)
+        if job is None:
+            raise JournalError(f"Unknown job: {job_id}")
+        return job
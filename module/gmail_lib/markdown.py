@@ -0,0 +1,123 @@
+"""
+Minimal Markdown to HTML rendering for `gmail.send`'s `body_markdown`.
+
+Covers what people write in email: paragraphs, ATX headings, bullet and
+numbered lists, blockquotes, fenced code blocks, horizontal rules, and the
+inline forms `code`, **bold**, *emphasis*, [links](url) and <autolinks>.
+Everything else passes through as text. All text is HTML-escaped before any
+markup is added, so the output never contains tags the author didn't ask
+for.
+"""
+
+import html
+import re
+from typing import List
+
+HEADING_RE = re.compile(r'^(#{1,6})\s+(.*?)\s*#*\s*$')
+BULLET_RE = re.compile(r'^\s*[-*+]\s+(.*)$')
+NUMBERED_RE = re.compile(r'^\s*\d+[.)]\s+(.*)$')
+RULE_RE = re.compile(r'^\s*([-*_])(\s*\1){2,}\s*$')
+FENCE_RE = re.compile(r'^\s*```')
+
+# Applied in order to escaped text; code spans are cut out first so their
+# contents aren't formatted
+INLINE_RULES = (
+    (re.compile(r'\*\*(.+?)\*\*|__(.+?)__'), lambda m: f"<strong>{m.group(1) or m.group(2)}</strong>"),
+    (re.compile(r'(?<![\w*])\*(?!\s)(.+?)(?<!\s)\*(?![\w*])|(?<![\w_])_(?!\s)(.+?)(?<!\s)_(?![\w_])'),
+     lambda m: f"<em>{m.group(1) or m.group(2)}</em>"),
+    (re.compile(r'\[([^\]]+)\]\(((?:https?|mailto):[^)\s]+)\)'), lambda m: f'<a href="{m.group(2)}">{m.group(1)}</a>'),
+    (re.compile(r'&lt;((?:https?|mailto):[^\s&]+)&gt;'), lambda m: f'<a href="{m.group(1)}">{m.group(1)}</a>'),
+)
+
+CODE_SPAN_RE = re.compile(r'`([^`]+)`')
+
+
+def render_inline(text: str) -> str:
+    """Escape a line of text and apply inline formatting."""
+    pieces = CODE_SPAN_RE.split(text)
+    out = []
+    for i, piece in enumerate(pieces):
+        escaped = html.escape(piece)
+        if i % 2:
+            out.append(f"<code>{escaped}</code>")
+            continue
+        for pattern, repl in INLINE_RULES:
+            escaped = pattern.sub(repl, escaped)
+        out.append(escaped)
+    return ''.join(out)
+
+
+def render(markdown: str) -> str:
+    """Render Markdown source to an HTML fragment."""
+    lines = markdown.replace('\r\n', '\n').split('\n')
+    blocks: List[str] = []
+    paragraph: List[str] = []
+
+    def flush_paragraph():
+        if paragraph:
+            blocks.append(f"<p>{'<br>'.join(render_inline(line.strip()) for line in paragraph)}</p>")
+            paragraph.clear()
+
+    i = 0
+    while i < len(lines):
+        line = lines[i]
+
+        if FENCE_RE.match(line):
+            flush_paragraph()
+            code = []
+            i += 1
+            while i < len(lines) and not FENCE_RE.match(lines[i]):
+                code.append(lines[i])
+                i += 1
+            blocks.append(f"<pre><code>{html.escape(chr(10).join(code), quote=False)}</code></pre>")
+            i += 1
+            continue
+
+        if not line.strip():
+            flush_paragraph()
+            i += 1
+            continue
+
+        heading = HEADING_RE.match(line)
+        if heading:
+            flush_paragraph()
+            level = len(heading.group(1))
+            blocks.append(f"<h{level}>{render_inline(heading.group(2))}</h{level}>")
+            i += 1
+            continue
+
+        if RULE_RE.match(line):
+            flush_paragraph()
+            blocks.append("<hr>")
+            i += 1
+            continue
+
+        if line.lstrip().startswith('>'):
+            flush_paragraph()
+            quoted = []
+            while i < len(lines) and lines[i].lstrip().startswith('>'):
+                quoted.append(re.sub(r'^\s*>\s?', '', lines[i]))
+                i += 1
+            blocks.append(f"<blockquote>{render(chr(10).join(quoted))}</blockquote>")
+            continue
+
+        for pattern, tag in ((BULLET_RE, 'ul'), (NUMBERED_RE, 'ol')):
+            if pattern.match(line):
+                flush_paragraph()
+                items = []
+                while i < len(lines) and pattern.match(lines[i]):
+                    items.append(f"<li>{render_inline(pattern.match(lines[i]).group(1))}</li>")
+                    i += 1
+                blocks.append(f"<{tag}>{''.join(items)}</{tag}>")
+                break
+        else:
+            paragraph.append(line)
+            i += 1
+
+    flush_paragraph()
+    return '\n'.join(blocks)
+
+
+def to_html_document(markdown: str) -> str:
+    """Render Markdown as a complete HTML document for an email body."""
+    return f'<!DOCTYPE html>\n<html><body>\n{render(markdown)}\n</body></html>\n'
@@ -0,0 +1,96 @@
+"""
+Per-method call metrics.
+
+`dispatch` records every call's total duration, the part of it spent
+waiting on the Gmail API, and whether it succeeded. Each method keeps
+lifetime counters plus a rolling window of the last `window` calls from
+which p50/p95/max are computed, so the numbers reflect current behavior
+rather than the whole uptime. Recording is O(1) under a single lock;
+percentiles are only computed when a snapshot is taken.
+"""
+
+import math
+import threading
+import time
+from collections import deque
+from typing import Any, Deque, Dict, List, Optional, Tuple
+
+DEFAULT_WINDOW = 200
+
+
+def percentile(sorted_values: List[float], fraction: float) -> Optional[float]:
+    """Nearest-rank percentile of an ascending list, or None if it's empty."""
+    if not sorted_values:
+        return None
+    rank = max(1, math.ceil(fraction * len(sorted_values)))
+    return sorted_values[min(rank, len(sorted_values)) - 1]
+
+
+def _summary(values: List[float]) -> Dict[str, Optional[float]]:
+    ordered = sorted(values)
+    return {
+        "p50_ms": _round(percentile(ordered, 0.50)),
+        "p95_ms": _round(percentile(ordered, 0.95)),
+        "max_ms": _round(ordered[-1] if ordered else None),
+    }
+
+
+def _round(value: Optional[float]) -> Optional[float]:
+    return None if value is None else round(value, 1)
+
+
+class _MethodStats:
+    def __init__(self, window: int):
+        self.count = 0
+        self.errors = 0
+        self.last_error: Optional[str] = None
+        # (total_ms, api_ms, ok) for the most recent calls
+        self.recent: Deque[Tuple[float, float, bool]] = deque(maxlen=window)
+
+    def snapshot(self) -> Dict[str, Any]:
+        recent = list(self.recent)
+        recent_errors = sum(1 for _, _, ok in recent if not ok)
+        total = _summary([total_ms for total_ms, _, _ in recent])
+        api = _summary([api_ms for _, api_ms, _ in recent])
+        return {
+            "count": self.count,
+            "errors": self.errors,
+            "last_error": self.last_error,
+            "window": len(recent),
+            "window_error_rate": recent_errors / len(recent) if recent else 0.0,
+            **total,
+            "api_p50_ms": api["p50_ms"],
+            "api_p95_ms": api["p95_ms"],
+            "api_max_ms": api["max_ms"],
+        }
+
+
+class Metrics:
+    """Thread-safe per-method latency and error tracking."""
+
+    def __init__(self, window: int = DEFAULT_WINDOW):
+        self.window = window
+        self._lock = threading.Lock()
+        self._methods: Dict[str, _MethodStats] = {}
+        self.since = time.time()
+
+    def record(self, method: str, total_ms: float, api_ms: float, error: Optional[str] = None):
+        with self._lock:
+            stats = self._methods.get(method)
+            if stats is None:
+                stats = self._methods[method] = _MethodStats(self.window)
+            stats.count += 1
+            if error is not None:
+                stats.errors += 1
+                stats.last_error = error
+            stats.recent.append((total_ms, api_ms, error is None))
+
+    def snapshot(self) -> Dict[str, Dict[str, Any]]:
+        """Structured stats for every method that has been called."""
+        with self._lock:
+            return {method: stats.snapshot() for method, stats in sorted(self._methods.items())}
+
+    def reset(self):
+        with self._lock:
+            self._methods.clear()
+            self.since = time.time()
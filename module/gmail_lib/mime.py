@@ -0,0 +1,273 @@
+"""
+MIME payload helpers for Gmail API message resources.
+
+Gmail returns `full` messages as a tree of parts with base64url-encoded
+bodies, and `raw` messages as a single base64url RFC 822 blob. These helpers
+turn either shape into decoded text/HTML bodies plus attachment metadata.
+"""
+
+import base64
+import re
+from email import errors, policy
+from email.message import Message
+from email.parser import BytesParser
+from html.parser import HTMLParser
+from typing import Any, Dict, List, Optional
+
+
+def decode_body(data: str) -> str:
+    """Decode a base64url body part to text."""
+    return base64.urlsafe_b64decode(data).decode('utf-8', errors='replace')
+
+
+def decode_raw(raw: str) -> bytes:
+    """Decode a base64url `raw` message, tolerating missing padding."""
+    return base64.urlsafe_b64decode(raw + '=' * (-len(raw) % 4))
+
+
+def header_map(payload: Dict[str, Any]) -> Dict[str, str]:
+    """Map header names to values for a payload."""
+    return {h['name']: h['value'] for h in payload.get('headers', [])}
+
+
+def extract_content(payload: Dict[str, Any]) -> Dict[str, Any]:
+    """
+    Pull the text body, HTML body, and attachment list out of a `full` payload.
+
+    Returns a dict with `body_text`, `body_html` (None when absent), and
+    `attachments` (list of {id, filename, mime_type, size}).
+    """
+    body_text: Optional[str] = None
+    body_html: Optional[str] = None
+    attachments: List[Dict[str, Any]] = []
+
+    def process_parts(parts):
+        nonlocal body_text, body_html
+        for part in parts:
+            mime_type = part.get('mimeType', '')
+            part_body = part.get('body', {})
+            filename = part.get('filename', '')
+
+            if mime_type == 'text/plain' and not filename:
+                data = part_body.get('data')
+                if data:
+                    body_text = decode_body(data)
+            elif mime_type == 'text/html' and not filename:
+                data = part_body.get('data')
+                if data:
+                    body_html = decode_body(data)
+            elif filename or part_body.get('attachmentId'):
+                # This is an attachment
+                attachments.append({
+                    'id': part_body.get('attachmentId'),
+                    'filename': filename or 'untitled',
+                    'mime_type': mime_type,
+                    'size': part_body.get('size', 0)
+                })
+
+            # Recursively process nested parts
+            if 'parts' in part:
+                process_parts(part['parts'])
+
+    # Handle simple messages (no parts)
+    if payload.get('body', {}).get('data'):
+        mime_type = payload.get('mimeType', 'text/plain')
+        data = payload['body']['data']
+        if 'text/plain' in mime_type:
+            body_text = decode_body(data)
+        elif 'text/html' in mime_type:
+            body_html = decode_body(data)
+
+    # Handle multipart messages
+    if 'parts' in payload:
+        process_parts(payload['parts'])
+
+    return {'body_text': body_text, 'body_html': body_html, 'attachments': attachments}
+
+
+def attachment_metadata(attachments: List[Dict[str, Any]]) -> List[Dict[str, Any]]:
+    """`extract_content`-style attachment entries as listings report them:
+    {filename, mime_type, size, attachment_id}."""
+    return [
+        {
+            'filename': attachment['filename'],
+            'mime_type': attachment['mime_type'],
+            'size': attachment['size'],
+            'attachment_id': attachment['id'],
+        }
+        for attachment in attachments
+    ]
+
+
+# Lines that introduce a quoted earlier message in a reply
+QUOTE_INTRO_RE = re.compile(
+    r"^\s*(On\b.{0,300}\bwrote:|-{2,}\s*Original Message\s*-{2,}|-{2,}\s*Forwarded message\s*-{2,})\s*$",
+    re.IGNORECASE,
+)
+
+
+def dequote(text: str) -> str:
+    """Strip the quoted trail from a plain-text reply.
+
+    Everything from an "On ... wrote:" line (which clients may wrap onto a
+    second line) or an "Original Message" separator onward is dropped, then
+    any trailing `>`-quoted lines. Quotes interleaved with new text are kept.
+    """
+    lines = text.splitlines()
+    for i, line in enumerate(lines):
+        wrapped = f"{line} {lines[i + 1]}" if i + 1 < len(lines) else line
+        if QUOTE_INTRO_RE.match(line) or (line.lstrip().startswith('On ') and QUOTE_INTRO_RE.match(wrapped)):
+            lines = lines[:i]
+            break
+    while lines and (not lines[-1].strip() or lines[-1].lstrip().startswith('>')):
+        lines.pop()
+    return '\n'.join(lines)
+
+
+class _TextExtractor(HTMLParser):
+    """Collects an HTML document's text, with line breaks where blocks end."""
+
+    BLOCK_TAGS = frozenset({
+        'p', 'div', 'br', 'tr', 'li', 'ul', 'ol', 'table', 'blockquote', 'pre', 'hr',
+        'h1', 'h2', 'h3', 'h4', 'h5', 'h6',
+    })
+    SKIPPED_TAGS = frozenset({'script', 'style', 'head', 'title'})
+
+    def __init__(self):
+        super().__init__(convert_charrefs=True)
+        self.chunks: List[str] = []
+        self._skipping = 0
+        self._href: Optional[str] = None
+        self._link_start = 0
+
+    def handle_starttag(self, tag, attrs):
+        if tag in self.SKIPPED_TAGS:
+            self._skipping += 1
+        elif tag in self.BLOCK_TAGS:
+            self.chunks.append('\n')
+            if tag == 'li':
+                self.chunks.append('- ')
+        elif tag == 'a':
+            self._href = dict(attrs).get('href')
+            self._link_start = len(self.chunks)
+
+    def handle_endtag(self, tag):
+        if tag in self.SKIPPED_TAGS:
+            self._skipping = max(0, self._skipping - 1)
+        elif tag in self.BLOCK_TAGS and tag != 'li':
+            self.chunks.append('\n')
+        elif tag == 'a' and self._href:
+            text = ''.join(self.chunks[self._link_start:]).strip()
+            if self._href.startswith(('http:', 'https:')) and self._href != text:
+                self.chunks.append(f' ({self._href})')
+            self._href = None
+
+    def handle_data(self, data):
+        if not self._skipping:
+            self.chunks.append(re.sub(r'\s+', ' ', data))
+
+
+def html_to_text(html: str) -> str:
+    """A readable plain-text rendering of an HTML body.
+
+    Drops scripts and styles, breaks lines at block elements, bullets list
+    items, and keeps link targets after their text.
+    """
+    parser = _TextExtractor()
+    parser.feed(html)
+    parser.close()
+    lines = [line.strip() for line in ''.join(parser.chunks).split('\n')]
+    return re.sub(r'\n{3,}', '\n\n', '\n'.join(lines)).strip()
+
+
+def parse_raw(raw: str) -> Message:
+    """Parse a base64url `raw` message into an email.message.Message."""
+    return BytesParser(policy=policy.default).parsebytes(decode_raw(raw))
+
+
+def parse_eml(data: bytes) -> Message:
+    """Parse an RFC 822 file, raising ValueError unless it looks like a message.
+
+    Python's parser accepts almost anything, so this also requires a From
+    header and a header block that ends where the body begins.
+    """
+    if not data.strip():
+        raise ValueError("file is empty")
+    message = BytesParser(policy=policy.default).parsebytes(data)
+    if any(isinstance(defect, errors.MissingHeaderBodySeparatorDefect) for defect in message.defects):
+        raise ValueError("no valid header block (expected 'Name: value' lines, then a blank line)")
+    if message['From'] is None:
+        raise ValueError("missing From header")
+    return message
+
+
+def payload_from_email(message: Message) -> Dict[str, Any]:
+    """
+    Describe a parsed email in the Gmail API payload shape (mimeType,
+    headers, parts), without bodies. Lets payload-based helpers such as
+    restriction detection work on `raw` messages too.
+    """
+    payload = {
+        'mimeType': message.get_content_type(),
+        'filename': message.get_filename() or '',
+        'headers': [{'name': k, 'value': str(v)} for k, v in message.items()],
+    }
+    if message.is_multipart():
+        payload['parts'] = [payload_from_email(part) for part in message.iter_parts()]
+    return payload
+
+
+def extract_email_content(message: Message) -> Dict[str, Any]:
+    """Text/HTML bodies and attachment metadata from a parsed raw message."""
+    body_text: Optional[str] = None
+    body_html: Optional[str] = None
+    attachments: List[Dict[str, Any]] = []
+
+    for part in message.walk():
+        if part.is_multipart():
+            continue
+        filename = part.get_filename()
+        mime_type = part.get_content_type()
+        if filename or part.get_content_disposition() == 'attachment':
+            content = part.get_payload(decode=True) or b''
+            attachments.append({
+                'id': None,
+                'filename': filename or 'untitled',
+                'mime_type': mime_type,
+                'size': len(content)
+            })
+        elif mime_type == 'text/plain' and body_text is None:
+            body_text = part.get_content()
+        elif mime_type == 'text/html' and body_html is None:
+            body_html = part.get_content()
+
+    return {'body_text': body_text, 'body_html': body_html, 'attachments': attachments}
+
+
+# Leading-byte signatures for common attachment types
+MAGIC_SIGNATURES = (
+    (b'%PDF-', 'application/pdf'),
+    (b'\x89PNG\r\n\x1a\n', 'image/png'),
+    (b'\xff\xd8\xff', 'image/jpeg'),
+    (b'GIF87a', 'image/gif'),
+    (b'GIF89a', 'image/gif'),
+    (b'PK\x03\x04', 'application/zip'),
+    (b'\x1f\x8b', 'application/gzip'),
+    (b'\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1', 'application/x-ole-storage'),
+    (b'BEGIN:VCALENDAR', 'text/calendar'),
+    (b'BEGIN:VCARD', 'text/vcard'),
+)
+
+
+def sniff_mime_type(data: bytes) -> str:
+    """Best-effort MIME type detection from file content."""
+    for signature, mime_type in MAGIC_SIGNATURES:
+        if data.startswith(signature):
+            return mime_type
+    if data.startswith(b'RIFF') and data[8:12] == b'WEBP':
+        return 'image/webp'
+    try:
+        data[:4096].decode('utf-8')
+    except UnicodeDecodeError:
+        return 'application/octet-stream'
+    return 'text/plain'
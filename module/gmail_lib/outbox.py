@@ -0,0 +1,273 @@
+"""
+Outbox for sends that failed and should be retried later.
+
+`gmail.send` with `queue_on_failure` hands a message that couldn't be sent
+(network down, HTTP 429 or 5xx) to the outbox instead of losing it. Queued
+messages are jobs in a `Journal` under the outbox directory, retried on a
+background timer with exponential backoff or on demand via
+`gmail.send_queued`, and given up after `max_attempts` tries.
+
+Layout:
+
+    outbox/snapshot.json, wal.log   the journal of pending sends
+    outbox/sent/<id>.json           sent messages, with their Gmail ids
+    outbox/failed/<id>.json         messages the outbox gave up on
+
+A message is never sent twice:
+
+- Every queued message carries its own Message-ID header. Before each
+  retry the outbox searches the mailbox for it, so a send whose success
+  response was lost (a 5xx or timeout after Gmail accepted it) is
+  recognized rather than repeated.
+- The journal claim is recorded before the send, and the sent record
+  (with the Gmail id) is written atomically before the job is completed.
+  After a crash, a claimed job with a sent record is completed; one
+  without goes back to pending and is checked against the mailbox first.
+"""
+
+import json
+import logging
+import os
+import threading
+import time
+from pathlib import Path
+from typing import Any, Callable, Dict, List, Optional
+
+from .backend import http_status
+from .journal import CLAIMED, Journal, JournalError
+
+log = logging.getLogger("fgp_gmail.outbox")
+
+JOB_KIND = "send"
+
+DEFAULT_MAX_ATTEMPTS = 5
+DEFAULT_INITIAL_BACKOFF_SECS = 60
+DEFAULT_MAX_BACKOFF_SECS = 3600
+DEFAULT_INTERVAL_SECS = 60
+
+PENDING = "pending"
+SENDING = "sending"
+SENT = "sent"
+GAVE_UP = "gave_up"
+
+# Returns the Gmail message resource from messages.send
+SendCall = Callable[[str, str], Dict[str, Any]]
+# Returns the Gmail id of a message with this Message-ID, or None
+FindCall = Callable[[str, str], Optional[str]]
+
+
+def queueable(error: Exception) -> bool:
+    """Whether a failed send is worth queueing: no HTTP response at all
+    (network trouble), rate limiting, or a server error."""
+    status = http_status(error)
+    return status is None or status == 429 or status >= 500
+
+
+def _write_json(path: Path, data: Dict[str, Any]):
+    """Write a JSON file atomically."""
+    path.parent.mkdir(parents=True, exist_ok=True)
+    tmp = path.with_suffix(".json.tmp")
+    with open(tmp, "w") as f:
+        json.dump(data, f, indent=2)
+        f.flush()
+        os.fsync(f.fileno())
+    os.replace(tmp, path)
+
+
+class Outbox:
+    """Persistent queue of sends awaiting retry. Thread-safe."""
+
+    def __init__(self, directory: Path, send: SendCall, find_sent: FindCall,
+                 max_attempts: int = DEFAULT_MAX_ATTEMPTS,
+                 initial_backoff_secs: float = DEFAULT_INITIAL_BACKOFF_SECS,
+                 max_backoff_secs: float = DEFAULT_MAX_BACKOFF_SECS,
+                 interval: float = DEFAULT_INTERVAL_SECS, fsync: bool = True):
+        self.directory = Path(directory)
+        self._send = send
+        self._find_sent = find_sent
+        self.max_attempts = max(1, max_attempts)
+        self.initial_backoff_secs = initial_backoff_secs
+        self.max_backoff_secs = max_backoff_secs
+        self.interval = interval
+        self.journal = Journal(self.directory, fsync=fsync)
+        self._lock = threading.Lock()
+        self._stop = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+        self._recover()
+
+    @property
+    def sent_dir(self) -> Path:
+        return self.directory / "sent"
+
+    @property
+    def failed_dir(self) -> Path:
+        return self.directory / "failed"
+
+    def backoff_secs(self, attempts: int) -> float:
+        """Delay before the next try after `attempts` failed ones."""
+        return min(self.initial_backoff_secs * 2 ** (attempts - 1), self.max_backoff_secs)
+
+    def _recover(self):
+        for job in self.journal.in_doubt(JOB_KIND):
+            if (self.sent_dir / f"{job.id}.json").exists():
+                self.journal.complete(job.id)
+            else:
+                # Checked against the mailbox before it's sent again
+                self.journal.release(job.id, at=time.time())
+
+    # -- background retries --------------------------------------------------
+
+    def start(self):
+        self._stop.clear()
+        self._thread = threading.Thread(target=self._run, name="gmail-outbox", daemon=True)
+        self._thread.start()
+
+    def stop(self):
+        self._stop.set()
+        if self._thread is not None:
+            self._thread.join(5)
+        self.journal.close()
+
+    def _run(self):
+        while not self._stop.wait(self.interval):
+            try:
+                self.run_due()
+            except Exception:
+                log.exception("Outbox retry pass failed")
+
+    def run_due(self) -> List[Dict[str, Any]]:
+        """Try every queued message whose backoff has elapsed."""
+        return [self._attempt(job.id) for job in self.journal.due(kind=JOB_KIND)]
+
+    # -- API -----------------------------------------------------------------
+
+    def enqueue(self, account: str, raw: str, rfc822_message_id: str, error: Exception,
+                to: str = "", subject: str = "") -> Dict[str, Any]:
+        """Queue a message whose first send failed with `error`."""
+        now = time.time()
+        payload = {
+            "account": account,
+            "raw": raw,
+            "rfc822_message_id": rfc822_message_id,
+            "to": to,
+            "subject": subject,
+            "attempts": 1,
+            "last_error": str(error),
+            "last_attempt_at": now,
+            "queued_at": now,
+        }
+        job_id = self.journal.schedule(JOB_KIND, payload, at=now + self.backoff_secs(1))
+        log.warning("Queued send to %s for retry as %s: %s", to, job_id, error)
+        return self._describe(job_id, PENDING, payload, next_attempt_at=now + self.backoff_secs(1))
+
+    def items(self) -> List[Dict[str, Any]]:
+        """Queued and given-up messages, oldest first, without their content."""
+        items = [
+            self._describe(job.id, SENDING if job.state == CLAIMED else PENDING, job.payload,
+                           next_attempt_at=job.at)
+            for job in self.journal.pending(JOB_KIND) + self.journal.in_doubt(JOB_KIND)
+        ]
+        if self.failed_dir.exists():
+            for path in self.failed_dir.glob("*.json"):
+                with open(path) as f:
+                    record = json.load(f)
+                items.append(self._describe(record["id"], GAVE_UP, record))
+        return sorted(items, key=lambda item: item["queued_at"])
+
+    def retry(self, item_id: Optional[str] = None) -> List[Dict[str, Any]]:
+        """Try queued messages now, ignoring backoff.
+
+        With `item_id`, just that message; a given-up one is re-queued under
+        the same id with a fresh attempt count first.
+        """
+        if item_id is None:
+            return [self._attempt(job.id) for job in self.journal.pending(JOB_KIND)]
+
+        with self._lock:
+            failed = self.failed_dir / f"{item_id}.json"
+            if self.journal.get(item_id) is None and failed.exists():
+                with open(failed) as f:
+                    record = json.load(f)
+                payload = {key: record[key] for key in (
+                    "account", "raw", "rfc822_message_id", "to", "subject", "last_error",
+                    "last_attempt_at", "queued_at")}
+                payload["attempts"] = 0
+                self.journal.schedule(JOB_KIND, payload, at=time.time(), job_id=item_id)
+                failed.unlink()
+        if self.journal.get(item_id) is None:
+            raise KeyError(f"No queued message with id {item_id!r}")
+        return [self._attempt(item_id)]
+
+    # -- one attempt ---------------------------------------------------------
+
+    def _attempt(self, job_id: str) -> Dict[str, Any]:
+        try:
+            job = self.journal.claim(job_id)
+        except JournalError:
+            # Another retry pass got to it first
+            if self.journal.get(job_id) is not None:
+                state = SENDING
+            else:
+                state = SENT if (self.sent_dir / f"{job_id}.json").exists() else GAVE_UP
+            return {"id": job_id, "state": state}
+
+        payload = dict(job.payload)
+        account = payload["account"]
+        try:
+            message_id = self._find_sent(account, payload["rfc822_message_id"])
+            if message_id is not None:
+                return self._record_sent(job_id, payload, {"id": message_id}, reconciled=True)
+            result = self._send(account, payload["raw"])
+        except Exception as e:
+            return self._record_failure(job_id, payload, e)
+        return self._record_sent(job_id, payload, result, reconciled=False)
+
+    def _record_sent(self, job_id: str, payload: Dict[str, Any], result: Dict[str, Any],
+                     reconciled: bool) -> Dict[str, Any]:
+        record = {key: value for key, value in payload.items() if key != "raw"}
+        record.update(
+            id=job_id,
+            message_id=result.get("id"),
+            thread_id=result.get("threadId"),
+            sent_at=time.time(),
+            reconciled=reconciled,
+        )
+        if not reconciled:
+            record["attempts"] = payload["attempts"] + 1
+        # The Gmail id is on disk before the job is marked done
+        _write_json(self.sent_dir / f"{job_id}.json", record)
+        self.journal.complete(job_id)
+        log.info("Outbox message %s sent as %s", job_id, record["message_id"])
+        return {"id": job_id, "state": SENT, "message_id": record["message_id"],
+                "thread_id": record["thread_id"], "reconciled": reconciled}
+
+    def _record_failure(self, job_id: str, payload: Dict[str, Any], error: Exception) -> Dict[str, Any]:
+        now = time.time()
+        payload.update(attempts=payload["attempts"] + 1, last_error=str(error), last_attempt_at=now)
+        if payload["attempts"] >= self.max_attempts or not queueable(error):
+            with self._lock:
+                _write_json(self.failed_dir / f"{job_id}.json", dict(payload, id=job_id, gave_up_at=now))
+                self.journal.complete(job_id)
+            log.error("Outbox gave up on %s after %d attempts: %s", job_id, payload["attempts"], error)
+            return {"id": job_id, "state": GAVE_UP, "attempts": payload["attempts"], "error": str(error)}
+
+        next_at = now + self.backoff_secs(payload["attempts"])
+        self.journal.release(job_id, at=next_at, payload=payload)
+        return {"id": job_id, "state": PENDING, "attempts": payload["attempts"], "error": str(error),
+                "next_attempt_at": next_at}
+
+    @staticmethod
+    def _describe(item_id: str, state: str, payload: Dict[str, Any],
+                  next_attempt_at: Optional[float] = None) -> Dict[str, Any]:
+        return {
+            "id": item_id,
+            "state": state,
+            "account": payload["account"],
+            "to": payload["to"],
+            "subject": payload["subject"],
+            "attempts": payload["attempts"],
+            "last_error": payload["last_error"],
+            "last_attempt_at": payload["last_attempt_at"],
+            "queued_at": payload["queued_at"],
+            "next_attempt_at": next_attempt_at,
+        }
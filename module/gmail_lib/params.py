@@ -0,0 +1,133 @@
+"""
+Validation for method params shared across handlers.
+"""
+
+import datetime
+import logging
+import re
+import time
+from typing import Any, Dict, List, Optional
+
+log = logging.getLogger("fgp_gmail")
+
+DEFAULT_LIMIT = 10
+DEFAULT_MAX_LIMIT = 100
+
+# Structured search params and the Gmail operator each becomes
+SEARCH_TEXT_OPERATORS = {"from": "from", "to": "to", "subject": "subject", "label": "label"}
+SEARCH_DATE_OPERATORS = {"after": "after", "before": "before"}
+SEARCH_FLAG_OPERATORS = {"has_attachment": "has:attachment", "is_unread": "is:unread"}
+DATE_RE = re.compile(r"\d{4}-\d{2}-\d{2}")
+SEARCH_PARAMS = (
+    tuple(SEARCH_TEXT_OPERATORS) + tuple(SEARCH_DATE_OPERATORS) + tuple(SEARCH_FLAG_OPERATORS)
+)
+
+# Relative-age params (`7d`, `2w`, `1h`, ...), and seconds per unit for the
+# units Gmail's age operators lack
+AGE_PARAMS = {"older_than": "before", "newer_than": "after"}
+AGE_RE = re.compile(r"(\d+)([hdwmy])")
+AGE_UNIT_SECS = {"h": 3600, "w": 7 * 86400}
+
+
+def parse_limit(value: Any, default: int = DEFAULT_LIMIT, max_limit: int = DEFAULT_MAX_LIMIT,
+                name: str = "limit") -> int:
+    """Validate a `limit`-style param, clamping it to `max_limit`.
+
+    Missing means `default`. Anything other than a positive integer (floats,
+    strings, booleans, zero, negatives) is rejected rather than silently
+    replaced, so callers learn their request was malformed.
+    """
+    if value is None:
+        return default
+    if isinstance(value, bool) or not isinstance(value, int) or value <= 0:
+        raise ValueError(f"{name} must be a positive integer")
+    if value > max_limit:
+        log.warning("Clamping %s %d to max %d", name, value, max_limit)
+        return max_limit
+    return value
+
+
+def parse_char_limit(value: Any, name: str) -> Optional[int]:
+    """Validate an optional character cap; missing (or null) means no cap."""
+    if value is None:
+        return None
+    if isinstance(value, bool) or not isinstance(value, int) or value <= 0:
+        raise ValueError(f"{name} must be a positive integer (characters)")
+    return value
+
+
+def age_terms(params: Dict[str, Any], now: Optional[float] = None) -> List[str]:
+    """Gmail terms for the `older_than`/`newer_than` params.
+
+    Ages are a count and a unit: `h`ours, `d`ays, `w`eeks, `m`onths, or
+    `y`ears. Gmail's own `older_than:`/`newer_than:` only go down to days, so
+    hours and weeks become `before:`/`after:` with an epoch timestamp.
+    """
+    terms = []
+    for param, operator in AGE_PARAMS.items():
+        value = params.get(param)
+        if value is None:
+            continue
+        match = AGE_RE.fullmatch(value) if isinstance(value, str) else None
+        if not match or int(match.group(1)) == 0:
+            raise ValueError(f"{param} must be an age like 7d, 1h, 2w, 3m or 1y (got {value!r})")
+        count, unit = int(match.group(1)), match.group(2)
+        if unit in AGE_UNIT_SECS:
+            cutoff = int((time.time() if now is None else now) - count * AGE_UNIT_SECS[unit])
+            terms.append(f"{operator}:{cutoff}")
+        else:
+            terms.append(f"{param}:{value}")
+    return terms
+
+
+def _quote(value: str) -> str:
+    """Quote a search term if Gmail would otherwise split it."""
+    if value and not any(c.isspace() or c in '"(){}' for c in value):
+        return value
+    return '"' + value.replace('"', '') + '"'
+
+
+def compose_query(params: Dict[str, Any]) -> Optional[str]:
+    """Build a Gmail `q` string from `query` plus the structured search params.
+
+    Structured terms are ANDed with the raw query (wrapped in parentheses so
+    an `OR` inside it stays scoped). Dates must be `YYYY-MM-DD`. Returns None
+    if neither was given.
+    """
+    terms: List[str] = []
+
+    for param, operator in SEARCH_TEXT_OPERATORS.items():
+        value = params.get(param)
+        if value is None or value == "":
+            continue
+        if not isinstance(value, str):
+            raise ValueError(f"{param} must be a string")
+        terms.append(f"{operator}:{_quote(value)}")
+
+    for param, operator in SEARCH_DATE_OPERATORS.items():
+        value = params.get(param)
+        if value is None:
+            continue
+        try:
+            if not isinstance(value, str) or not DATE_RE.fullmatch(value):
+                raise ValueError
+            date = datetime.datetime.strptime(value, "%Y-%m-%d").date()
+        except ValueError:
+            raise ValueError(f"{param} must be a date in YYYY-MM-DD format (got {value!r})") from None
+        # Gmail's own date syntax is YYYY/MM/DD
+        terms.append(f"{operator}:{date:%Y/%m/%d}")
+
+    for param, operator in SEARCH_FLAG_OPERATORS.items():
+        value = params.get(param)
+        if value is None:
+            continue
+        if not isinstance(value, bool):
+            raise ValueError(f"{param} must be a boolean")
+        terms.append(operator if value else f"-{operator}")
+
+    query = params.get("query")
+    if query is not None and not isinstance(query, str):
+        raise ValueError("query must be a string")
+    if query and query.strip():
+        terms.insert(0, f"({query.strip()})" if terms else query.strip())
+    return " ".join(terms) or None
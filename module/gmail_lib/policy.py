@@ -0,0 +1,128 @@
+"""
+Retry and timeout policy for Gmail API calls.
+
+Both are configured by the daemon (see `[retry]` and `[timeouts]` in
+config.toml) and handed over through environment variables:
+
+- `RetryPolicy` retries transient API errors (HTTP 429 and 5xx) with
+  exponential backoff. Sends are only retried on 429, which Gmail returns
+  before doing any work; a 5xx after a send may mean it went out.
+- `CallTimeouts` gives each method a time budget. The budget is checked
+  before every API call a method makes, so a method that pages or batches
+  stops with a TimeoutError instead of running on; a single in-flight API
+  request is not interrupted.
+
+The defaults (one attempt, no timeouts) match the behavior before either
+existed.
+"""
+
+import json
+import time
+from dataclasses import dataclass
+from typing import Callable, Dict, Iterable, Mapping, Optional, TypeVar
+
+from .backend import http_status
+
+T = TypeVar("T")
+
+RETRYABLE_STATUSES = frozenset({429, 500, 502, 503, 504})
+
+# API calls that must not be repeated unless Gmail rejected them outright
+NON_IDEMPOTENT_CALLS = frozenset({"messages.send", "drafts.send"})
+
+DEFAULT_MAX_ATTEMPTS = 1
+DEFAULT_INITIAL_BACKOFF_MS = 500
+DEFAULT_MAX_BACKOFF_MS = 8000
+
+# Key in [timeouts] that applies to methods without their own entry
+DEFAULT_TIMEOUT_KEY = "default"
+
+
+@dataclass(frozen=True)
+class RetryPolicy:
+    max_attempts: int = DEFAULT_MAX_ATTEMPTS
+    initial_backoff_ms: int = DEFAULT_INITIAL_BACKOFF_MS
+    max_backoff_ms: int = DEFAULT_MAX_BACKOFF_MS
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str]) -> "RetryPolicy":
+        return cls(
+            max_attempts=max(1, int(environ.get("FGP_GMAIL_RETRY_MAX_ATTEMPTS", DEFAULT_MAX_ATTEMPTS))),
+            initial_backoff_ms=int(environ.get("FGP_GMAIL_RETRY_INITIAL_BACKOFF_MS", DEFAULT_INITIAL_BACKOFF_MS)),
+            max_backoff_ms=int(environ.get("FGP_GMAIL_RETRY_MAX_BACKOFF_MS", DEFAULT_MAX_BACKOFF_MS)),
+        )
+
+    def retryable(self, name: str, error: Exception) -> bool:
+        status = http_status(error)
+        if name in NON_IDEMPOTENT_CALLS:
+            return status == 429
+        return status in RETRYABLE_STATUSES
+
+    def backoff_secs(self, attempt: int) -> float:
+        """Delay before retry number `attempt` (1-based)."""
+        return min(self.initial_backoff_ms * 2 ** (attempt - 1), self.max_backoff_ms) / 1000
+
+    def run(self, name: str, call: Callable[[], T], deadline: Optional[float] = None,
+            sleep: Callable[[float], None] = time.sleep) -> T:
+        """Run `call()`, retrying transient errors while attempts and time remain."""
+        attempt = 1
+        while True:
+            try:
+                return call()
+            except Exception as e:
+                if attempt >= self.max_attempts or not self.retryable(name, e):
+                    raise
+                delay = self.backoff_secs(attempt)
+                if deadline is not None and time.monotonic() + delay >= deadline:
+                    raise
+                sleep(delay)
+                attempt += 1
+
+    def to_dict(self) -> Dict[str, int]:
+        return {
+            "max_attempts": self.max_attempts,
+            "initial_backoff_ms": self.initial_backoff_ms,
+            "max_backoff_ms": self.max_backoff_ms,
+        }
+
+
+class CallTimeouts:
+    """Per-method time budgets in seconds, keyed by short method name."""
+
+    def __init__(self, timeouts: Optional[Mapping[str, float]] = None):
+        self.timeouts = dict(timeouts or {})
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str], methods: Iterable[str]) -> "CallTimeouts":
+        """Parse `FGP_GMAIL_METHOD_TIMEOUTS` (a JSON object of seconds).
+
+        Raises ValueError for keys that aren't known methods, so a typo in
+        the config fails startup instead of silently doing nothing.
+        """
+        raw = environ.get("FGP_GMAIL_METHOD_TIMEOUTS")
+        if not raw:
+            return cls()
+        try:
+            timeouts = json.loads(raw)
+        except ValueError as e:
+            raise ValueError(f"FGP_GMAIL_METHOD_TIMEOUTS is not valid JSON: {e}") from None
+        if not isinstance(timeouts, dict):
+            raise ValueError("FGP_GMAIL_METHOD_TIMEOUTS must be a JSON object")
+
+        known = {method.split(".", 1)[-1] for method in methods} | {DEFAULT_TIMEOUT_KEY}
+        parsed = {}
+        for key, secs in timeouts.items():
+            short = key.split(".", 1)[-1] if key.startswith("gmail.") else key
+            if short not in known:
+                raise ValueError(f"[timeouts] {key}: unknown method (known: {', '.join(sorted(known))})")
+            if isinstance(secs, bool) or not isinstance(secs, (int, float)) or secs <= 0:
+                raise ValueError(f"[timeouts] {key}: must be a positive number of seconds")
+            parsed[short] = float(secs)
+        return cls(parsed)
+
+    def for_method(self, method: str) -> Optional[float]:
+        short = method.split(".", 1)[-1]
+        return self.timeouts.get(short, self.timeouts.get(DEFAULT_TIMEOUT_KEY))
+
+    def to_dict(self) -> Dict[str, float]:
+        return dict(sorted(self.timeouts.items()))
@@ -0,0 +1,154 @@
+"""
+Speculative prefetch of the next page of paginated reads.
+
+After a paginated call (e.g. `gmail.search`) returns a `next_page_token`,
+the module hands the follow-up call to `Prefetcher.submit`. A single
+low-priority worker thread runs it and stores the result in the result
+cache under the key the client's follow-up request will use, so fetching
+page 2 is a cache hit. With `pages > 1` each prefetched page chains the
+next one.
+
+Prefetching spends quota on pages that may never be read, so it can be
+vetoed: `gate()` returns a reason to skip (quota budget low, API backing
+off, ...) or None to proceed, and a fetch that finds it can't run right
+away raises `Skip` to be counted as skipped rather than failed. Pending work is bounded; when the queue is
+full new prefetches are dropped rather than delaying anything.
+
+`stats()` reports how many prefetched pages were actually requested
+(`hit_rate`), to tell whether prefetch is earning its quota cost.
+"""
+
+import logging
+import queue
+import threading
+from collections import OrderedDict
+from typing import Any, Callable, Dict, Hashable, Iterable, Optional
+
+log = logging.getLogger("fgp_gmail.prefetch")
+
+DEFAULT_PAGES = 1
+DEFAULT_METHODS = ("gmail.inbox", "gmail.search")
+MAX_PENDING = 8
+
+# Prefetched keys remembered for hit accounting; older ones have long since
+# expired from the cache
+MAX_TRACKED = 1024
+
+
+_UNTRACKED = object()
+
+
+class Skip(Exception):
+    """Raised by a fetch that chose not to run; `reason` is counted in `skipped`."""
+
+    def __init__(self, reason: str):
+        super().__init__(reason)
+        self.reason = reason
+
+
+class Prefetcher:
+    """Runs prefetches on one background worker and tracks their payoff."""
+
+    def __init__(self, enabled: bool = False, pages: int = DEFAULT_PAGES,
+                 methods: Iterable[str] = DEFAULT_METHODS,
+                 gate: Callable[[], Optional[str]] = lambda: None):
+        self.enabled = enabled and pages > 0
+        self.pages = pages
+        self.methods = frozenset(methods)
+        self.gate = gate
+        self._queue: "queue.Queue[Callable[[], None]]" = queue.Queue(MAX_PENDING)
+        self._lock = threading.Lock()
+        self._worker: Optional[threading.Thread] = None
+        self._prefetched: "OrderedDict[Hashable, None]" = OrderedDict()
+        self.issued = 0
+        self.completed = 0
+        self.failed = 0
+        self.skipped: Dict[str, int] = {}
+        self.hits = 0
+
+    def wants(self, method: str) -> bool:
+        return self.enabled and method in self.methods
+
+    def submit(self, key: Hashable, fetch: Callable[[], Any], store: Callable[[Any], None],
+               depth: int = 1, chain: Optional[Callable[[Any, int], None]] = None):
+        """Queue `fetch()` for cache key `key`; `store(result)` caches it.
+
+        `chain(result, depth)` is called afterwards so the caller can submit
+        the page after it while `depth < pages`.
+        """
+        reason = self.gate()
+        if reason is not None:
+            self._skip(reason)
+            return
+
+        def run():
+            try:
+                result = fetch()
+            except Skip as e:
+                self._skip(e.reason)
+                return
+            except Exception as e:
+                with self._lock:
+                    self.failed += 1
+                log.debug("Prefetch failed: %s", e)
+                return
+            store(result)
+            with self._lock:
+                self.completed += 1
+                self._prefetched[key] = None
+                while len(self._prefetched) > MAX_TRACKED:
+                    self._prefetched.popitem(last=False)
+            if chain is not None and depth < self.pages:
+                chain(result, depth + 1)
+
+        try:
+            self._queue.put_nowait(run)
+        except queue.Full:
+            self._skip("queue_full")
+            return
+        with self._lock:
+            self.issued += 1
+        self._ensure_worker()
+
+    def note_hit(self, key: Hashable):
+        """Record a cache hit; counts toward the hit rate if we prefetched it."""
+        with self._lock:
+            if self._prefetched.pop(key, _UNTRACKED) is not _UNTRACKED:
+                self.hits += 1
+
+    def _skip(self, reason: str):
+        with self._lock:
+            self.skipped[reason] = self.skipped.get(reason, 0) + 1
+
+    def _ensure_worker(self):
+        with self._lock:
+            if self._worker is not None and self._worker.is_alive():
+                return
+            self._worker = threading.Thread(target=self._run, name="gmail-prefetch", daemon=True)
+            self._worker.start()
+
+    def _run(self):
+        while True:
+            job = self._queue.get()
+            try:
+                job()
+            finally:
+                self._queue.task_done()
+
+    def drain(self):
+        """Block until queued prefetches have finished."""
+        self._queue.join()
+
+    def stats(self) -> Dict[str, Any]:
+        with self._lock:
+            return {
+                "enabled": self.enabled,
+                "pages": self.pages,
+                "methods": sorted(self.methods),
+                "issued": self.issued,
+                "completed": self.completed,
+                "failed": self.failed,
+                "skipped": dict(self.skipped),
+                "hits": self.hits,
+                "hit_rate": round(self.hits / self.completed, 3) if self.completed else None,
+            }
@@ -0,0 +1,131 @@
+"""
+Token-bucket rate limiting for Gmail calls.
+
+Gmail meters each user's API use in quota units and answers bursts with
+HTTP 429. `RateLimiter` keeps one bucket per account, refilled at
+`rate_per_sec` tokens per second (`[gmail] rate_limit_per_sec` in
+config.toml; 0, the default, turns limiting off). Each call takes tokens
+according to `METHOD_WEIGHTS`: sends and other writes cost Gmail more quota
+than reads, so they cost more here too.
+
+When a bucket is short, the call waits for the refill, but never longer
+than `max_wait_secs` or past the call's own deadline; past either it fails
+with `RateLimited` so the caller can back off instead of hanging. Waiting
+callers reserve their tokens (the bucket goes negative), so later callers
+queue up behind them rather than overtaking.
+"""
+
+import threading
+import time
+from typing import Any, Callable, Dict, Mapping, Optional
+
+DEFAULT_MAX_WAIT_SECS = 2.0
+
+DEFAULT_WEIGHT = 1
+
+# Tokens per call, by method. Unlisted methods cost DEFAULT_WEIGHT; methods
+# that don't call Gmail cost nothing.
+METHOD_WEIGHTS: Dict[str, int] = {
+    "gmail.send": 5,
+    "gmail.forward": 5,
+    "gmail.send_draft": 5,
+    "gmail.bulk_modify": 5,
+    "gmail.create_draft": 2,
+    "gmail.archive": 2,
+    "gmail.unarchive": 2,
+    "gmail.import_raw": 2,
+    "gmail.filter_create": 2,
+    "gmail.filter_delete": 2,
+    "gmail.vacation_set": 2,
+    "gmail.auth_status": 0,
+    "gmail.auth_login": 0,
+    "gmail.auth_login_status": 0,
+    "gmail.auth_login_cancel": 0,
+    "gmail.watch_status": 0,
+}
+
+
+class RateLimited(RuntimeError):
+    """Raised when a call would have to wait too long for rate-limit tokens."""
+
+    def __init__(self, method: str, retry_after: float):
+        super().__init__(f"{method} is rate limited; retry in {retry_after:.1f}s")
+        self.method = method
+        self.retry_after = retry_after
+
+
+def weight(method: str) -> int:
+    return METHOD_WEIGHTS.get(method, DEFAULT_WEIGHT)
+
+
+class RateLimiter:
+    """Per-account token buckets. Thread-safe."""
+
+    def __init__(self, rate_per_sec: float = 0, max_wait_secs: float = DEFAULT_MAX_WAIT_SECS,
+                 clock: Callable[[], float] = time.monotonic,
+                 sleep: Callable[[float], None] = time.sleep):
+        self.rate = max(float(rate_per_sec), 0.0)
+        # One second's worth of calls, but always room for the heaviest one
+        self.capacity = max(self.rate, float(max(METHOD_WEIGHTS.values())))
+        self.max_wait_secs = max_wait_secs
+        self._clock = clock
+        self._sleep = sleep
+        self._lock = threading.Lock()
+        # account -> (tokens, last refill time)
+        self._buckets: Dict[Optional[str], tuple] = {}
+
+    @classmethod
+    def from_env(cls, environ: Mapping[str, str]) -> "RateLimiter":
+        return cls(rate_per_sec=float(environ.get("FGP_GMAIL_RATE_LIMIT") or 0))
+
+    @property
+    def enabled(self) -> bool:
+        return self.rate > 0
+
+    def _tokens(self, account: Optional[str], now: float) -> float:
+        """Refill and return the account's balance. Caller holds the lock."""
+        tokens, updated = self._buckets.get(account, (self.capacity, now))
+        return min(self.capacity, tokens + (now - updated) * self.rate)
+
+    def acquire(self, method: str, account: Optional[str] = None,
+                deadline: Optional[float] = None, wait: bool = True) -> float:
+        """Take `method`'s tokens, waiting for them if need be.
+
+        Returns the seconds waited. Raises RateLimited, taking nothing, if
+        the wait would exceed `max_wait_secs` or run past `deadline` (a
+        `time.monotonic()` value), or at all when `wait` is false.
+        """
+        cost = weight(method)
+        if not self.enabled or cost == 0:
+            return 0.0
+        with self._lock:
+            now = self._clock()
+            tokens = self._tokens(account, now)
+            needed = max(0.0, (cost - tokens) / self.rate)
+            limit = self.max_wait_secs if wait else 0.0
+            if deadline is not None:
+                limit = min(limit, deadline - now)
+            if needed > limit:
+                raise RateLimited(method, needed)
+            self._buckets[account] = (tokens - cost, now)
+        if needed:
+            self._sleep(needed)
+        return needed
+
+    def headroom(self) -> float:
+        """Fraction of capacity left in the emptiest bucket (1.0 when unused)."""
+        if not self.enabled:
+            return 1.0
+        with self._lock:
+            now = self._clock()
+            balances = [self._tokens(account, now) for account in self._buckets]
+        return max(0.0, min(balances, default=self.capacity) / self.capacity)
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "rate_per_sec": self.rate,
+            "capacity": self.capacity,
+            "max_wait_secs": self.max_wait_secs,
+            "weights": dict(sorted(METHOD_WEIGHTS.items())),
+            "default_weight": DEFAULT_WEIGHT,
+        }
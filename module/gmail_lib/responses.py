@@ -0,0 +1,84 @@
+"""
+Bounding response size.
+
+The daemon converts each result into a JSON value before sending it, so a
+thread of dozens of long messages briefly exists several times over. With
+`[gmail] max_response_bytes` (default 4 MiB; 0 disables the check), a result
+whose JSON encoding would exceed that fails with `ResponseTooLarge` instead.
+The size is measured while encoding, and measuring stops as soon as the
+limit is passed, so an oversized result is never encoded in full. A result
+is checked before it's cached, so a failed call leaves nothing behind.
+
+Bodies are also counted as they're decoded: each call runs under a
+`response_budget`, and `charge` adds up the body text a handler builds,
+failing as soon as the total passes the limit, so a thread of huge messages
+stops at the message that crosses it rather than after decoding them all.
+"""
+
+import json
+import threading
+from contextlib import contextmanager
+from typing import Any, Mapping
+
+DEFAULT_MAX_RESPONSE_BYTES = 4 * 1024 * 1024
+
+_encoder = json.JSONEncoder(default=str, ensure_ascii=False)
+
+# Budgets of the calls running on this thread, innermost last; a batch's
+# operations run under their own budget and the batch's at once
+_budgets = threading.local()
+
+
+class ResponseTooLarge(ValueError):
+    """Raised when a result is bigger than max_response_bytes."""
+
+    def __init__(self, method: str, max_bytes: int):
+        super().__init__(
+            f"{method} response exceeded max_response_bytes ({max_bytes}); ask for less "
+            f"(a lower limit or max_messages, include_bodies false, or truncate_body)"
+        )
+        self.method = method
+        self.max_bytes = max_bytes
+
+
+def max_response_bytes(environ: Mapping[str, str]) -> int:
+    return int(environ.get("FGP_GMAIL_MAX_RESPONSE_BYTES") or DEFAULT_MAX_RESPONSE_BYTES)
+
+
+def check_response_size(method: str, result: Any, max_bytes: int):
+    """Raise ResponseTooLarge if `result` encodes to more than `max_bytes`."""
+    if max_bytes <= 0:
+        return
+    size = 0
+    for chunk in _encoder.iterencode(result):
+        size += len(chunk.encode())
+        if size > max_bytes:
+            raise ResponseTooLarge(method, max_bytes)
+
+
+@contextmanager
+def response_budget(method: str, max_bytes: int):
+    """Count the bodies `charge`d on this thread against `max_bytes`."""
+    stack = getattr(_budgets, "stack", None)
+    if stack is None:
+        stack = _budgets.stack = []
+    stack.append([method, max_bytes, 0])
+    try:
+        yield
+    finally:
+        stack.pop()
+
+
+def charge(*texts: Any):
+    """Count decoded body `texts` (None is skipped) against the running
+    calls' budgets; raise ResponseTooLarge once one is overspent. Does
+    nothing outside a budget, e.g. on prefetch threads."""
+    stack = getattr(_budgets, "stack", None)
+    if not stack:
+        return
+    size = sum(len(text.encode()) for text in texts if text is not None)
+    for budget in stack:
+        method, max_bytes, used = budget
+        budget[2] = used + size
+        if max_bytes > 0 and budget[2] > max_bytes:
+            raise ResponseTooLarge(method, max_bytes)
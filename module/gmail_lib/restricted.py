@@ -0,0 +1,89 @@
+"""
+Detection of messages whose content Gmail can't hand back to us.
+
+Two cases come back with empty or placeholder bodies:
+
+- Confidential mode: Gmail strips the real content and replaces it with a
+  "view this message in Gmail" stub. It is flagged via Gmail-specific
+  headers and, for messages the user sent, a CONFIDENTIAL label.
+- S/MIME encryption: the payload is an opaque `application/pkcs7-mime`
+  envelope. Signed-only mail (`application/pkcs7-signature`) stays readable
+  and is NOT treated as restricted.
+"""
+
+from typing import Any, Dict, Iterable, Optional
+
+CONFIDENTIAL_MODE = "confidential_mode"
+SMIME_ENCRYPTED = "smime_encrypted"
+
+# Header names (lowercase) whose presence marks a confidential-mode message
+CONFIDENTIAL_HEADERS = frozenset({
+    "x-gm-confidential-mode",
+    "x-gmail-confidential-mode",
+    "x-gm-confidential",
+})
+
+# Label ids Gmail attaches to confidential-mode messages
+CONFIDENTIAL_LABELS = frozenset({"CONFIDENTIAL"})
+
+# MIME types of encrypted S/MIME envelopes
+SMIME_MIME_TYPES = frozenset({
+    "application/pkcs7-mime",
+    "application/x-pkcs7-mime",
+})
+
+NOTES = {
+    CONFIDENTIAL_MODE: (
+        "Message was sent with Gmail confidential mode; its content is only "
+        "viewable in the Gmail web or mobile client."
+    ),
+    SMIME_ENCRYPTED: (
+        "Message is S/MIME encrypted; its content can't be read without the "
+        "recipient's private key."
+    ),
+}
+
+
+def _iter_parts(payload: Dict[str, Any]) -> Iterable[Dict[str, Any]]:
+    """Yield the payload and every nested MIME part."""
+    stack = [payload]
+    while stack:
+        part = stack.pop()
+        yield part
+        stack.extend(part.get("parts") or [])
+
+
+def detect_restriction(msg: Dict[str, Any]) -> Optional[str]:
+    """
+    Classify a raw Gmail API message resource.
+
+    Returns `"confidential_mode"`, `"smime_encrypted"`, or None. Works with
+    both `full` and `metadata` formats; metadata responses only carry the
+    top-level MIME type, which is where S/MIME envelopes live anyway.
+    """
+    payload = msg.get("payload") or {}
+
+    header_names = {h.get("name", "").lower() for h in payload.get("headers", [])}
+    if header_names & CONFIDENTIAL_HEADERS:
+        return CONFIDENTIAL_MODE
+    if CONFIDENTIAL_LABELS.intersection(msg.get("labelIds") or []):
+        return CONFIDENTIAL_MODE
+
+    for part in _iter_parts(payload):
+        mime_type = (part.get("mimeType") or "").lower()
+        if mime_type in SMIME_MIME_TYPES:
+            return SMIME_ENCRYPTED
+
+    return None
+
+
+def restriction_note(restriction: Optional[str]) -> Optional[str]:
+    """Human-readable explanation for a restriction, or None."""
+    return NOTES.get(restriction) if restriction else None
+
+
+def filter_restricted(emails: Iterable[Dict[str, Any]], include_restricted: bool) -> list:
+    """Drop restricted messages from an export unless explicitly requested."""
+    if include_restricted:
+        return list(emails)
+    return [e for e in emails if not e.get("content_restricted")]
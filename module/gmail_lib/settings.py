@@ -0,0 +1,137 @@
+"""
+Translation between method params and Gmail's vacation and send-as settings.
+
+    params                  users.settings vacation resource
+    ----------------------  ------------------------------------
+    enabled                 enableAutoReply
+    subject                 responseSubject
+    body                    responseBodyPlainText
+    start, end (ISO 8601)   startTime, endTime (epoch millis)
+    restrict_to_contacts    restrictToContacts
+    restrict_to_domain      restrictToDomain
+
+`updateVacation` replaces the whole setting, so `build_vacation` starts
+from the current one and only changes the fields that were passed.
+"""
+
+from datetime import date, datetime, timezone
+from email.utils import formataddr, parseaddr
+from typing import Any, Dict, List, Optional, Tuple
+
+VACATION_FIELDS = {
+    "enabled": "enableAutoReply",
+    "subject": "responseSubject",
+    "body": "responseBodyPlainText",
+    "restrict_to_contacts": "restrictToContacts",
+    "restrict_to_domain": "restrictToDomain",
+}
+VACATION_BOOLEANS = ("enabled", "restrict_to_contacts", "restrict_to_domain")
+VACATION_TIMES = {"start": "startTime", "end": "endTime"}
+
+
+def parse_instant(value: Any, name: str) -> int:
+    """An ISO 8601 date or datetime as epoch milliseconds.
+
+    A date means midnight UTC; a datetime without a zone is taken as UTC.
+    """
+    if not isinstance(value, str) or not value:
+        raise ValueError(f"{name} must be an ISO 8601 date or datetime (got {value!r})")
+    try:
+        if len(value) == 10:
+            parsed = datetime.combine(date.fromisoformat(value), datetime.min.time())
+        else:
+            parsed = datetime.fromisoformat(value.replace("Z", "+00:00"))
+    except ValueError:
+        raise ValueError(f"{name} must be an ISO 8601 date or datetime (got {value!r})") from None
+    if parsed.tzinfo is None:
+        parsed = parsed.replace(tzinfo=timezone.utc)
+    return int(parsed.timestamp() * 1000)
+
+
+def _iso(millis: Any) -> Optional[str]:
+    if millis in (None, ""):
+        return None
+    parsed = datetime.fromtimestamp(int(millis) / 1000, timezone.utc)
+    return parsed.isoformat().replace("+00:00", "Z")
+
+
+def build_vacation(params: Dict[str, Any], current: Dict[str, Any]) -> Dict[str, Any]:
+    """The vacation resource to save: `current` with the passed params applied.
+
+    Raises ValueError, before anything is sent, for bad types, bad dates,
+    or an `end` that isn't after `start`.
+    """
+    if "enabled" not in params:
+        raise ValueError("enabled parameter is required")
+    body = {key: value for key, value in current.items() if key in VACATION_FIELDS.values()
+            or key in VACATION_TIMES.values() or key == "responseBodyHtml"}
+
+    for param, key in VACATION_FIELDS.items():
+        if param not in params:
+            continue
+        value = params[param]
+        if param in VACATION_BOOLEANS:
+            if not isinstance(value, bool):
+                raise ValueError(f"{param} must be a boolean")
+        elif not isinstance(value, str):
+            raise ValueError(f"{param} must be a string")
+        body[key] = value
+    if "body" in params:
+        # Gmail prefers the HTML body when there is one, which would hide the new text
+        body.pop("responseBodyHtml", None)
+
+    for param, key in VACATION_TIMES.items():
+        if param not in params:
+            continue
+        if params[param] is None:
+            body.pop(key, None)
+        else:
+            body[key] = str(parse_instant(params[param], param))
+    start, end = body.get("startTime"), body.get("endTime")
+    if start and end and int(end) <= int(start):
+        raise ValueError(f"end ({_iso(end)}) must be after start ({_iso(start)})")
+    return body
+
+
+def describe_vacation(resource: Dict[str, Any]) -> Dict[str, Any]:
+    """A vacation resource in param vocabulary."""
+    return {
+        "enabled": bool(resource.get("enableAutoReply")),
+        "subject": resource.get("responseSubject", ""),
+        "body": resource.get("responseBodyPlainText", ""),
+        "body_html": resource.get("responseBodyHtml"),
+        "start": _iso(resource.get("startTime")),
+        "end": _iso(resource.get("endTime")),
+        "restrict_to_contacts": bool(resource.get("restrictToContacts")),
+        "restrict_to_domain": bool(resource.get("restrictToDomain")),
+    }
+
+
+def describe_send_as(alias: Dict[str, Any]) -> Dict[str, Any]:
+    """A sendAs resource in param vocabulary."""
+    return {
+        "email": alias.get("sendAsEmail", ""),
+        "name": alias.get("displayName", ""),
+        "reply_to": alias.get("replyToAddress") or None,
+        "is_primary": bool(alias.get("isPrimary")),
+        "is_default": bool(alias.get("isDefault")),
+        # The primary address has no verification status; it's always usable
+        "verified": bool(alias.get("isPrimary")) or alias.get("verificationStatus") == "accepted",
+    }
+
+
+def resolve_from(value: Any, aliases: List[Dict[str, Any]]) -> Tuple[str, Dict[str, Any]]:
+    """The From header for `value`, which must name a verified send-as alias.
+
+    `value` is an address or `Name <address>`; without a name, the alias's
+    display name is used. Returns the header and the alias.
+    """
+    if not isinstance(value, str) or not value.strip():
+        raise ValueError("from must be a string")
+    name, address = parseaddr(value)
+    usable = [alias for alias in aliases if alias["verified"]]
+    for alias in usable:
+        if alias["email"].lower() == address.lower():
+            return formataddr((name or alias["name"], alias["email"])), alias
+    valid = ", ".join(alias["email"] for alias in usable) or "(none)"
+    raise ValueError(f"from must be one of your send-as addresses: {valid} (got {value!r})")
@@ -0,0 +1,138 @@
+"""
+Per-call log spans with a correlation id.
+
+Calls run concurrently, so their log lines interleave. `call_span` gives
+each call a request id, logs the call's start with its params summarized
+and its end with the outcome and duration, and tags every log line written
+while the call runs with the id. Responses carry the same `request_id`, so
+a client can grep the daemon log for one call.
+
+Params are logged as `summarize_params` renders them: bodies, raw content,
+and attachments become their sizes, recipient lists become counts, and
+addresses anywhere else are replaced with stable hashes, as in recordings.
+"""
+
+import contextvars
+import json
+import logging
+import sys
+import time
+import uuid
+from contextlib import contextmanager
+from typing import Any, Dict, Iterator, Optional
+
+from .backend import redact
+
+log = logging.getLogger("fgp_gmail.calls")
+
+# Request id of the call running on this thread, or "-" outside a call
+current_request_id: contextvars.ContextVar = contextvars.ContextVar("fgp_gmail_request_id", default="-")
+
+CONTENT_PARAMS = frozenset({"body", "body_html", "body_markdown", "comment", "raw", "data", "attachments"})
+RECIPIENT_PARAMS = frozenset({"to", "cc", "bcc"})
+
+LOG_FORMAT = "%(asctime)s %(levelname)s %(name)s [%(request_id)s] %(message)s"
+
+# tracing filter level -> logging level
+LEVELS = {
+    "trace": logging.DEBUG,
+    "debug": logging.DEBUG,
+    "info": logging.INFO,
+    "warn": logging.WARNING,
+    "error": logging.ERROR,
+    "off": logging.CRITICAL + 1,
+}
+
+
+def new_request_id() -> str:
+    return uuid.uuid4().hex[:16]
+
+
+def _size(value: Any) -> str:
+    if isinstance(value, str):
+        return f"<{len(value)} chars>"
+    if isinstance(value, list):
+        return f"<{len(value)} items>"
+    return "<omitted>"
+
+
+def summarize_params(params: Dict[str, Any]) -> Dict[str, Any]:
+    """Params safe to log at info level."""
+    summary = {}
+    for key, value in params.items():
+        if key in CONTENT_PARAMS:
+            summary[key] = _size(value)
+        elif key in RECIPIENT_PARAMS and isinstance(value, (str, list)):
+            recipients = value.split(',') if isinstance(value, str) else value
+            summary[key] = f"<{len([r for r in recipients if isinstance(r, str) and r.strip()])} recipients>"
+        elif key == "operations" and isinstance(value, list):
+            # gmail.batch: each operation is logged in its own span
+            summary[key] = [op.get("method") if isinstance(op, dict) else None for op in value]
+        else:
+            summary[key] = redact(value)
+    return summary
+
+
+@contextmanager
+def call_span(method: str, params: Dict[str, Any]) -> Iterator[str]:
+    """Run a call under a fresh request id; yields the id."""
+    request_id = new_request_id()
+    token = current_request_id.set(request_id)
+    started = time.monotonic()
+    if log.isEnabledFor(logging.INFO):
+        log.info("%s started params=%s", method, json.dumps(summarize_params(params), default=str))
+    try:
+        yield request_id
+    except Exception as e:
+        log.warning("%s failed in %.1fms: %s: %s", method, (time.monotonic() - started) * 1000,
+                    type(e).__name__, redact(str(e)))
+        raise
+    else:
+        log.info("%s ok in %.1fms", method, (time.monotonic() - started) * 1000)
+    finally:
+        current_request_id.reset(token)
+
+
+class RequestIdFilter(logging.Filter):
+    """Adds the running call's request id to every record as `request_id`."""
+
+    def filter(self, record: logging.LogRecord) -> bool:
+        record.request_id = current_request_id.get()
+        return True
+
+
+def level_from_filter(spec: str) -> Optional[int]:
+    """The `fgp_gmail` level in a tracing filter like `fgp_gmail=info,fgp_daemon=debug`.
+
+    A bare level (`info`) applies to everything. Returns None if the filter
+    doesn't mention the module.
+    """
+    level = None
+    for directive in spec.split(","):
+        target, _, name = directive.strip().rpartition("=")
+        name = name.strip().lower()
+        if name not in LEVELS:
+            continue
+        if target == "fgp_gmail":
+            return LEVELS[name]
+        if not target:
+            level = LEVELS[name]
+    return level
+
+
+def configure_logging(spec: Optional[str], stream=None):
+    """Send `fgp_gmail` logs to stderr at the level the daemon's filter sets.
+
+    Does nothing without a filter (e.g. under tests) or if the logger is
+    already configured.
+    """
+    root = logging.getLogger("fgp_gmail")
+    level = level_from_filter(spec) if spec else None
+    if level is None or root.handlers:
+        return
+    handler = logging.StreamHandler(stream or sys.stderr)
+    handler.setFormatter(logging.Formatter(LOG_FORMAT))
+    handler.addFilter(RequestIdFilter())
+    root.addHandler(handler)
+    root.setLevel(level)
+    root.propagate = False
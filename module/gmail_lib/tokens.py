@@ -0,0 +1,121 @@
+"""
+Serialized access to cached token files.
+
+A token is refreshed and rewritten by calls that find it expired, by the
+auth monitor, and by sign-in flows, and a sign-in may run in a separate
+`fgp-gmail auth` process while the daemon is up. `token_lock` makes them
+take turns: it holds a per-file thread lock plus an exclusive `flock` on a
+`.lock` file next to the token, so only one thread in one process refreshes
+or writes a given token at a time. Holders should re-read the token once
+they have the lock, since whoever held it before may have refreshed it.
+
+The lock is re-entrant within a thread. An `flock` belongs to its open
+file, so a process that dies while holding one releases it with the file;
+a thread that raises releases it on the way out. Neither can leave it stuck.
+
+`write_token` writes through a uniquely named temp file and renames it over
+the token, so readers never see a partial file. Tokens hold refresh tokens
+(and the JSON copy the client secret), so the file is created readable by
+its owner only, whatever the umask.
+"""
+
+import os
+import threading
+import time
+from contextlib import contextmanager
+from pathlib import Path
+from typing import Dict, Iterator, Optional
+
+try:
+    import fcntl
+except ImportError:  # Windows: threads are still serialized, processes aren't
+    fcntl = None
+
+DEFAULT_LOCK_TIMEOUT_SECS = 30.0
+POLL_SECS = 0.05
+
+
+class TokenLockTimeout(TimeoutError):
+    """Raised when another thread or process held a token's lock too long."""
+
+    def __init__(self, path: Path, waited: float):
+        super().__init__(f"Timed out after {waited:.0f}s waiting for the lock on {path}; "
+                         f"is another fgp-gmail process signing in?")
+        self.path = path
+
+
+class _FileLock:
+    def __init__(self, path: Path):
+        self.path = path
+        self._lock = threading.RLock()
+        self._depth = 0
+        self._fd: Optional[int] = None
+
+    def acquire(self, timeout: float):
+        started = time.monotonic()
+        if not self._lock.acquire(timeout=timeout):
+            raise TokenLockTimeout(self.path, time.monotonic() - started)
+        try:
+            if self._depth == 0 and fcntl is not None:
+                self._fd = self._flock(started + timeout, started)
+        except BaseException:
+            self._lock.release()
+            raise
+        self._depth += 1
+
+    def _flock(self, deadline: float, started: float) -> int:
+        self.path.parent.mkdir(parents=True, exist_ok=True)
+        fd = os.open(self.path, os.O_RDWR | os.O_CREAT, 0o600)
+        while True:
+            try:
+                fcntl.flock(fd, fcntl.LOCK_EX | fcntl.LOCK_NB)
+                return fd
+            except BlockingIOError:
+                if time.monotonic() >= deadline:
+                    os.close(fd)
+                    raise TokenLockTimeout(self.path, time.monotonic() - started) from None
+                time.sleep(POLL_SECS)
+
+    def release(self):
+        self._depth -= 1
+        if self._depth == 0 and self._fd is not None:
+            fd, self._fd = self._fd, None
+            os.close(fd)  # drops the flock
+        self._lock.release()
+
+
+_locks: Dict[Path, _FileLock] = {}
+_locks_guard = threading.Lock()
+
+
+def lock_path(token_file: Path) -> Path:
+    return token_file.with_name(f".{token_file.name}.lock")
+
+
+@contextmanager
+def token_lock(token_file: Path, timeout: float = DEFAULT_LOCK_TIMEOUT_SECS) -> Iterator[None]:
+    """Hold the lock for `token_file` for the duration of the block."""
+    path = lock_path(Path(token_file))
+    with _locks_guard:
+        lock = _locks.setdefault(path, _FileLock(path))
+    lock.acquire(timeout)
+    try:
+        yield
+    finally:
+        lock.release()
+
+
+def write_token(token_file: Path, data: bytes):
+    """Atomically replace `token_file` with `data`, under its lock, as a
+    mode 0600 file."""
+    token_file = Path(token_file)
+    token_file.parent.mkdir(parents=True, exist_ok=True)
+    with token_lock(token_file):
+        tmp = token_file.with_name(f".{token_file.name}.{os.getpid()}.{threading.get_ident()}.tmp")
+        try:
+            with os.fdopen(os.open(tmp, os.O_WRONLY | os.O_CREAT | os.O_EXCL, 0o600), 'wb') as f:
+                f.write(data)
+            os.replace(tmp, token_file)
+        finally:
+            if tmp.exists():
+                tmp.unlink()
@@ -0,0 +1,611 @@
+"""
+Typed response models.
+
+Handlers parse Gmail API resources into these models instead of passing
+dicts straight through, so a change in the API (or a malformed resource)
+surfaces as a clear `UnexpectedOutput` error naming the offending field
+rather than as a silently different response shape. `to_dict()` produces
+the JSON returned to clients; `from_dict()` parses that JSON back, which
+lets consumers and tests check responses against the same contract.
+
+`EmailSummary` is the one message shape shared by inbox, unread, search,
+thread, and watch events. Its `date` is RFC 3339 (from the Date header, or
+Gmail's `internalDate` when the header is missing or unparseable); anything
+that only some sources provide, such as the raw Date header, goes in
+`extra` so the top-level fields never vary.
+"""
+
+import re
+from dataclasses import dataclass, field
+from datetime import datetime, timezone
+from email.utils import getaddresses, parsedate_to_datetime
+from typing import Any, Dict, List, Optional, Tuple
+
+from .mime import attachment_metadata, dequote, extract_content
+from .responses import charge
+from .restricted import detect_restriction, restriction_note
+
+# Headers requested for message summaries
+SUMMARY_HEADERS = ['From', 'To', 'Subject', 'Date']
+
+# Extra headers requested for threads, used to build the reply tree and
+# the participant list
+THREAD_HEADERS = SUMMARY_HEADERS + ['Cc', 'Message-ID', 'In-Reply-To', 'References']
+
+MESSAGE_ID_RE = re.compile(r"<[^<>\s]+>")
+
+
+class UnexpectedOutput(ValueError):
+    """Raised when an API resource or response doesn't match its model."""
+
+
+_TYPE_NAMES = {str: "string", bool: "boolean", int: "integer", list: "array", dict: "object"}
+
+
+def _field(data: Any, key: str, kind: type, required: bool = True, default: Any = None,
+           where: str = "") -> Any:
+    """Fetch and type-check one field of a JSON object."""
+    suffix = f" in {where}" if where else ""
+    if not isinstance(data, dict):
+        raise UnexpectedOutput(f"unexpected API output: expected an object{suffix}")
+    value = data.get(key)
+    if value is None:
+        if required:
+            raise UnexpectedOutput(f"unexpected API output: missing field `{key}`{suffix}")
+        return default
+    if not isinstance(value, kind) or (kind is int and isinstance(value, bool)):
+        raise UnexpectedOutput(
+            f"unexpected API output: field `{key}`{suffix} should be {_TYPE_NAMES.get(kind, kind.__name__)}, "
+            f"got {type(value).__name__}"
+        )
+    return value
+
+
+def _string_list(data: Any, key: str, where: str) -> List[str]:
+    values = _field(data, key, list, required=False, default=[], where=where)
+    for value in values:
+        if not isinstance(value, str):
+            raise UnexpectedOutput(
+                f"unexpected API output: field `{key}` in {where} should contain strings, "
+                f"got {type(value).__name__}"
+            )
+    return list(values)
+
+
+def parse_headers(msg: Dict[str, Any], where: str = "message") -> Dict[str, str]:
+    """Map header names to values, validating the payload shape."""
+    payload = _field(msg, 'payload', dict, required=False, default={}, where=where)
+    headers = _field(payload, 'headers', list, required=False, default=[], where=f"{where} payload")
+    result = {}
+    for header in headers:
+        name = _field(header, 'name', str, where=f"{where} header")
+        result[name] = _field(header, 'value', str, required=False, default='', where=f"{where} header")
+    return result
+
+
+def rfc3339_date(date_header: str, internal_date_ms: Optional[int] = None) -> str:
+    """Normalize a message date to RFC 3339, or '' if there's nothing to go on.
+
+    A Date header without a zone is taken as UTC.
+    """
+    try:
+        parsed = parsedate_to_datetime(date_header)
+    except (TypeError, ValueError, IndexError):
+        parsed = None
+    if parsed is None and internal_date_ms is not None:
+        parsed = datetime.fromtimestamp(internal_date_ms / 1000, timezone.utc)
+    if parsed is None:
+        return ''
+    if parsed.tzinfo is None:
+        parsed = parsed.replace(tzinfo=timezone.utc)
+    return parsed.isoformat().replace('+00:00', 'Z')
+
+
+def _internal_date_ms(msg: Dict[str, Any], where: str) -> Optional[int]:
+    value = _field(msg, 'internalDate', str, required=False, where=where)
+    if value is None:
+        return None
+    try:
+        return int(value)
+    except ValueError:
+        raise UnexpectedOutput(
+            f"unexpected API output: field `internalDate` in {where} should be an integer string, "
+            f"got {value!r}"
+        ) from None
+
+
+@dataclass
+class EmailSummary:
+    """One message as listed by inbox, unread, search, and thread.
+
+    `date` is RFC 3339; `extra` holds source-specific fields (`date_header`,
+    the Date header as sent, when there is one). `attachments` is only set
+    when asked for and the message's MIME tree was fetched (`full` format).
+    """
+
+    id: str
+    thread_id: str
+    from_: str = ''
+    to: str = ''
+    subject: str = ''
+    snippet: str = ''
+    date: str = ''
+    labels: List[str] = field(default_factory=list)
+    unread: bool = False
+    content_restricted: Optional[str] = None
+    content_note: Optional[str] = None
+    extra: Dict[str, Any] = field(default_factory=dict)
+    attachments: Optional[List[Dict[str, Any]]] = None
+
+    @classmethod
+    def from_api(cls, msg: Dict[str, Any], snippet_limit: Optional[int] = 100,
+                 include_attachments: bool = False) -> "EmailSummary":
+        """Parse a `metadata` or `full` format message resource;
+        `include_attachments` needs the `full` format."""
+        where = "message"
+        message_id = _field(msg, 'id', str, where=where)
+        where = f"message {message_id}"
+        thread_id = _field(msg, 'threadId', str, where=where)
+        labels = _string_list(msg, 'labelIds', where)
+        snippet = _field(msg, 'snippet', str, required=False, default='', where=where)
+        headers = parse_headers(msg, where)
+
+        restriction = detect_restriction(msg)
+        if restriction:
+            # Placeholder content isn't worth summarizing
+            snippet = ''
+        elif snippet_limit is not None:
+            snippet = snippet[:snippet_limit]
+
+        date_header = headers.get('Date', '')
+        extra = {'date_header': date_header} if date_header else {}
+        attachments = None
+        if include_attachments and not restriction:
+            attachments = attachment_metadata(extract_content(msg.get('payload', {}))['attachments'])
+
+        return cls(
+            id=message_id,
+            thread_id=thread_id,
+            from_=headers.get('From', ''),
+            to=headers.get('To', ''),
+            subject=headers.get('Subject', ''),
+            snippet=snippet,
+            date=rfc3339_date(date_header, _internal_date_ms(msg, where)),
+            labels=labels,
+            unread='UNREAD' in labels,
+            content_restricted=restriction,
+            content_note=restriction_note(restriction),
+            extra=extra,
+            attachments=attachments,
+        )
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "EmailSummary":
+        """Parse a summary as serialized by `to_dict`."""
+        where = "email summary"
+        return cls(
+            id=_field(data, 'id', str, where=where),
+            thread_id=_field(data, 'thread_id', str, where=where),
+            from_=_field(data, 'from', str, where=where),
+            to=_field(data, 'to', str, where=where),
+            subject=_field(data, 'subject', str, where=where),
+            snippet=_field(data, 'snippet', str, where=where),
+            date=_field(data, 'date', str, where=where),
+            labels=_string_list(data, 'labels', where),
+            unread=_field(data, 'unread', bool, where=where),
+            content_restricted=_field(data, 'content_restricted', str, required=False, where=where),
+            content_note=_field(data, 'content_note', str, required=False, where=where),
+            extra=_field(data, 'extra', dict, where=where),
+            attachments=_field(data, 'attachments', list, required=False, where=where),
+        )
+
+    def to_dict(self) -> Dict[str, Any]:
+        result = {
+            'id': self.id,
+            'thread_id': self.thread_id,
+            'from': self.from_,
+            'to': self.to,
+            'subject': self.subject,
+            'snippet': self.snippet,
+            'date': self.date,
+            'labels': self.labels,
+            'unread': self.unread,
+            'content_restricted': self.content_restricted,
+            'extra': self.extra,
+        }
+        if self.content_note:
+            result['content_note'] = self.content_note
+        if self.attachments is not None:
+            result.update(has_attachments=bool(self.attachments), attachment_count=len(self.attachments),
+                          attachments=self.attachments)
+        return result
+
+
+def truncate_text(text: Optional[str], limit: Optional[int]) -> Tuple[Optional[str], bool]:
+    """`text` cut to `limit` characters, and whether anything was cut."""
+    if text is None or limit is None or len(text) <= limit:
+        return text, False
+    return text[:limit], True
+
+
+def _message_ids(value: str) -> List[str]:
+    """The `<id@host>` tokens in a Message-ID, In-Reply-To, or References header."""
+    return MESSAGE_ID_RE.findall(value or '')
+
+
+def _internal_date(msg: Dict[str, Any], date_header: str, where: str) -> int:
+    """Epoch milliseconds Gmail received the message, falling back to the
+    Date header (then 0) for resources fetched without `internalDate`."""
+    value = _internal_date_ms(msg, where)
+    if value is not None:
+        return value
+    try:
+        return int(parsedate_to_datetime(date_header).timestamp() * 1000)
+    except (TypeError, ValueError, IndexError):
+        return 0
+
+
+@dataclass
+class ThreadMessage(EmailSummary):
+    """A message within a thread, with its place in the reply tree.
+
+    `parent_id` is the Gmail id of the message this one replies to, resolved
+    through In-Reply-To (or the last References entry), or None for the root
+    and for replies whose parent isn't in the thread. `body` is the plain-text
+    body with its quoted trail removed, or None when bodies weren't fetched,
+    the message has no text part, or its content is restricted. With a
+    `body_limit`, longer bodies are cut to it and `body_truncated` says
+    whether this one was; without one, `body_truncated` is None and left out.
+    """
+
+    cc: str = ''
+    body: Optional[str] = None
+    body_truncated: Optional[bool] = None
+    internal_date: int = 0
+    rfc822_message_id: Optional[str] = None
+    in_reply_to: Optional[str] = None
+    references: List[str] = field(default_factory=list)
+    parent_id: Optional[str] = None
+
+    @classmethod
+    def from_api(cls, msg: Dict[str, Any], snippet_limit: Optional[int] = 100,
+                 include_body: bool = False, include_attachments: bool = False,
+                 body_limit: Optional[int] = None) -> "ThreadMessage":
+        """Parse a message resource; `include_body` and `include_attachments`
+        need the `full` format."""
+        summary = EmailSummary.from_api(msg, snippet_limit, include_attachments)
+        where = f"message {summary.id}"
+        headers = parse_headers(msg, where)
+        own_ids = _message_ids(headers.get('Message-ID') or headers.get('Message-Id', ''))
+        reply_ids = _message_ids(headers.get('In-Reply-To', ''))
+        body = None
+        if include_body and not summary.content_restricted:
+            text = extract_content(msg.get('payload', {}))['body_text']
+            body = dequote(text) if text is not None else None
+        body, truncated = truncate_text(body, body_limit)
+        charge(body)
+        return cls(
+            **summary.__dict__,
+            cc=headers.get('Cc', ''),
+            body=body,
+            body_truncated=truncated if body_limit is not None else None,
+            internal_date=_internal_date(msg, headers.get('Date', ''), where),
+            rfc822_message_id=own_ids[0] if own_ids else None,
+            in_reply_to=reply_ids[0] if reply_ids else None,
+            references=_message_ids(headers.get('References', '')),
+        )
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "ThreadMessage":
+        where = "thread message"
+        summary = EmailSummary.from_dict(data)
+        return cls(
+            **summary.__dict__,
+            cc=_field(data, 'cc', str, where=where),
+            body=_field(data, 'body', str, required=False, where=where),
+            body_truncated=_field(data, 'body_truncated', bool, required=False, where=where),
+            internal_date=_field(data, 'internal_date', int, where=where),
+            rfc822_message_id=_field(data, 'rfc822_message_id', str, required=False, where=where),
+            in_reply_to=_field(data, 'in_reply_to', str, required=False, where=where),
+            references=_string_list(data, 'references', where),
+            parent_id=_field(data, 'parent_id', str, required=False, where=where),
+        )
+
+    def to_dict(self) -> Dict[str, Any]:
+        result = super().to_dict()
+        result.update({
+            'cc': self.cc,
+            'body': self.body,
+            'internal_date': self.internal_date,
+            'rfc822_message_id': self.rfc822_message_id,
+            'in_reply_to': self.in_reply_to,
+            'references': self.references,
+            'parent_id': self.parent_id,
+        })
+        if self.body_truncated is not None:
+            result['body_truncated'] = self.body_truncated
+        return result
+
+
+@dataclass
+class ThreadGap:
+    """A message that thread members reference but the thread doesn't contain.
+
+    `after` and `before` are the Gmail ids of the messages it most likely sat
+    between: the nearest present message listed before it in a References
+    header, and the first message that references it.
+    """
+
+    missing_message_id: str
+    before: str
+    after: Optional[str] = None
+    referenced_by: List[str] = field(default_factory=list)
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'missing_message_id': self.missing_message_id,
+            'after': self.after,
+            'before': self.before,
+            'referenced_by': self.referenced_by,
+        }
+
+
+@dataclass
+class Participant:
+    """Someone who sent or received a message in a thread."""
+
+    email: str
+    name: str = ''
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {'name': self.name, 'email': self.email}
+
+
+def _participants(messages: List[ThreadMessage]) -> List[Participant]:
+    """Everyone in From/To/Cc across the thread, in order of first
+    appearance, deduplicated by address (case-insensitively)."""
+    seen: Dict[str, Participant] = {}
+    for msg in messages:
+        for name, address in getaddresses([msg.from_, msg.to, msg.cc]):
+            if not address:
+                continue
+            person = seen.get(address.lower())
+            if person is None:
+                seen[address.lower()] = Participant(email=address, name=name)
+            elif not person.name:
+                person.name = name
+    return list(seen.values())
+
+
+@dataclass
+class Thread:
+    """A conversation and its messages.
+
+    Messages are sorted oldest first by `internalDate`; ties are broken by
+    Gmail message id, so the order is the same no matter how the API
+    returned them. With `max_messages`, only the newest messages are kept
+    and `truncated` is set; `total_count` and `participants` always cover
+    the whole thread.
+    """
+
+    id: str
+    messages: List[ThreadMessage] = field(default_factory=list)
+    gaps: List[ThreadGap] = field(default_factory=list)
+    participants: List[Participant] = field(default_factory=list)
+    total_count: int = 0
+    truncated: bool = False
+
+    @classmethod
+    def from_api(cls, thread: Dict[str, Any], snippet_limit: Optional[int] = 100,
+                 include_bodies: bool = False, max_messages: Optional[int] = None,
+                 include_attachments: bool = False, body_limit: Optional[int] = None) -> "Thread":
+        thread_id = _field(thread, 'id', str, where="thread")
+        messages = _field(thread, 'messages', list, where=f"thread {thread_id}")
+        parsed = sorted(
+            (ThreadMessage.from_api(msg, snippet_limit, include_bodies, include_attachments, body_limit)
+             for msg in messages),
+            key=lambda msg: (msg.internal_date, msg.id),
+        )
+        gaps = _link_replies(parsed)
+        kept = parsed
+        if max_messages is not None and len(parsed) > max_messages:
+            kept = parsed[-max_messages:]
+            kept_ids = {msg.id for msg in kept}
+            gaps = [gap for gap in gaps if gap.before in kept_ids]
+        return cls(
+            id=thread_id,
+            messages=kept,
+            gaps=gaps,
+            participants=_participants(parsed),
+            total_count=len(parsed),
+            truncated=len(kept) < len(parsed),
+        )
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "Thread":
+        messages = _field(data, 'messages', list, where="thread")
+        gaps = _field(data, 'gaps', list, required=False, default=[], where="thread")
+        participants = _field(data, 'participants', list, where="thread")
+        return cls(
+            id=_field(data, 'thread_id', str, where="thread"),
+            messages=[ThreadMessage.from_dict(msg) for msg in messages],
+            gaps=[
+                ThreadGap(
+                    missing_message_id=_field(gap, 'missing_message_id', str, where="thread gap"),
+                    before=_field(gap, 'before', str, where="thread gap"),
+                    after=_field(gap, 'after', str, required=False, where="thread gap"),
+                    referenced_by=_string_list(gap, 'referenced_by', "thread gap"),
+                )
+                for gap in gaps
+            ],
+            participants=[
+                Participant(
+                    email=_field(person, 'email', str, where="thread participant"),
+                    name=_field(person, 'name', str, where="thread participant"),
+                )
+                for person in participants
+            ],
+            total_count=_field(data, 'total_count', int, where="thread"),
+            truncated=_field(data, 'truncated', bool, where="thread"),
+        )
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'thread_id': self.id,
+            'messages': [msg.to_dict() for msg in self.messages],
+            'count': len(self.messages),
+            'total_count': self.total_count,
+            'truncated': self.truncated,
+            'participants': [person.to_dict() for person in self.participants],
+            'gaps': [gap.to_dict() for gap in self.gaps],
+        }
+
+
+def _link_replies(messages: List[ThreadMessage]) -> List[ThreadGap]:
+    """Set each message's `parent_id` and return the gaps, in thread order."""
+    present = {msg.rfc822_message_id: msg.id for msg in messages if msg.rfc822_message_id}
+    gaps: Dict[str, ThreadGap] = {}
+
+    for msg in messages:
+        chain = list(msg.references)
+        if msg.in_reply_to and msg.in_reply_to not in chain:
+            chain.append(msg.in_reply_to)
+
+        parent = msg.in_reply_to or (chain[-1] if chain else None)
+        msg.parent_id = present.get(parent) if parent else None
+        if msg.parent_id == msg.id:
+            msg.parent_id = None
+
+        after = None
+        for ref in chain:
+            if ref in present:
+                after = present[ref]
+                continue
+            gap = gaps.get(ref)
+            if gap is None:
+                gap = gaps[ref] = ThreadGap(missing_message_id=ref, before=msg.id, after=after)
+            if msg.id not in gap.referenced_by:
+                gap.referenced_by.append(msg.id)
+
+    return list(gaps.values())
+
+
+@dataclass
+class Draft:
+    """A draft and the message it holds."""
+
+    id: str
+    message_id: str
+    thread_id: str
+    summary: Optional[EmailSummary] = None
+
+    @classmethod
+    def from_api(cls, draft: Dict[str, Any]) -> "Draft":
+        """Parse a draft resource. Its message carries headers only if it was
+        fetched with `drafts.get`."""
+        draft_id = _field(draft, 'id', str, where="draft")
+        where = f"draft {draft_id}"
+        message = _field(draft, 'message', dict, where=where)
+        summary = EmailSummary.from_api(message) if 'payload' in message else None
+        return cls(
+            id=draft_id,
+            message_id=_field(message, 'id', str, where=f"{where} message"),
+            thread_id=_field(message, 'threadId', str, where=f"{where} message"),
+            summary=summary,
+        )
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "Draft":
+        where = "draft"
+        summary = _field(data, 'message', dict, required=False, where=where)
+        return cls(
+            id=_field(data, 'draft_id', str, where=where),
+            message_id=_field(data, 'message_id', str, where=where),
+            thread_id=_field(data, 'thread_id', str, where=where),
+            summary=EmailSummary.from_dict(summary) if summary else None,
+        )
+
+    def to_dict(self) -> Dict[str, Any]:
+        result = {
+            'draft_id': self.id,
+            'message_id': self.message_id,
+            'thread_id': self.thread_id,
+        }
+        if self.summary is not None:
+            result['message'] = self.summary.to_dict()
+        return result
+
+
+@dataclass
+class SendResult:
+    """Outcome of sending a message."""
+
+    id: str
+    thread_id: str
+    attachments: Optional[List[Dict[str, Any]]] = None
+
+    @classmethod
+    def from_api(cls, result: Dict[str, Any],
+                 attachments: Optional[List[Dict[str, Any]]] = None) -> "SendResult":
+        """Parse the message resource returned by `messages.send`."""
+        return cls(
+            id=_field(result, 'id', str, where="send result"),
+            thread_id=_field(result, 'threadId', str, where="send result"),
+            attachments=attachments or None,
+        )
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "SendResult":
+        return cls(
+            id=_field(data, 'message_id', str, where="send result"),
+            thread_id=_field(data, 'thread_id', str, where="send result"),
+            attachments=_field(data, 'attachments', list, required=False, where="send result"),
+        )
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'sent': True,
+            'message_id': self.id,
+            'thread_id': self.thread_id,
+            'attachments': self.attachments,
+        }
+
+
+@dataclass
+class Profile:
+    """The signed-in mailbox, from `users.getProfile`."""
+
+    email_address: str
+    messages_total: int
+    threads_total: int
+    history_id: str
+
+    @classmethod
+    def from_api(cls, profile: Dict[str, Any]) -> "Profile":
+        where = "profile"
+        return cls(
+            email_address=_field(profile, 'emailAddress', str, where=where),
+            messages_total=_field(profile, 'messagesTotal', int, required=False, default=0, where=where),
+            threads_total=_field(profile, 'threadsTotal', int, required=False, default=0, where=where),
+            history_id=_field(profile, 'historyId', str, where=where),
+        )
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "Profile":
+        where = "profile"
+        return cls(
+            email_address=_field(data, 'email_address', str, where=where),
+            messages_total=_field(data, 'messages_total', int, where=where),
+            threads_total=_field(data, 'threads_total', int, where=where),
+            history_id=_field(data, 'history_id', str, where=where),
+        )
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'email_address': self.email_address,
+            'messages_total': self.messages_total,
+            'threads_total': self.threads_total,
+            'history_id': self.history_id,
+            # The Gmail API doesn't report quota usage; that needs the Drive API
+            'storage_used_bytes': None,
+        }
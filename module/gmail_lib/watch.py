@@ -0,0 +1,290 @@
+"""
+New-mail watch: a background poller per account.
+
+Each `Watch` remembers the mailbox history id and, every `interval`
+seconds, asks `history.list` for messages added to the inbox since then.
+Each new message is summarized and handed to a sink as an event:
+
+    {"type": "new_message", "account": "work", "at": "...", "message": {...}}
+
+Sinks:
+
+- `QueueSink` writes one JSON file per event under a directory
+  (`~/.fgp/services/gmail/events/<account>/` by default), atomically, so
+  consumers can pick events up and delete them.
+- `CommandSink` runs a shell command with the event JSON on stdin. When
+  the watch stops, a command still running gets SIGTERM, then SIGKILL if
+  it hasn't exited within `SINK_KILL_GRACE_SECS`.
+- `WebhookSink` POSTs the event JSON to a URL.
+
+Poll and sink failures are logged and counted but never stop the loop; the
+next poll picks up from the last history id that was fully processed.
+"""
+
+import json
+import logging
+import os
+import signal
+import subprocess
+import threading
+import time
+import urllib.request
+from pathlib import Path
+from typing import Any, Callable, Dict, List, Optional
+
+from .backend import http_status
+from .types import SUMMARY_HEADERS, EmailSummary
+
+log = logging.getLogger("fgp_gmail.watch")
+
+DEFAULT_INTERVAL_SECS = 60
+MIN_INTERVAL_SECS = 5
+
+# Command and webhook sinks give up on a single event after this long
+SINK_TIMEOUT_SECS = 30
+
+# How long a stopped watch's sink command gets to exit after SIGTERM
+SINK_KILL_GRACE_SECS = 2
+
+Api = Callable[..., Dict[str, Any]]
+
+
+class QueueSink:
+    """Append events as individual JSON files in a directory."""
+
+    kind = "queue"
+
+    def __init__(self, directory: Path):
+        self.directory = Path(directory)
+
+    @property
+    def target(self) -> str:
+        return str(self.directory)
+
+    def emit(self, event: Dict[str, Any]):
+        self.directory.mkdir(parents=True, exist_ok=True)
+        name = f"{time.time_ns()}-{event['message']['id']}.json"
+        tmp = self.directory / f".{name}.tmp"
+        with open(tmp, "w") as f:
+            json.dump(event, f)
+        os.replace(tmp, self.directory / name)
+
+
+class CommandSink:
+    """Run a shell command per event with the event JSON on stdin."""
+
+    kind = "command"
+
+    def __init__(self, command: str):
+        self.command = command
+        self._lock = threading.Lock()
+        self._running: Optional[subprocess.Popen] = None
+        self._closed = False
+
+    @property
+    def target(self) -> str:
+        return self.command
+
+    def emit(self, event: Dict[str, Any]):
+        with self._lock:
+            if self._closed:
+                raise RuntimeError("sink is closed")
+            proc = self._running = subprocess.Popen(
+                self.command, shell=True, text=True,
+                stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.PIPE,
+                start_new_session=True,  # so close() reaches the command, not just its shell
+            )
+        try:
+            _, stderr = proc.communicate(json.dumps(event), timeout=SINK_TIMEOUT_SECS)
+        except subprocess.TimeoutExpired:
+            self._signal(proc, signal.SIGKILL)
+            proc.communicate()
+            raise
+        finally:
+            with self._lock:
+                self._running = None
+        if proc.returncode != 0:
+            raise RuntimeError(f"command exited with {proc.returncode}: {stderr.strip()[:200]}")
+
+    def close(self, grace: float = SINK_KILL_GRACE_SECS):
+        """Stop a running command (SIGTERM, then SIGKILL) and refuse new events."""
+        with self._lock:
+            self._closed = True
+            proc = self._running
+        if proc is None or proc.poll() is not None:
+            return
+        self._signal(proc, signal.SIGTERM)
+        try:
+            proc.wait(grace)
+        except subprocess.TimeoutExpired:
+            log.warning("Sink command didn't exit after SIGTERM; killing it: %s", self.command)
+            self._signal(proc, signal.SIGKILL)
+
+    @staticmethod
+    def _signal(proc: subprocess.Popen, signum: int):
+        try:
+            os.killpg(proc.pid, signum)
+        except ProcessLookupError:
+            pass
+
+
+class WebhookSink:
+    """POST each event as JSON to a URL."""
+
+    kind = "webhook"
+
+    def __init__(self, url: str):
+        if not url.startswith(("http://", "https://")):
+            raise ValueError(f"webhook must be an http(s) URL (got {url!r})")
+        self.url = url
+
+    @property
+    def target(self) -> str:
+        return self.url
+
+    def emit(self, event: Dict[str, Any]):
+        request = urllib.request.Request(
+            self.url, data=json.dumps(event).encode(), method="POST",
+            headers={"Content-Type": "application/json"},
+        )
+        with urllib.request.urlopen(request, timeout=SINK_TIMEOUT_SECS):
+            pass
+
+
+class Watch:
+    """Polls one account for new inbox messages. Thread-safe."""
+
+    def __init__(self, account: str, api: Api, sink, interval: float = DEFAULT_INTERVAL_SECS,
+                 clock: Callable[[], float] = time.time):
+        self.account = account
+        self.api = api
+        self.sink = sink
+        self.interval = max(float(interval), MIN_INTERVAL_SECS)
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._stop = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+        self.history_id: Optional[str] = None
+        self.started_at = self._clock()
+        self.last_poll: Optional[float] = None
+        self.last_error: Optional[str] = None
+        self.consecutive_failures = 0
+        self.events_fired = 0
+        self.sink_errors = 0
+
+    @property
+    def active(self) -> bool:
+        return self._thread is not None and self._thread.is_alive() and not self._stop.is_set()
+
+    def start(self) -> "Watch":
+        self._thread = threading.Thread(
+            target=self._run, name=f"gmail-watch-{self.account}", daemon=True)
+        self._thread.start()
+        return self
+
+    def stop(self, timeout: Optional[float] = 5):
+        self._stop.set()
+        close = getattr(self.sink, "close", None)
+        if close is not None:
+            close()
+        if self._thread is not None and self._thread is not threading.current_thread():
+            self._thread.join(timeout)
+
+    def _run(self):
+        while not self._stop.is_set():
+            self.poll_once()
+            self._stop.wait(self.interval)
+
+    def poll_once(self) -> int:
+        """Poll once and emit events for new messages. Returns how many fired."""
+        with self._lock:
+            try:
+                fired = self._poll()
+            except Exception as e:
+                self.consecutive_failures += 1
+                self.last_error = str(e)
+                log.warning("Watch poll failed for account %s: %s", self.account, e)
+                fired = 0
+            else:
+                self.consecutive_failures = 0
+                self.last_error = None
+            self.last_poll = self._clock()
+            return fired
+
+    def _poll(self) -> int:
+        if self.history_id is None:
+            # First poll establishes the baseline; existing mail isn't "new"
+            self.history_id = self.api('getProfile')['historyId']
+            return 0
+
+        try:
+            added, latest = self._added_since(self.history_id)
+        except Exception as e:
+            if http_status(e) != 404:
+                raise
+            # History ids expire after about a week; start over from now
+            log.warning("History id %s expired for account %s; resyncing", self.history_id, self.account)
+            self.history_id = self.api('getProfile')['historyId']
+            return 0
+
+        fired = 0
+        for message_id in added:
+            detail = self.api('messages.get', id=message_id, format='metadata',
+                              metadataHeaders=SUMMARY_HEADERS)
+            event = {
+                "type": "new_message",
+                "account": self.account,
+                "at": time.strftime("%Y-%m-%dT%H:%M:%SZ", time.gmtime(self._clock())),
+                "message": EmailSummary.from_api(detail).to_dict(),
+            }
+            try:
+                self.sink.emit(event)
+            except Exception as e:
+                self.sink_errors += 1
+                log.warning("Watch %s sink failed for message %s: %s", self.sink.kind, message_id, e)
+                continue
+            self.events_fired += 1
+            fired += 1
+
+        self.history_id = latest
+        return fired
+
+    def _added_since(self, history_id: str):
+        """Ids of messages added to the inbox after `history_id`, oldest first."""
+        added: List[str] = []
+        seen = set()
+        latest = history_id
+        page_token = None
+        while True:
+            params = {"startHistoryId": history_id, "historyTypes": ["messageAdded"],
+                      "labelId": "INBOX"}
+            if page_token:
+                params["pageToken"] = page_token
+            page = self.api('history.list', **params)
+            for record in page.get('history', []):
+                for entry in record.get('messagesAdded', []):
+                    message_id = entry['message']['id']
+                    if message_id not in seen:
+                        seen.add(message_id)
+                        added.append(message_id)
+            latest = page.get('historyId', latest)
+            page_token = page.get('nextPageToken')
+            if not page_token:
+                return added, latest
+
+    def status(self) -> Dict[str, Any]:
+        return {
+            "account": self.account,
+            "active": self.active,
+            "interval": self.interval,
+            "sink": self.sink.kind,
+            "target": self.sink.target,
+            "started_at": self.started_at,
+            "last_poll": self.last_poll,
+            "last_error": self.last_error,
+            "consecutive_failures": self.consecutive_failures,
+            "events_fired": self.events_fired,
+            "sink_errors": self.sink_errors,
+            "history_id": self.history_id,
+        }
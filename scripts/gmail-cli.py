@@ -9,7 +9,7 @@ Usage:
     gmail-cli.py inbox [--limit N]
     gmail-cli.py unread
     gmail-cli.py search QUERY [--limit N]
-    gmail-cli.py send TO SUBJECT BODY
+    gmail-cli.py send TO SUBJECT BODY [--html HTML | --markdown]
     gmail-cli.py thread THREAD_ID
 """
 
@@ -19,9 +19,14 @@ import json
 import os
 import pickle
 import sys
+from email.mime.multipart import MIMEMultipart
 from email.mime.text import MIMEText
 from pathlib import Path
 
+# Share the daemon module's Markdown renderer
+sys.path.insert(0, str(Path(__file__).resolve().parent.parent / "module"))
+from gmail_lib.markdown import to_html_document  # noqa: E402
+
 # Google API imports
 try:
     from google.auth.transport.requests import Request
@@ -207,7 +212,14 @@ def cmd_send(args):
     """Send an email."""
     service = get_service()
 
-    message = MIMEText(args.body)
+    html = to_html_document(args.body) if args.markdown else args.html
+    if html:
+        # Plain text and HTML as alternatives; the markdown source is the plain text
+        message = MIMEMultipart('alternative')
+        message.attach(MIMEText(args.body, 'plain'))
+        message.attach(MIMEText(html, 'html'))
+    else:
+        message = MIMEText(args.body)
     message['to'] = args.to
     message['subject'] = args.subject
 
@@ -280,6 +292,9 @@ def main():
     p_send.add_argument('to', help='Recipient email')
     p_send.add_argument('subject', help='Email subject')
     p_send.add_argument('body', help='Email body')
+    p_send_html = p_send.add_mutually_exclusive_group()
+    p_send_html.add_argument('--html', help='HTML alternative to the body')
+    p_send_html.add_argument('--markdown', action='store_true', help='Render the body as Markdown for the HTML part')
     p_send.set_defaults(func=cmd_send)
 
     # thread
@@ -0,0 +1,119 @@
+//! What serves calls.
+//!
+//! Normally every call goes to the embedded Python module. A daemon built
+//! with `--features native-http` can serve the read-only methods itself
+//! instead (see the `native` module), so a machine whose Python or Google
+//! client libraries are missing or broken can still list and read mail.
+//! `[daemon] runtime` picks the backend:
+//!
+//! - `python`: the Python module; startup fails if it doesn't load.
+//! - `native`: the native backend; startup fails if the binary was built
+//!   without it.
+//! - `auto` (default): the Python module, falling back to the native
+//!   backend, when built, if the module can't be loaded.
+//!
+//! The server sees one `GmailService` either way, and its health check
+//! names the backend in use.
+
+use anyhow::Result;
+use fgp_daemon::python::PythonModule;
+use fgp_daemon::service::{FgpService, HealthStatus, MethodInfo};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// One way of serving the daemon's methods.
+pub trait Backend: Send + Sync {
+    /// Short name the health check reports (`python`, `native`).
+    fn name(&self) -> &'static str;
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value>;
+    fn method_list(&self) -> Vec<MethodInfo>;
+    fn on_start(&self) -> Result<()>;
+    fn on_stop(&self) -> Result<()>;
+    fn health_check(&self) -> HashMap<String, HealthStatus>;
+}
+
+/// The embedded Python module, which implements every method.
+pub struct PythonBackend(pub PythonModule);
+
+impl Backend for PythonBackend {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        self.0.dispatch(method, params)
+    }
+
+    fn method_list(&self) -> Vec<MethodInfo> {
+        self.0.method_list()
+    }
+
+    fn on_start(&self) -> Result<()> {
+        self.0.on_start()
+    }
+
+    fn on_stop(&self) -> Result<()> {
+        self.0.on_stop()
+    }
+
+    fn health_check(&self) -> HashMap<String, HealthStatus> {
+        self.0.health_check()
+    }
+}
+
+/// The service the daemon serves, backed by whichever backend was picked.
+pub struct GmailService {
+    backend: Box<dyn Backend>,
+}
+
+impl GmailService {
+    pub fn new(backend: Box<dyn Backend>) -> Self {
+        GmailService { backend }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+}
+
+impl FgpService for GmailService {
+    fn name(&self) -> &str {
+        "gmail"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        self.backend.dispatch(method, params)
+    }
+
+    fn method_list(&self) -> Vec<MethodInfo> {
+        self.backend.method_list()
+    }
+
+    fn on_start(&self) -> Result<()> {
+        self.backend.on_start()
+    }
+
+    fn on_stop(&self) -> Result<()> {
+        self.backend.on_stop()
+    }
+
+    fn health_check(&self) -> HashMap<String, HealthStatus> {
+        let mut health = self.backend.health_check();
+        health.insert(
+            "backend".to_string(),
+            HealthStatus {
+                ok: true,
+                latency_ms: None,
+                message: Some(format!(
+                    "Serving calls with the {} backend",
+                    self.backend.name()
+                )),
+            },
+        );
+        health
+    }
+}
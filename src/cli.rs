@@ -0,0 +1,163 @@
+//! Command-line arguments for the daemon binary.
+//!
+//! Parsed by hand to keep the binary dependency-light. Settings that the
+//! Python module needs are handed over through `FGP_GMAIL_*` environment
+//! variables before the module is loaded.
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+/// Which backend serves Gmail API calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Live Gmail API via googleapiclient.
+    Api,
+    /// Recorded responses from a replay file.
+    Replay,
+}
+
+/// What the binary was asked to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run the daemon (the default).
+    Serve,
+    /// Sign in (device code, or a local browser with `--browser`), then exit.
+    Auth {
+        account: Option<String>,
+        browser: bool,
+    },
+    /// Report whether cached tokens are usable, then exit (1 if not).
+    CheckAuth { account: Option<String> },
+}
+
+/// Parsed daemon arguments.
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    pub backend: BackendKind,
+    pub replay_file: Option<PathBuf>,
+    /// Record backend responses into a session replay file.
+    pub record: bool,
+    /// Keep message bodies and addresses in recordings.
+    pub record_unsafe: bool,
+    /// Start even if the pidfile names a live process.
+    pub force: bool,
+}
+
+const USAGE: &str = "\
+Usage: fgp-gmail [OPTIONS]
+       fgp-gmail auth [--account <NAME>] [--browser]
+       fgp-gmail --check-auth [--account <NAME>]
+
+Commands:
+  auth                    Sign in on a headless machine with a device code
+
+Options:
+  --backend <api|replay>  Backend serving Gmail API calls (default: api)
+  --replay-file <PATH>    Replay file for --backend replay
+  --record                Record redacted backend responses for this session
+  --record-unsafe         Record without redacting bodies and addresses
+  --force                 Start even if the pidfile names a running process,
+                          removing the pidfile and socket
+  --check-auth            Print token status for every account (or --account) and exit
+  --account <NAME>        Account for `auth` or `--check-auth`
+  --browser               Sign in with `auth` using a local browser instead
+  -h, --help              Print this help";
+
+impl Args {
+    /// Parse arguments, excluding the program name.
+    ///
+    /// Arguments we don't recognize (e.g. `start --foreground` passed by
+    /// supervisors) are ignored with a warning rather than rejected.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut parsed = Args {
+            command: Command::Serve,
+            backend: BackendKind::Api,
+            replay_file: None,
+            record: false,
+            record_unsafe: false,
+            force: false,
+        };
+
+        let mut args = args.into_iter().peekable();
+        if args.peek().map(String::as_str) == Some("auth") {
+            args.next();
+            parsed.command = Command::Auth {
+                account: None,
+                browser: false,
+            };
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--backend" => {
+                    parsed.backend = match args.next().as_deref() {
+                        Some("api") => BackendKind::Api,
+                        Some("replay") => BackendKind::Replay,
+                        Some(other) => bail!("Unknown backend: {} (expected api or replay)", other),
+                        None => bail!("--backend requires a value\n\n{}", USAGE),
+                    }
+                }
+                "--replay-file" => match args.next() {
+                    Some(path) => parsed.replay_file = Some(PathBuf::from(path)),
+                    None => bail!("--replay-file requires a path\n\n{}", USAGE),
+                },
+                "--record" => parsed.record = true,
+                "--record-unsafe" => {
+                    parsed.record = true;
+                    parsed.record_unsafe = true;
+                }
+                "--force" => parsed.force = true,
+                "--check-auth" if parsed.command == Command::Serve => {
+                    parsed.command = Command::CheckAuth { account: None };
+                }
+                "--account" if parsed.command != Command::Serve => match args.next() {
+                    Some(name) => match &mut parsed.command {
+                        Command::Auth { account, .. } | Command::CheckAuth { account } => {
+                            *account = Some(name)
+                        }
+                        Command::Serve => {}
+                    },
+                    None => bail!("--account requires a name\n\n{}", USAGE),
+                },
+                "--browser" if parsed.command != Command::Serve => {
+                    if let Command::Auth { browser, .. } = &mut parsed.command {
+                        *browser = true;
+                    }
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                other => tracing::warn!("Ignoring unrecognized argument: {}", other),
+            }
+        }
+
+        if parsed.backend == BackendKind::Replay {
+            if parsed.replay_file.is_none() {
+                bail!("--backend replay requires --replay-file <PATH>");
+            }
+            if parsed.record {
+                bail!("--record can't be combined with --backend replay");
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Export settings for the Python module.
+    pub fn apply_env(&self) {
+        if self.backend == BackendKind::Replay {
+            std::env::set_var("FGP_GMAIL_BACKEND", "replay");
+        }
+        if let Some(path) = &self.replay_file {
+            std::env::set_var("FGP_GMAIL_REPLAY_FILE", path);
+        }
+        if self.record {
+            std::env::set_var("FGP_GMAIL_RECORD", "1");
+        }
+        if self.record_unsafe {
+            std::env::set_var("FGP_GMAIL_RECORD_UNSAFE", "1");
+        }
+    }
+}
@@ -0,0 +1,822 @@
+//! Daemon configuration file.
+//!
+//! Settings are read from `~/.fgp/services/gmail/config.toml`, or from the
+//! file named by `FGP_GMAIL_CONFIG`. Every setting is optional and a missing
+//! default file means built-in defaults:
+//!
+//! ```toml
+//! [daemon]
+//! socket = "~/.fgp/services/gmail/daemon.sock"
+//! log_filter = "fgp_gmail=debug,fgp_daemon=debug"
+//! drain_timeout_secs = 10  # wait for running calls on SIGTERM/SIGINT
+//! runtime = "auto"          # python, native, or auto (python, else native)
+//!
+//! [python]
+//! interpreter = "~/.fgp/services/gmail/.venv/bin/python"
+//! module = "~/.fgp/services/gmail/module/gmail.py"
+//!
+//! [gmail]
+//! default_account = "work"
+//! max_limit = 100
+//! max_response_bytes = 4194304  # larger results fail; 0 means no limit
+//! auth_check_interval_secs = 600
+//! rate_limit_per_sec = 10   # quota budget; 0 (the default) means unlimited
+//! max_concurrent_calls = 4  # Gmail calls in flight at once
+//! busy_wait_secs = 10       # how long a call queues for a slot before failing
+//! idempotency_ttl_secs = 86400  # how long a send's idempotency_key is remembered
+//!
+//! [cache]
+//! ttl_secs = 30
+//! max_entries = 256
+//!
+//! [contacts]          # gmail.contacts autocomplete index
+//! lookback = 500      # newest messages scanned for addresses
+//! max_age_secs = 21600  # older indexes are rebuilt in the background
+//!
+//! [timeouts]          # seconds; `default` applies to unlisted methods
+//! probe = 3           # gmail.health API probe
+//! default = 60
+//! bulk_modify = 300
+//!
+//! [retry]             # transient Gmail API errors (429, 5xx)
+//! max_attempts = 3
+//! initial_backoff_ms = 500
+//! max_backoff_ms = 8000
+//!
+//! [audit]             # one redacted JSON line per call; off by default
+//! enabled = true
+//! path = "~/.fgp/services/gmail/audit.log"
+//! max_bytes = 10485760  # rotate past this size
+//! keep = 5              # rotated files kept (audit.log.1 ... audit.log.5)
+//! text_chars = 32       # subject/body prefix kept; 0 logs a sha256 instead
+//! ```
+//!
+//! Only the TOML these settings need is understood: `[section]` headers and
+//! `key = value` lines with string, integer, float, or boolean values, plus
+//! `#` comments. Anything else fails startup with the file, line, and field.
+//!
+//! Environment variables (`FGP_GMAIL_PYTHON`, `FGP_GMAIL_CACHE_TTL`, ...)
+//! override the file, and command-line flags override both. Settings the
+//! Python module needs are handed over through `FGP_GMAIL_*` variables.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::expand_tilde;
+use crate::lifecycle::DEFAULT_DRAIN_TIMEOUT_SECS;
+
+/// Socket the daemon listens on when the config doesn't say otherwise.
+pub const DEFAULT_SOCKET: &str = "~/.fgp/services/gmail/daemon.sock";
+
+/// Tracing filter used when the config doesn't say otherwise.
+pub const DEFAULT_LOG_FILTER: &str = "fgp_gmail=debug,fgp_daemon=debug";
+
+const SECTIONS: &[&str] = &[
+    "daemon", "python", "gmail", "cache", "contacts", "timeouts", "retry", "audit",
+];
+
+/// Methods `[timeouts]` may name, without the `gmail.` prefix, plus
+/// `default`. Mirrors the module's `method_list` (checked by test_config.py).
+const TIMEOUT_METHODS: &[&str] = &[
+    "accounts",
+    "archive",
+    "audit_tail",
+    "auth_login",
+    "auth_login_cancel",
+    "auth_login_status",
+    "auth_status",
+    "batch",
+    "bulk_modify",
+    "config",
+    "contacts",
+    "create_draft",
+    "default",
+    "download_attachment",
+    "drain",
+    "export_raw",
+    "filter_create",
+    "filter_delete",
+    "filters",
+    "forward",
+    "get_attachment",
+    "health",
+    "history",
+    "import_raw",
+    "inbox",
+    "list_drafts",
+    "message",
+    "outbox",
+    "profile",
+    "read",
+    "search",
+    "send",
+    "send_draft",
+    "send_queued",
+    "sendas_list",
+    "stats",
+    "thread",
+    "unarchive",
+    "unread",
+    "unwatch",
+    "vacation_get",
+    "vacation_set",
+    "watch",
+    "watch_status",
+];
+
+/// Retry policy for transient Gmail API errors. Unset fields keep the
+/// module's defaults (no retries).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: Option<u64>,
+    pub initial_backoff_ms: Option<u64>,
+    pub max_backoff_ms: Option<u64>,
+}
+
+/// Audit log settings. Unset fields keep the module's defaults (disabled,
+/// 10 MB files, 5 kept, 32 characters of text).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditLog {
+    pub enabled: Option<bool>,
+    pub path: Option<PathBuf>,
+    pub max_bytes: Option<u64>,
+    pub keep: Option<u64>,
+    pub text_chars: Option<u64>,
+}
+
+/// What serves calls; see the `backend` module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    /// The Python module if it loads, else the native backend (when built).
+    Auto,
+    Python,
+    /// The read-only native backend (`--features native-http`).
+    Native,
+}
+
+/// Effective daemon settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// File the settings were read from; `None` when running on defaults.
+    pub source: Option<PathBuf>,
+    pub socket: String,
+    pub log_filter: String,
+    /// How long shutdown waits for running calls before exiting anyway.
+    pub drain_timeout_secs: f64,
+    pub runtime: Runtime,
+    pub python: Option<PathBuf>,
+    pub module: Option<PathBuf>,
+    pub default_account: Option<String>,
+    pub max_limit: Option<u64>,
+    /// Results whose JSON is larger fail with `ResponseTooLarge`; 0 disables.
+    pub max_response_bytes: Option<u64>,
+    pub auth_check_interval_secs: Option<f64>,
+    /// Token-bucket refill rate for Gmail calls; 0 disables the limiter.
+    pub rate_limit_per_sec: Option<f64>,
+    /// Calls allowed to run handlers at once; the rest queue for a slot.
+    pub max_concurrent_calls: Option<u64>,
+    pub busy_wait_secs: Option<f64>,
+    /// How long `idempotency_key`s of sends are remembered.
+    pub idempotency_ttl_secs: Option<f64>,
+    pub cache_ttl_secs: Option<f64>,
+    pub cache_max_entries: Option<u64>,
+    /// Messages `gmail.contacts` scans to build its index.
+    pub contacts_lookback: Option<u64>,
+    pub contacts_max_age_secs: Option<f64>,
+    pub probe_timeout_secs: Option<f64>,
+    /// Per-method call timeouts in seconds, keyed by method name without
+    /// the `gmail.` prefix (plus `default`).
+    pub method_timeouts: BTreeMap<String, f64>,
+    pub retry: RetryPolicy,
+    pub audit: AuditLog,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            source: None,
+            socket: DEFAULT_SOCKET.to_string(),
+            log_filter: DEFAULT_LOG_FILTER.to_string(),
+            drain_timeout_secs: DEFAULT_DRAIN_TIMEOUT_SECS,
+            runtime: Runtime::Auto,
+            python: None,
+            module: None,
+            default_account: None,
+            max_limit: None,
+            max_response_bytes: None,
+            auth_check_interval_secs: None,
+            rate_limit_per_sec: None,
+            max_concurrent_calls: None,
+            busy_wait_secs: None,
+            idempotency_ttl_secs: None,
+            cache_ttl_secs: None,
+            cache_max_entries: None,
+            contacts_lookback: None,
+            contacts_max_age_secs: None,
+            probe_timeout_secs: None,
+            method_timeouts: BTreeMap::new(),
+            retry: RetryPolicy::default(),
+            audit: AuditLog::default(),
+        }
+    }
+}
+
+/// A parsed TOML value.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl Config {
+    /// Load the config file, or defaults when the default file is absent.
+    ///
+    /// A file named explicitly by `FGP_GMAIL_CONFIG` must exist.
+    pub fn load() -> Result<Self> {
+        let (path, explicit) = match std::env::var("FGP_GMAIL_CONFIG") {
+            Ok(value) if !value.trim().is_empty() => (expand_tilde(value.trim()), true),
+            _ => match dirs::home_dir() {
+                Some(home) => (
+                    home.join(".fgp")
+                        .join("services")
+                        .join("gmail")
+                        .join("config.toml"),
+                    false,
+                ),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        if !path.exists() {
+            if explicit {
+                bail!(
+                    "Config file not found: {} (from FGP_GMAIL_CONFIG)",
+                    path.display()
+                );
+            }
+            return Ok(Config::default());
+        }
+
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config = Config::parse(&text, &path.display().to_string())?;
+        config.source = Some(path);
+        Ok(config)
+    }
+
+    /// Parse config text. `origin` names the file in error messages.
+    pub fn parse(text: &str, origin: &str) -> Result<Self> {
+        let mut config = Config::default();
+        let mut section: Option<String> = None;
+        let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+
+        for (index, raw) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let at = |message: String| anyhow::anyhow!("{}:{}: {}", origin, line_no, message);
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix('[') {
+                let header = strip_comment(rest);
+                let name = match header.strip_suffix(']') {
+                    Some(name) => name.trim(),
+                    None => return Err(at(format!("malformed section header: {}", line))),
+                };
+                if !SECTIONS.contains(&name) {
+                    return Err(at(format!(
+                        "unknown section [{}] (expected one of: {})",
+                        name,
+                        SECTIONS.join(", ")
+                    )));
+                }
+                section = Some(name.to_string());
+                continue;
+            }
+
+            let Some(section) = section.as_deref() else {
+                return Err(at("setting outside of a [section]".to_string()));
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(at(format!("expected `key = value`, got: {}", line)));
+            };
+            let key = parse_key(key.trim()).map_err(at)?;
+            let field = format!("{}.{}", section, key);
+            let value = parse_value(value.trim()).map_err(|e| at(format!("{}: {}", field, e)))?;
+            if let Some(first) = seen.insert(field.clone(), line_no) {
+                return Err(at(format!("{} is already set on line {}", field, first)));
+            }
+            config.set(section, &key, value).map_err(at)?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: Value) -> std::result::Result<(), String> {
+        let field = format!("{}.{}", section, key);
+        match (section, key) {
+            ("daemon", "socket") => self.socket = string(&field, value)?,
+            ("daemon", "log_filter") => self.log_filter = string(&field, value)?,
+            ("daemon", "drain_timeout_secs") => {
+                self.drain_timeout_secs = seconds(&field, value, true)?
+            }
+            ("daemon", "runtime") => self.runtime = runtime(&field, value)?,
+            ("python", "interpreter") => self.python = Some(path(&field, value)?),
+            ("python", "module") => self.module = Some(path(&field, value)?),
+            ("gmail", "default_account") => self.default_account = Some(string(&field, value)?),
+            ("gmail", "max_limit") => self.max_limit = Some(positive_int(&field, value)?),
+            ("gmail", "max_response_bytes") => {
+                self.max_response_bytes = Some(non_negative_int(&field, value)?)
+            }
+            ("gmail", "auth_check_interval_secs") => {
+                self.auth_check_interval_secs = Some(seconds(&field, value, false)?)
+            }
+            ("gmail", "rate_limit_per_sec") => self.rate_limit_per_sec = Some(rate(&field, value)?),
+            ("gmail", "max_concurrent_calls") => {
+                self.max_concurrent_calls = Some(positive_int(&field, value)?)
+            }
+            ("gmail", "busy_wait_secs") => {
+                self.busy_wait_secs = Some(seconds(&field, value, true)?)
+            }
+            ("gmail", "idempotency_ttl_secs") => {
+                self.idempotency_ttl_secs = Some(seconds(&field, value, false)?)
+            }
+            ("cache", "ttl_secs") => self.cache_ttl_secs = Some(seconds(&field, value, true)?),
+            ("cache", "max_entries") => self.cache_max_entries = Some(positive_int(&field, value)?),
+            ("contacts", "lookback") => self.contacts_lookback = Some(positive_int(&field, value)?),
+            ("contacts", "max_age_secs") => {
+                self.contacts_max_age_secs = Some(seconds(&field, value, true)?)
+            }
+            ("timeouts", "probe") => self.probe_timeout_secs = Some(seconds(&field, value, false)?),
+            ("timeouts", method) => {
+                let method = method.strip_prefix("gmail.").unwrap_or(method);
+                if !TIMEOUT_METHODS.contains(&method) {
+                    return Err(format!(
+                        "unknown key `{}` in [timeouts] (expected a method name, `default`, or `probe`)",
+                        key
+                    ));
+                }
+                let secs = seconds(&field, value, false)?;
+                self.method_timeouts.insert(method.to_string(), secs);
+            }
+            ("retry", "max_attempts") => {
+                self.retry.max_attempts = Some(positive_int(&field, value)?)
+            }
+            ("retry", "initial_backoff_ms") => {
+                self.retry.initial_backoff_ms = Some(non_negative_int(&field, value)?)
+            }
+            ("retry", "max_backoff_ms") => {
+                self.retry.max_backoff_ms = Some(non_negative_int(&field, value)?)
+            }
+            ("audit", "enabled") => self.audit.enabled = Some(boolean(&field, value)?),
+            ("audit", "path") => self.audit.path = Some(path(&field, value)?),
+            ("audit", "max_bytes") => self.audit.max_bytes = Some(positive_int(&field, value)?),
+            ("audit", "keep") => self.audit.keep = Some(non_negative_int(&field, value)?),
+            ("audit", "text_chars") => {
+                self.audit.text_chars = Some(non_negative_int(&field, value)?)
+            }
+            _ => return Err(format!("unknown key `{}` in [{}]", key, section)),
+        }
+        Ok(())
+    }
+
+    /// Export the settings the Python module reads. Variables that are
+    /// already set win over the file.
+    pub fn apply_env(&self) -> Result<()> {
+        fn set_default(name: &str, value: impl ToString) {
+            if std::env::var_os(name).is_none() {
+                std::env::set_var(name, value.to_string());
+            }
+        }
+
+        if let Some(account) = &self.default_account {
+            set_default("FGP_GMAIL_DEFAULT_ACCOUNT", account);
+        }
+        if let Some(limit) = self.max_limit {
+            set_default("FGP_GMAIL_MAX_LIMIT", limit);
+        }
+        if let Some(bytes) = self.max_response_bytes {
+            set_default("FGP_GMAIL_MAX_RESPONSE_BYTES", bytes);
+        }
+        if let Some(interval) = self.auth_check_interval_secs {
+            set_default("FGP_GMAIL_AUTH_CHECK_INTERVAL", interval);
+        }
+        if let Some(rate) = self.rate_limit_per_sec {
+            set_default("FGP_GMAIL_RATE_LIMIT", rate);
+        }
+        if let Some(calls) = self.max_concurrent_calls {
+            set_default("FGP_GMAIL_MAX_CONCURRENT", calls);
+        }
+        if let Some(wait) = self.busy_wait_secs {
+            set_default("FGP_GMAIL_BUSY_WAIT", wait);
+        }
+        if let Some(ttl) = self.idempotency_ttl_secs {
+            set_default("FGP_GMAIL_IDEMPOTENCY_TTL", ttl);
+        }
+        if let Some(ttl) = self.cache_ttl_secs {
+            set_default("FGP_GMAIL_CACHE_TTL", ttl);
+        }
+        if let Some(entries) = self.cache_max_entries {
+            set_default("FGP_GMAIL_CACHE_MAX_ENTRIES", entries);
+        }
+        if let Some(lookback) = self.contacts_lookback {
+            set_default("FGP_GMAIL_CONTACTS_LOOKBACK", lookback);
+        }
+        if let Some(age) = self.contacts_max_age_secs {
+            set_default("FGP_GMAIL_CONTACTS_MAX_AGE", age);
+        }
+        if let Some(timeout) = self.probe_timeout_secs {
+            set_default("FGP_GMAIL_PROBE_TIMEOUT", timeout);
+        }
+        if !self.method_timeouts.is_empty() {
+            let timeouts = serde_json::to_string(&self.method_timeouts)
+                .context("Failed to encode method timeouts")?;
+            set_default("FGP_GMAIL_METHOD_TIMEOUTS", timeouts);
+        }
+        if let Some(attempts) = self.retry.max_attempts {
+            set_default("FGP_GMAIL_RETRY_MAX_ATTEMPTS", attempts);
+        }
+        if let Some(backoff) = self.retry.initial_backoff_ms {
+            set_default("FGP_GMAIL_RETRY_INITIAL_BACKOFF_MS", backoff);
+        }
+        if let Some(backoff) = self.retry.max_backoff_ms {
+            set_default("FGP_GMAIL_RETRY_MAX_BACKOFF_MS", backoff);
+        }
+
+        if let Some(enabled) = self.audit.enabled {
+            set_default("FGP_GMAIL_AUDIT", if enabled { "1" } else { "0" });
+        }
+        if let Some(path) = &self.audit.path {
+            set_default("FGP_GMAIL_AUDIT_PATH", path.display());
+        }
+        if let Some(bytes) = self.audit.max_bytes {
+            set_default("FGP_GMAIL_AUDIT_MAX_BYTES", bytes);
+        }
+        if let Some(keep) = self.audit.keep {
+            set_default("FGP_GMAIL_AUDIT_KEEP", keep);
+        }
+        if let Some(chars) = self.audit.text_chars {
+            set_default("FGP_GMAIL_AUDIT_TEXT_CHARS", chars);
+        }
+
+        // Reported by gmail.config; the daemon itself has already used them
+        let source = self
+            .source
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        std::env::set_var("FGP_GMAIL_CONFIG_FILE", source);
+        std::env::set_var("FGP_GMAIL_SOCKET", &self.socket);
+        std::env::set_var("FGP_GMAIL_LOG_FILTER", &self.log_filter);
+        std::env::set_var(
+            "FGP_GMAIL_DRAIN_TIMEOUT",
+            self.drain_timeout_secs.to_string(),
+        );
+        Ok(())
+    }
+}
+
+/// Drop a trailing `# comment` from text that has no strings in it.
+fn strip_comment(text: &str) -> &str {
+    text.split('#').next().unwrap_or("").trim()
+}
+
+fn parse_key(key: &str) -> std::result::Result<String, String> {
+    if let Some(quoted) = key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        if !quoted.is_empty() && !quoted.contains(['"', '\\']) {
+            return Ok(quoted.to_string());
+        }
+    } else if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Ok(key.to_string());
+    }
+    Err(format!("invalid key: {}", key))
+}
+
+fn parse_value(text: &str) -> std::result::Result<Value, String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return trailing(chars.as_str()).map(|_| Value::Str(value)),
+                '\\' => match chars.next() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(other) => return Err(format!("unsupported escape \\{}", other)),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        return match rest.split_once('\'') {
+            Some((value, after)) => trailing(after).map(|_| Value::Str(value.to_string())),
+            None => Err("unterminated string".to_string()),
+        };
+    }
+
+    let bare = strip_comment(text);
+    match bare {
+        "" => Err("missing value".to_string()),
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => {
+            let digits = bare.replace('_', "");
+            if let Ok(int) = digits.parse::<i64>() {
+                Ok(Value::Int(int))
+            } else if let Ok(float) = digits.parse::<f64>() {
+                Ok(Value::Float(float))
+            } else {
+                Err(format!(
+                    "expected a quoted string, number, or boolean, got: {}",
+                    bare
+                ))
+            }
+        }
+    }
+}
+
+/// Only whitespace or a comment may follow a closing quote.
+fn trailing(rest: &str) -> std::result::Result<(), String> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected text after string: {}", rest))
+    }
+}
+
+fn string(field: &str, value: Value) -> std::result::Result<String, String> {
+    match value {
+        Value::Str(s) if !s.trim().is_empty() => Ok(s),
+        Value::Str(_) => Err(format!("{} must not be empty", field)),
+        _ => Err(format!("{} must be a string", field)),
+    }
+}
+
+fn runtime(field: &str, value: Value) -> std::result::Result<Runtime, String> {
+    match string(field, value)?.as_str() {
+        "auto" => Ok(Runtime::Auto),
+        "python" => Ok(Runtime::Python),
+        "native" => Ok(Runtime::Native),
+        other => Err(format!(
+            "{} must be auto, python, or native (got {:?})",
+            field, other
+        )),
+    }
+}
+
+fn boolean(field: &str, value: Value) -> std::result::Result<bool, String> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        _ => Err(format!("{} must be true or false", field)),
+    }
+}
+
+fn path(field: &str, value: Value) -> std::result::Result<PathBuf, String> {
+    string(field, value).map(|s| expand_tilde(s.trim()))
+}
+
+fn non_negative_int(field: &str, value: Value) -> std::result::Result<u64, String> {
+    match value {
+        Value::Int(n) if n >= 0 => Ok(n as u64),
+        _ => Err(format!("{} must be a non-negative integer", field)),
+    }
+}
+
+fn positive_int(field: &str, value: Value) -> std::result::Result<u64, String> {
+    match value {
+        Value::Int(n) if n > 0 => Ok(n as u64),
+        _ => Err(format!("{} must be a positive integer", field)),
+    }
+}
+
+fn rate(field: &str, value: Value) -> std::result::Result<f64, String> {
+    match value {
+        Value::Int(n) if n >= 0 => Ok(n as f64),
+        Value::Float(f) if f.is_finite() && f >= 0.0 => Ok(f),
+        _ => Err(format!(
+            "{} must be a number of calls per second (0 = unlimited)",
+            field
+        )),
+    }
+}
+
+fn seconds(field: &str, value: Value, allow_zero: bool) -> std::result::Result<f64, String> {
+    let secs = match value {
+        Value::Int(n) => n as f64,
+        Value::Float(f) if f.is_finite() => f,
+        _ => return Err(format!("{} must be a number of seconds", field)),
+    };
+    if secs < 0.0 || (secs == 0.0 && !allow_zero) {
+        return Err(format!(
+            "{} must be {} seconds",
+            field,
+            if allow_zero {
+                "at least 0"
+            } else {
+                "more than 0"
+            }
+        ));
+    }
+    Ok(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        Config::parse(text, "config.toml").unwrap_err().to_string()
+    }
+
+    #[test]
+    fn syntax_errors_name_the_line() {
+        assert_eq!(
+            error("# settings\n[gmail\n"),
+            "config.toml:2: malformed section header: [gmail"
+        );
+        assert_eq!(
+            error("max_limit = 5\n"),
+            "config.toml:1: setting outside of a [section]"
+        );
+        assert_eq!(
+            error("[gmail]\n\nmax_limit\n"),
+            "config.toml:3: expected `key = value`, got: max_limit"
+        );
+        assert_eq!(
+            error("[daemon]\nsocket = \"/tmp/gmail.sock\n"),
+            "config.toml:2: daemon.socket: unterminated string"
+        );
+        assert_eq!(
+            error("[daemon]\nsocket = \"/tmp/a\" extra\n"),
+            "config.toml:2: daemon.socket: unexpected text after string: extra"
+        );
+        assert_eq!(
+            error("[gmail]\nmax limit = 5\n"),
+            "config.toml:2: invalid key: max limit"
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_name_both_lines() {
+        assert_eq!(
+            error("[gmail]\nmax_limit = 5\n# again\nmax_limit = 6\n"),
+            "config.toml:4: gmail.max_limit is already set on line 2"
+        );
+    }
+
+    #[test]
+    fn unknown_sections_and_keys() {
+        assert!(error("[cache]\n[gmali]\n")
+            .starts_with("config.toml:2: unknown section [gmali] (expected one of: daemon,"));
+        assert_eq!(
+            error("[cache]\nttl = 5\n"),
+            "config.toml:2: unknown key `ttl` in [cache]"
+        );
+        assert!(error("[timeouts]\ndefault = 60\nserach = 5\n")
+            .starts_with("config.toml:3: unknown key `serach` in [timeouts]"));
+    }
+
+    #[test]
+    fn timeouts() {
+        let config = Config::parse(
+            "[timeouts]\nprobe = 2\ndefault = 60\n\"gmail.bulk_modify\" = 300\nsearch = 1.5\n",
+            "config.toml",
+        )
+        .unwrap();
+        assert_eq!(config.probe_timeout_secs, Some(2.0));
+        assert_eq!(
+            config.method_timeouts,
+            BTreeMap::from([
+                ("bulk_modify".to_string(), 300.0),
+                ("default".to_string(), 60.0),
+                ("search".to_string(), 1.5),
+            ])
+        );
+    }
+
+    #[test]
+    fn type_and_range_errors() {
+        for (text, message) in [
+            (
+                "[daemon]\nsocket = 5",
+                "config.toml:2: daemon.socket must be a string",
+            ),
+            (
+                "[daemon]\nruntime = \"ruby\"",
+                "config.toml:2: daemon.runtime must be auto, python, or native (got \"ruby\")",
+            ),
+            (
+                "[gmail]\ndefault_account = \"  \"",
+                "config.toml:2: gmail.default_account must not be empty",
+            ),
+            (
+                "[gmail]\nmax_limit = 0",
+                "config.toml:2: gmail.max_limit must be a positive integer",
+            ),
+            (
+                "[gmail]\nmax_limit = 2.5",
+                "config.toml:2: gmail.max_limit must be a positive integer",
+            ),
+            (
+                "[gmail]\nrate_limit_per_sec = -1",
+                "config.toml:2: gmail.rate_limit_per_sec must be a number of calls per second (0 = unlimited)",
+            ),
+            (
+                "[cache]\nttl_secs = \"30\"",
+                "config.toml:2: cache.ttl_secs must be a number of seconds",
+            ),
+            (
+                "[cache]\nttl_secs = -1",
+                "config.toml:2: cache.ttl_secs must be at least 0 seconds",
+            ),
+            (
+                "[timeouts]\ninbox = 0",
+                "config.toml:2: timeouts.inbox must be more than 0 seconds",
+            ),
+            (
+                "[retry]\ninitial_backoff_ms = -5",
+                "config.toml:2: retry.initial_backoff_ms must be a non-negative integer",
+            ),
+            (
+                "[audit]\nenabled = \"yes\"",
+                "config.toml:2: audit.enabled must be true or false",
+            ),
+        ] {
+            assert_eq!(error(text), message, "parsing {:?}", text);
+        }
+    }
+
+    #[test]
+    fn values() {
+        let config = Config::parse(
+            "[daemon]\nsocket = '~/gmail.sock'  # literal\nruntime = \"native\"\n\
+             [gmail]\nmax_response_bytes = 1_048_576\nbusy_wait_secs = 0\n\
+             [audit]\nenabled = false\n",
+            "config.toml",
+        )
+        .unwrap();
+        assert_eq!(config.socket, "~/gmail.sock");
+        assert_eq!(config.runtime, Runtime::Native);
+        assert_eq!(config.max_response_bytes, Some(1_048_576));
+        assert_eq!(config.busy_wait_secs, Some(0.0));
+        assert_eq!(config.audit.enabled, Some(false));
+    }
+
+    #[test]
+    fn apply_env_exports_settings_without_overriding() {
+        let config = Config::parse(
+            "[gmail]\ndefault_account = \"work\"\nmax_response_bytes = 0\n\
+             [cache]\nttl_secs = 5\n\
+             [contacts]\nlookback = 50\n\
+             [timeouts]\nprobe = 2\n\
+             [retry]\nmax_attempts = 4\n\
+             [audit]\nenabled = true\n",
+            "config.toml",
+        )
+        .unwrap();
+        std::env::set_var("FGP_GMAIL_CACHE_TTL", "7");
+        config.apply_env().unwrap();
+
+        let var = |name: &str| std::env::var(name).ok();
+        assert_eq!(var("FGP_GMAIL_DEFAULT_ACCOUNT").as_deref(), Some("work"));
+        assert_eq!(var("FGP_GMAIL_MAX_RESPONSE_BYTES").as_deref(), Some("0"));
+        assert_eq!(var("FGP_GMAIL_CACHE_TTL").as_deref(), Some("7"));
+        assert_eq!(var("FGP_GMAIL_CONTACTS_LOOKBACK").as_deref(), Some("50"));
+        assert_eq!(var("FGP_GMAIL_PROBE_TIMEOUT").as_deref(), Some("2"));
+        assert_eq!(var("FGP_GMAIL_RETRY_MAX_ATTEMPTS").as_deref(), Some("4"));
+        assert_eq!(var("FGP_GMAIL_AUDIT").as_deref(), Some("1"));
+        assert_eq!(var("FGP_GMAIL_CONFIG_FILE").as_deref(), Some(""));
+        assert_eq!(var("FGP_GMAIL_SOCKET").as_deref(), Some(DEFAULT_SOCKET));
+        assert_eq!(var("FGP_GMAIL_RATE_LIMIT"), None);
+    }
+
+    #[test]
+    fn contacts_section() {
+        let config = Config::parse(
+            "[contacts]\nlookback = 200\nmax_age_secs = 3600\n",
+            "config.toml",
+        )
+        .unwrap();
+        assert_eq!(config.contacts_lookback, Some(200));
+        assert_eq!(config.contacts_max_age_secs, Some(3600.0));
+
+        let error = Config::parse("[contacts]\nlookback = 0\n", "config.toml").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("config.toml:2: contacts.lookback"));
+    }
+}
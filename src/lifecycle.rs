@@ -0,0 +1,193 @@
+//! Daemon lifecycle: the pidfile and graceful shutdown.
+//!
+//! On startup the daemon writes its pid to `~/.fgp/services/gmail/daemon.pid`
+//! and refuses to start while another live process holds it. A pidfile left
+//! by a process that is gone is removed, along with its socket. `--force`
+//! takes over even when the recorded pid is alive, for when it has been
+//! reused by an unrelated process.
+//!
+//! On SIGTERM or SIGINT the daemon:
+//! 1. opens one last connection, then unlinks the socket, so no new clients
+//!    can connect while calls on open connections are still answered;
+//! 2. calls `gmail.drain` on it, which turns new calls away, waits up to
+//!    `drain_timeout_secs` for running ones, and stops the watches (and their
+//!    sink commands, SIGTERM then SIGKILL) and the outbox;
+//! 3. removes the pidfile and exits with code 0.
+//!
+//! A second signal during the drain exits at once.
+
+use anyhow::{bail, Context, Result};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How long the drain waits for running calls when the config doesn't say.
+pub const DEFAULT_DRAIN_TIMEOUT_SECS: f64 = 10.0;
+
+/// Time on top of the drain timeout for stopping watches and the outbox.
+const STOP_GRACE_SECS: f64 = 10.0;
+
+/// Where the pidfile lives.
+pub fn pidfile_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Could not determine home directory")?;
+    Ok(home
+        .join(".fgp")
+        .join("services")
+        .join("gmail")
+        .join("daemon.pid"))
+}
+
+/// This process's claim on the pidfile. Dropping it removes the file.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write our pid to `path`, clearing out a stale pidfile and `socket`.
+    pub fn acquire(path: &Path, socket: &Path, force: bool) -> Result<Self> {
+        if let Some(pid) = read_pid(path) {
+            if pid != std::process::id() && is_alive(pid) {
+                if !force {
+                    bail!(
+                        "Another fgp-gmail is running (pid {}, pidfile {})\n\
+                         Stop it first (`fgp stop gmail` or `kill {}`), or pass --force \
+                         if that pid is no longer the daemon.",
+                        pid,
+                        path.display(),
+                        pid
+                    );
+                }
+                tracing::warn!("--force: taking over the pidfile from live pid {}", pid);
+            } else {
+                tracing::info!("Removing stale pidfile (pid {} is gone)", pid);
+            }
+            remove_socket(socket);
+        } else if force {
+            remove_socket(socket);
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write pidfile {}", path.display()))?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        remove_pidfile(&self.path);
+    }
+}
+
+/// Remove the pidfile, unless another instance has taken it over.
+fn remove_pidfile(path: &Path) {
+    if read_pid(path) == Some(std::process::id()) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether `pid` is a running process (that we could signal).
+fn is_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn remove_socket(socket: &Path) {
+    match std::fs::remove_file(socket) {
+        Ok(()) => tracing::info!("Removed socket {}", socket.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!("Failed to remove socket {}: {}", socket.display(), e),
+    }
+}
+
+/// Shut down gracefully on the first SIGTERM or SIGINT; see the module docs.
+pub fn handle_signals(socket: PathBuf, pidfile: PathBuf, drain_timeout_secs: f64) -> Result<()> {
+    let mut signals =
+        Signals::new([SIGTERM, SIGINT]).context("Failed to install signal handlers")?;
+    std::thread::Builder::new()
+        .name("gmail-signals".to_string())
+        .spawn(move || {
+            let mut signals = signals.forever();
+            if signals.next().is_none() {
+                return;
+            }
+            {
+                let pidfile = pidfile.clone();
+                std::thread::spawn(move || {
+                    shutdown(&socket, drain_timeout_secs);
+                    remove_pidfile(&pidfile);
+                    std::process::exit(0);
+                });
+            }
+            if signals.next().is_some() {
+                eprintln!("Second signal; exiting without waiting for running calls");
+                remove_pidfile(&pidfile);
+                std::process::exit(1);
+            }
+        })
+        .context("Failed to start the signal thread")?;
+    Ok(())
+}
+
+fn shutdown(socket: &Path, drain_timeout_secs: f64) {
+    println!(
+        "Shutting down; waiting up to {}s for running calls",
+        drain_timeout_secs
+    );
+    // Connect before unlinking: the server keeps serving connections it has
+    // accepted after the path is gone
+    let stream = UnixStream::connect(socket);
+    remove_socket(socket);
+    match stream.and_then(|stream| drain(stream, drain_timeout_secs)) {
+        Ok(response) if response["ok"] == true => {
+            let result = &response["result"];
+            if result["drained"] == true {
+                println!("All calls finished");
+            } else {
+                println!("Gave up on {} running call(s)", result["abandoned"]);
+            }
+        }
+        Ok(response) => tracing::warn!("gmail.drain failed: {}", response["error"]),
+        Err(e) => tracing::warn!("Couldn't drain the module: {}", e),
+    }
+}
+
+fn drain(mut stream: UnixStream, drain_timeout_secs: f64) -> std::io::Result<serde_json::Value> {
+    stream.set_read_timeout(Some(Duration::from_secs_f64(
+        drain_timeout_secs + STOP_GRACE_SECS,
+    )))?;
+    let request = serde_json::json!({
+        "id": format!("shutdown-{}", std::process::id()),
+        "v": 1,
+        "method": "gmail.drain",
+        "params": {"timeout_secs": drain_timeout_secs},
+    });
+    writeln!(stream, "{}", request)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(std::io::Error::other)
+}
@@ -13,24 +13,119 @@
 //! - PyO3 warm connection: ~30-50ms (10-100x faster!)
 //!
 //! # Methods
+//! All methods except `gmail.accounts`, `gmail.audit_tail`, `gmail.batch`,
+//! `gmail.config`, `gmail.drain`, `gmail.health`, and `gmail.stats` accept an
+//! optional `account` param.
+//! - `gmail.accounts` - List configured accounts and token status
+//! - `gmail.profile` - Signed-in address, mailbox totals, and history id
+//! - `gmail.config` - Effective daemon configuration (no secrets)
+//! - `gmail.health` - Structured per-subsystem health with rollup status
+//! - `gmail.stats` - Per-method latency percentiles and error counts
+//! - `gmail.audit_tail` - Newest audit log entries (when `[audit]` is enabled)
+//! - `gmail.drain` - Stop taking calls and wait for running ones (sent on SIGTERM)
 //! - `gmail.inbox` - List recent inbox emails
 //! - `gmail.unread` - Get ACCURATE unread count and summaries
 //! - `gmail.search` - Search emails by query
 //! - `gmail.read` - Read full email with body and attachment info
-//! - `gmail.send` - Send an email with optional attachments
+//! - `gmail.message` - Get a single message (full, metadata, or raw format)
+//! - `gmail.export_raw` - Save a message's RFC 822 source as a .eml file
+//! - `gmail.import_raw` - Insert an .eml file into the mailbox
+//! - `gmail.send` - Send an email with optional attachments (or queue it on failure)
+//! - `gmail.forward` - Forward a message with its attachments
+//! - `gmail.outbox` - List queued and given-up sends
+//! - `gmail.send_queued` - Retry queued sends now
+//! - `gmail.create_draft` - Stage an email as a draft for review
+//! - `gmail.send_draft` - Send a previously created draft
+//! - `gmail.list_drafts` - List drafts
+//! - `gmail.filters` - List filters
+//! - `gmail.filter_create` - Create a filter (criteria + actions, label names)
+//! - `gmail.filter_delete` - Delete a filter by ID
+//! - `gmail.vacation_get` - Get the vacation auto-reply setting
+//! - `gmail.vacation_set` - Turn the vacation auto-reply on or off
+//! - `gmail.sendas_list` - List send-as aliases usable as `send`'s `from`
+//! - `gmail.bulk_modify` - Archive/trash/mark read/label every search match
+//! - `gmail.archive` - Remove messages from the inbox
+//! - `gmail.unarchive` - Move archived messages back to the inbox
+//! - `gmail.batch` - Run several methods in one call with per-operation results
 //! - `gmail.download_attachment` - Download attachment by ID
-//! - `gmail.thread` - Get email thread
+//! - `gmail.get_attachment` - Get attachment as base64 + MIME type, or save to a file
+//! - `gmail.thread` - Get a thread with de-quoted bodies and its participants
+//! - `gmail.auth_status` - Whether the account's cached token is valid, and its expiry
+//! - `gmail.auth_login` - Start a device-code OAuth login (URL + user code)
+//! - `gmail.auth_login_status` - Poll the device-code login's state
+//! - `gmail.auth_login_cancel` - Cancel a pending device-code login
+//! - `gmail.watch` - Poll for new mail in the background and emit events, or register Pub/Sub push
+//! - `gmail.unwatch` - Stop the new-mail watch
+//! - `gmail.watch_status` - Watch state, last poll time, event count, and push expiration
+//! - `gmail.history` - Message changes since a history id
 //!
 //! # Setup
 //! 1. Place Google OAuth credentials in ~/.fgp/auth/google/credentials.json
-//! 2. Run once to complete OAuth flow
+//! 2. Sign in once: `fgp-gmail auth --browser` (or `fgp-gmail auth` for a
+//!    device code on a machine without a browser)
 //! 3. Daemon will use cached tokens for subsequent calls
 //!
+//! The daemon never runs an interactive sign-in itself. If the default
+//! account has no usable token, startup fails with instructions; calls for
+//! other accounts fail with `AuthExpired`. Tokens are re-checked in the
+//! background, and calls for an account known to need sign-in fail with
+//! `AuthRequired`; `fgp-gmail --check-auth` runs the same check offline.
+//!
+//! # Logging
+//! The module logs every call under a request id, which is also returned
+//! in the response's `request_id`, so concurrent calls' lines can be told
+//! apart. The `fgp_gmail` level in the log filter applies to the module.
+//!
+//! # Concurrency
+//! Each connection is served on its own thread. The module lets
+//! `max_concurrent_calls` of them talk to Gmail at once and queues the rest
+//! for up to `busy_wait_secs`. Token refreshes and writes hold a per-token
+//! `flock` shared with `fgp-gmail auth` processes, which is released when
+//! its holder exits, so a crash can't leave it held.
+//!
+//! # Shutdown
+//! SIGTERM or SIGINT stops the daemon gracefully: the socket is removed so no
+//! new clients connect, calls already running get up to `drain_timeout_secs`
+//! (default 10) to finish, watches and the outbox are stopped, and the
+//! process exits with code 0. A second signal exits immediately. A pidfile
+//! at `~/.fgp/services/gmail/daemon.pid` keeps a second daemon from starting;
+//! `--force` overrides it. See the `lifecycle` module.
+//!
+//! # Backends
+//! Calls are served by the Python module. Built with `--features
+//! native-http`, the daemon can serve the read-only methods natively when
+//! Python isn't usable; `[daemon] runtime` chooses. See the `backend` module.
+//!
+//! # Configuration
+//! Settings are read from `~/.fgp/services/gmail/config.toml` (or
+//! `FGP_GMAIL_CONFIG`); see the `config` module for the format.
+//!
+//! # Python environment
+//! The Google client libraries are imported by the embedded interpreter. If
+//! they live in a virtualenv, point `FGP_GMAIL_PYTHON` at its interpreter
+//! (e.g. `~/.fgp/services/gmail/.venv/bin/python`) and its site-packages are
+//! added to the module search path. The venv must use the same Python minor
+//! version the daemon was built against.
+//!
+//! `gmail.py` itself is looked up next to the binary, then under
+//! `~/.fgp/services/gmail/module/`, then in the source tree. Set
+//! `FGP_GMAIL_MODULE` (or `[python] module`) to use one elsewhere. When none
+//! of those has it, startup fails listing every searched path, and they are
+//! logged at debug level.
+//!
 //! # Run
 //! ```bash
 //! cargo run --release
 //! ```
 //!
+//! # Recording and replay
+//! `--record` stores redacted request/response pairs for every Gmail API call
+//! under ~/.fgp/services/gmail/recordings/ (`--record-unsafe` keeps bodies and
+//! addresses). Serve a recording with:
+//! ```bash
+//! fgp-gmail --backend replay --replay-file session.jsonl
+//! ```
+//!
 //! # Test
 //! ```bash
 //! fgp call gmail.inbox -p '{"limit": 5}'
@@ -46,77 +141,348 @@
 //! 01/13/2026 - Switched to PyO3 PythonModule for warm connections (Claude)
 //! 01/12/2026 - Initial implementation with subprocess per call (Claude)
 
+mod backend;
+mod cli;
+mod config;
+mod lifecycle;
+#[cfg(feature = "native-http")]
+mod native;
+
 use anyhow::{bail, Context, Result};
+use backend::{Backend, GmailService, PythonBackend};
+use cli::{Args, BackendKind, Command as CliCommand};
+use config::{Config, Runtime};
 use fgp_daemon::python::PythonModule;
 use fgp_daemon::FgpServer;
-use std::path::PathBuf;
+use lifecycle::PidFile;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Interpreter used when `FGP_GMAIL_PYTHON` is not set.
+const DEFAULT_PYTHON: &str = "python3";
+
+/// Expand a leading `~/` to the user's home directory.
+fn expand_tilde(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// The Python interpreter set by `FGP_GMAIL_PYTHON`, then the config file's
+/// `[python] interpreter`, or None when neither is set.
+///
+/// An explicitly configured interpreter must exist; a typo should fail
+/// startup rather than silently picking up whatever is on PATH.
+fn configured_python_path(config: &Config) -> Result<Option<PathBuf>> {
+    let (path, origin) = match std::env::var("FGP_GMAIL_PYTHON") {
+        Ok(value) if !value.trim().is_empty() => {
+            (expand_tilde(value.trim()), "FGP_GMAIL_PYTHON".to_string())
+        }
+        _ => match (&config.python, &config.source) {
+            (Some(path), Some(source)) => (
+                path.clone(),
+                format!("{} [python] interpreter", source.display()),
+            ),
+            _ => return Ok(None),
+        },
+    };
+    if !path.exists() {
+        bail!(
+            "Python interpreter not found: {} (from {})",
+            path.display(),
+            origin
+        );
+    }
+    Ok(Some(path))
+}
+
+/// The configured Python interpreter, falling back to `python3` on PATH.
+fn resolve_python_path(config: &Config) -> Result<PathBuf> {
+    Ok(configured_python_path(config)?.unwrap_or_else(|| PathBuf::from(DEFAULT_PYTHON)))
+}
+
+/// Verify the interpreter runs and return its site-packages directories.
+fn python_site_packages(python: &Path) -> Result<Vec<String>> {
+    let version = Command::new(python)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to run Python interpreter: {}", python.display()))?;
+    if !version.status.success() {
+        bail!(
+            "Python interpreter {} failed --version check: {}",
+            python.display(),
+            String::from_utf8_lossy(&version.stderr).trim()
+        );
+    }
+    tracing::debug!(
+        "Using Python interpreter {} ({})",
+        python.display(),
+        String::from_utf8_lossy(&version.stdout).trim()
+    );
+
+    let output = Command::new(python)
+        .args([
+            "-c",
+            "import json, site; print(json.dumps(site.getsitepackages()))",
+        ])
+        .output()
+        .with_context(|| format!("Failed to query site-packages from {}", python.display()))?;
+    if !output.status.success() {
+        bail!(
+            "Failed to query site-packages from {}: {}",
+            python.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Unexpected site-packages output from {}", python.display()))
+}
+
+/// Prepend the interpreter's site-packages to `PYTHONPATH` so the embedded
+/// interpreter picks them up when it initializes.
+fn configure_python_env(python: &Path) -> Result<()> {
+    let mut paths = python_site_packages(python)?;
+    if let Ok(existing) = std::env::var("PYTHONPATH") {
+        if !existing.is_empty() {
+            paths.push(existing);
+        }
+    }
+    std::env::set_var("PYTHONPATH", paths.join(":"));
+    Ok(())
+}
 
 /// Find the Gmail Python module.
 ///
-/// Searches in order:
+/// A module named by `FGP_GMAIL_MODULE`, or else by the config file's
+/// `[python] module`, must exist. Otherwise searches in order:
 /// 1. Next to the binary: ./module/gmail.py
 /// 2. FGP services directory: ~/.fgp/services/gmail/module/gmail.py
 /// 3. Cargo manifest directory (development): ./module/gmail.py
-fn find_module_path() -> Result<PathBuf> {
-    // Check next to the binary
+///
+/// When none of them has it, the error lists every path that was checked.
+fn find_module_path(config: &Config) -> Result<PathBuf> {
+    let explicit = match std::env::var("FGP_GMAIL_MODULE") {
+        Ok(value) if !value.trim().is_empty() => {
+            Some((expand_tilde(value.trim()), "FGP_GMAIL_MODULE".to_string()))
+        }
+        _ => config.module.as_ref().map(|path| {
+            let source = config
+                .source
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            (path.clone(), format!("[python] module in {}", source))
+        }),
+    };
+    if let Some((path, origin)) = explicit {
+        if !path.exists() {
+            bail!(
+                "Gmail module not found: {} (from {})",
+                path.display(),
+                origin
+            );
+        }
+        return Ok(path);
+    }
+
+    let candidates = module_candidates();
+    if let Some(found) = candidates.iter().find(|path| path.exists()) {
+        return Ok(found.clone());
+    }
+
+    let searched: Vec<String> = candidates
+        .iter()
+        .map(|path| format!("  - {}", path.display()))
+        .collect();
+    tracing::debug!("Gmail module not found in:\n{}", searched.join("\n"));
+    bail!(
+        "Gmail module not found. Searched:\n{}\n\
+         Set FGP_GMAIL_MODULE or [python] module in the config file to its path.",
+        searched.join("\n")
+    )
+}
+
+/// Default locations of the module, in search order.
+fn module_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    // Next to the binary
     if let Ok(exe_path) = std::env::current_exe() {
         if let Some(exe_dir) = exe_path.parent() {
-            let module_path = exe_dir.join("module").join("gmail.py");
-            if module_path.exists() {
-                return Ok(module_path);
-            }
+            candidates.push(exe_dir.join("module").join("gmail.py"));
         }
     }
 
-    // Check FGP services directory
+    // FGP services directory
     if let Some(home) = dirs::home_dir() {
-        let module_path = home
-            .join(".fgp")
-            .join("services")
-            .join("gmail")
+        candidates.push(
+            home.join(".fgp")
+                .join("services")
+                .join("gmail")
+                .join("module")
+                .join("gmail.py"),
+        );
+    }
+
+    // Cargo manifest directory (development)
+    candidates.push(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("module")
-            .join("gmail.py");
-        if module_path.exists() {
-            return Ok(module_path);
-        }
+            .join("gmail.py"),
+    );
+    candidates
+}
+
+/// Load the Python module, pointing it at the configured interpreter if any.
+///
+/// Without one, the embedded interpreter's own environment is left alone:
+/// a `python3` on PATH may be a different minor version whose C extensions
+/// can't be loaded into the daemon.
+fn load_python(config: &Config) -> Result<PythonBackend> {
+    if let Some(python_path) = configured_python_path(config)? {
+        configure_python_env(&python_path)?;
+        std::env::set_var("FGP_GMAIL_PYTHON", &python_path);
+        println!("Python interpreter: {}", python_path.display());
     }
 
-    // Fallback to cargo manifest directory (development)
-    let cargo_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("module")
-        .join("gmail.py");
-    if cargo_path.exists() {
-        return Ok(cargo_path);
+    // Find and load the Python module
+    let module_path = find_module_path(config)?;
+    println!("Loading Python module: {}", module_path.display());
+
+    let module =
+        PythonModule::load(&module_path, "GmailModule").context("Failed to load GmailModule")?;
+    Ok(PythonBackend(module))
+}
+
+/// The native backend, when this binary was built with it.
+fn load_native(args: &Args) -> Result<Box<dyn Backend>> {
+    if args.backend == BackendKind::Replay || args.record {
+        bail!(
+            "--backend replay and --record need the Python module ([daemon] runtime = \"python\")"
+        );
     }
+    native_backend()
+}
+
+#[cfg(feature = "native-http")]
+fn native_backend() -> Result<Box<dyn Backend>> {
+    Ok(Box::new(native::NativeBackend::from_env()?))
+}
 
+#[cfg(not(feature = "native-http"))]
+fn native_backend() -> Result<Box<dyn Backend>> {
     bail!(
-        "Gmail module not found. Searched:\n\
-         - <exe_dir>/module/gmail.py\n\
-         - ~/.fgp/services/gmail/module/gmail.py\n\
-         - {}/module/gmail.py",
-        env!("CARGO_MANIFEST_DIR")
+        "This fgp-gmail was built without the native backend; \
+         rebuild it with `cargo build --release --features native-http`"
     )
 }
 
+/// Pick the backend `[daemon] runtime` asks for; see the `backend` module.
+fn load_backend(config: &Config, args: &Args) -> Result<Box<dyn Backend>> {
+    match config.runtime {
+        Runtime::Python => Ok(Box::new(load_python(config)?)),
+        Runtime::Native => load_native(args),
+        Runtime::Auto => match load_python(config) {
+            Ok(python) => Ok(Box::new(python)),
+            Err(e)
+                if cfg!(feature = "native-http")
+                    && args.backend == BackendKind::Api
+                    && !args.record =>
+            {
+                tracing::warn!(
+                    "Python module unavailable, serving read-only methods natively: {:#}",
+                    e
+                );
+                load_native(args)
+            }
+            Err(e) => Err(e),
+        },
+    }
+}
+
+/// Run one of the module's command-line utilities (`auth`, `check-auth`) in
+/// the configured Python interpreter and exit with its status. They read and
+/// write tokens where the daemon looks for them, so they work before the
+/// daemon has ever started.
+fn run_module_command(
+    python: &Path,
+    module_path: &Path,
+    subcommand: &str,
+    account: Option<&str>,
+    extra: &[&str],
+) -> Result<()> {
+    let mut command = Command::new(python);
+    command.arg(module_path).arg(subcommand);
+    if let Some(account) = account {
+        command.args(["--account", account]);
+    }
+    command.args(extra);
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {}", python.display()))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
+
 fn main() -> Result<()> {
+    // Load the config first; it sets the log filter
+    let config = Config::load()?;
+
     // Initialize tracing
     tracing_subscriber::fmt()
-        .with_env_filter("fgp_gmail=debug,fgp_daemon=debug")
+        .with_env_filter(config.log_filter.as_str())
         .init();
 
-    println!("Starting Gmail daemon (PyO3 warm connection)...");
-    println!();
+    let args = Args::parse(std::env::args().skip(1))?;
+    config.apply_env()?;
+    args.apply_env();
 
-    // Find and load the Python module
-    let module_path = find_module_path()?;
-    println!("Loading Python module: {}", module_path.display());
+    match &args.command {
+        CliCommand::Serve => {}
+        CliCommand::Auth { account, browser } => {
+            let extra: &[&str] = if *browser { &["--browser"] } else { &[] };
+            return run_module_command(
+                &resolve_python_path(&config)?,
+                &find_module_path(&config)?,
+                "auth",
+                account.as_deref(),
+                extra,
+            );
+        }
+        CliCommand::CheckAuth { account } => {
+            return run_module_command(
+                &resolve_python_path(&config)?,
+                &find_module_path(&config)?,
+                "check-auth",
+                account.as_deref(),
+                &[],
+            );
+        }
+    }
 
-    let module =
-        PythonModule::load(&module_path, "GmailModule").context("Failed to load GmailModule")?;
+    // Claim the pidfile before anything else can touch the socket
+    let socket = expand_tilde(&config.socket);
+    let pidfile = PidFile::acquire(&lifecycle::pidfile_path()?, &socket, args.force)?;
+
+    match &config.source {
+        Some(path) => println!("Config: {}", path.display()),
+        None => println!("Config: defaults (no config file)"),
+    }
+
+    println!("Starting Gmail daemon...");
+    println!();
+
+    let service = GmailService::new(load_backend(&config, &args)?);
 
-    println!("Gmail service initialized (warm connection ready)");
+    println!(
+        "Gmail service initialized ({} backend)",
+        service.backend_name()
+    );
     println!();
-    println!("Socket: ~/.fgp/services/gmail/daemon.sock");
+    println!("Socket: {}", config.socket);
     println!();
     println!("Test with:");
     println!("  fgp call gmail.inbox -p '{{\"limit\": 5}}'");
@@ -124,7 +490,12 @@ fn main() -> Result<()> {
     println!("  fgp call gmail.search -p '{{\"query\": \"is:unread\"}}'");
     println!();
 
-    let server = FgpServer::new(module, "~/.fgp/services/gmail/daemon.sock")?;
+    let server = FgpServer::new(service, config.socket.as_str())?;
+    lifecycle::handle_signals(
+        socket,
+        pidfile.path().to_path_buf(),
+        config.drain_timeout_secs,
+    )?;
     server.serve()?;
 
     Ok(())
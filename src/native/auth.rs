@@ -0,0 +1,303 @@
+//! Accounts and OAuth tokens for the native backend.
+//!
+//! The Python module caches each account's token as a pickle, which only
+//! Python can read, so whenever it saves one it also writes
+//! `gmail_token.json` next to it with the access token, its expiry, the
+//! refresh token, and the OAuth client (`token_uri`, `client_id`,
+//! `client_secret`). That file is what this module reads.
+//!
+//! An access token that has expired, or that Gmail rejects, is refreshed
+//! with the refresh token and the new one is kept in memory. The files stay
+//! the Python side's to write.
+//!
+//! Accounts are laid out as the Python module expects: the auth directory
+//! itself is the `default` account (failing that, the legacy
+//! `~/.wolfie-gateway/auth/google`), and each subdirectory holding
+//! credentials or a token is another. `FGP_GMAIL_DEFAULT_ACCOUNT` picks the account for calls that
+//! don't pass `account`.
+
+use super::dates::parse_utc;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Token file the Python module writes alongside `gmail_token.pickle`.
+pub const TOKEN_JSON_FILE: &str = "gmail_token.json";
+
+/// Files that make a directory an account. Keep in step with
+/// `ACCOUNT_FILES` in module/gmail_lib/accounts.py, so both backends find
+/// the same accounts.
+const ACCOUNT_FILES: &[&str] = &["credentials.json", "gmail_token.pickle", "gmail_token.json"];
+
+const DEFAULT_ACCOUNT: &str = "default";
+
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+/// Refresh this long before the recorded expiry, so a token doesn't lapse
+/// between the check and the call.
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// `gmail_token.json`, as written by the Python module.
+#[derive(Debug, Deserialize)]
+struct TokenFile {
+    token: Option<String>,
+    refresh_token: Option<String>,
+    token_uri: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    /// UTC, e.g. `2026-01-14T09:30:00Z`.
+    expiry: Option<String>,
+}
+
+/// Reply from the token endpoint.
+#[derive(Debug, Deserialize)]
+struct RefreshReply {
+    access_token: String,
+    expires_in: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct AccessToken {
+    value: String,
+    /// Seconds since the epoch; `None` when the file didn't say.
+    expires_at: Option<i64>,
+}
+
+impl AccessToken {
+    fn is_fresh(&self) -> bool {
+        self.expires_at
+            .is_none_or(|at| now_secs() + EXPIRY_MARGIN_SECS < at)
+    }
+}
+
+/// Access tokens by account name.
+pub struct Tokens {
+    auth_dir: PathBuf,
+    legacy_dir: Option<PathBuf>,
+    default_account: Option<String>,
+    cache: Mutex<HashMap<String, AccessToken>>,
+}
+
+impl Tokens {
+    pub fn new(
+        auth_dir: PathBuf,
+        legacy_dir: Option<PathBuf>,
+        default_account: Option<String>,
+    ) -> Self {
+        Tokens {
+            auth_dir,
+            legacy_dir,
+            default_account,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Account directories by name.
+    pub fn discover(&self) -> BTreeMap<String, PathBuf> {
+        let is_account = |dir: &Path| ACCOUNT_FILES.iter().any(|file| dir.join(file).exists());
+        let mut accounts = BTreeMap::new();
+        let mut top_level = std::iter::once(&self.auth_dir).chain(self.legacy_dir.as_ref());
+        if let Some(dir) = top_level.find(|dir| is_account(dir)) {
+            accounts.insert(DEFAULT_ACCOUNT.to_string(), dir.clone());
+        }
+        if let Ok(entries) = std::fs::read_dir(&self.auth_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if path.is_dir() && is_account(&path) && !accounts.contains_key(name) {
+                    accounts.insert(name.to_string(), path.clone());
+                }
+            }
+        }
+        accounts
+    }
+
+    /// Name of the account used when a call omits `account`.
+    pub fn default_name(&self) -> Option<String> {
+        if let Some(name) = &self.default_account {
+            return Some(name.clone());
+        }
+        let accounts = self.discover();
+        if accounts.contains_key(DEFAULT_ACCOUNT) {
+            return Some(DEFAULT_ACCOUNT.to_string());
+        }
+        match accounts.len() {
+            1 => accounts.into_keys().next(),
+            _ => None,
+        }
+    }
+
+    /// Name and directory of `account`, or of the default account.
+    pub fn resolve(&self, account: Option<&str>) -> Result<(String, PathBuf)> {
+        let accounts = self.discover();
+        let known = accounts.keys().cloned().collect::<Vec<_>>().join(", ");
+        let name = match account {
+            Some(name) => name.to_string(),
+            None => match self.default_name() {
+                Some(name) => name,
+                None if accounts.is_empty() => bail!(
+                    "No Gmail accounts configured. Place credentials.json in {} or {}/<account>/",
+                    self.auth_dir.display(),
+                    self.auth_dir.display()
+                ),
+                None => bail!(
+                    "Multiple accounts configured ({}); pass 'account' or set \
+                     FGP_GMAIL_DEFAULT_ACCOUNT",
+                    known
+                ),
+            },
+        };
+        match accounts.get(&name) {
+            Some(dir) => Ok((name, dir.clone())),
+            None => bail!(
+                "Unknown account: {:?}. Known accounts: {}",
+                name,
+                if known.is_empty() { "none" } else { &known }
+            ),
+        }
+    }
+
+    /// A usable access token for `account`, refreshing it if it has expired.
+    pub fn access_token(&self, agent: &ureq::Agent, account: &str, dir: &Path) -> Result<String> {
+        if let Some(token) = self.cached(account).filter(AccessToken::is_fresh) {
+            return Ok(token.value);
+        }
+        let file = read_token_file(account, dir)?;
+        let saved = file.token.clone().map(|value| AccessToken {
+            value,
+            expires_at: file.expiry.as_deref().and_then(parse_utc),
+        });
+        let token = match saved.filter(AccessToken::is_fresh) {
+            Some(token) => token,
+            None => refresh(agent, account, &file)?,
+        };
+        Ok(self.store(account, token))
+    }
+
+    /// Refresh `account`'s access token even if it looks fresh, for when
+    /// Gmail has rejected it.
+    pub fn refresh(&self, agent: &ureq::Agent, account: &str, dir: &Path) -> Result<String> {
+        let token = refresh(agent, account, &read_token_file(account, dir)?)?;
+        Ok(self.store(account, token))
+    }
+
+    // The cache is only locked to read or store an entry, never across a
+    // refresh, so a slow token endpoint holds up only the calls waiting on it
+
+    fn cached(&self, account: &str) -> Option<AccessToken> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.get(account).cloned()
+    }
+
+    fn store(&self, account: &str, token: AccessToken) -> String {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        let value = token.value.clone();
+        cache.insert(account.to_string(), token);
+        value
+    }
+}
+
+fn read_token_file(account: &str, dir: &Path) -> Result<TokenFile> {
+    let path = dir.join(TOKEN_JSON_FILE);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!(
+            "Account '{}' has no {} for the native backend. It's written whenever the \
+             Python module saves the token: sign in again with `fgp-gmail auth`{}, or \
+             run the daemon with Python once.",
+            account,
+            path.display(),
+            if account == DEFAULT_ACCOUNT {
+                String::new()
+            } else {
+                format!(" --account {}", account)
+            }
+        ),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    serde_json::from_str(&text).with_context(|| format!("Unreadable token file {}", path.display()))
+}
+
+fn refresh(agent: &ureq::Agent, account: &str, file: &TokenFile) -> Result<AccessToken> {
+    let (Some(refresh_token), Some(client_id), Some(client_secret)) = (
+        file.refresh_token.as_deref(),
+        file.client_id.as_deref(),
+        file.client_secret.as_deref(),
+    ) else {
+        bail!(
+            "Account '{}' needs to sign in: its token has expired and can't be refreshed. \
+             Run `fgp-gmail auth`",
+            account
+        );
+    };
+    let token_uri = file.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+    tracing::debug!("Refreshing the access token for account '{}'", account);
+    let reply: RefreshReply = match agent.post(token_uri).send_form(&[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+    ]) {
+        Ok(response) => response
+            .into_json()
+            .context("Unexpected reply from the token endpoint")?,
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            return Err(anyhow!(
+                "Account '{}' needs to sign in: token refresh was rejected ({} {}). \
+                 Run `fgp-gmail auth`",
+                account,
+                status,
+                body.trim()
+            ));
+        }
+        Err(e) => return Err(e).context("Token refresh failed"),
+    };
+    Ok(AccessToken {
+        value: reply.access_token,
+        expires_at: reply.expires_in.map(|secs| now_secs() + secs),
+    })
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_account_file_marks_an_account() {
+        let root = std::env::temp_dir().join(format!("fgp-gmail-accounts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let legacy = root.join("legacy");
+        for (dir, file) in [
+            ("auth/work", "credentials.json"),
+            ("auth/home", "gmail_token.pickle"),
+            ("auth/laptop", "gmail_token.json"),
+            ("legacy", "gmail_token.pickle"),
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(file), "{}").unwrap();
+        }
+        std::fs::create_dir_all(root.join("auth/scratch")).unwrap();
+
+        let tokens = Tokens::new(root.join("auth"), Some(legacy.clone()), None);
+        let accounts = tokens.discover();
+        assert_eq!(
+            accounts.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["default", "home", "laptop", "work"]
+        );
+        assert_eq!(accounts["default"], legacy);
+        assert_eq!(tokens.default_name().as_deref(), Some("default"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
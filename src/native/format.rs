@@ -0,0 +1,241 @@
+//! Turning Gmail API resources into the daemon's results.
+//!
+//! Field names match the Python module's, so a client doesn't need to know
+//! which backend answered, but the results are plainer: `date` is the UTC
+//! `internalDate` rather than the normalized Date header, thread bodies keep
+//! their quoted trails, and attachments and participants are left out.
+//! Confidential-mode and S/MIME encrypted messages are flagged the way the
+//! Python module's `restricted` module flags them, with their placeholder
+//! bodies and snippets dropped.
+
+use super::dates::rfc3339_utc;
+use base64::alphabet::URL_SAFE;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use serde_json::{json, Map, Value};
+
+/// Headers fetched for listings.
+pub const SUMMARY_HEADERS: &[&str] = &["From", "To", "Subject", "Date"];
+
+/// Listings carry this many characters of the snippet.
+const SNIPPET_CHARS: usize = 100;
+
+/// Header names (lowercase) whose presence marks a confidential-mode message.
+const CONFIDENTIAL_HEADERS: &[&str] = &[
+    "x-gm-confidential-mode",
+    "x-gmail-confidential-mode",
+    "x-gm-confidential",
+];
+
+/// MIME types of encrypted S/MIME envelopes; signed-only mail stays readable.
+const SMIME_MIME_TYPES: &[&str] = &["application/pkcs7-mime", "application/x-pkcs7-mime"];
+
+/// Gmail's base64url, which may or may not be padded.
+const BASE64URL: GeneralPurpose = GeneralPurpose::new(
+    &URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// A payload's headers by name; a repeated header keeps its last value.
+pub fn headers(payload: &Value) -> Map<String, Value> {
+    let mut headers = Map::new();
+    for header in payload["headers"].as_array().into_iter().flatten() {
+        if let (Some(name), Some(value)) = (header["name"].as_str(), header["value"].as_str()) {
+            headers.insert(name.to_string(), Value::from(value));
+        }
+    }
+    headers
+}
+
+fn header<'a>(headers: &'a Map<String, Value>, name: &str) -> &'a str {
+    headers.get(name).and_then(Value::as_str).unwrap_or("")
+}
+
+fn labels(msg: &Value) -> Vec<Value> {
+    msg["labelIds"].as_array().cloned().unwrap_or_default()
+}
+
+fn date(msg: &Value) -> String {
+    msg["internalDate"]
+        .as_str()
+        .and_then(|ms| ms.parse::<i64>().ok())
+        .map(rfc3339_utc)
+        .unwrap_or_default()
+}
+
+/// `"confidential_mode"` or `"smime_encrypted"` for a message whose content
+/// Gmail can't hand back, or None. Works on `metadata` messages too: S/MIME
+/// envelopes are the top-level MIME type.
+pub fn restriction(msg: &Value) -> Option<&'static str> {
+    let payload = &msg["payload"];
+    let confidential_header = payload["headers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|header| header["name"].as_str())
+        .any(|name| CONFIDENTIAL_HEADERS.contains(&name.to_ascii_lowercase().as_str()));
+    if confidential_header || labels(msg).iter().any(|label| label == "CONFIDENTIAL") {
+        return Some("confidential_mode");
+    }
+    let mut parts = vec![payload];
+    while let Some(part) = parts.pop() {
+        let mime_type = part["mimeType"].as_str().unwrap_or("").to_ascii_lowercase();
+        if SMIME_MIME_TYPES.contains(&mime_type.as_str()) {
+            return Some("smime_encrypted");
+        }
+        parts.extend(part["parts"].as_array().into_iter().flatten());
+    }
+    None
+}
+
+/// Why a restricted message's content is missing, worded as the Python
+/// module words it.
+pub fn restriction_note(restriction: Option<&str>) -> Option<&'static str> {
+    match restriction? {
+        "confidential_mode" => Some(
+            "Message was sent with Gmail confidential mode; its content is only \
+             viewable in the Gmail web or mobile client.",
+        ),
+        "smime_encrypted" => Some(
+            "Message is S/MIME encrypted; its content can't be read without the \
+             recipient's private key.",
+        ),
+        _ => None,
+    }
+}
+
+/// A `metadata` or `full` message as inbox, unread, and search list it.
+pub fn summary(msg: &Value) -> Value {
+    let headers = headers(&msg["payload"]);
+    let labels = labels(msg);
+    let restriction = restriction(msg);
+    // Placeholder content isn't worth summarizing
+    let snippet: String = match restriction {
+        Some(_) => String::new(),
+        None => msg["snippet"]
+            .as_str()
+            .unwrap_or("")
+            .chars()
+            .take(SNIPPET_CHARS)
+            .collect(),
+    };
+    let date_header = header(&headers, "Date");
+    let extra = if date_header.is_empty() {
+        json!({})
+    } else {
+        json!({ "date_header": date_header })
+    };
+    let mut result = json!({
+        "id": msg["id"],
+        "thread_id": msg["threadId"],
+        "from": header(&headers, "From"),
+        "to": header(&headers, "To"),
+        "subject": header(&headers, "Subject"),
+        "snippet": snippet,
+        "date": date(msg),
+        "unread": labels.iter().any(|label| label == "UNREAD"),
+        "labels": labels,
+        "content_restricted": restriction,
+        "extra": extra,
+    });
+    if let (Some(note), Some(fields)) = (restriction_note(restriction), result.as_object_mut()) {
+        fields.insert("content_note".to_string(), Value::from(note));
+    }
+    result
+}
+
+/// A message of a `full` thread: its summary plus `cc`, the plain-text
+/// body, and `internal_date`.
+pub fn thread_message(msg: &Value) -> Value {
+    let mut result = summary(msg);
+    let headers = headers(&msg["payload"]);
+    let body_text = match restriction(msg) {
+        Some(_) => None,
+        None => bodies(&msg["payload"]).0,
+    };
+    if let Some(fields) = result.as_object_mut() {
+        fields.insert("cc".to_string(), Value::from(header(&headers, "Cc")));
+        fields.insert(
+            "body".to_string(),
+            body_text.map_or(Value::Null, Value::from),
+        );
+        let internal_date = msg["internalDate"]
+            .as_str()
+            .and_then(|ms| ms.parse::<i64>().ok());
+        fields.insert(
+            "internal_date".to_string(),
+            internal_date.map_or(Value::Null, Value::from),
+        );
+    }
+    result
+}
+
+/// The first `text/plain` and `text/html` parts that aren't attachments.
+pub fn bodies(payload: &Value) -> (Option<String>, Option<String>) {
+    let mut text = None;
+    let mut html = None;
+    let mut parts = vec![payload];
+    while let Some(part) = parts.pop() {
+        let is_attachment = part["filename"]
+            .as_str()
+            .is_some_and(|name| !name.is_empty());
+        let decoded = || {
+            let data = part["body"]["data"].as_str()?;
+            let bytes = BASE64URL.decode(data).ok()?;
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        };
+        match part["mimeType"].as_str() {
+            Some("text/plain") if text.is_none() && !is_attachment => text = decoded(),
+            Some("text/html") if html.is_none() && !is_attachment => html = decoded(),
+            _ => {}
+        }
+        // Reversed so parts are visited in document order
+        if let Some(children) = part["parts"].as_array() {
+            parts.extend(children.iter().rev());
+        }
+    }
+    (text, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restriction_detection() {
+        let confidential_header = json!({"payload": {"headers": [
+            {"name": "X-Gm-Confidential-Mode", "value": "1"},
+        ]}});
+        let confidential_label = json!({"labelIds": ["SENT", "CONFIDENTIAL"], "payload": {}});
+        let encrypted = json!({"payload": {"mimeType": "multipart/mixed", "parts": [
+            {"mimeType": "text/plain"},
+            {"mimeType": "Application/PKCS7-MIME"},
+        ]}});
+        let signed = json!({"payload": {"mimeType": "multipart/signed", "parts": [
+            {"mimeType": "text/plain"},
+            {"mimeType": "application/pkcs7-signature"},
+        ]}});
+        assert_eq!(restriction(&confidential_header), Some("confidential_mode"));
+        assert_eq!(restriction(&confidential_label), Some("confidential_mode"));
+        assert_eq!(restriction(&encrypted), Some("smime_encrypted"));
+        assert_eq!(restriction(&signed), None);
+    }
+
+    #[test]
+    fn restricted_summary_drops_the_snippet() {
+        let msg = json!({
+            "id": "m1",
+            "snippet": "View this message in Gmail",
+            "labelIds": ["INBOX", "CONFIDENTIAL"],
+            "payload": {"headers": []},
+        });
+        let summary = summary(&msg);
+        assert_eq!(summary["content_restricted"], "confidential_mode");
+        assert_eq!(summary["snippet"], "");
+        assert_eq!(
+            summary["content_note"].as_str(),
+            restriction_note(Some("confidential_mode"))
+        );
+    }
+}
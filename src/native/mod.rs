@@ -0,0 +1,588 @@
+//! Native backend: the read-only methods over Gmail's REST API.
+//!
+//! Built with `--features native-http`. It serves `gmail.inbox`,
+//! `gmail.unread`, `gmail.search`, `gmail.thread`, and `gmail.message` with
+//! a blocking HTTP client and the tokens the Python module caches (see
+//! `auth`), so it runs without Python. Every other method, including all
+//! writes, fails with "not supported by the native backend".
+//!
+//! Only the params in `method_list` are accepted. The Python module's extras
+//! (`include_attachments`, `group_by`, structured search params, ...) are
+//! rejected rather than silently ignored; `fresh` is accepted, as nothing is
+//! cached. Results use the Python module's field names; `format` describes
+//! what they leave out.
+//!
+//! `FGP_GMAIL_NATIVE_API_URL` replaces the Gmail API base URL, for tests.
+
+mod auth;
+mod dates;
+mod format;
+
+use crate::backend::Backend;
+use anyhow::{anyhow, bail, Context, Result};
+use auth::{Tokens, TOKEN_JSON_FILE};
+use fgp_daemon::service::{HealthStatus, MethodInfo, ParamInfo};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_API_URL: &str = "https://gmail.googleapis.com/gmail/v1/users/me";
+
+/// Same defaults as the Python module's `limit` params.
+const DEFAULT_LIMIT: u64 = 10;
+const DEFAULT_MAX_LIMIT: u64 = 100;
+
+/// Same default as the Python module's `max_response_bytes`.
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Per-request timeout for the API and the token endpoint.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Params every method takes.
+const COMMON_PARAMS: &[&str] = &["account", "fresh"];
+
+/// The read-only methods, served over HTTP.
+pub struct NativeBackend {
+    agent: ureq::Agent,
+    api_url: String,
+    tokens: Tokens,
+    max_limit: u64,
+    /// Decoded body bytes one call may return; 0 means no limit.
+    max_response_bytes: u64,
+    in_flight: Mutex<usize>,
+    idle: Condvar,
+    draining: AtomicBool,
+}
+
+/// Counts a call as running until dropped.
+struct Call<'a>(&'a NativeBackend);
+
+impl Drop for Call<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.0.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        *in_flight -= 1;
+        self.0.idle.notify_all();
+    }
+}
+
+impl NativeBackend {
+    /// Configured from the same `FGP_GMAIL_*` variables the Python module
+    /// reads (`FGP_GMAIL_DEFAULT_ACCOUNT`, `FGP_GMAIL_MAX_LIMIT`,
+    /// `FGP_GMAIL_MAX_RESPONSE_BYTES`).
+    pub fn from_env() -> Result<Self> {
+        let home = dirs::home_dir().context("Could not determine home directory")?;
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let max_limit = match env("FGP_GMAIL_MAX_LIMIT") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .with_context(|| format!("FGP_GMAIL_MAX_LIMIT must be an integer: {}", value))?,
+            None => DEFAULT_MAX_LIMIT,
+        };
+        let max_response_bytes = match env("FGP_GMAIL_MAX_RESPONSE_BYTES") {
+            Some(value) => value.trim().parse::<u64>().with_context(|| {
+                format!("FGP_GMAIL_MAX_RESPONSE_BYTES must be an integer: {}", value)
+            })?,
+            None => DEFAULT_MAX_RESPONSE_BYTES,
+        };
+        Ok(NativeBackend {
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            api_url: env("FGP_GMAIL_NATIVE_API_URL")
+                .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            tokens: Tokens::new(
+                home.join(".fgp").join("auth").join("google"),
+                Some(home.join(".wolfie-gateway").join("auth").join("google")),
+                env("FGP_GMAIL_DEFAULT_ACCOUNT"),
+            ),
+            max_limit,
+            max_response_bytes,
+            in_flight: Mutex::new(0),
+            idle: Condvar::new(),
+            draining: AtomicBool::new(false),
+        })
+    }
+
+    /// GET `path` under the API URL as `account`. A rejected access token
+    /// is refreshed and the request retried once.
+    fn get(&self, account: Option<&str>, path: &str, query: &[(&str, &str)]) -> Result<Value> {
+        let (name, dir) = self.tokens.resolve(account)?;
+        let token = self.tokens.access_token(&self.agent, &name, &dir)?;
+        let response = match self.request(path, query, &token) {
+            Err(ureq::Error::Status(401, _)) => {
+                let token = self.tokens.refresh(&self.agent, &name, &dir)?;
+                self.request(path, query, &token)
+            }
+            response => response,
+        };
+        match response {
+            Ok(response) => response
+                .into_json()
+                .with_context(|| format!("Unexpected response from Gmail for {}", path)),
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Value = response.into_json().unwrap_or_default();
+                let message = body["error"]["message"].as_str().unwrap_or("no details");
+                Err(anyhow!(
+                    "Gmail API error {} for {}: {}",
+                    status,
+                    path,
+                    message
+                ))
+            }
+            Err(e) => Err(e).with_context(|| format!("Gmail API request for {} failed", path)),
+        }
+    }
+
+    fn request(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        token: &str,
+    ) -> std::result::Result<ureq::Response, ureq::Error> {
+        let mut request = self
+            .agent
+            .get(&format!("{}/{}", self.api_url, path))
+            .set("Authorization", &format!("Bearer {}", token));
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+        request.call()
+    }
+
+    /// Summaries of listed messages, one metadata fetch each.
+    fn summaries(&self, account: Option<&str>, listed: &Value) -> Result<Vec<Value>> {
+        let mut query = vec![("format", "metadata")];
+        query.extend(
+            format::SUMMARY_HEADERS
+                .iter()
+                .map(|h| ("metadataHeaders", *h)),
+        );
+        listed["messages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|msg| {
+                let id = msg["id"].as_str().context("Listed message has no id")?;
+                let detail = self.get(account, &format!("messages/{}", id), &query)?;
+                Ok(format::summary(&detail))
+            })
+            .collect()
+    }
+
+    fn inbox(&self, params: &Params) -> Result<Value> {
+        params.only(&["limit", "page_token"])?;
+        let limit = params.limit(self.max_limit)?.to_string();
+        let mut query = vec![("labelIds", "INBOX"), ("maxResults", limit.as_str())];
+        query.extend(params.string("page_token")?.map(|t| ("pageToken", t)));
+        let listed = self.get(params.account()?, "messages", &query)?;
+        let emails = self.summaries(params.account()?, &listed)?;
+        Ok(json!({
+            "count": emails.len(),
+            "emails": emails,
+            "next_page_token": listed["nextPageToken"],
+        }))
+    }
+
+    fn unread(&self, params: &Params) -> Result<Value> {
+        params.only(&["limit"])?;
+        let limit = params.limit(self.max_limit)?.to_string();
+        let account = params.account()?;
+        let label = self.get(account, "labels/UNREAD", &[])?;
+        let query = [
+            ("labelIds", "INBOX"),
+            ("labelIds", "UNREAD"),
+            ("maxResults", limit.as_str()),
+        ];
+        let listed = self.get(account, "messages", &query)?;
+        let emails = self.summaries(account, &listed)?;
+        Ok(json!({
+            "unread_count": label["messagesUnread"].as_u64().unwrap_or(0),
+            "count": emails.len(),
+            "emails": emails,
+        }))
+    }
+
+    fn search(&self, params: &Params) -> Result<Value> {
+        params.only(&["query", "limit", "page_token"])?;
+        let Some(q) = params.string("query")?.filter(|q| !q.trim().is_empty()) else {
+            bail!("query parameter is required");
+        };
+        let limit = params.limit(self.max_limit)?.to_string();
+        let mut query = vec![("q", q), ("maxResults", limit.as_str())];
+        query.extend(params.string("page_token")?.map(|t| ("pageToken", t)));
+        let listed = self.get(params.account()?, "messages", &query)?;
+        let emails = self.summaries(params.account()?, &listed)?;
+        Ok(json!({
+            "query": q,
+            "count": emails.len(),
+            "emails": emails,
+            "next_page_token": listed["nextPageToken"],
+        }))
+    }
+
+    fn thread(&self, params: &Params) -> Result<Value> {
+        params.only(&["thread_id", "include_bodies"])?;
+        let thread_id = params.id("thread_id")?;
+        let include_bodies = params.boolean("include_bodies", true)?;
+        let mut query = vec![("format", if include_bodies { "full" } else { "metadata" })];
+        if !include_bodies {
+            query.extend(
+                format::SUMMARY_HEADERS
+                    .iter()
+                    .map(|h| ("metadataHeaders", *h)),
+            );
+            query.push(("metadataHeaders", "Cc"));
+        }
+        let thread = self.get(params.account()?, &format!("threads/{}", thread_id), &query)?;
+
+        // Bodies are counted as they're decoded, so a thread of huge
+        // messages stops at the one that passes the limit
+        let mut used = 0;
+        let mut messages = Vec::new();
+        for msg in thread["messages"].as_array().into_iter().flatten() {
+            let message = format::thread_message(msg);
+            self.charge(
+                "gmail.thread",
+                &mut used,
+                &[message["body"].as_str()],
+                "include_bodies false",
+            )?;
+            messages.push(message);
+        }
+        // Oldest first, ties broken by id, as the Python module orders them
+        messages.sort_by(|a, b| {
+            let key = |m: &Value| {
+                (
+                    m["internal_date"].as_i64(),
+                    m["id"].as_str().map(String::from),
+                )
+            };
+            key(a).cmp(&key(b))
+        });
+        Ok(json!({
+            "thread_id": thread["id"],
+            "count": messages.len(),
+            "total_count": messages.len(),
+            "truncated": false,
+            "messages": messages,
+        }))
+    }
+
+    fn message(&self, params: &Params) -> Result<Value> {
+        params.only(&["message_id", "format"])?;
+        let message_id = params.id("message_id")?;
+        let fmt = params.string("format")?.unwrap_or("full");
+        if fmt != "full" && fmt != "metadata" {
+            bail!(
+                "format must be full or metadata with the native backend (got {:?})",
+                fmt
+            );
+        }
+        let msg = self.get(
+            params.account()?,
+            &format!("messages/{}", message_id),
+            &[("format", fmt)],
+        )?;
+        // Restricted messages only carry placeholder content
+        let restriction = format::restriction(&msg);
+        let (body_text, body_html) = match (fmt, restriction) {
+            ("full", None) => format::bodies(&msg["payload"]),
+            _ => (None, None),
+        };
+        self.charge(
+            "gmail.message",
+            &mut 0,
+            &[body_text.as_deref(), body_html.as_deref()],
+            "format metadata",
+        )?;
+        let snippet = match restriction {
+            Some(_) => "",
+            None => msg["snippet"].as_str().unwrap_or(""),
+        };
+        Ok(json!({
+            "id": msg["id"],
+            "thread_id": msg["threadId"],
+            "format": fmt,
+            "headers": format::headers(&msg["payload"]),
+            "snippet": snippet,
+            "labels": msg["labelIds"].as_array().cloned().unwrap_or_default(),
+            "body_text": body_text,
+            "body_html": body_html,
+            "attachments": null,
+            "content_restricted": restriction,
+            "content_note": format::restriction_note(restriction),
+        }))
+    }
+
+    /// Add decoded `bodies` to the `used` bytes of a `method` call, failing
+    /// past `max_response_bytes` as the Python module does (`advice` says
+    /// how to ask for less).
+    fn charge(
+        &self,
+        method: &str,
+        used: &mut u64,
+        bodies: &[Option<&str>],
+        advice: &str,
+    ) -> Result<()> {
+        *used += bodies
+            .iter()
+            .flatten()
+            .map(|body| body.len() as u64)
+            .sum::<u64>();
+        if self.max_response_bytes > 0 && *used > self.max_response_bytes {
+            bail!(
+                "{} response exceeded max_response_bytes ({}); ask for less ({})",
+                method,
+                self.max_response_bytes,
+                advice
+            );
+        }
+        Ok(())
+    }
+
+    /// Stop taking calls and wait for running ones, as `gmail.drain` does
+    /// for the Python module.
+    fn drain(&self, params: &Params) -> Result<Value> {
+        let timeout = match params.get("timeout_secs") {
+            None => crate::lifecycle::DEFAULT_DRAIN_TIMEOUT_SECS,
+            Some(value) => value
+                .as_f64()
+                .filter(|secs| *secs >= 0.0 && !value.is_boolean())
+                .context("timeout_secs must be a non-negative number")?,
+        };
+        self.draining.store(true, Ordering::SeqCst);
+        let started = Instant::now();
+        let in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        let (in_flight, _) = self
+            .idle
+            .wait_timeout_while(in_flight, Duration::from_secs_f64(timeout), |n| *n > 0)
+            .unwrap_or_else(|e| e.into_inner());
+        Ok(json!({
+            "drained": *in_flight == 0,
+            "abandoned": *in_flight,
+            "waited_secs": started.elapsed().as_secs_f64(),
+        }))
+    }
+}
+
+impl Backend for NativeBackend {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        let params = Params(params);
+        if method == "gmail.drain" {
+            return self.drain(&params);
+        }
+        if self.draining.load(Ordering::SeqCst) {
+            bail!("Daemon is shutting down");
+        }
+        *self.in_flight.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        let _call = Call(self);
+        match method {
+            "gmail.inbox" => self.inbox(&params),
+            "gmail.unread" => self.unread(&params),
+            "gmail.search" => self.search(&params),
+            "gmail.thread" => self.thread(&params),
+            "gmail.message" => self.message(&params),
+            _ => bail!(
+                "{} is not supported by the native backend, which only serves \
+                 gmail.inbox, gmail.unread, gmail.search, gmail.thread, and gmail.message. \
+                 It needs the Python module (see `[daemon] runtime`).",
+                method
+            ),
+        }
+    }
+
+    fn method_list(&self) -> Vec<MethodInfo> {
+        let limit = param("limit", "integer", false);
+        let page_token = param("page_token", "string", false);
+        let common = || {
+            vec![
+                param("fresh", "boolean", false),
+                param("account", "string", false),
+            ]
+        };
+        let with_common = |mut params: Vec<ParamInfo>| {
+            params.extend(common());
+            params
+        };
+        vec![
+            method(
+                "gmail.inbox",
+                "List recent inbox emails",
+                with_common(vec![limit.clone(), page_token.clone()]),
+            ),
+            method(
+                "gmail.unread",
+                "Get unread email count and summaries",
+                with_common(vec![limit.clone()]),
+            ),
+            method(
+                "gmail.search",
+                "Search emails by Gmail query",
+                with_common(vec![param("query", "string", true), limit, page_token]),
+            ),
+            method(
+                "gmail.thread",
+                "Get a thread's messages with their bodies",
+                with_common(vec![
+                    param("thread_id", "string", true),
+                    param("include_bodies", "boolean", false),
+                ]),
+            ),
+            method(
+                "gmail.message",
+                "Get a single message by ID with headers and decoded body",
+                with_common(vec![
+                    param("message_id", "string", true),
+                    param("format", "string", false),
+                ]),
+            ),
+            method(
+                "gmail.drain",
+                "Stop taking calls and wait for running ones",
+                vec![param("timeout_secs", "number", false)],
+            ),
+        ]
+    }
+
+    /// Like the Python module, fails startup when the default account has
+    /// no usable token rather than failing the first call.
+    fn on_start(&self) -> Result<()> {
+        if self.tokens.default_name().is_none() && !self.tokens.discover().is_empty() {
+            return Ok(());
+        }
+        let (name, dir) = self.tokens.resolve(None)?;
+        self.tokens.access_token(&self.agent, &name, &dir)?;
+        tracing::info!("Native backend ready for account '{}'", name);
+        Ok(())
+    }
+
+    fn on_stop(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn health_check(&self) -> HashMap<String, HealthStatus> {
+        let status = match self.tokens.resolve(None) {
+            Ok((name, dir)) if dir.join(TOKEN_JSON_FILE).exists() => HealthStatus {
+                ok: true,
+                latency_ms: None,
+                message: Some(format!(
+                    "Read-only native backend (default account '{}')",
+                    name
+                )),
+            },
+            Ok((name, dir)) => HealthStatus {
+                ok: false,
+                latency_ms: None,
+                message: Some(format!(
+                    "Account '{}' has no {}",
+                    name,
+                    dir.join(TOKEN_JSON_FILE).display()
+                )),
+            },
+            Err(e) => HealthStatus {
+                ok: false,
+                latency_ms: None,
+                message: Some(format!("{:#}", e)),
+            },
+        };
+        HashMap::from([("gmail_service".to_string(), status)])
+    }
+}
+
+fn method(name: &str, description: &str, params: Vec<ParamInfo>) -> MethodInfo {
+    MethodInfo {
+        name: name.to_string(),
+        description: description.to_string(),
+        params,
+    }
+}
+
+fn param(name: &str, kind: &str, required: bool) -> ParamInfo {
+    ParamInfo {
+        name: name.to_string(),
+        param_type: kind.to_string(),
+        required,
+        default: None,
+    }
+}
+
+/// A call's params, validated as the Python module validates them.
+struct Params(HashMap<String, Value>);
+
+impl Params {
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name).filter(|value| !value.is_null())
+    }
+
+    /// Reject params this backend doesn't implement.
+    fn only(&self, supported: &[&str]) -> Result<()> {
+        let mut unsupported: Vec<&str> = self
+            .0
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !supported.contains(name) && !COMMON_PARAMS.contains(name))
+            .collect();
+        unsupported.sort_unstable();
+        if !unsupported.is_empty() {
+            bail!(
+                "Not supported by the native backend: {}",
+                unsupported.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    fn string(&self, name: &str) -> Result<Option<&str>> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_str()
+                .map(Some)
+                .with_context(|| format!("{} must be a string", name)),
+        }
+    }
+
+    fn account(&self) -> Result<Option<&str>> {
+        self.string("account")
+    }
+
+    fn boolean(&self, name: &str, default: bool) -> Result<bool> {
+        match self.get(name) {
+            None => Ok(default),
+            Some(value) => value
+                .as_bool()
+                .with_context(|| format!("{} must be a boolean", name)),
+        }
+    }
+
+    /// `limit`, defaulting to 10 and clamped to `max_limit`.
+    fn limit(&self, max_limit: u64) -> Result<u64> {
+        let limit = match self.get("limit") {
+            None => DEFAULT_LIMIT,
+            Some(value) => value
+                .as_u64()
+                .filter(|n| *n > 0)
+                .context("limit must be a positive integer")?,
+        };
+        if limit > max_limit {
+            tracing::warn!("Clamping limit {} to max {}", limit, max_limit);
+        }
+        Ok(limit.min(max_limit))
+    }
+
+    /// A Gmail message or thread id, which goes into the request path.
+    fn id(&self, name: &str) -> Result<&str> {
+        match self.string(name)? {
+            Some(id) if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()) => Ok(id),
+            Some(id) => bail!("{} is not a Gmail id: {:?}", name, id),
+            None => bail!("{} parameter is required", name),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+"""
+Opt-in end-to-end suite against a real, throwaway Gmail account.
+
+Runs only with `FGP_GMAIL_E2E=1` and a cached token for the account
+(`FGP_GMAIL_E2E_ACCOUNT`, default: the default account). Run it on its own;
+it imports the real Google client libraries, which the unit suite stubs:
+
+    FGP_GMAIL_E2E=1 python3 -m unittest discover -s tests/e2e -v
+
+It mails itself, exercises the main read/write flows, and checks responses
+against golden/schemas.json. Everything it creates (messages, labels,
+filters, drafts) is registered for teardown as soon as it exists, so it is
+removed even when a step fails.
+"""
+
+import base64
+import json
+import os
+import sys
+import time
+import unittest
+import uuid
+from pathlib import Path
+
+MODULE_DIR = Path(__file__).resolve().parents[2] / "module"
+GOLDEN = json.loads((Path(__file__).resolve().parent / "golden" / "schemas.json").read_text())
+
+# Gmail indexes new mail and label changes asynchronously
+ARRIVAL_DEADLINE_SECS = 120
+SETTLE_DEADLINE_SECS = 60
+POLL_INTERVAL_SECS = 3
+
+
+def _skip_reason():
+    if os.environ.get("FGP_GMAIL_E2E") != "1":
+        return "set FGP_GMAIL_E2E=1 to run against a real Gmail account"
+    sys.path.insert(0, str(MODULE_DIR))
+    try:
+        from gmail_lib.accounts import AccountRegistry
+        import gmail  # noqa: F401
+    except ImportError as e:
+        return f"Google client libraries not importable: {e}"
+    registry = AccountRegistry(gmail.FGP_AUTH_DIR, gmail.LEGACY_AUTH_DIR,
+                               os.environ.get("FGP_GMAIL_DEFAULT_ACCOUNT") or None)
+    try:
+        account = registry.resolve(os.environ.get("FGP_GMAIL_E2E_ACCOUNT") or None)
+    except ValueError as e:
+        return f"no Gmail credentials: {e}"
+    if not account.token_file.exists():
+        return f"no cached token for account '{account.name}' ({account.token_file})"
+    return None
+
+
+SKIP_REASON = _skip_reason()
+
+
+def eventually(check, deadline_secs=SETTLE_DEADLINE_SECS, what="condition"):
+    """Retry `check()` until it returns a truthy value or the deadline passes."""
+    deadline = time.monotonic() + deadline_secs
+    last = None
+    while True:
+        try:
+            last = check()
+            if last:
+                return last
+        except AssertionError as e:
+            last = e
+        if time.monotonic() >= deadline:
+            raise AssertionError(f"Timed out after {deadline_secs}s waiting for {what} (last: {last!r})")
+        time.sleep(POLL_INTERVAL_SECS)
+
+
+class Teardown:
+    """Cleanup actions run in reverse order; failures are reported, not raised."""
+
+    def __init__(self):
+        self.actions = []
+
+    def add(self, description, action):
+        self.actions.append((description, action))
+
+    def run(self):
+        failures = []
+        while self.actions:
+            description, action = self.actions.pop()
+            try:
+                action()
+            except Exception as e:
+                failures.append(f"{description}: {e}")
+        if failures:
+            print("\nE2E cleanup failures:\n  " + "\n  ".join(failures), file=sys.stderr)
+
+
+@unittest.skipIf(SKIP_REASON, SKIP_REASON or "")
+class LiveGmailTest(unittest.TestCase):
+    """One ordered scenario; each step builds on the previous one."""
+
+    @classmethod
+    def setUpClass(cls):
+        import gmail
+
+        cls.account = os.environ.get("FGP_GMAIL_E2E_ACCOUNT") or None
+        cls.module = gmail.GmailModule()
+        cls.teardown = Teardown()
+        cls.addClassCleanup(cls.teardown.run)
+        cls.token = f"fgp-e2e-{uuid.uuid4().hex[:12]}"
+        cls.query = f'subject:"{cls.token}"'
+        cls.address = cls.api("getProfile")["emailAddress"]
+        cls.state = {}
+
+    @classmethod
+    def call(cls, method, **params):
+        if cls.account:
+            params["account"] = cls.account
+        if method in ("gmail.search", "gmail.thread"):
+            # Cached reads would hide the changes the scenario is waiting for
+            params.setdefault("fresh", True)
+        return cls.module.dispatch(method, params)
+
+    @classmethod
+    def api(cls, name, **params):
+        account = cls.module.accounts.resolve(cls.account)
+        return cls.module._api_for(account)(name, **params)
+
+    def assertSchema(self, name, value):
+        missing = set(GOLDEN[name]) - set(value)
+        self.assertFalse(missing, f"{name} response is missing keys: {sorted(missing)}")
+
+    def test_1_send_and_arrive(self):
+        attachment = base64.b64encode(b"fgp e2e attachment\n").decode()
+        sent = self.call("gmail.send", to=self.address, subject=self.token, body="End-to-end check",
+                         attachments=[{"filename": "e2e.txt", "data": attachment}])
+        self.teardown.add("trash test messages", lambda: self.call(
+            "gmail.bulk_modify", query=f"{self.query} in:anywhere", action="trash"))
+        self.assertSchema("send", sent)
+        self.state["thread_id"] = sent["thread_id"]
+
+        found = eventually(
+            lambda: [e for e in self.call("gmail.search", query=f"{self.query} in:inbox")["emails"]],
+            ARRIVAL_DEADLINE_SECS, "the message to arrive")
+        self.state["message_id"] = found[0]["id"]
+
+    def test_2_read_and_schemas(self):
+        from gmail_lib.types import EmailSummary, Thread
+
+        search = self.call("gmail.search", query=self.query)
+        self.assertSchema("search", search)
+        for email in search["emails"]:
+            self.assertSchema("email_summary", email)
+            EmailSummary.from_dict(email)
+
+        thread = self.call("gmail.thread", thread_id=self.state["thread_id"])
+        self.assertSchema("thread", thread)
+        for message in thread["messages"]:
+            self.assertSchema("thread_message", message)
+        Thread.from_dict(thread)
+
+        read = self.call("gmail.read", message_id=self.state["message_id"])
+        self.assertSchema("read", read)
+        self.assertEqual(read["subject"], self.token)
+        attachments = read["attachments"] or []
+        self.assertEqual([a["filename"] for a in attachments], ["e2e.txt"])
+        self.assertSchema("attachment", attachments[0])
+
+        data = self.call("gmail.get_attachment", message_id=self.state["message_id"],
+                         attachment_id=attachments[0]["id"])
+        self.assertEqual(base64.b64decode(data["data"]), b"fgp e2e attachment\n")
+
+    def test_3_labels_and_filters(self):
+        label = f"{self.token}-label"
+        created = self.call("gmail.filter_create", subject=self.token, add_label=label,
+                            create_label_if_missing=True)
+        self.teardown.add("delete filter", lambda: self.call("gmail.filter_delete", filter_id=created["filter_id"]))
+        label_id = created["action"]["addLabelIds"][0]
+        self.teardown.add("delete label", lambda: self.api("labels.delete", id=label_id))
+        self.assertEqual(created["created_labels"], [label])
+
+        listed = self.call("gmail.filters")["filters"]
+        self.assertIn(created["filter_id"], [f["id"] for f in listed])
+
+        applied = self.call("gmail.bulk_modify", query=self.query, action="add_label", label=label)
+        self.assertSchema("bulk_modify", applied)
+        self.assertGreaterEqual(applied["succeeded"], 1)
+        eventually(lambda: self.call("gmail.search", query=f"{self.query} label:{label}")["count"],
+                   what="the label to apply")
+
+    def test_4_archive(self):
+        archived = self.call("gmail.bulk_modify", query=f"{self.query} in:inbox", action="archive")
+        self.assertFalse(archived["stopped_early"])
+        eventually(lambda: self.call("gmail.search", query=f"{self.query} in:inbox")["count"] == 0,
+                   what="the message to leave the inbox")
+
+    def test_5_drafts(self):
+        draft = self.call("gmail.create_draft", to=self.address, subject=f"{self.token} draft",
+                          body="Draft for review")
+        self.teardown.add("delete draft", lambda: self.api("drafts.delete", id=draft["draft_id"]))
+        self.assertSchema("draft", draft)
+        eventually(lambda: draft["draft_id"] in [d["draft_id"] for d in self.call("gmail.list_drafts", limit=50)["drafts"]],
+                   what="the draft to be listed")
+
+    def test_6_trash(self):
+        trashed = self.call("gmail.bulk_modify", query=self.query, action="trash")
+        self.assertGreaterEqual(trashed["succeeded"], 1)
+        eventually(lambda: self.call("gmail.search", query=f"{self.query} in:trash")["count"],
+                   what="the message to reach the trash")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,35 @@
+"""
+GmailModule over a slow, fake Gmail API, for tests that run the daemon.
+
+`tests/shutdown.rs` points `FGP_GMAIL_MODULE` here. Every `messages.list`
+takes `FGP_GMAIL_TEST_DELAY` seconds (default 2), so a `gmail.inbox` call
+is still running when the test signals the daemon. The Google client
+libraries are stubbed as in the unit suite.
+"""
+
+import os
+import sys
+import time
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).resolve().parents[1]))
+
+from helpers import REPO_ROOT, FakeGmailService, load_gmail_module  # noqa: E402
+
+gmail = load_gmail_module()
+
+from gmail_lib.accounts import AccountRegistry  # noqa: E402
+from gmail_lib.backend import ApiBackend  # noqa: E402
+
+
+def slow_list(**kwargs):
+    time.sleep(float(os.environ.get("FGP_GMAIL_TEST_DELAY") or 2))
+    return {"messages": []}
+
+
+class GmailModule(gmail.GmailModule):
+    def __init__(self):
+        super().__init__(
+            backend=ApiBackend(FakeGmailService({"messages.list": slow_list})),
+            accounts=AccountRegistry(REPO_ROOT / "tests" / "no-such-auth-dir"),
+        )
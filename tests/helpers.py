@@ -0,0 +1,124 @@
+"""
+Shared helpers for the Python test suite.
+
+Run from the repo root with:
+
+    python3 -m unittest discover -s tests
+
+The Google client libraries are stubbed out so the suite runs without
+credentials or network access.
+"""
+
+import json
+import sys
+import types
+from pathlib import Path
+
+REPO_ROOT = Path(__file__).resolve().parent.parent
+MODULE_DIR = REPO_ROOT / "module"
+FIXTURES_DIR = Path(__file__).resolve().parent / "fixtures"
+
+if str(MODULE_DIR) not in sys.path:
+    sys.path.insert(0, str(MODULE_DIR))
+
+
+def load_fixture(name: str):
+    """Load a JSON fixture from tests/fixtures/."""
+    with open(FIXTURES_DIR / name) as f:
+        return json.load(f)
+
+
+def _stub(name: str, **attrs):
+    module = sys.modules.get(name) or types.ModuleType(name)
+    for key, value in attrs.items():
+        setattr(module, key, value)
+    sys.modules[name] = module
+    return module
+
+
+def stub_google_modules():
+    """Install minimal stand-ins for the Google client libraries."""
+    class _Placeholder:
+        def __init__(self, *args, **kwargs):
+            pass
+
+    _stub("google")
+    _stub("google.auth")
+    _stub("google.auth.transport")
+    _stub("google.auth.exceptions", RefreshError=type("RefreshError", (Exception,), {}))
+    _stub("google.auth.transport.requests", Request=_Placeholder)
+    _stub("google.oauth2")
+    _stub("google.oauth2.credentials", Credentials=_Placeholder)
+    _stub("google_auth_oauthlib")
+    _stub("google_auth_oauthlib.flow", InstalledAppFlow=_Placeholder)
+    _stub("googleapiclient")
+    _stub("googleapiclient.discovery", build=lambda *a, **k: None)
+    _stub("googleapiclient.errors", HttpError=type("HttpError", (Exception,), {}))
+
+
+def load_gmail_module():
+    """Import module/gmail.py with Google libraries stubbed."""
+    stub_google_modules()
+    import gmail  # noqa: WPS433
+    return gmail
+
+
+def make_module(service=None, backend=None, accounts=None):
+    """Build a GmailModule around a fake API service without running OAuth.
+
+    Accounts default to an empty registry so tests never read the real
+    ~/.fgp/auth directory.
+    """
+    gmail = load_gmail_module()
+    from gmail_lib.accounts import AccountRegistry
+    from gmail_lib.backend import ApiBackend
+
+    return gmail.GmailModule(
+        backend=backend or ApiBackend(service),
+        accounts=accounts or AccountRegistry(REPO_ROOT / "tests" / "no-such-auth-dir"),
+    )
+
+
+class HttpError(Exception):
+    """A googleapiclient-style HttpError carrying an HTTP `status`."""
+
+    def __init__(self, status):
+        super().__init__(f"HTTP {status}")
+        self.resp = type("Resp", (), {"status": status})()
+
+
+class FakeGmailService:
+    """
+    Stand-in for the googleapiclient Gmail service.
+
+    Any chain like `service.users().messages().get(id=...).execute()` is
+    routed to `handlers["messages.get"](**kwargs)`. Every call is recorded
+    in `calls` as `(name, kwargs)`.
+    """
+
+    def __init__(self, handlers):
+        self.handlers = handlers
+        self.calls = []
+
+    def users(self):
+        return _FakeNode(self, [])
+
+
+class _FakeNode:
+    def __init__(self, service, path, kwargs=None):
+        self._service = service
+        self._path = path
+        self._kwargs = kwargs or {}
+
+    def __getattr__(self, name):
+        def call(**kwargs):
+            return _FakeNode(self._service, self._path + [name], kwargs)
+        return call
+
+    def execute(self):
+        name = ".".join(self._path)
+        self._service.calls.append((name, self._kwargs))
+        handler = self._service.handlers.get(name)
+        if handler is None:
+            raise AssertionError(f"Unexpected Gmail API call: {name}")
+        return handler(**self._kwargs) if callable(handler) else handler
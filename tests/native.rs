@@ -0,0 +1,274 @@
+//! The native backend end to end: the daemon runs with
+//! `[daemon] runtime = "native"` against a fake Gmail API on a local port,
+//! with an expired access token that it has to refresh first, and with
+//! `max_response_bytes` lowered to `MAX_RESPONSE_BYTES`.
+//!
+//! Run with `cargo test --features native-http --test native`.
+
+#![cfg(feature = "native-http")]
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const MAX_RESPONSE_BYTES: usize = 3000;
+
+/// Requests the fake API has seen, as `METHOD path` plus the bearer token.
+type Seen = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Answer Gmail API and token endpoint requests with canned responses.
+fn fake_gmail() -> (String, Seen) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let seen: Seen = Arc::default();
+    let log = seen.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            respond(stream, &log);
+        }
+    });
+    (url, seen)
+}
+
+fn respond(mut stream: TcpStream, seen: &Seen) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let (mut length, mut token) = (0, String::new());
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse().unwrap(),
+            "authorization" => token = value.trim_start_matches("Bearer ").to_string(),
+            _ => {}
+        }
+    }
+    reader.read_exact(&mut vec![0; length]).unwrap();
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+    seen.lock()
+        .unwrap()
+        .push((format!("{} {}", method, path), token.clone()));
+
+    let (status, body) = match path {
+        "/token" => (200, json!({"access_token": "fresh", "expires_in": 3600})),
+        _ if token != "fresh" => (401, json!({"error": {"message": "Invalid Credentials"}})),
+        "/messages" => (200, json!({"messages": [{"id": "m1", "threadId": "t1"}]})),
+        "/messages/m1" => (
+            200,
+            json!({
+                "id": "m1",
+                "threadId": "t1",
+                "labelIds": ["INBOX", "UNREAD"],
+                "snippet": "Lunch?",
+                "internalDate": "1768383000000",
+                "payload": {"headers": [
+                    {"name": "From", "value": "Alice <alice@example.com>"},
+                    {"name": "Subject", "value": "Lunch"},
+                ]},
+            }),
+        ),
+        "/messages/m2" => (
+            200,
+            json!({
+                "id": "m2",
+                "threadId": "t2",
+                "labelIds": ["INBOX"],
+                "snippet": "View this message in Gmail",
+                "payload": {
+                    "mimeType": "text/plain",
+                    "headers": [
+                        {"name": "Subject", "value": "Secret"},
+                        {"name": "X-Gm-Confidential-Mode", "value": "1"},
+                    ],
+                    "body": {"data": base64_text(100)},
+                },
+            }),
+        ),
+        "/threads/t3" => (
+            200,
+            json!({
+                "id": "t3",
+                "messages": ["m5", "m6", "m7"].map(|id| json!({
+                    "id": id,
+                    "threadId": "t3",
+                    "internalDate": "1768383000000",
+                    "payload": {"mimeType": "text/plain", "body": {"data": base64_text(2000)}},
+                })),
+            }),
+        ),
+        _ => (404, json!({"error": {"message": "Not Found"}})),
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+    .unwrap();
+}
+
+/// Base64url of `bytes` bytes of text (rounded up to a multiple of 3).
+fn base64_text(bytes: usize) -> String {
+    "YWFh".repeat(bytes.div_ceil(3))
+}
+
+struct Daemon {
+    home: PathBuf,
+    socket: PathBuf,
+    child: Child,
+}
+
+impl Daemon {
+    fn start(api_url: &str, name: &str) -> Self {
+        let home =
+            std::env::temp_dir().join(format!("fgp-gmail-native-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        let auth_dir = home.join(".fgp/auth/google");
+        std::fs::create_dir_all(&auth_dir).unwrap();
+        let token = json!({
+            "token": "stale",
+            "refresh_token": "refresh",
+            "token_uri": format!("{}/token", api_url),
+            "client_id": "client",
+            "client_secret": "secret",
+            "expiry": "2020-01-01T00:00:00Z",
+        });
+        std::fs::write(auth_dir.join("gmail_token.json"), token.to_string()).unwrap();
+
+        let socket = home.join("daemon.sock");
+        let config = home.join("config.toml");
+        std::fs::write(
+            &config,
+            format!(
+                "[daemon]\nsocket = \"{}\"\nruntime = \"native\"\n",
+                socket.display()
+            ),
+        )
+        .unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_fgp-gmail"))
+            .env("HOME", &home)
+            .env("FGP_GMAIL_CONFIG", &config)
+            .env("FGP_GMAIL_NATIVE_API_URL", api_url)
+            .env(
+                "FGP_GMAIL_MAX_RESPONSE_BYTES",
+                MAX_RESPONSE_BYTES.to_string(),
+            )
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while UnixStream::connect(&socket).is_err() {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for the socket"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Daemon {
+            home,
+            socket,
+            child,
+        }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
+
+fn call(socket: &Path, method: &str, params: Value) -> Value {
+    let mut stream = UnixStream::connect(socket).unwrap();
+    let request = json!({"id": "test", "v": 1, "method": method, "params": params});
+    writeln!(stream, "{}", request).unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+#[test]
+fn native_backend_serves_reads_and_refuses_writes() {
+    let (api_url, seen) = fake_gmail();
+    let daemon = Daemon::start(&api_url, "reads");
+
+    let inbox = call(&daemon.socket, "gmail.inbox", json!({"limit": 5}));
+    assert_eq!(inbox["ok"], true, "{}", inbox);
+    let email = &inbox["result"]["emails"][0];
+    assert_eq!(email["from"], "Alice <alice@example.com>");
+    assert_eq!(email["date"], "2026-01-14T09:30:00Z");
+    assert_eq!(email["unread"], true);
+
+    // The stale token was refreshed before the first API call
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen[0].0, "POST /token");
+    assert!(seen[1..].iter().all(|(_, token)| token == "fresh"));
+
+    let send = call(
+        &daemon.socket,
+        "gmail.send",
+        json!({"to": "bob@example.com", "subject": "Hi", "body": "Hello"}),
+    );
+    assert_eq!(send["ok"], false);
+    assert!(
+        send["error"]
+            .to_string()
+            .contains("not supported by the native backend"),
+        "{}",
+        send
+    );
+
+    let unsupported = call(
+        &daemon.socket,
+        "gmail.inbox",
+        json!({"include_attachments": true}),
+    );
+    assert_eq!(unsupported["ok"], false, "{}", unsupported);
+}
+
+#[test]
+fn native_backend_flags_restricted_messages_and_bounds_responses() {
+    let (api_url, _) = fake_gmail();
+    let daemon = Daemon::start(&api_url, "limits");
+
+    let message = call(&daemon.socket, "gmail.message", json!({"message_id": "m2"}));
+    assert_eq!(message["ok"], true, "{}", message);
+    let message = &message["result"];
+    assert_eq!(message["content_restricted"], "confidential_mode");
+    assert!(message["content_note"]
+        .to_string()
+        .contains("confidential mode"));
+    assert_eq!(message["body_text"], Value::Null);
+    assert_eq!(message["snippet"], "");
+
+    let thread = call(&daemon.socket, "gmail.thread", json!({"thread_id": "t3"}));
+    assert_eq!(thread["ok"], false, "{}", thread);
+    assert!(
+        thread["error"].to_string().contains(&format!(
+            "gmail.thread response exceeded max_response_bytes ({})",
+            MAX_RESPONSE_BYTES
+        )),
+        "{}",
+        thread
+    );
+}
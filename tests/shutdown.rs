@@ -0,0 +1,144 @@
+//! Graceful shutdown end to end: a call that is running when the daemon gets
+//! SIGTERM still completes, and the socket and pidfile are removed.
+//!
+//! The daemon loads `tests/fixtures/slow_gmail.py`, whose fake Gmail API
+//! takes two seconds per call, so no credentials or network are needed. It
+//! runs with `HOME` in a scratch directory, where the pidfile goes too.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+struct Daemon {
+    home: PathBuf,
+    socket: PathBuf,
+    pidfile: PathBuf,
+    config: PathBuf,
+}
+
+impl Daemon {
+    fn new(name: &str) -> Self {
+        let home = std::env::temp_dir().join(format!("fgp-gmail-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        let socket = home.join("daemon.sock");
+        let config = home.join("config.toml");
+        std::fs::write(
+            &config,
+            format!(
+                "[daemon]\nsocket = \"{}\"\ndrain_timeout_secs = 10\n",
+                socket.display()
+            ),
+        )
+        .unwrap();
+        Daemon {
+            pidfile: home.join(".fgp/services/gmail/daemon.pid"),
+            home,
+            socket,
+            config,
+        }
+    }
+
+    fn command(&self) -> Command {
+        let module = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/slow_gmail.py");
+        let mut command = Command::new(env!("CARGO_BIN_EXE_fgp-gmail"));
+        command
+            .env("HOME", &self.home)
+            .env("FGP_GMAIL_CONFIG", &self.config)
+            .env("FGP_GMAIL_MODULE", module)
+            .stdout(Stdio::null());
+        command
+    }
+
+    fn start(&self) -> Child {
+        let child = self.command().spawn().unwrap();
+        wait_for("the daemon's socket", Duration::from_secs(30), || {
+            UnixStream::connect(&self.socket).is_ok()
+        });
+        child
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
+
+fn wait_for(what: &str, timeout: Duration, mut ready: impl FnMut() -> bool) {
+    let deadline = Instant::now() + timeout;
+    while !ready() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn call(socket: &Path, method: &str, params: Value) -> Value {
+    let mut stream = UnixStream::connect(socket).unwrap();
+    let request = json!({"id": "test", "v": 1, "method": method, "params": params});
+    writeln!(stream, "{}", request).unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+fn signal(child: &Child, name: &str) {
+    let status = Command::new("kill")
+        .arg(format!("-{}", name))
+        .arg(child.id().to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn sigterm_lets_running_calls_finish() {
+    let daemon = Daemon::new("shutdown");
+    let mut child = daemon.start();
+    assert!(daemon.pidfile.exists());
+
+    let slow = {
+        let socket = daemon.socket.clone();
+        std::thread::spawn(move || call(&socket, "gmail.inbox", json!({})))
+    };
+    std::thread::sleep(Duration::from_millis(500));
+    signal(&child, "TERM");
+
+    let response = slow.join().unwrap();
+    assert_eq!(response["ok"], true, "{}", response);
+    assert_eq!(response["result"]["count"], 0);
+
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert!(!daemon.socket.exists());
+    assert!(!daemon.pidfile.exists());
+}
+
+#[test]
+fn a_second_daemon_refuses_to_start() {
+    let daemon = Daemon::new("pidfile");
+    let mut child = daemon.start();
+
+    let second = daemon.command().stderr(Stdio::piped()).output().unwrap();
+    assert!(!second.status.success());
+    let stderr = String::from_utf8_lossy(&second.stderr);
+    assert!(
+        stderr.contains("Another fgp-gmail is running"),
+        "{}",
+        stderr
+    );
+    assert!(UnixStream::connect(&daemon.socket).is_ok());
+
+    signal(&child, "TERM");
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+
+    // A pidfile left by a crash doesn't block the next start
+    std::fs::create_dir_all(daemon.pidfile.parent().unwrap()).unwrap();
+    std::fs::write(&daemon.pidfile, "999999999\n").unwrap();
+    let mut child = daemon.start();
+    signal(&child, "TERM");
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+}
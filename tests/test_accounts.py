@@ -0,0 +1,115 @@
+import re
+import tempfile
+import unittest
+from pathlib import Path
+
+from helpers import REPO_ROOT, FakeGmailService, make_module
+
+from gmail_lib.accounts import ACCOUNT_FILES, AccountRegistry, UnknownAccount
+from gmail_lib.backend import ApiBackend
+
+
+def make_auth_dir(root: Path, *accounts: str, top_level: bool = False) -> Path:
+    if top_level:
+        (root / "credentials.json").write_text("{}")
+    for name in accounts:
+        (root / name).mkdir()
+        (root / name / "credentials.json").write_text("{}")
+    return root
+
+
+def inbox_service(message_id):
+    return FakeGmailService({
+        "messages.list": {"messages": [{"id": message_id}]},
+        "messages.get": {"id": message_id, "threadId": "t", "labelIds": ["INBOX"],
+                         "payload": {"headers": [{"name": "Subject", "value": message_id}]}},
+    })
+
+
+class AccountRegistryTest(unittest.TestCase):
+    def setUp(self):
+        self._tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(self._tmp.cleanup)
+        self.root = Path(self._tmp.name)
+
+    def test_top_level_files_are_the_default_account(self):
+        registry = AccountRegistry(make_auth_dir(self.root, "work", top_level=True))
+        self.assertEqual(registry.names(), ["default", "work"])
+        self.assertEqual(registry.resolve(None).directory, self.root)
+        self.assertEqual(registry.resolve("work").directory, self.root / "work")
+
+    def test_single_named_account_is_the_default(self):
+        registry = AccountRegistry(make_auth_dir(self.root, "work"))
+        self.assertEqual(registry.resolve(None).name, "work")
+
+    def test_configured_default_wins(self):
+        registry = AccountRegistry(make_auth_dir(self.root, "personal", "work", top_level=True),
+                                   default_account="work")
+        self.assertEqual(registry.resolve(None).name, "work")
+
+    def test_several_accounts_without_default_need_a_name(self):
+        registry = AccountRegistry(make_auth_dir(self.root, "personal", "work"))
+        with self.assertRaisesRegex(UnknownAccount, r"Multiple accounts configured \(personal, work\)"):
+            registry.resolve(None)
+
+    def test_unknown_account_lists_known_accounts(self):
+        registry = AccountRegistry(make_auth_dir(self.root, "personal", "work"))
+        with self.assertRaisesRegex(UnknownAccount, "Unknown account: 'home'. Known accounts: personal, work"):
+            registry.resolve("home")
+
+    def test_any_token_file_marks_an_account(self):
+        for name, file in [("home", "gmail_token.pickle"), ("laptop", "gmail_token.json")]:
+            (self.root / name).mkdir()
+            (self.root / name / file).write_text("{}")
+        self.assertEqual(AccountRegistry(self.root).names(), ["home", "laptop"])
+
+    def test_native_backend_uses_the_same_account_files(self):
+        source = (REPO_ROOT / "src" / "native" / "auth.rs").read_text()
+        declared = re.search(r"const ACCOUNT_FILES: &\[&str\] = &\[(.*?)\];", source, re.S).group(1)
+        self.assertEqual(tuple(re.findall(r'"([^"]+)"', declared)), ACCOUNT_FILES)
+
+    def test_directories_without_credentials_are_ignored(self):
+        make_auth_dir(self.root, "work")
+        (self.root / "scratch").mkdir()
+        self.assertEqual(AccountRegistry(self.root).names(), ["work"])
+
+
+class AccountDispatchTest(unittest.TestCase):
+    def setUp(self):
+        self._tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(self._tmp.cleanup)
+        registry = AccountRegistry(make_auth_dir(Path(self._tmp.name), "personal", "work"),
+                                   default_account="work")
+        self.work = inbox_service("work-1")
+        self.personal = inbox_service("personal-1")
+        self.module = make_module(FakeGmailService({}), accounts=registry)
+        # Route per account the way the daemon does once each account connects
+        self.module.backend = None
+        self.module._account_backends = {
+            "work": ApiBackend(self.work),
+            "personal": ApiBackend(self.personal),
+        }
+
+    def inbox_ids(self, **params):
+        return [e["id"] for e in self.module.dispatch("gmail.inbox", params)["emails"]]
+
+    def test_account_param_selects_credentials(self):
+        self.assertEqual(self.inbox_ids(account="personal"), ["personal-1"])
+        self.assertEqual(self.inbox_ids(account="work"), ["work-1"])
+
+    def test_omitted_account_uses_default(self):
+        self.assertEqual(self.inbox_ids(), ["work-1"])
+        self.assertEqual(self.personal.calls, [])
+
+    def test_cache_is_per_account(self):
+        self.inbox_ids(account="work")
+        self.assertEqual(self.inbox_ids(account="personal"), ["personal-1"])
+
+    def test_unknown_account_is_rejected_before_any_call(self):
+        with self.assertRaisesRegex(UnknownAccount, "Known accounts: personal, work"):
+            self.module.dispatch("gmail.inbox", {"account": "home"})
+        self.assertEqual(self.work.calls + self.personal.calls, [])
+
+
+if __name__ == "__main__":
+    unittest.main()
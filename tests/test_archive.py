@@ -0,0 +1,50 @@
+import unittest
+
+from helpers import FakeGmailService, HttpError, make_module
+
+
+def modify(id, body, **kwargs):
+    if id == "gone":
+        raise HttpError(404)
+    labels = {"INBOX", "UNREAD"} - set(body["removeLabelIds"]) | set(body["addLabelIds"])
+    return {"id": id, "labelIds": sorted(labels)}
+
+
+class ArchiveTest(unittest.TestCase):
+    def test_archive_removes_inbox_label(self):
+        service = FakeGmailService({"messages.modify": modify})
+        result = make_module(service).dispatch("gmail.archive", {"message_id": "m1"})
+        self.assertEqual(result["results"], [{"id": "m1", "ok": True, "labels": ["UNREAD"]}])
+        self.assertEqual(service.calls[0][1]["body"], {"addLabelIds": [], "removeLabelIds": ["INBOX"]})
+
+    def test_unarchive_adds_inbox_label(self):
+        service = FakeGmailService({"messages.modify": modify})
+        result = make_module(service).dispatch("gmail.unarchive", {"message_ids": ["m1"]})
+        self.assertEqual(result["results"][0]["labels"], ["INBOX", "UNREAD"])
+
+    def test_failures_are_reported_per_id(self):
+        service = FakeGmailService({"messages.modify": modify})
+        result = make_module(service).dispatch("gmail.archive", {"message_ids": ["m1", "gone", "m2", "m1"]})
+        self.assertEqual([r["ok"] for r in result["results"]], [True, False, True])
+        self.assertEqual((result["succeeded"], result["failed"]), (2, 1))
+        self.assertEqual(result["results"][1]["error_type"], "HttpError")
+
+    def test_requires_ids(self):
+        module = make_module(FakeGmailService({}))
+        for params in ({}, {"message_ids": "m1"}, {"message_ids": [""]}, {"message_ids": [f"m{n}" for n in range(101)]}):
+            with self.subTest(params=params), self.assertRaises(ValueError):
+                module.dispatch("gmail.archive", params)
+
+    def test_archive_invalidates_cached_inbox(self):
+        service = FakeGmailService({"messages.list": {"messages": []}, "messages.modify": modify})
+        module = make_module(service)
+        module.dispatch("gmail.inbox", {})
+        module.dispatch("gmail.inbox", {})
+        module.dispatch("gmail.archive", {"message_id": "m1"})
+        module.dispatch("gmail.inbox", {})
+        self.assertEqual([name for name, _ in service.calls],
+                         ["messages.list", "messages.modify", "messages.list"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,97 @@
+import base64
+import os
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, make_module
+
+PDF = b"%PDF-1.7\n..."
+
+
+class GetAttachmentTest(unittest.TestCase):
+    def setUp(self):
+        base = Path(tempfile.mkdtemp()).resolve()
+        self.addCleanup(shutil.rmtree, base)
+        self.root = base / "root"
+        self.root.mkdir()
+        self.outside = base / "outside"
+        self.outside.mkdir()
+        self.service = FakeGmailService({
+            "messages.attachments.get": {"data": base64.urlsafe_b64encode(PDF).decode()},
+        })
+        self.module = make_module(self.service)
+        self.module.attachments_root = self.root
+
+    def save(self, save_path):
+        return self.module.dispatch("gmail.get_attachment", {
+            "message_id": "m1", "attachment_id": "a1", "save_path": str(save_path)})
+
+    def assert_refused(self, save_path, error, message):
+        with self.assertRaisesRegex(error, message):
+            self.save(save_path)
+        self.assertEqual(self.service.calls, [])
+
+    def test_saves_new_file_inside_root(self):
+        (self.root / "invoices").mkdir()
+        result = self.save(self.root / "invoices" / "jan.pdf")
+        path = self.root / "invoices" / "jan.pdf"
+        self.assertEqual((result["saved"], result["path"], result["size"]), (True, str(path), len(PDF)))
+        self.assertEqual(path.read_bytes(), PDF)
+
+    def test_inline_without_save_path(self):
+        result = self.module.dispatch("gmail.get_attachment", {"message_id": "m1", "attachment_id": "a1"})
+        self.assertEqual((base64.b64decode(result["data"]), result["mime_type"]), (PDF, "application/pdf"))
+
+    def test_dot_dot_segments(self):
+        (self.root / "invoices").mkdir()
+        for path in (self.root / "invoices" / ".." / "jan.pdf", self.root / ".." / "outside" / "jan.pdf"):
+            with self.subTest(path=path):
+                self.assert_refused(path, ValueError, r"must not contain '\.\.' segments")
+        self.assertFalse((self.outside / "jan.pdf").exists())
+
+    def test_absolute_path_outside_root(self):
+        self.assert_refused(self.outside / "jan.pdf", PermissionError, "save_path must be inside")
+        self.assertFalse((self.outside / "jan.pdf").exists())
+
+    def test_relative_path_outside_root(self):
+        cwd = os.getcwd()
+        os.chdir(self.outside)
+        self.addCleanup(os.chdir, cwd)
+        self.assert_refused("jan.pdf", PermissionError, "save_path must be inside")
+
+    def test_symlinked_parent(self):
+        (self.root / "link").symlink_to(self.outside, target_is_directory=True)
+        (self.outside / "nested").mkdir()
+        for path in (self.root / "link" / "jan.pdf", self.root / "link" / "nested" / "jan.pdf"):
+            with self.subTest(path=path):
+                self.assert_refused(path, PermissionError, "symlinked directory")
+        self.assertEqual(sorted(p.name for p in self.outside.iterdir()), ["nested"])
+
+    def test_root_reached_through_symlink(self):
+        alias = self.outside / "alias"
+        alias.symlink_to(self.root, target_is_directory=True)
+        self.module.attachments_root = alias
+        self.save(self.root / "jan.pdf")
+        self.save(alias / "feb.pdf")
+        self.assertEqual(sorted(p.name for p in self.root.iterdir()), ["feb.pdf", "jan.pdf"])
+
+    def test_refuses_to_overwrite(self):
+        existing = self.root / "jan.pdf"
+        existing.write_bytes(b"keep me")
+        self.assert_refused(existing, FileExistsError, "not overwriting")
+        self.assertEqual(existing.read_bytes(), b"keep me")
+
+        dangling = self.root / "dangling.pdf"
+        dangling.symlink_to(self.outside / "target.pdf")
+        self.assert_refused(dangling, FileExistsError, "not overwriting")
+        self.assertFalse((self.outside / "target.pdf").exists())
+
+    def test_directory_checks(self):
+        self.assert_refused(self.root / "missing" / "jan.pdf", FileNotFoundError, "Directory does not exist")
+        self.assert_refused(self.root, ValueError, "is a directory")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,83 @@
+import json
+import tempfile
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.audit import AuditLog, redact_params
+
+ALICE = "alice@example.com"
+
+
+class RedactionTest(unittest.TestCase):
+    def test_addresses_are_hashed_everywhere(self):
+        redacted = redact_params({
+            "to": [ALICE, "Bob <bob@example.com>"],
+            "cc": f"{ALICE}, carol@example.com",
+            "operations": [{"method": "gmail.send", "params": {"to": [ALICE], "bcc": [["dave@example.com"]]}}],
+        })
+        text = json.dumps(redacted)
+        self.assertNotIn("@example.com", text)
+        hashed = redacted["to"][0]
+        self.assertRegex(hashed, r"^user-[0-9a-f]{10}@redacted\.invalid$")
+        self.assertTrue(redacted["to"][1].startswith("Bob <user-"))
+        self.assertEqual(redacted["operations"][0]["params"]["to"], [hashed])
+        self.assertEqual(redacted["cc"].split(", ")[0], hashed)
+
+    def test_text_is_truncated_or_hashed(self):
+        params = {"subject": "Quarterly numbers for alice@example.com", "body": "short",
+                  "message_id": "18d2b0000000b001", "attachments": [{"filename": "a.pdf", "data": "QUJD"}]}
+        redacted = redact_params(params, text_chars=10)
+        self.assertEqual(redacted["subject"], "Quarterly … <39 chars>")
+        self.assertEqual(redacted["body"], "short")
+        self.assertEqual(redacted["message_id"], "18d2b0000000b001")
+        self.assertEqual(redacted["attachments"], [{"filename": "a.pdf", "data": "[redacted]"}])
+
+        hashed = redact_params({"operations": [{"params": {"body": "short"}}]}, text_chars=0)
+        self.assertEqual(hashed["operations"][0]["params"]["body"],
+                         "sha256:f9b0078b5df596d2ea19010c001bbd009e651de2c57e8fb7e355f31eb9d3f739")
+
+
+class AuditLogTest(unittest.TestCase):
+    def setUp(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        self.path = Path(tmp.name) / "audit.log"
+
+    def test_rotation_keeps_newest_files(self):
+        audit = AuditLog(self.path, max_bytes=400, keep=2)
+        for index in range(20):
+            audit.record("gmail.read", {"message_id": f"m{index}"}, 1.0)
+        self.assertEqual(sorted(p.name for p in self.path.parent.iterdir()),
+                         ["audit.log", "audit.log.1", "audit.log.2"])
+        self.assertTrue(all(p.stat().st_size <= 400 for p in self.path.parent.iterdir()))
+        entries = audit.tail(100)
+        self.assertEqual(entries[-1]["params"]["message_id"], "m19")
+        self.assertEqual([e["params"]["message_id"] for e in audit.tail(3)], ["m17", "m18", "m19"])
+        self.assertLess(len(entries), 20)
+
+    def test_dispatch_records_calls(self):
+        service = FakeGmailService({"labels.get": {"messagesUnread": 0}, "messages.list": {}})
+        module = make_module(service)
+        module.audit = AuditLog(self.path)
+        module.dispatch("gmail.unread", {})
+        with self.assertRaises(ValueError):
+            module.dispatch("gmail.search", {"query": "", "to": 42})
+
+        result = module.dispatch("gmail.audit_tail", {"limit": 5})
+        self.assertTrue(result["enabled"])
+        first, second = result["entries"]
+        self.assertEqual((first["method"], first["ok"], first["error_code"]), ("gmail.unread", True, None))
+        self.assertEqual((second["method"], second["ok"], second["error_code"]), ("gmail.search", False, "ValueError"))
+        self.assertIn("duration_ms", second)
+        self.assertEqual(module.dispatch("gmail.audit_tail", {"method": "gmail.search"})["count"], 1)
+        self.assertEqual(module.dispatch("gmail.config", {})["audit"]["path"], str(self.path))
+
+    def test_disabled_writes_nothing(self):
+        AuditLog(self.path, enabled=False).record("gmail.inbox", {}, 1.0)
+        self.assertFalse(self.path.exists())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,193 @@
+import contextlib
+import datetime
+import io
+import json
+import os
+import pickle
+import tempfile
+import unittest
+from pathlib import Path
+from unittest import mock
+
+from helpers import FakeGmailService, load_gmail_module, make_module
+
+from gmail_lib.accounts import AccountRegistry, AuthExpired
+from gmail_lib.auth_monitor import AuthRequired
+from gmail_lib.backend import ApiBackend
+from gmail_lib.health import DEGRADED, FAILED, OK
+
+gmail = load_gmail_module()
+RefreshError = gmail.RefreshError
+
+
+class FakeCreds:
+    def __init__(self, valid=True, expired=False, refresh_token="r", expiry=None, refresh_error=None,
+                 scopes=None):
+        self.valid = valid
+        self.scopes = scopes
+        self.expired = expired
+        self.refresh_token = refresh_token
+        self.expiry = expiry
+        self.refresh_error = refresh_error
+
+    def refresh(self, request):
+        if self.refresh_error:
+            raise RefreshError(self.refresh_error)
+        self.valid = True
+        self.expired = False
+
+
+class AuthTest(unittest.TestCase):
+    def setUp(self):
+        self._tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(self._tmp.cleanup)
+        self.auth_dir = Path(self._tmp.name)
+        (self.auth_dir / "credentials.json").write_text("{}")
+        self.module = make_module(FakeGmailService({}), accounts=AccountRegistry(self.auth_dir))
+        self.account = self.module.accounts.resolve(None)
+
+    def cache_token(self, creds):
+        with open(self.account.token_file, "wb") as f:
+            pickle.dump(creds, f)
+
+    def test_missing_token_raises_instead_of_prompting(self):
+        with self.assertRaisesRegex(AuthExpired, "no cached token.*fgp-gmail auth"):
+            self.module._get_credentials(self.account)
+
+    def test_rejected_refresh_raises_auth_expired(self):
+        self.cache_token(FakeCreds(valid=False, expired=True, refresh_error="invalid_grant"))
+        with self.assertRaisesRegex(AuthExpired, "refresh was rejected"):
+            self.module._get_credentials(self.account)
+
+    def test_expired_token_is_refreshed_and_saved(self):
+        self.cache_token(FakeCreds(valid=False, expired=True))
+        self.assertTrue(self.module._get_credentials(self.account).valid)
+        with open(self.account.token_file, "rb") as f:
+            self.assertTrue(pickle.load(f).valid)
+
+    def test_saved_token_has_a_json_copy(self):
+        creds = FakeCreds(expiry=datetime.datetime(2026, 1, 14, 9, 30, 0, 250000))
+        creds.token, creds.token_uri = "access", "https://oauth2.example.com/token"
+        gmail.save_credentials(self.account, creds)
+        saved = json.loads(self.account.token_json_file.read_text())
+        self.assertEqual(saved["token"], "access")
+        self.assertEqual(saved["refresh_token"], "r")
+        self.assertEqual(saved["token_uri"], "https://oauth2.example.com/token")
+        self.assertEqual(saved["expiry"], "2026-01-14T09:30:00.250000Z")
+        self.assertIsNone(saved["client_id"])
+
+    def test_on_start_fails_fast_without_token(self):
+        self.module.backend = None
+        with self.assertRaises(AuthExpired) as raised:
+            self.module.on_start()
+        self.assertEqual(raised.exception.account, "default")
+
+    def test_auth_status_without_token(self):
+        status = self.module.dispatch("gmail.auth_status", {"account": "default"})
+        self.assertFalse(status["authenticated"])
+        self.assertTrue(status["needs_auth"])
+        self.assertIn("fgp-gmail auth", status["remediation"])
+
+    def test_auth_status_reports_expiry(self):
+        expiry = datetime.datetime.utcnow() + datetime.timedelta(hours=1)
+        self.cache_token(FakeCreds(expiry=expiry))
+        status = self.module.dispatch("gmail.auth_status", {"account": "default"})
+        self.assertTrue(status["authenticated"])
+        self.assertIsNone(status["remediation"])
+        self.assertEqual(status["token_expiry"], expiry.isoformat())
+        self.assertAlmostEqual(status["expires_in_secs"], 3600, delta=5)
+
+    def test_refreshable_token_counts_as_authenticated(self):
+        self.cache_token(FakeCreds(valid=False, expired=True))
+        status = self.module.dispatch("gmail.auth_status", {"account": "default"})
+        self.assertTrue(status["authenticated"])
+        self.assertFalse(status["token_valid"])
+
+
+class AuthMonitorTest(unittest.TestCase):
+    def setUp(self):
+        self._tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(self._tmp.cleanup)
+        self.auth_dir = Path(self._tmp.name)
+        (self.auth_dir / "credentials.json").write_text("{}")
+        self.service = FakeGmailService({"messages.list": {"messages": []}})
+        self.module = make_module(FakeGmailService({}), accounts=AccountRegistry(self.auth_dir))
+        # Route through the account the way the daemon does
+        self.module.backend = None
+        self.module._account_backends = {"default": ApiBackend(self.service)}
+        self.account = self.module.accounts.resolve(None)
+
+    def cache_token(self, creds):
+        with open(self.account.token_file, "wb") as f:
+            pickle.dump(creds, f)
+
+    def health(self):
+        with mock.patch.object(self.module, "_probe_api", lambda: gmail.SubsystemHealth("gmail_api", OK, "stub", "")):
+            return {s.name: s for s in self.module._health_report().subsystems}["auth"]
+
+    def test_revoked_grant_is_caught_by_the_check(self):
+        self.cache_token(FakeCreds(valid=False, expired=True, refresh_error="invalid_grant"))
+        state = gmail.check_token(self.account)
+        self.assertFalse(state["usable"])
+        self.assertIn("refresh was rejected", state["reason"])
+
+    def test_check_reports_missing_scopes(self):
+        self.cache_token(FakeCreds(scopes=gmail.SCOPES[:3]))
+        state = gmail.check_token(self.account)
+        self.assertTrue(state["usable"])
+        self.assertEqual(state["missing_scopes"], gmail.SCOPES[3:])
+
+    def test_calls_fail_fast_while_auth_is_broken(self):
+        self.module.auth_monitor.check_all()
+        with self.assertRaisesRegex(AuthRequired, "no cached token.*fgp-gmail auth"):
+            self.module.dispatch("gmail.inbox", {})
+        self.assertEqual(self.service.calls, [])
+
+    def test_auth_methods_still_work_while_broken(self):
+        self.module.auth_monitor.check_all()
+        status = self.module.dispatch("gmail.auth_status", {})
+        self.assertTrue(status["needs_auth"])
+        self.assertIsNotNone(status["last_checked"])
+
+    def test_new_token_clears_the_broken_state(self):
+        self.module.auth_monitor.check_all()
+        self.cache_token(FakeCreds())
+        self.assertEqual(self.module.dispatch("gmail.inbox", {})["count"], 0)
+
+    def test_rejected_refresh_overrides_a_refreshable_looking_token(self):
+        self.cache_token(FakeCreds(valid=False, expired=True, refresh_error="invalid_grant"))
+        self.module.auth_monitor.check_all()
+        status = self.module.dispatch("gmail.auth_status", {})
+        self.assertFalse(status["authenticated"])
+        self.assertIn("refresh was rejected", status["reason"])
+
+    def test_health_auth_entry(self):
+        self.assertEqual(self.health().reason, "auth_unchecked")
+
+        self.module.auth_monitor.check_all()
+        entry = self.health()
+        self.assertEqual((entry.status, entry.reason), (FAILED, "auth_required"))
+        self.assertIn("fgp-gmail auth", entry.remediation)
+
+        self.cache_token(FakeCreds(scopes=gmail.SCOPES[:3]))
+        self.module.auth_monitor.check_all()
+        self.assertEqual((self.health().status, self.health().reason), (DEGRADED, "scopes_missing"))
+
+        self.cache_token(FakeCreds(scopes=gmail.SCOPES))
+        self.module.auth_monitor.check_all()
+        self.assertEqual(self.health().status, OK)
+
+    def test_check_auth_cli(self):
+        out = io.StringIO()
+        with mock.patch.object(gmail, "FGP_AUTH_DIR", self.auth_dir), \
+                mock.patch.dict(os.environ, {"FGP_GMAIL_DEFAULT_ACCOUNT": ""}), \
+                contextlib.redirect_stdout(out), contextlib.redirect_stderr(io.StringIO()):
+            self.assertEqual(gmail.main(["check-auth"]), 1)
+            self.cache_token(FakeCreds())
+            self.assertEqual(gmail.main(["check-auth", "--account", "default"]), 0)
+        reports = [json.loads(chunk) for chunk in out.getvalue().replace("}\n{", "}\0{").split("\0")]
+        self.assertEqual([r["accounts"][0]["usable"] for r in reports], [False, True])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,141 @@
+import json
+import os
+import tempfile
+import unittest
+from pathlib import Path
+from unittest import mock
+
+from helpers import FIXTURES_DIR, FakeGmailService, load_gmail_module
+
+from gmail_lib.backend import (
+    REDACTED,
+    ApiBackend,
+    RecordingBackend,
+    ReplayBackend,
+    ReplayMismatch,
+    redact,
+)
+
+SESSION = FIXTURES_DIR / "replay" / "search_invoices.jsonl"
+QUERY = "from:billing@vendor.example.com invoice"
+
+
+class RedactionTest(unittest.TestCase):
+    def test_strips_content_and_addresses(self):
+        msg = {
+            "snippet": "private text",
+            "raw": "UkFX",
+            "payload": {
+                "headers": [
+                    {"name": "Subject", "value": "Lunch on Friday?"},
+                    {"name": "From", "value": "Carol <carol@example.net>"},
+                    {"name": "To", "value": "Alice <alice@example.com>, bob@example.org"},
+                    {"name": "Date", "value": "Mon, 5 Jan 2026 09:00:00 +0000"},
+                ],
+                "parts": [{
+                    "mimeType": "text/plain",
+                    "headers": [{"name": "Content-Type", "value": "text/plain; charset=UTF-8"}],
+                    "body": {"data": "SGVsbG8=", "size": 5},
+                }],
+            },
+        }
+        out = redact(msg)
+        self.assertEqual(out["snippet"], REDACTED)
+        self.assertEqual(out["raw"], REDACTED)
+        self.assertEqual(out["payload"]["parts"][0]["body"]["data"], REDACTED)
+        self.assertEqual(out["payload"]["parts"][0]["body"]["size"], 5)
+        self.assertEqual(out["payload"]["headers"], [
+            {"name": "Subject", "value": REDACTED},
+            {"name": "From", "value": REDACTED},
+            {"name": "To", "value": REDACTED},
+            {"name": "Date", "value": "Mon, 5 Jan 2026 09:00:00 +0000"},
+        ])
+        self.assertEqual(out["payload"]["parts"][0]["headers"], msg["payload"]["parts"][0]["headers"])
+
+    def test_addresses_outside_content_are_replaced(self):
+        out = redact({"error": "Delegation denied for alice@example.com"})
+        self.assertNotIn("alice@example.com", out["error"])
+        self.assertIn("user-", out["error"])
+
+    def test_address_placeholders_are_stable(self):
+        self.assertEqual(redact("A@Example.com"), redact("a@example.com"))
+
+    def test_unsafe_recording_keeps_content(self):
+        service = FakeGmailService({"messages.get": {"id": "1", "snippet": "hi bob@example.com"}})
+        with tempfile.TemporaryDirectory() as tmp:
+            path = Path(tmp) / "s.jsonl"
+            backend = RecordingBackend(ApiBackend(service), path, unsafe_keep_content=True)
+            backend.call("messages.get", userId="me", id="1")
+            record = json.loads(path.read_text().strip())
+        self.assertEqual(record["response"]["snippet"], "hi bob@example.com")
+
+    def test_recording_redacts_vacation_responder(self):
+        vacation = {
+            "enableAutoReply": True,
+            "responseSubject": "Out until Monday",
+            "responseBodyPlainText": "I'm hiking, call 555-0100 if urgent",
+            "responseBodyHtml": "<p>I'm hiking, call 555-0100 if urgent</p>",
+            "restrictToContacts": False,
+        }
+        service = FakeGmailService({
+            "settings.getVacation": vacation,
+            "settings.updateVacation": lambda body, **kwargs: body,
+        })
+        with tempfile.TemporaryDirectory() as tmp:
+            path = Path(tmp) / "s.jsonl"
+            backend = RecordingBackend(ApiBackend(service), path)
+            self.assertEqual(backend.call("settings.getVacation", userId="me"), vacation)
+            backend.call("settings.updateVacation", userId="me", body=vacation)
+            text = path.read_text()
+            replay = ReplayBackend(path)
+        for secret in ("Out until Monday", "hiking", "555-0100"):
+            self.assertNotIn(secret, text)
+        recorded = replay.call("settings.getVacation", userId="me")
+        self.assertEqual(recorded["responseSubject"], REDACTED)
+        self.assertTrue(recorded["enableAutoReply"])
+        # The request body is redacted the same way, so it still matches
+        self.assertEqual(replay.call("settings.updateVacation", userId="me", body=vacation)["responseBodyHtml"],
+                         REDACTED)
+
+    def test_recording_captures_errors(self):
+        def fail(**kwargs):
+            raise RuntimeError("quota exceeded")
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = Path(tmp) / "s.jsonl"
+            backend = RecordingBackend(ApiBackend(FakeGmailService({"messages.get": fail})), path)
+            with self.assertRaises(RuntimeError):
+                backend.call("messages.get", userId="me", id="1")
+            replay = ReplayBackend(path)
+        with self.assertRaisesRegex(RuntimeError, "quota exceeded"):
+            replay.call("messages.get", userId="me", id="1")
+
+
+class ReplaySessionTest(unittest.TestCase):
+    """Replays the recorded fixture session through the full module."""
+
+    def setUp(self):
+        env = {"FGP_GMAIL_BACKEND": "replay", "FGP_GMAIL_REPLAY_FILE": str(SESSION)}
+        with mock.patch.dict(os.environ, env):
+            self.module = load_gmail_module().GmailModule()
+
+    def test_search_replays_recorded_session(self):
+        result = self.module.dispatch("gmail.search", {"query": QUERY, "limit": 5})
+        self.assertEqual(result["count"], 2)
+        self.assertEqual(
+            [e["subject"] for e in result["emails"]],
+            ["Invoice January 2026", "Re: Invoice January 2026"],
+        )
+        self.assertEqual(self.module.backend.unused(), [])
+
+    def test_unmatched_request_errors(self):
+        with self.assertRaises(ReplayMismatch):
+            self.module.dispatch("gmail.search", {"query": QUERY, "limit": 10})
+
+    def test_health_reports_replay_backend(self):
+        status = self.module.health_check()["gmail_service"]
+        self.assertIn("replay", status["message"])
+
+
+if __name__ == "__main__":
+    unittest.main()
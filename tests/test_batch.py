@@ -0,0 +1,91 @@
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+
+def message(message_id, labels=("INBOX", "UNREAD")):
+    return {"id": message_id, "threadId": "t-" + message_id, "labelIds": list(labels),
+            "payload": {"headers": [{"name": "Subject", "value": message_id}]}}
+
+
+class BatchTest(unittest.TestCase):
+    def setUp(self):
+        self.service = FakeGmailService({
+            "messages.list": {"messages": [{"id": "m-1"}]},
+            "messages.get": lambda id, **kw: message(id),
+            "labels.get": {"id": "UNREAD", "messagesUnread": 1},
+        })
+        self.module = make_module(self.service)
+
+    def batch(self, *operations):
+        return self.module.dispatch("gmail.batch", {"operations": list(operations)})
+
+    def test_runs_operations_in_order(self):
+        result = self.batch(
+            {"method": "gmail.inbox", "params": {"limit": 1}},
+            {"method": "gmail.message", "params": {"message_id": "m-1", "format": "metadata"}},
+        )
+        self.assertEqual(result["count"], 2)
+        self.assertEqual(result["succeeded"], 2)
+        self.assertEqual([r["method"] for r in result["results"]], ["gmail.inbox", "gmail.message"])
+        self.assertEqual(result["results"][0]["result"]["emails"][0]["id"], "m-1")
+        self.assertTrue(all(r["ok"] for r in result["results"]))
+
+    def test_failure_does_not_abort_the_rest(self):
+        result = self.batch(
+            {"method": "gmail.message", "params": {}},
+            {"method": "gmail.nope"},
+            {"method": "gmail.inbox"},
+        )
+        first, second, third = result["results"]
+        self.assertEqual((first["ok"], first["error_type"]), (False, "ValueError"))
+        self.assertIn("message_id", first["error"])
+        self.assertEqual(second["error"], "Unknown method: gmail.nope")
+        self.assertTrue(third["ok"])
+        self.assertEqual((result["succeeded"], result["failed"]), (1, 2))
+
+    def test_operation_errors_keep_their_type(self):
+        result = self.batch({"method": "gmail.inbox", "params": {"account": "missing"}})
+        self.assertEqual(result["results"][0]["error_type"], "UnknownAccount")
+
+    def test_nesting_is_rejected_per_operation(self):
+        result = self.batch(
+            {"method": "gmail.batch", "params": {"operations": [{"method": "gmail.inbox"}]}},
+            {"method": "gmail.inbox"},
+        )
+        self.assertEqual(result["results"][0]["error"], "gmail.batch can't be nested")
+        self.assertTrue(result["results"][1]["ok"])
+
+    def test_daemon_control_methods_are_rejected(self):
+        methods = ["gmail.drain", "gmail.config", "gmail.stats", "gmail.audit_tail", "gmail.inbox"]
+        result = self.batch(*[{"method": method} for method in methods])
+        errors = [entry.get("error") for entry in result["results"]]
+        self.assertEqual(errors[:4], [
+            "gmail.drain can't run inside gmail.batch",
+            "gmail.config can't run inside gmail.batch",
+            "gmail.stats can't run inside gmail.batch",
+            "gmail.audit_tail can't run inside gmail.batch",
+        ])
+        self.assertTrue(result["results"][4]["ok"])
+        self.assertFalse(self.module.in_flight.draining)
+
+    def test_malformed_operations_are_reported(self):
+        result = self.batch("gmail.inbox", {"method": "gmail.inbox", "params": []})
+        self.assertEqual(result["results"][0]["error"], "operation needs a method name")
+        self.assertIsNone(result["results"][0]["method"])
+        self.assertEqual(result["results"][1]["error"], "operation params must be an object")
+
+    def test_operations_are_required_and_bounded(self):
+        with self.assertRaisesRegex(ValueError, "non-empty list"):
+            self.module.dispatch("gmail.batch", {"operations": []})
+        with self.assertRaisesRegex(ValueError, "at most 50 operations"):
+            self.batch(*[{"method": "gmail.inbox"}] * 51)
+
+    def test_operations_are_metered_individually(self):
+        self.batch({"method": "gmail.inbox"}, {"method": "gmail.unread"})
+        methods = self.module.metrics.snapshot()
+        self.assertEqual(sorted(methods), ["gmail.inbox", "gmail.unread"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,86 @@
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+
+def mailbox(count, page_size=3):
+    ids = [f"m{n}" for n in range(count)]
+
+    def list_messages(maxResults, pageToken=None, **kwargs):
+        start = int(pageToken or 0)
+        end = min(start + min(maxResults, page_size), len(ids))
+        page = {"messages": [{"id": i} for i in ids[start:end]]}
+        if end < len(ids):
+            page["nextPageToken"] = str(end)
+        return page
+
+    return list_messages
+
+
+class BulkModifyTest(unittest.TestCase):
+    def test_dry_run_changes_nothing(self):
+        service = FakeGmailService({
+            "messages.list": mailbox(5),
+            "messages.get": lambda id, **kw: {"id": id, "threadId": id, "payload": {
+                "headers": [{"name": "Subject", "value": f"Newsletter {id}"}]}},
+        })
+        result = make_module(service).dispatch("gmail.bulk_modify", {
+            "query": "from:news", "action": "archive", "dry_run": True,
+        })
+        self.assertEqual(result["matched"], 5)
+        self.assertEqual(result["sample"][0]["subject"], "Newsletter m0")
+        self.assertNotIn("messages.batchModify", [name for name, _ in service.calls])
+
+    def test_batches_and_cap(self):
+        service = FakeGmailService({"messages.list": mailbox(250, page_size=100), "messages.batchModify": {}})
+        result = make_module(service).dispatch("gmail.bulk_modify", {
+            "query": "from:news", "action": "mark_read", "max_messages": 150,
+        })
+        self.assertEqual((result["matched"], result["succeeded"], result["truncated"]), (150, 150, True))
+        batches = [kw["body"] for name, kw in service.calls if name == "messages.batchModify"]
+        self.assertEqual([len(b["ids"]) for b in batches], [100, 50])
+        self.assertEqual(batches[0]["removeLabelIds"], ["UNREAD"])
+
+    def test_stops_at_failed_batch(self):
+        calls = []
+
+        def batch_modify(body, **kwargs):
+            calls.append(body)
+            if len(calls) == 2:
+                raise RuntimeError("HTTP 500")
+            return {}
+
+        service = FakeGmailService({"messages.list": mailbox(250, page_size=100),
+                                    "messages.batchModify": batch_modify})
+        result = make_module(service).dispatch("gmail.bulk_modify", {
+            "query": "x", "action": "archive", "max_messages": 250,
+        })
+        self.assertEqual((result["succeeded"], result["failed"], result["skipped"]), (100, 100, 50))
+        self.assertTrue(result["stopped_early"])
+        self.assertIn("HTTP 500", result["error"])
+        self.assertEqual(len(calls), 2)
+
+    def test_label_actions_resolve_names(self):
+        service = FakeGmailService({
+            "messages.list": mailbox(2),
+            "labels.list": {"labels": [{"id": "Label_7", "name": "Newsletters"}]},
+            "messages.batchModify": {},
+        })
+        make_module(service).dispatch("gmail.bulk_modify", {
+            "query": "x", "action": "add_label", "label": "newsletters",
+        })
+        body = [kw["body"] for name, kw in service.calls if name == "messages.batchModify"][0]
+        self.assertEqual(body["addLabelIds"], ["Label_7"])
+
+    def test_validation(self):
+        module = make_module(FakeGmailService({}))
+        with self.assertRaisesRegex(ValueError, "action must be one of"):
+            module.dispatch("gmail.bulk_modify", {"query": "x", "action": "delete"})
+        with self.assertRaisesRegex(ValueError, "label parameter is required"):
+            module.dispatch("gmail.bulk_modify", {"query": "x", "action": "add_label"})
+        with self.assertRaisesRegex(ValueError, "max_messages must be a positive integer"):
+            module.dispatch("gmail.bulk_modify", {"query": "x", "action": "trash", "max_messages": 0})
+
+
+if __name__ == "__main__":
+    unittest.main()
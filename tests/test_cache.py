@@ -0,0 +1,139 @@
+import unittest
+from unittest import mock
+
+from helpers import FakeGmailService, load_fixture, make_module
+
+from gmail_lib.cache import MISSING, ResultCache
+
+THREAD = load_fixture("types/thread_nested_replies.json")
+
+# One call of each cached method, and the API calls it's served by
+READS = [
+    ("gmail.inbox", {}, ["messages.list"]),
+    ("gmail.unread", {}, ["labels.get", "messages.list"]),
+    ("gmail.search", {"query": "label:Receipts"}, ["messages.list"]),
+    ("gmail.thread", {"thread_id": "18d2c0000000c001"}, ["threads.get"]),
+    ("gmail.message", {"message_id": "18d2c0000000c002"}, ["messages.get"]),
+]
+FETCHED = [api for _, _, calls in READS for api in calls]
+
+# Every method that can change labels or messages (gmail.send_queued is
+# covered in test_outbox)
+WRITES = [
+    "gmail.archive", "gmail.bulk_modify", "gmail.create_draft", "gmail.filter_create", "gmail.filter_delete",
+    "gmail.forward", "gmail.import_raw", "gmail.send", "gmail.send_draft", "gmail.unarchive",
+]
+
+
+class Clock:
+    def __init__(self):
+        self.now = 0.0
+
+    def __call__(self):
+        return self.now
+
+
+class ResultCacheTest(unittest.TestCase):
+    def setUp(self):
+        self.clock = Clock()
+
+    def test_entries_expire_after_ttl(self):
+        cache = ResultCache(ttl=30, clock=self.clock)
+        cache.put("k", {"v": 1})
+        self.clock.now = 29.9
+        self.assertEqual(cache.get("k"), {"v": 1})
+        self.clock.now = 30
+        self.assertIs(cache.get("k"), MISSING)
+        self.assertEqual(cache.stats()["entries"], 0)
+
+    def test_least_recently_used_is_evicted(self):
+        cache = ResultCache(ttl=30, max_entries=2, clock=self.clock)
+        cache.put("a", 1)
+        cache.put("b", 2)
+        cache.get("a")
+        cache.put("c", 3)
+        self.assertIs(cache.get("b"), MISSING)
+        self.assertEqual((cache.get("a"), cache.get("c")), (1, 3))
+        self.assertEqual(cache.stats()["evictions"], 1)
+
+    def test_values_are_copied(self):
+        cache = ResultCache(clock=self.clock)
+        value = {"labels": ["INBOX"]}
+        cache.put("k", value)
+        value["labels"].append("UNREAD")
+        cache.get("k")["labels"].clear()
+        self.assertEqual(cache.get("k"), {"labels": ["INBOX"]})
+
+    def test_invalidate_is_per_account(self):
+        cache = ResultCache(clock=self.clock)
+        work = cache.key("gmail.inbox", "work", {})
+        personal = cache.key("gmail.inbox", "personal", {})
+        cache.put(work, 1)
+        cache.put(personal, 2)
+        cache.invalidate(["gmail.inbox"], "work")
+        self.assertIs(cache.get(work), MISSING)
+        self.assertEqual(cache.get(personal), 2)
+
+    def test_key_ignores_param_order(self):
+        self.assertEqual(ResultCache.key("gmail.search", None, {"query": "a", "limit": 5}),
+                         ResultCache.key("gmail.search", None, {"limit": 5, "query": "a"}))
+
+    def test_disabled(self):
+        cache = ResultCache(ttl=0, clock=self.clock)
+        cache.put("k", 1)
+        self.assertIs(cache.get("k"), MISSING)
+
+
+class DispatchCacheTest(unittest.TestCase):
+    def setUp(self):
+        self.service = FakeGmailService({
+            "messages.list": {"messages": []},
+            "labels.get": {"id": "UNREAD", "messagesUnread": 0},
+            "threads.get": THREAD,
+            "messages.get": THREAD["messages"][1],
+        })
+        self.module = make_module(self.service)
+
+    def api_calls(self):
+        calls = [name for name, _ in self.service.calls]
+        self.service.calls.clear()
+        return calls
+
+    def read_all(self):
+        for method, params, _ in READS:
+            self.module.dispatch(method, params)
+
+    def test_repeated_reads_are_served_from_cache(self):
+        self.read_all()
+        self.assertEqual(self.api_calls(), FETCHED)
+        self.read_all()
+        self.assertEqual(self.api_calls(), [])
+
+    def test_fresh_bypasses_and_refills_the_cache(self):
+        self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001", "fresh": True})
+        self.assertEqual(self.api_calls(), ["threads.get", "threads.get"])
+        self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        self.assertEqual(self.api_calls(), [])
+
+    def test_every_write_clears_every_cached_read(self):
+        for method in WRITES:
+            handler = f"_cmd_{method.split('.', 1)[1]}"
+            with self.subTest(method=method), mock.patch.object(self.module, handler, return_value={}):
+                self.read_all()
+                self.api_calls()
+                self.module.dispatch(method, {})
+                self.read_all()
+                self.assertEqual(self.api_calls(), FETCHED)
+
+    def test_dry_run_keeps_the_cache(self):
+        self.read_all()
+        self.api_calls()
+        with mock.patch.object(self.module, "_cmd_archive", return_value={"dry_run": True}):
+            self.module.dispatch("gmail.archive", {"dry_run": True})
+        self.read_all()
+        self.assertEqual(self.api_calls(), [])
+
+
+if __name__ == "__main__":
+    unittest.main()
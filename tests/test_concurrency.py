@@ -0,0 +1,188 @@
+import threading
+import time
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.backend import ApiBackend
+from gmail_lib.concurrency import CallSlots, DaemonBusy, ShuttingDown
+
+
+class SlowGmail:
+    """messages.list that takes `delay` seconds and tracks how many overlap."""
+
+    def __init__(self, delay=0.02):
+        self.delay = delay
+        self.lock = threading.Lock()
+        self.active = 0
+        self.peak = 0
+        self.release = None
+
+    def list(self, **kwargs):
+        with self.lock:
+            self.active += 1
+            self.peak = max(self.peak, self.active)
+        try:
+            if self.release is not None:
+                self.release.wait(5)
+            else:
+                time.sleep(self.delay)
+            return {"messages": []}
+        finally:
+            with self.lock:
+                self.active -= 1
+
+
+class CallSlotsTest(unittest.TestCase):
+    def test_waits_then_fails_when_busy(self):
+        slots = CallSlots(max_concurrent=1, max_wait_secs=0.05)
+        with slots.slot("gmail.search"):
+            with self.assertRaisesRegex(DaemonBusy, "Daemon busy: gmail.inbox waited"):
+                with slots.slot("gmail.inbox"):
+                    pass
+        with slots.slot("gmail.inbox"):
+            pass
+        stats = slots.to_dict()
+        self.assertEqual((stats["running"], stats["peak"], stats["rejected"]), (0, 1, 1))
+
+    def test_wait_is_bounded_by_the_deadline(self):
+        slots = CallSlots(max_concurrent=1, max_wait_secs=60)
+        with slots.slot("gmail.search"):
+            started = time.monotonic()
+            with self.assertRaises(DaemonBusy):
+                with slots.slot("gmail.inbox", deadline=started + 0.05):
+                    pass
+            self.assertLess(time.monotonic() - started, 5)
+
+
+class ConcurrentDispatchTest(unittest.TestCase):
+    def test_fifty_simultaneous_calls(self):
+        gmail = SlowGmail()
+        module = make_module(FakeGmailService({"messages.list": gmail.list}))
+        module.call_slots = CallSlots(max_concurrent=4, max_wait_secs=30)
+
+        start = threading.Barrier(50)
+        errors = []
+
+        def call(n):
+            start.wait()
+            try:
+                module.dispatch("gmail.search", {"query": f"from:sender{n}@example.com"})
+            except Exception as e:  # pragma: no cover - reported below
+                errors.append(e)
+
+        threads = [threading.Thread(target=call, args=(n,)) for n in range(50)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join(30)
+
+        self.assertFalse([t for t in threads if t.is_alive()], "calls deadlocked")
+        self.assertEqual(errors, [])
+        self.assertLessEqual(gmail.peak, 4)
+        self.assertEqual(module.call_slots.to_dict()["peak"], gmail.peak)
+        self.assertEqual(module.dispatch("gmail.stats", {})["methods"]["gmail.search"]["count"], 50)
+
+    def test_busy_daemon_still_serves_cache_hits(self):
+        gmail = SlowGmail()
+        module = make_module(FakeGmailService({"messages.list": gmail.list}))
+        module.call_slots = CallSlots(max_concurrent=1, max_wait_secs=0.05)
+        module.dispatch("gmail.inbox", {})
+
+        gmail.release = threading.Event()
+        slow = threading.Thread(target=module.dispatch, args=("gmail.search", {"query": "slow"}))
+        slow.start()
+        try:
+            while gmail.active == 0:
+                time.sleep(0.001)
+            self.assertEqual(module.dispatch("gmail.inbox", {})["count"], 0)
+            with self.assertRaises(DaemonBusy):
+                module.dispatch("gmail.search", {"query": "other"})
+        finally:
+            gmail.release.set()
+            slow.join(5)
+
+
+class DrainTest(unittest.TestCase):
+    def test_drain_waits_for_running_calls_and_rejects_new_ones(self):
+        gmail = SlowGmail()
+        gmail.release = threading.Event()
+        module = make_module(FakeGmailService({"messages.list": gmail.list}))
+        results = []
+        slow = threading.Thread(target=lambda: results.append(module.dispatch("gmail.search", {"query": "slow"})))
+        slow.start()
+        while gmail.active == 0:
+            time.sleep(0.001)
+
+        drain = threading.Thread(target=lambda: results.append(module.dispatch("gmail.drain", {"timeout_secs": 5})))
+        drain.start()
+        while not module.in_flight.draining:
+            time.sleep(0.001)
+        with self.assertRaisesRegex(ShuttingDown, "gmail.inbox was not started"):
+            module.dispatch("gmail.inbox", {})
+        gmail.release.set()
+        slow.join(5)
+        drain.join(5)
+
+        searched, drained = results
+        self.assertEqual(searched["count"], 0)
+        self.assertTrue(drained["drained"])
+        self.assertEqual(drained["abandoned"], 0)
+        self.assertEqual(module.in_flight.to_dict(), {"in_flight": 0, "draining": True})
+
+    def test_drain_gives_up_after_timeout(self):
+        gmail = SlowGmail()
+        gmail.release = threading.Event()
+        module = make_module(FakeGmailService({"messages.list": gmail.list}))
+        slow = threading.Thread(target=module.dispatch, args=("gmail.search", {"query": "slow"}))
+        slow.start()
+        try:
+            while gmail.active == 0:
+                time.sleep(0.001)
+            result = module.dispatch("gmail.drain", {"timeout_secs": 0.05})
+            self.assertEqual((result["drained"], result["abandoned"]), (False, 1))
+        finally:
+            gmail.release.set()
+            slow.join(5)
+
+    def test_batch_operations_are_part_of_the_running_call(self):
+        module = make_module(FakeGmailService({"messages.list": {}}))
+        with module.in_flight.call("gmail.batch"):
+            self.assertTrue(module.in_flight.drain(0)["drained"])
+            self.assertEqual(module.dispatch("gmail.inbox", {})["count"], 0)
+
+
+class ThreadLocalHttpTest(unittest.TestCase):
+    def test_each_thread_gets_its_own_connection(self):
+        made = []
+
+        class Request:
+            def __init__(self, **kwargs):
+                pass
+
+            def execute(self, http=None):
+                return {"http": http}
+
+        class Node:
+            def __getattr__(self, name):
+                return Request
+
+        class Service:
+            def users(self):
+                return Node()
+
+        backend = ApiBackend(Service(), http_factory=lambda: made.append(object()) or made[-1])
+        seen = []
+        for _ in range(2):
+            seen.append(backend.call("getProfile")["http"])
+        thread = threading.Thread(target=lambda: seen.append(backend.call("getProfile")["http"]))
+        thread.start()
+        thread.join()
+
+        self.assertEqual(len(made), 2)
+        self.assertIs(seen[0], seen[1])
+        self.assertIsNot(seen[0], seen[2])
+
+
+if __name__ == "__main__":
+    unittest.main()
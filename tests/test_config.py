@@ -0,0 +1,137 @@
+import json
+import os
+import re
+import unittest
+from unittest import mock
+
+from helpers import REPO_ROOT, FakeGmailService, HttpError, make_module
+
+from gmail_lib.policy import DEFAULT_TIMEOUT_KEY, CallTimeouts, RetryPolicy
+
+
+def flaky(*statuses, result=None):
+    """Handler that fails with each status in turn, then returns `result`."""
+    remaining = list(statuses)
+
+    def handler(**kwargs):
+        if remaining:
+            raise HttpError(remaining.pop(0))
+        return result or {}
+    return handler
+
+
+class RetryPolicyTest(unittest.TestCase):
+    def test_defaults_do_not_retry(self):
+        with self.assertRaises(HttpError):
+            RetryPolicy().run("messages.list", flaky(503), sleep=lambda s: None)
+
+    def test_retries_transient_errors_with_backoff(self):
+        delays = []
+        policy = RetryPolicy(max_attempts=3, initial_backoff_ms=100, max_backoff_ms=150)
+        self.assertEqual(policy.run("messages.list", flaky(429, 503, result={"ok": 1}), sleep=delays.append), {"ok": 1})
+        self.assertEqual(delays, [0.1, 0.15])
+
+    def test_gives_up_after_max_attempts(self):
+        policy = RetryPolicy(max_attempts=2, initial_backoff_ms=0)
+        with self.assertRaises(HttpError):
+            policy.run("messages.list", flaky(503, 503, result={}), sleep=lambda s: None)
+
+    def test_client_errors_are_not_retried(self):
+        calls = []
+        policy = RetryPolicy(max_attempts=3, initial_backoff_ms=0)
+        with self.assertRaises(HttpError):
+            policy.run("messages.get", lambda: calls.append(1) or flaky(404)(), sleep=lambda s: None)
+        self.assertEqual(len(calls), 1)
+
+    def test_sends_are_only_retried_on_rate_limit(self):
+        policy = RetryPolicy(max_attempts=3, initial_backoff_ms=0)
+        self.assertEqual(policy.run("messages.send", flaky(429, result={"id": "m"}), sleep=lambda s: None), {"id": "m"})
+        with self.assertRaises(HttpError):
+            policy.run("messages.send", flaky(503, result={"id": "m"}), sleep=lambda s: None)
+
+    def test_from_env(self):
+        policy = RetryPolicy.from_env({"FGP_GMAIL_RETRY_MAX_ATTEMPTS": "4", "FGP_GMAIL_RETRY_MAX_BACKOFF_MS": "1000"})
+        self.assertEqual(policy.to_dict(), {"max_attempts": 4, "initial_backoff_ms": 500, "max_backoff_ms": 1000})
+
+
+class CallTimeoutsTest(unittest.TestCase):
+    METHODS = ["gmail.search", "gmail.bulk_modify"]
+
+    def test_method_entry_wins_over_default(self):
+        timeouts = CallTimeouts.from_env(
+            {"FGP_GMAIL_METHOD_TIMEOUTS": json.dumps({"default": 60, "bulk_modify": 300})}, self.METHODS)
+        self.assertEqual(timeouts.for_method("gmail.bulk_modify"), 300)
+        self.assertEqual(timeouts.for_method("gmail.search"), 60)
+
+    def test_no_timeouts_by_default(self):
+        self.assertIsNone(CallTimeouts.from_env({}, self.METHODS).for_method("gmail.search"))
+
+    def test_unknown_method_is_rejected(self):
+        with self.assertRaisesRegex(ValueError, r"\[timeouts\] serch: unknown method"):
+            CallTimeouts.from_env({"FGP_GMAIL_METHOD_TIMEOUTS": json.dumps({"serch": 5})}, self.METHODS)
+
+    def test_non_positive_timeout_is_rejected(self):
+        with self.assertRaisesRegex(ValueError, "positive number of seconds"):
+            CallTimeouts.from_env({"FGP_GMAIL_METHOD_TIMEOUTS": json.dumps({"search": 0})}, self.METHODS)
+
+    def test_config_file_knows_every_method(self):
+        source = (REPO_ROOT / "src" / "config.rs").read_text()
+        declared = re.search(r"const TIMEOUT_METHODS: &\[&str\] = &\[(.*?)\];", source, re.S).group(1)
+        methods = {m["name"].split(".", 1)[1] for m in make_module(FakeGmailService({})).method_list()}
+        self.assertEqual(set(re.findall(r'"([^"]+)"', declared)), methods | {DEFAULT_TIMEOUT_KEY})
+
+
+class ModuleConfigTest(unittest.TestCase):
+    def test_config_reports_effective_settings(self):
+        env = {
+            "FGP_GMAIL_CONFIG_FILE": "/home/u/.fgp/services/gmail/config.toml",
+            "FGP_GMAIL_SOCKET": "/tmp/gmail.sock",
+            "FGP_GMAIL_CACHE_TTL": "5",
+            "FGP_GMAIL_METHOD_TIMEOUTS": json.dumps({"gmail.search": 20}),
+            "FGP_GMAIL_RETRY_MAX_ATTEMPTS": "3",
+        }
+        with mock.patch.dict(os.environ, env):
+            config = make_module(FakeGmailService({})).dispatch("gmail.config", {})
+        self.assertEqual(config["source"], env["FGP_GMAIL_CONFIG_FILE"])
+        self.assertEqual(config["daemon"]["socket"], "/tmp/gmail.sock")
+        self.assertEqual(config["cache"]["ttl_secs"], 5.0)
+        self.assertEqual(config["timeouts"]["methods"], {"search": 20.0})
+        self.assertEqual(config["retry"]["max_attempts"], 3)
+
+    def test_defaults_without_config_file(self):
+        with mock.patch.dict(os.environ, {}, clear=False):
+            os.environ.pop("FGP_GMAIL_CONFIG_FILE", None)
+            config = make_module(FakeGmailService({})).dispatch("gmail.config", {})
+        self.assertIsNone(config["source"])
+        self.assertEqual(config["retry"]["max_attempts"], 1)
+        self.assertEqual(config["timeouts"]["methods"], {})
+
+    def test_api_calls_retry_through_the_module(self):
+        service = FakeGmailService({"messages.list": flaky(503, result={"messages": []})})
+        with mock.patch.dict(os.environ, {"FGP_GMAIL_RETRY_MAX_ATTEMPTS": "2",
+                                          "FGP_GMAIL_RETRY_INITIAL_BACKOFF_MS": "0"}):
+            module = make_module(service)
+        self.assertEqual(module.dispatch("gmail.inbox", {})["count"], 0)
+        self.assertEqual([name for name, _ in service.calls], ["messages.list", "messages.list"])
+
+    def test_method_timeout_stops_further_api_calls(self):
+        now = [0.0]
+
+        def slow_list(**kwargs):
+            now[0] += 9
+            return {"messages": [{"id": "m-1"}, {"id": "m-2"}]}
+
+        service = FakeGmailService({
+            "messages.list": slow_list,
+            "messages.get": {"id": "m-1", "threadId": "t", "payload": {"headers": []}},
+        })
+        with mock.patch.dict(os.environ, {"FGP_GMAIL_METHOD_TIMEOUTS": json.dumps({"inbox": 5})}):
+            module = make_module(service)
+        with mock.patch("gmail.time.monotonic", lambda: now[0]):
+            with self.assertRaisesRegex(TimeoutError, "gmail.inbox exceeded its 5s timeout"):
+                module.dispatch("gmail.inbox", {"fresh": True})
+        self.assertEqual([name for name, _ in service.calls], ["messages.list"])
+
+
+if __name__ == "__main__":
+    unittest.main()
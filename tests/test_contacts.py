@@ -0,0 +1,154 @@
+import json
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.contacts import ContactBook, matches, normalize
+
+NOW = 1768383000.0  # 2026-01-14T09:30:00Z
+DAY_MS = 86400 * 1000
+
+
+def message(msg_id, days_ago, sent=False, **headers):
+    return {
+        "id": msg_id,
+        "labelIds": ["SENT"] if sent else ["INBOX"],
+        "internalDate": str(int(NOW * 1000 - days_ago * DAY_MS)),
+        "payload": {"headers": [{"name": name, "value": value} for name, value in headers.items()]},
+    }
+
+
+MESSAGES = [
+    message("m1", 1, From="Dave Smith <Dave@Example.com>", To="me@example.com"),
+    message("m2", 2, From="me@example.com", To="dave@example.com, Carol <carol@example.com>", sent=True),
+    message("m3", 40, From='"Smith, Dave" <dave@example.com>', To="me@example.com"),
+    message("m4", 3, From="Dave Smith <dave@example.com>", Cc="ops-alerts@example.com"),
+    message("m5", 90, From="Davina Jones <davina@jones.dev>", To="Me <me@example.com>"),
+]
+
+
+def gmail(messages=MESSAGES, page_size=None):
+    by_id = {msg["id"]: msg for msg in messages}
+
+    def list_messages(maxResults, pageToken=None, **kwargs):
+        start = int(pageToken or 0)
+        end = start + min(maxResults, page_size or maxResults)
+        result = {"messages": [{"id": msg["id"]} for msg in messages[start:end]]}
+        if end < len(messages):
+            result["nextPageToken"] = str(end)
+        return result
+
+    return FakeGmailService({
+        "getProfile": {"emailAddress": "Me@example.com", "historyId": "1"},
+        "messages.list": list_messages,
+        "messages.get": lambda id, **kwargs: by_id[id],
+    })
+
+
+class ContactsTest(unittest.TestCase):
+    def setUp(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        self.path = Path(tmp.name) / "contacts.json"
+        self.now = NOW
+
+    def make(self, service, **book_kwargs):
+        module = make_module(service)
+        module.contacts = ContactBook(self.path, clock=lambda: self.now, **book_kwargs)
+        self.addCleanup(module.contacts.stop)
+        return module
+
+    def test_addresses_are_deduplicated_with_the_most_common_name(self):
+        result = self.make(gmail()).dispatch("gmail.contacts", {"query": "Dav"})
+        dave = result["contacts"][0]
+        self.assertEqual(dave["email"], "dave@example.com")
+        self.assertEqual(dave["name"], "Dave Smith")
+        self.assertEqual(dave["count"], 4)
+        self.assertEqual(dave["last_seen"], "2026-01-13T09:30:00Z")
+        self.assertEqual([c["email"] for c in result["contacts"]], ["dave@example.com", "davina@jones.dev"])
+
+    def test_own_address_is_left_out(self):
+        result = self.make(gmail()).dispatch("gmail.contacts", {"query": ""})
+        emails = [c["email"] for c in result["contacts"]]
+        self.assertNotIn("me@example.com", emails)
+        self.assertEqual(result["index"]["messages_scanned"], 5)
+
+    def test_sent_to_and_recent_contacts_score_higher(self):
+        contacts = self.make(gmail()).dispatch("gmail.contacts", {"query": ""})["contacts"]
+        scores = {c["email"]: c["score"] for c in contacts}
+        # Carol was written to once; ops-alerts only cc'd us, a day later
+        self.assertGreater(scores["carol@example.com"], scores["ops-alerts@example.com"])
+        self.assertGreater(scores["ops-alerts@example.com"], scores["davina@jones.dev"])
+        self.assertEqual([c["email"] for c in contacts], sorted(scores, key=lambda e: -scores[e]))
+
+    def test_matching(self):
+        contact = {"email": "dave.smith@example.com", "name": "Dave Smith"}
+        self.assertTrue(matches(contact, "DAVE"))
+        self.assertTrue(matches(contact, "smi"))
+        self.assertTrue(matches(contact, "dave.sm"))
+        self.assertTrue(matches(contact, "dave s"))
+        self.assertFalse(matches(contact, "example"))
+        self.assertFalse(matches(contact, "ave"))
+
+    def test_normalize(self):
+        self.assertEqual(normalize(' "Dave  Smith" ', " <Dave@Example.COM> "), ("Dave Smith", "dave@example.com"))
+        self.assertEqual(normalize("dave@example.com", "dave@example.com"), ("", "dave@example.com"))
+        self.assertIsNone(normalize("Undisclosed recipients", ""))
+
+    def test_index_is_cached_on_disk(self):
+        self.make(gmail()).dispatch("gmail.contacts", {"query": "dave"})
+        self.assertIn("default", json.loads(self.path.read_text())["accounts"])
+
+        service = gmail()
+        result = self.make(service).dispatch("gmail.contacts", {"query": "carol"})
+        self.assertEqual(result["contacts"][0]["name"], "Carol")
+        self.assertEqual(service.calls, [])
+
+    def test_lookback_bounds_the_scan(self):
+        service = gmail(page_size=2)
+        result = self.make(service, lookback=3).dispatch("gmail.contacts", {"query": ""})
+        self.assertEqual(result["index"]["messages_scanned"], 3)
+        gets = [kwargs["id"] for name, kwargs in service.calls if name == "messages.get"]
+        self.assertEqual(gets, ["m1", "m2", "m3"])
+
+    def test_stale_index_is_answered_then_rebuilt_in_the_background(self):
+        self.make(gmail(MESSAGES[:1])).dispatch("gmail.contacts", {"query": ""})
+        self.now += 7 * 3600
+
+        release = threading.Event()
+        service = gmail()
+        slow_get = service.handlers["messages.get"]
+        service.handlers["messages.get"] = lambda **kwargs: release.wait(5) and slow_get(**kwargs)
+        module = self.make(service)
+
+        result = module.dispatch("gmail.contacts", {"query": "carol"})
+        self.assertEqual(result["contacts"], [])
+        self.assertTrue(result["index"]["stale"])
+        self.assertTrue(result["index"]["refreshing"])
+
+        release.set()
+        deadline = time.monotonic() + 5
+        while module.dispatch("gmail.contacts", {"query": "carol"})["count"] == 0:
+            self.assertLess(time.monotonic(), deadline)
+            time.sleep(0.01)
+
+    def test_refresh_rebuilds_before_answering(self):
+        self.make(gmail(MESSAGES[:1])).dispatch("gmail.contacts", {"query": ""})
+        result = self.make(gmail()).dispatch("gmail.contacts", {"query": "carol", "refresh": True})
+        self.assertEqual(result["count"], 1)
+        self.assertFalse(result["index"]["stale"])
+
+    def test_params_are_validated(self):
+        module = self.make(gmail())
+        with self.assertRaisesRegex(ValueError, "query must be a string"):
+            module.dispatch("gmail.contacts", {})
+        with self.assertRaisesRegex(ValueError, "refresh must be a boolean"):
+            module.dispatch("gmail.contacts", {"query": "d", "refresh": "yes"})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,124 @@
+import threading
+import unittest
+
+import helpers  # noqa: F401  (puts module/ on sys.path)
+
+from gmail_lib.device_auth import (
+    CANCELLED,
+    COMPLETE,
+    DENIED,
+    DEVICE_CODE_URL,
+    EXPIRED,
+    PENDING,
+    SLOW_DOWN_STEP_SECS,
+    DeviceAuthError,
+    DeviceLogin,
+    OAuthClient,
+)
+
+CLIENT = OAuthClient("client-id", "client-secret")
+
+DEVICE_CODE = {
+    "device_code": "dev-123",
+    "user_code": "ABCD-EFGH",
+    "verification_url": "https://www.google.com/device",
+    "expires_in": 1800,
+    "interval": 5,
+}
+
+
+class FakeClock:
+    def __init__(self):
+        self.now = 1000.0
+
+    def __call__(self):
+        return self.now
+
+
+class FakeEndpoint:
+    """Answers the device-code request, then token polls in order."""
+
+    def __init__(self, *token_replies):
+        self.token_replies = list(token_replies)
+        self.requests = []
+
+    def __call__(self, url, fields):
+        self.requests.append((url, fields))
+        if url == DEVICE_CODE_URL:
+            return dict(DEVICE_CODE)
+        return self.token_replies.pop(0)
+
+
+def make_login(endpoint, tokens=None, clock=None):
+    tokens = [] if tokens is None else tokens
+    return DeviceLogin("default", CLIENT, ["scope-a", "scope-b"], tokens.append,
+                       post=endpoint, clock=clock or FakeClock())
+
+
+class DeviceLoginTest(unittest.TestCase):
+    def test_start_returns_code_and_url(self):
+        endpoint = FakeEndpoint()
+        login = make_login(endpoint)
+        doc = login.to_dict()
+        self.assertEqual(doc["user_code"], "ABCD-EFGH")
+        self.assertEqual(doc["verification_url"], "https://www.google.com/device")
+        self.assertEqual(doc["expires_in"], 1800)
+        self.assertEqual(doc["state"], PENDING)
+        self.assertEqual(endpoint.requests[0][1]["scope"], "scope-a scope-b")
+
+    def test_pending_then_token(self):
+        tokens = []
+        token = {"access_token": "at", "refresh_token": "rt", "expires_in": 3599}
+        login = make_login(FakeEndpoint({"error": "authorization_pending"}, token), tokens)
+        self.assertEqual(login.poll_once(), PENDING)
+        self.assertEqual(login.poll_once(), COMPLETE)
+        self.assertEqual(tokens, [token])
+
+    def test_slow_down_backs_off(self):
+        login = make_login(FakeEndpoint({"error": "slow_down"}))
+        login.poll_once()
+        self.assertEqual(login.interval, 5 + SLOW_DOWN_STEP_SECS)
+        self.assertEqual(login.state, PENDING)
+
+    def test_denied(self):
+        login = make_login(FakeEndpoint({"error": "access_denied"}))
+        self.assertEqual(login.poll_once(), DENIED)
+
+    def test_expiry(self):
+        clock = FakeClock()
+        endpoint = FakeEndpoint()
+        login = make_login(endpoint, clock=clock)
+        clock.now += 1801
+        self.assertEqual(login.poll_once(), EXPIRED)
+        # No token request once the code is known to be dead
+        self.assertEqual(len(endpoint.requests), 1)
+
+    def test_server_reported_expiry(self):
+        login = make_login(FakeEndpoint({"error": "expired_token"}))
+        self.assertEqual(login.poll_once(), EXPIRED)
+
+    def test_cancel_stops_poller(self):
+        polled = threading.Event()
+
+        def endpoint(url, fields):
+            if url == DEVICE_CODE_URL:
+                return dict(DEVICE_CODE, interval=0.01)
+            polled.set()
+            return {"error": "authorization_pending"}
+
+        login = make_login(endpoint).start()
+        self.assertTrue(polled.wait(1))
+        self.assertTrue(login.cancel())
+        self.assertEqual(login.wait(1), CANCELLED)
+        self.assertFalse(login.cancel())
+
+    def test_rejected_device_code_request(self):
+        def endpoint(url, fields):
+            return {"error": "invalid_client", "error_description": "wrong client type"}
+
+        with self.assertRaisesRegex(DeviceAuthError, "invalid_client: wrong client type"):
+            make_login(endpoint)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,61 @@
+import base64
+import email
+import unittest
+from unittest import mock
+
+from helpers import FakeGmailService, HttpError, make_module
+
+
+def not_found(**kwargs):
+    raise HttpError(404)
+
+
+class DraftTest(unittest.TestCase):
+    def test_create_draft_uses_send_params(self):
+        service = FakeGmailService({
+            "drafts.create": {"id": "r-1", "message": {"id": "m-1", "threadId": "t-1"}},
+        })
+        result = make_module(service).dispatch("gmail.create_draft", {
+            "to": "a@example.com", "subject": "Review me", "body": "Draft body", "cc": "b@example.com",
+        })
+        self.assertEqual(result["draft_id"], "r-1")
+        self.assertEqual(result["message_id"], "m-1")
+
+        name, kwargs = service.calls[0]
+        raw = base64.urlsafe_b64decode(kwargs["body"]["message"]["raw"])
+        parsed = email.message_from_bytes(raw)
+        self.assertEqual(parsed["subject"], "Review me")
+        self.assertEqual(parsed["cc"], "b@example.com")
+
+    def test_create_draft_validates_like_send(self):
+        with self.assertRaisesRegex(ValueError, "to, subject, and body"):
+            make_module(FakeGmailService({})).dispatch("gmail.create_draft", {"to": "a@example.com"})
+
+    def test_send_draft(self):
+        service = FakeGmailService({"drafts.send": {"id": "m-1", "threadId": "t-1"}})
+        result = make_module(service).dispatch("gmail.send_draft", {"draft_id": "r-1"})
+        self.assertEqual(result, {"sent": True, "message_id": "m-1", "thread_id": "t-1",
+                                  "attachments": None, "draft_id": "r-1", "request_id": mock.ANY})
+
+    def test_send_missing_draft(self):
+        module = make_module(FakeGmailService({"drafts.send": not_found}))
+        with self.assertRaisesRegex(ValueError, "Draft not found: r-gone"):
+            module.dispatch("gmail.send_draft", {"draft_id": "r-gone"})
+
+    def test_list_drafts(self):
+        service = FakeGmailService({
+            "drafts.list": {"drafts": [{"id": "r-1", "message": {"id": "m-1", "threadId": "t-1"}}]},
+            "drafts.get": {"id": "r-1", "message": {
+                "id": "m-1", "threadId": "t-1", "labelIds": ["DRAFT"],
+                "payload": {"headers": [{"name": "Subject", "value": "Review me"}]},
+            }},
+        })
+        result = make_module(service).dispatch("gmail.list_drafts", {"limit": 5})
+        self.assertEqual(result["count"], 1)
+        self.assertEqual(result["drafts"][0]["draft_id"], "r-1")
+        self.assertEqual(result["drafts"][0]["message"]["subject"], "Review me")
+        self.assertEqual(service.calls[0][1]["maxResults"], 5)
+
+
+if __name__ == "__main__":
+    unittest.main()
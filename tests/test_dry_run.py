@@ -0,0 +1,100 @@
+import base64
+import unittest
+
+from helpers import FakeGmailService, HttpError, make_module
+
+from gmail_lib.backend import NotFound
+
+LABELS = {"labels": [
+    {"id": "INBOX", "name": "INBOX"},
+    {"id": "Label_1", "name": "Receipts"},
+]}
+
+
+def not_found(**kwargs):
+    raise HttpError(404)
+
+
+class DryRunTest(unittest.TestCase):
+    def test_send_returns_the_message_without_sending(self):
+        service = FakeGmailService({})
+        result = make_module(service).dispatch(
+            "gmail.send", {"to": "a@example.com", "subject": "Hi", "body": "Hello", "dry_run": True})
+        self.assertTrue(result["dry_run"])
+        [call] = result["would_call"]
+        self.assertEqual(call["method"], "messages.send")
+        self.assertEqual(call["params"]["userId"], "me")
+        self.assertIn(b"subject: Hi", base64.urlsafe_b64decode(call["params"]["body"]["raw"]))
+        self.assertEqual(service.calls, [])
+
+    def test_send_still_validates(self):
+        with self.assertRaisesRegex(FileNotFoundError, "Attachment not found"):
+            make_module(FakeGmailService({})).dispatch("gmail.send", {
+                "to": "a@example.com", "subject": "Hi", "body": "Hello",
+                "attachments": [{"path": "/nonexistent/file.pdf"}], "dry_run": True,
+            })
+
+    def test_dry_run_must_be_a_boolean(self):
+        with self.assertRaisesRegex(ValueError, "dry_run must be a boolean"):
+            make_module(FakeGmailService({})).dispatch("gmail.archive", {"message_id": "m1", "dry_run": "yes"})
+
+    def test_archive_lists_each_modify(self):
+        service = FakeGmailService({})
+        result = make_module(service).dispatch("gmail.archive", {"message_ids": ["m1", "m2"], "dry_run": True})
+        self.assertEqual(result["count"], 2)
+        self.assertEqual(result["would_call"], [
+            {"method": "messages.modify",
+             "params": {"id": message_id, "body": {"addLabelIds": [], "removeLabelIds": ["INBOX"]}, "userId": "me"}}
+            for message_id in ("m1", "m2")
+        ])
+        self.assertEqual(service.calls, [])
+
+    def test_send_draft_checks_the_draft_exists(self):
+        service = FakeGmailService({"drafts.get": {"id": "r-1"}})
+        result = make_module(service).dispatch("gmail.send_draft", {"draft_id": "r-1", "dry_run": True})
+        self.assertEqual(result["would_call"], [{"method": "drafts.send", "params": {"body": {"id": "r-1"}, "userId": "me"}}])
+        self.assertEqual([name for name, _ in service.calls], ["drafts.get"])
+
+        with self.assertRaises(NotFound):
+            make_module(FakeGmailService({"drafts.get": not_found})).dispatch(
+                "gmail.send_draft", {"draft_id": "gone", "dry_run": True})
+
+    def test_filter_create_plans_missing_labels(self):
+        service = FakeGmailService({"labels.list": LABELS})
+        result = make_module(service).dispatch("gmail.filter_create", {
+            "from": "ci@example.com", "add_label": "CI", "create_label_if_missing": True, "dry_run": True,
+        })
+        self.assertEqual([call["method"] for call in result["would_call"]], ["labels.create", "settings.filters.create"])
+        self.assertEqual(result["created_labels"], ["CI"])
+        self.assertEqual([name for name, _ in service.calls], ["labels.list"])
+
+    def test_bulk_modify_lists_batches(self):
+        ids = [{"id": f"m{i}"} for i in range(150)]
+        service = FakeGmailService({
+            "messages.list": {"messages": ids},
+            "messages.get": lambda id, **kw: {"id": id, "threadId": id, "payload": {"headers": []}},
+        })
+        result = make_module(service).dispatch("gmail.bulk_modify", {
+            "query": "from:ci", "action": "mark_read", "max_messages": 150, "dry_run": True,
+        })
+        self.assertEqual(result["matched"], 150)
+        self.assertEqual([len(call["params"]["body"]["ids"]) for call in result["would_call"]], [100, 50])
+        self.assertNotIn("messages.batchModify", [name for name, _ in service.calls])
+
+    def test_dry_run_keeps_the_cache(self):
+        service = FakeGmailService({"messages.list": {"messages": []}})
+        module = make_module(service)
+        module.dispatch("gmail.inbox", {})
+        module.dispatch("gmail.archive", {"message_id": "m1", "dry_run": True})
+        module.dispatch("gmail.inbox", {})
+        self.assertEqual([name for name, _ in service.calls], ["messages.list"])
+
+    def test_registered_on_mutating_methods_only(self):
+        methods = {m["name"]: [p["name"] for p in m["params"]] for m in make_module(FakeGmailService({})).method_list()}
+        self.assertIn("dry_run", methods["gmail.send"])
+        self.assertEqual(methods["gmail.bulk_modify"].count("dry_run"), 1)
+        self.assertNotIn("dry_run", methods["gmail.search"])
+
+
+if __name__ == "__main__":
+    unittest.main()
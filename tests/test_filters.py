@@ -0,0 +1,100 @@
+import unittest
+
+from helpers import FakeGmailService, HttpError, make_module
+
+from gmail_lib.backend import NotFound
+from gmail_lib.filters import build_filter, describe_filter
+
+LABELS = {"labels": [
+    {"id": "INBOX", "name": "INBOX"},
+    {"id": "UNREAD", "name": "UNREAD"},
+    {"id": "Label_1", "name": "Receipts"},
+]}
+
+
+class BuildFilterTest(unittest.TestCase):
+    def test_translates_params(self):
+        body = build_filter(
+            {"from": "billing@example.com", "has_attachment": True,
+             "add_label": "Receipts", "archive": True, "mark_read": True},
+            {"Receipts": "Label_1"}.__getitem__,
+        )
+        self.assertEqual(body, {
+            "criteria": {"from": "billing@example.com", "hasAttachment": True},
+            "action": {"addLabelIds": ["Label_1"], "removeLabelIds": ["INBOX", "UNREAD"]},
+        })
+
+    def test_requires_criterion_and_action(self):
+        with self.assertRaisesRegex(ValueError, "at least one criterion"):
+            build_filter({"archive": True}, str)
+        with self.assertRaisesRegex(ValueError, "at least one action"):
+            build_filter({"from": "a@example.com"}, str)
+
+    def test_describe_round_trips_vocabulary(self):
+        described = describe_filter(
+            {"id": "f1", "criteria": {"subject": "Invoice", "size": 1000},
+             "action": {"addLabelIds": ["Label_1"], "removeLabelIds": ["INBOX"]}},
+            {"Label_1": "Receipts"},
+        )
+        self.assertEqual(described["criteria"], {"subject": "Invoice", "size": 1000})
+        self.assertEqual(described["actions"]["add_label"], ["Receipts"])
+        self.assertTrue(described["actions"]["archive"])
+        self.assertFalse(described["actions"]["mark_read"])
+
+
+class FilterMethodsTest(unittest.TestCase):
+    def test_create_with_existing_label(self):
+        service = FakeGmailService({"labels.list": LABELS, "settings.filters.create": {"id": "f-new"}})
+        result = make_module(service).dispatch("gmail.filter_create", {
+            "query": "invoice", "add_label": "receipts",
+        })
+        self.assertEqual(result["filter_id"], "f-new")
+        self.assertEqual(result["action"], {"addLabelIds": ["Label_1"]})
+        self.assertEqual(result["created_labels"], [])
+
+    def test_missing_label(self):
+        service = FakeGmailService({"labels.list": LABELS})
+        with self.assertRaisesRegex(NotFound, "Label not found: 'Travel'"):
+            make_module(service).dispatch("gmail.filter_create", {"from": "a@x.com", "add_label": "Travel"})
+
+        service = FakeGmailService({
+            "labels.list": LABELS,
+            "labels.create": {"id": "Label_9", "name": "Travel"},
+            "settings.filters.create": {"id": "f-new"},
+        })
+        result = make_module(service).dispatch("gmail.filter_create", {
+            "from": "a@x.com", "add_label": "Travel", "create_label_if_missing": True,
+        })
+        self.assertEqual(result["created_labels"], ["Travel"])
+        self.assertEqual(result["action"]["addLabelIds"], ["Label_9"])
+
+    def test_invalid_filter_creates_no_labels(self):
+        service = FakeGmailService({"labels.list": LABELS})
+        with self.assertRaisesRegex(ValueError, "at least one criterion"):
+            make_module(service).dispatch("gmail.filter_create", {
+                "add_label": "Travel", "create_label_if_missing": True,
+            })
+        self.assertNotIn("labels.create", [name for name, _ in service.calls])
+
+    def test_list(self):
+        service = FakeGmailService({
+            "labels.list": LABELS,
+            "settings.filters.list": {"filter": [
+                {"id": "f1", "criteria": {"from": "a@x.com"}, "action": {"addLabelIds": ["Label_1"]}},
+            ]},
+        })
+        result = make_module(service).dispatch("gmail.filters", {})
+        self.assertEqual(result["count"], 1)
+        self.assertEqual(result["filters"][0]["actions"]["add_label"], ["Receipts"])
+
+    def test_delete_missing_filter(self):
+        def missing(**kwargs):
+            raise HttpError(404)
+
+        module = make_module(FakeGmailService({"settings.filters.delete": missing}))
+        with self.assertRaisesRegex(NotFound, "Filter not found: f-gone"):
+            module.dispatch("gmail.filter_delete", {"filter_id": "f-gone"})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,103 @@
+import base64
+import email
+import unittest
+from email import policy
+from email.message import EmailMessage
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.forward import forward_subject
+from gmail_lib.mime import html_to_text
+
+
+def original(html_only=False, attachment=True):
+    message = EmailMessage()
+    message["From"] = '"Alice Example" <alice@example.com>'
+    message["To"] = "bob@example.com"
+    message["Date"] = "Mon, 05 Jan 2026 09:30:00 +0000"
+    message["Subject"] = "Quarterly numbers"
+    if html_only:
+        message.set_content("<html><body><p>See <b>attached</b>.</p></body></html>", subtype="html")
+    else:
+        message.set_content("See attached.")
+        message.add_alternative("<p>See <b>attached</b>.</p>", subtype="html")
+    if attachment:
+        message.add_attachment(b"%PDF-1.4 numbers", maintype="application", subtype="pdf", filename="q1.pdf")
+    return {"id": "orig-1", "threadId": "t-1", "labelIds": ["INBOX"],
+            "raw": base64.urlsafe_b64encode(message.as_bytes()).decode()}
+
+
+def forward(params, **original_kwargs):
+    service = FakeGmailService({
+        "messages.get": original(**original_kwargs),
+        "messages.send": {"id": "fwd-1", "threadId": "t-2"},
+    })
+    result = make_module(service).dispatch("gmail.forward", dict({"message_id": "orig-1"}, **params))
+    sent = None
+    if service.calls[-1][0] == "messages.send":
+        raw = service.calls[-1][1]["body"]["raw"]
+        sent = email.message_from_bytes(base64.urlsafe_b64decode(raw), policy=policy.default)
+    return result, sent, service
+
+
+class ForwardTest(unittest.TestCase):
+    def test_forwards_with_header_block_and_attachments(self):
+        result, sent, service = forward({"to": ["carol@example.com", "dan@example.com"], "comment": "FYI"})
+        self.assertEqual(service.calls[0], ("messages.get", {"userId": "me", "id": "orig-1", "format": "raw"}))
+        self.assertEqual(result["forwarded_id"], "orig-1")
+        self.assertEqual(sent["To"], "carol@example.com, dan@example.com")
+        self.assertEqual(sent["Subject"], "Fwd: Quarterly numbers")
+
+        text = sent.get_body(("plain",)).get_content()
+        self.assertTrue(text.startswith("FYI\n\n---------- Forwarded message ---------\n"))
+        self.assertIn('From: Alice Example <alice@example.com>\nDate: Mon, 05 Jan 2026 09:30:00 +0000\n'
+                      'Subject: Quarterly numbers\nTo: bob@example.com\n\nSee attached.', text)
+        self.assertIn("<p>See <b>attached</b>.</p>", sent.get_body(("html",)).get_content())
+
+        [attachment] = list(sent.iter_attachments())
+        self.assertEqual(attachment.get_filename(), "q1.pdf")
+        self.assertEqual(attachment.get_content_type(), "application/pdf")
+        self.assertEqual(attachment.get_payload(decode=True), b"%PDF-1.4 numbers")
+
+    def test_without_attachments(self):
+        _, sent, _ = forward({"to": "carol@example.com", "include_attachments": False})
+        self.assertEqual(list(sent.iter_attachments()), [])
+
+    def test_html_only_original_gets_a_text_fallback(self):
+        _, sent, _ = forward({"to": "carol@example.com"}, html_only=True, attachment=False)
+        self.assertIn("\n\nSee attached.", sent.get_body(("plain",)).get_content())
+        self.assertNotIn("<body>", sent.get_body(("html",)).get_content())
+
+    def test_validates_before_fetching(self):
+        for params, error in (({}, "to parameter is required"), ({"to": [""]}, "to must be a string or a list"),
+                              ({"to": "c@example.com", "include_attachments": "no"}, "include_attachments")):
+            with self.subTest(params=params):
+                service = FakeGmailService({})
+                with self.assertRaisesRegex(ValueError, error):
+                    make_module(service).dispatch("gmail.forward", dict({"message_id": "orig-1"}, **params))
+                self.assertEqual(service.calls, [])
+
+    def test_dry_run(self):
+        result, sent, service = forward({"to": "carol@example.com", "dry_run": True})
+        self.assertIsNone(sent)
+        self.assertEqual(result["would_call"][0]["method"], "messages.send")
+        self.assertEqual(result["attachments"], [{"filename": "q1.pdf", "size": 16}])
+
+
+class ForwardHelpersTest(unittest.TestCase):
+    def test_subject_prefix_is_not_stacked(self):
+        self.assertEqual(forward_subject("Hello"), "Fwd: Hello")
+        self.assertEqual(forward_subject("FW: Hello"), "FW: Hello")
+        self.assertEqual(forward_subject("fwd:Hello"), "fwd:Hello")
+        self.assertEqual(forward_subject(""), "Fwd:")
+
+    def test_html_to_text(self):
+        self.assertEqual(
+            html_to_text('<style>p {}</style><p>Hi &amp; <b>bye</b></p><ul><li>one</li><li>two</li></ul>'
+                         '<a href="https://example.com">site</a><br>end'),
+            "Hi & bye\n\n- one\n- two\nsite (https://example.com)\nend",
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
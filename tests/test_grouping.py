@@ -0,0 +1,91 @@
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.grouping import group_summaries, sender_address, sender_domain
+
+MESSAGES = {
+    "m1": ('"Bar, Foo" <Foo@Bar.com>', ["INBOX", "UNREAD", "CATEGORY_UPDATES"]),
+    "m2": ("ci-bot@builds.bar.com", ["INBOX", "UNREAD", "Label_1"]),
+    "m3": ("Foo <foo@bar.com>", ["INBOX", "UNREAD"]),
+    "m4": ("alice@example.org", ["INBOX", "UNREAD", "Label_1"]),
+}
+
+
+def get_message(id, **kwargs):
+    sender, labels = MESSAGES[id]
+    return {"id": id, "threadId": id, "labelIds": labels,
+            "payload": {"headers": [{"name": "From", "value": sender}]}}
+
+
+def service():
+    return FakeGmailService({
+        "labels.get": {"messagesUnread": 240},
+        "labels.list": {"labels": [{"id": "Label_1", "name": "Builds"}]},
+        "messages.list": {"messages": [{"id": id} for id in MESSAGES], "nextPageToken": "p2"},
+        "messages.get": get_message,
+    })
+
+
+class SenderTest(unittest.TestCase):
+    def test_parses_from_headers(self):
+        self.assertEqual(sender_address('"Foo Bar" <foo@bar.com>'), "foo@bar.com")
+        self.assertEqual(sender_address('"Bar, Foo <x>" <Foo@Bar.com>'), "foo@bar.com")
+        self.assertEqual(sender_address("foo@bar.com"), "foo@bar.com")
+        self.assertEqual(sender_address("Mailer Daemon"), "mailer daemon")
+        self.assertEqual(sender_address(""), "(unknown sender)")
+        self.assertEqual(sender_domain("Foo <foo@Mail.Bar.com>"), "mail.bar.com")
+
+
+class GroupSummariesTest(unittest.TestCase):
+    def test_sorted_by_count_and_capped(self):
+        emails = [{"id": f"m{i}", "from": f"{name}@example.com"} for i, name in enumerate("aabbbc")]
+        groups = group_summaries(emails, "sender", max_per_group=2)
+        self.assertEqual([(g["key"], g["count"]) for g in groups],
+                         [("b@example.com", 3), ("a@example.com", 2), ("c@example.com", 1)])
+        self.assertEqual([e["id"] for e in groups[0]["emails"]], ["m2", "m3"])
+
+    def test_rejects_unknown_grouping(self):
+        with self.assertRaisesRegex(ValueError, "group_by must be one of"):
+            group_summaries([], "subject")
+
+
+class UnreadGroupingTest(unittest.TestCase):
+    def test_flat_by_default(self):
+        result = make_module(service()).dispatch("gmail.unread", {})
+        self.assertEqual(result["count"], 4)
+        self.assertNotIn("groups", result)
+
+    def test_group_by_sender(self):
+        result = make_module(service()).dispatch("gmail.unread", {"group_by": "sender", "max_per_group": 1})
+        self.assertEqual(result["unread_count"], 240)
+        self.assertTrue(result["truncated"])
+        self.assertNotIn("emails", result)
+        first = result["groups"][0]
+        self.assertEqual((first["key"], first["count"], first["name"]), ("foo@bar.com", 2, "Bar, Foo"))
+        self.assertEqual([e["id"] for e in first["emails"]], ["m1"])
+
+    def test_group_by_domain(self):
+        result = make_module(service()).dispatch("gmail.unread", {"group_by": "domain"})
+        self.assertEqual([(g["key"], g["count"]) for g in result["groups"]],
+                         [("bar.com", 2), ("builds.bar.com", 1), ("example.org", 1)])
+
+    def test_group_by_label_uses_names(self):
+        result = make_module(service()).dispatch("gmail.unread", {"group_by": "label"})
+        self.assertEqual([(g["key"], g["count"]) for g in result["groups"]],
+                         [("Builds", 2), ("(no label)", 1), ("CATEGORY_UPDATES", 1)])
+
+    def test_grouping_scans_up_to_max_limit(self):
+        gmail = service()
+        make_module(gmail).dispatch("gmail.unread", {"group_by": "sender"})
+        self.assertEqual(dict(gmail.calls)["messages.list"]["maxResults"], 100)
+
+    def test_age_filters_become_the_query(self):
+        gmail = service()
+        result = make_module(gmail).dispatch("gmail.unread", {"older_than": "7d"})
+        self.assertEqual(result["query"], "older_than:7d")
+        self.assertEqual(dict(gmail.calls)["messages.list"]["q"], "older_than:7d")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,180 @@
+import threading
+import time
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.health import (
+    CRITICAL,
+    DEGRADED,
+    DISABLED,
+    ERROR,
+    FAILED,
+    OK,
+    WARNING,
+    HealthReport,
+    SubsystemHealth,
+)
+
+
+PROFILE = {"emailAddress": "me@example.com", "messagesTotal": 10, "threadsTotal": 8, "historyId": "42"}
+
+
+def report(*subsystems):
+    return HealthReport(list(subsystems))
+
+
+def sub(name, status, core=False, **kwargs):
+    return SubsystemHealth(name, status, reason=f"{name}_{status}", message=name, core=core, **kwargs)
+
+
+class RollupTest(unittest.TestCase):
+    def test_all_ok(self):
+        doc = report(sub("auth", OK, core=True), sub("poller", OK)).to_dict()
+        self.assertEqual((doc["status"], doc["reason"], doc["severity"]), (OK, "all_ok", "info"))
+
+    def test_empty_report_is_ok(self):
+        self.assertEqual(report().overall_status(), OK)
+
+    def test_disabled_is_ignored(self):
+        doc = report(sub("auth", OK, core=True), sub("watch", DISABLED)).to_dict()
+        self.assertEqual(doc["status"], OK)
+
+    def test_core_failure_fails_overall(self):
+        self.assertEqual(report(sub("auth", FAILED, core=True), sub("poller", OK)).overall_status(), FAILED)
+
+    def test_auxiliary_failure_only_degrades(self):
+        doc = report(sub("auth", OK, core=True), sub("poller", FAILED)).to_dict()
+        self.assertEqual((doc["status"], doc["severity"]), (DEGRADED, ERROR))
+
+    def test_core_degraded_degrades(self):
+        self.assertEqual(report(sub("auth", DEGRADED, core=True)).overall_status(), DEGRADED)
+
+    def test_auth_failure_beats_degraded_poller(self):
+        doc = report(
+            sub("poller", DEGRADED),
+            sub("auth", FAILED, core=True, remediation="run fgp-gmail auth"),
+            sub("cache", OK),
+        ).to_dict()
+        self.assertEqual(doc["status"], FAILED)
+        self.assertEqual(doc["reason"], "auth_failed")
+        self.assertEqual(doc["severity"], CRITICAL)
+        self.assertEqual(doc["remediation"], "run fgp-gmail auth")
+
+    def test_core_failure_beats_auxiliary_failure(self):
+        doc = report(sub("poller", FAILED), sub("auth", FAILED, core=True)).to_dict()
+        self.assertEqual(doc["reason"], "auth_failed")
+
+    def test_failed_auxiliary_beats_degraded_core(self):
+        doc = report(sub("auth", DEGRADED, core=True), sub("poller", FAILED)).to_dict()
+        self.assertEqual((doc["status"], doc["reason"]), (DEGRADED, "poller_failed"))
+
+    def test_core_beats_auxiliary_at_same_status(self):
+        doc = report(sub("poller", DEGRADED), sub("venv", DEGRADED, core=True)).to_dict()
+        self.assertEqual(doc["reason"], "venv_degraded")
+
+    def test_explicit_severity_breaks_ties(self):
+        doc = report(sub("cache", DEGRADED), sub("poller", DEGRADED, severity=ERROR)).to_dict()
+        self.assertEqual(doc["reason"], "poller_degraded")
+
+    def test_default_severities(self):
+        self.assertEqual(sub("a", DEGRADED).severity, WARNING)
+        self.assertEqual(sub("a", FAILED).severity, ERROR)
+        self.assertEqual(sub("a", FAILED, core=True).severity, CRITICAL)
+
+    def test_invalid_status_rejected(self):
+        with self.assertRaises(ValueError):
+            sub("a", "sad")
+
+
+class FrameworkFlatteningTest(unittest.TestCase):
+    def test_flattened_shape(self):
+        flat = report(
+            sub("auth", OK, core=True),
+            sub("poller", DEGRADED, remediation="check network", latency_ms=12.5),
+            sub("watch", DISABLED),
+        ).to_framework()
+        self.assertEqual(set(flat), {"auth", "poller", "watch"})
+        self.assertEqual(flat["auth"], {"ok": True, "message": "auth", "latency_ms": None})
+        self.assertFalse(flat["poller"]["ok"])
+        self.assertEqual(flat["poller"]["latency_ms"], 12.5)
+        self.assertIn("[degraded]", flat["poller"]["message"])
+        self.assertIn("check network", flat["poller"]["message"])
+        self.assertTrue(flat["watch"]["ok"])
+
+    def test_module_health_method(self):
+        module = make_module(FakeGmailService({"getProfile": PROFILE}))
+        doc = module.dispatch("gmail.health", {})
+        self.assertEqual(doc["status"], OK)
+        self.assertEqual(doc["subsystems"]["gmail_service"]["reason"], "backend_ready")
+        self.assertTrue(module.health_check()["gmail_service"]["ok"])
+
+    def test_api_probe_reports_latency(self):
+        module = make_module(FakeGmailService({"getProfile": PROFILE}))
+        api = module.health_check()["gmail_api"]
+        self.assertTrue(api["ok"])
+        self.assertIsNotNone(api["latency_ms"])
+        self.assertIn("reachable as me@example.com", api["message"])
+
+    def test_api_probe_failure_fails_rollup(self):
+        def expired(**kwargs):
+            raise RuntimeError("invalid_grant: Token has been expired or revoked")
+
+        module = make_module(FakeGmailService({"getProfile": expired}))
+        doc = module.dispatch("gmail.health", {})
+        self.assertEqual(doc["status"], FAILED)
+        self.assertEqual(doc["reason"], "probe_failed")
+        self.assertIn("invalid_grant", doc["subsystems"]["gmail_api"]["message"])
+
+    def test_api_probe_timeout(self):
+        release = threading.Event()
+        module = make_module(FakeGmailService({"getProfile": lambda **kwargs: release.wait()}))
+        module.probe_timeout = 0.05
+        try:
+            self.assertEqual(module.dispatch("gmail.health", {})["reason"], "probe_timeout")
+            # A still-hung probe isn't stacked with another one
+            self.assertEqual(module.dispatch("gmail.health", {})["reason"], "probe_hung")
+        finally:
+            release.set()
+
+    def test_concurrent_health_checks_share_one_probe(self):
+        release = threading.Event()
+        probes = []
+
+        def get_profile(**kwargs):
+            probes.append(threading.current_thread().name)
+            release.wait(5)
+            return PROFILE
+
+        class CountingLock:
+            def __init__(self):
+                self.lock = threading.Lock()
+                self.entered = 0
+
+            def __enter__(self):
+                self.lock.acquire()
+                self.entered += 1
+
+            def __exit__(self, *exc):
+                self.lock.release()
+
+        module = make_module(FakeGmailService({"getProfile": get_profile}))
+        module.probe_timeout = 5
+        module._probe_lock = CountingLock()
+        results = []
+        callers = [threading.Thread(target=lambda: results.append(module.dispatch("gmail.health", {})))
+                   for _ in range(8)]
+        for caller in callers:
+            caller.start()
+        # Hold the probe until every caller has decided whether to start one
+        while module._probe_lock.entered < len(callers):
+            time.sleep(0.01)
+        release.set()
+        for caller in callers:
+            caller.join(5)
+        self.assertEqual(probes, ["gmail-health-probe"])
+        self.assertEqual([doc["status"] for doc in results], [OK] * 8)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,125 @@
+import time
+import unittest
+
+from helpers import FakeGmailService, HttpError, make_module
+
+from gmail_lib.history import FullSyncRequired, compact_changes, history_changes
+
+RECORDS = [
+    {"id": "101", "messagesAdded": [{"message": {"id": "m1", "threadId": "t1", "labelIds": ["INBOX", "UNREAD"]}}]},
+    {"id": "102", "labelsRemoved": [{"message": {"id": "m1", "threadId": "t1", "labelIds": ["INBOX"]},
+                                     "labelIds": ["UNREAD"]}]},
+    {"id": "103", "messagesDeleted": [{"message": {"id": "m0", "threadId": "t0"}}]},
+]
+
+LABELS = {"labels": [{"id": "INBOX", "name": "INBOX"}, {"id": "Label_1", "name": "Receipts"}]}
+
+
+def history_gone(**kwargs):
+    raise HttpError(404)
+
+
+class HistoryTest(unittest.TestCase):
+    def test_changes_are_flattened(self):
+        self.assertEqual(history_changes(RECORDS), [
+            {"history_id": "101", "type": "message_added", "message_id": "m1", "thread_id": "t1",
+             "labels": ["INBOX", "UNREAD"]},
+            {"history_id": "102", "type": "labels_removed", "message_id": "m1", "thread_id": "t1",
+             "labels": ["INBOX"], "changed_labels": ["UNREAD"]},
+            {"history_id": "103", "type": "message_deleted", "message_id": "m0", "thread_id": "t0", "labels": []},
+        ])
+
+    def test_label_changes_are_compacted(self):
+        def labels(history_id, field, message_id, changed):
+            return {"id": history_id, field: [{"message": {"id": message_id, "threadId": "t"}, "labelIds": changed}]}
+
+        changes = compact_changes(history_changes([
+            labels("1", "labelsRemoved", "m1", ["UNREAD"]),
+            labels("2", "labelsAdded", "m1", ["STARRED", "Label_1"]),
+            labels("3", "labelsAdded", "m2", ["STARRED"]),
+            labels("4", "labelsRemoved", "m1", ["STARRED", "INBOX"]),
+            labels("5", "labelsAdded", "m3", ["IMPORTANT"]),
+            {"id": "6", "messagesDeleted": [{"message": {"id": "m3", "threadId": "t"}}]},
+        ]))
+        self.assertEqual([(c["history_id"], c["type"], c["message_id"], c.get("changed_labels")) for c in changes], [
+            ("3", "labels_added", "m2", ["STARRED"]),
+            ("4", "labels_added", "m1", ["Label_1"]),
+            ("4", "labels_removed", "m1", ["UNREAD", "INBOX"]),
+            ("6", "message_deleted", "m3", None),
+        ])
+
+    def test_history_call(self):
+        service = FakeGmailService({"history.list": {"history": RECORDS, "historyId": "110", "nextPageToken": "p2"}})
+        result = make_module(service).dispatch("gmail.history", {
+            "start_history_id": 100, "history_types": ["message_added", "labels_removed"], "limit": 50,
+        })
+        self.assertEqual(service.calls[0][1], {
+            "userId": "me", "startHistoryId": "100", "maxResults": 50,
+            "historyTypes": ["messageAdded", "labelRemoved"],
+        })
+        self.assertEqual((result["history_id"], result["count"], result["next_page_token"]), ("110", 3, "p2"))
+
+    def test_validates_params(self):
+        module = make_module(FakeGmailService({}))
+        for params, error in (({}, "start_history_id parameter is required"),
+                              ({"start_history_id": "abc"}, "must be a history id"),
+                              ({"start_history_id": "1", "history_types": ["moved"]}, "history_types must be")):
+            with self.subTest(params=params):
+                with self.assertRaisesRegex(ValueError, error):
+                    module.dispatch("gmail.history", params)
+
+    def test_label_filter(self):
+        service = FakeGmailService({"labels.list": LABELS, "history.list": {"historyId": "110"}})
+        make_module(service).dispatch("gmail.history", {"start_history_id": "100", "label_filter": "receipts"})
+        self.assertEqual(service.calls[-1][1]["labelId"], "Label_1")
+
+    def test_expired_history_id(self):
+        module = make_module(FakeGmailService({"history.list": history_gone}))
+        with self.assertRaisesRegex(FullSyncRequired, "History id 5 is too old") as raised:
+            module.dispatch("gmail.history", {"start_history_id": "5"})
+        self.assertEqual((raised.exception.code, raised.exception.start_history_id), ("FullSyncRequired", "5"))
+        batch = module.dispatch("gmail.batch", {"operations": [
+            {"method": "gmail.history", "params": {"start_history_id": "5"}}]})
+        self.assertEqual(batch["results"][0]["error_type"], "FullSyncRequired")
+
+
+class PushWatchTest(unittest.TestCase):
+    def setUp(self):
+        self.expiration = str(int((time.time() + 7 * 86400) * 1000))
+        self.service = FakeGmailService({
+            "labels.list": LABELS,
+            "watch": {"historyId": 4321, "expiration": self.expiration},
+            "stop": {},
+        })
+        self.module = make_module(self.service)
+
+    def test_register_renew_and_stop(self):
+        result = self.module.dispatch("gmail.watch", {"topic": "projects/p/topics/gmail", "labels": ["Receipts"]})
+        self.assertEqual(self.service.calls[-1], ("watch", {"userId": "me", "body": {
+            "topicName": "projects/p/topics/gmail", "labelIds": ["Label_1"], "labelFilterBehavior": "include",
+        }}))
+        self.assertEqual(result["history_id"], "4321")
+        self.assertFalse(result["renewed"])
+        self.assertFalse(result["expired"])
+        self.assertGreater(result["expires_in_secs"], 6 * 86400)
+
+        self.assertTrue(self.module.dispatch("gmail.watch", {"topic": "projects/p/topics/gmail"})["renewed"])
+        self.assertEqual(self.module.dispatch("gmail.watch_status", {})["push"]["topic"], "projects/p/topics/gmail")
+
+        self.assertTrue(self.module.dispatch("gmail.unwatch", {})["push_stopped"])
+        self.assertEqual(self.service.calls[-1][0], "stop")
+        self.assertIsNone(self.module.dispatch("gmail.watch_status", {})["push"])
+        self.assertFalse(self.module.dispatch("gmail.unwatch", {})["push_stopped"])
+
+    def test_validates_before_registering(self):
+        for params, error in (({"topic": "gmail"}, "Pub/Sub topic"),
+                              ({"topic": "projects/p/topics/t", "label_filter_behavior": "only"}, "label_filter_behavior"),
+                              ({"topic": "projects/p/topics/t", "interval": 30}, "can't be combined")):
+            with self.subTest(params=params):
+                with self.assertRaisesRegex(ValueError, error):
+                    self.module.dispatch("gmail.watch", params)
+        self.assertEqual(self.service.calls, [])
+
+
+if __name__ == "__main__":
+    unittest.main()
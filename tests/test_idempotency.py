@@ -0,0 +1,124 @@
+import base64
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, HttpError, make_module
+
+from gmail_lib.idempotency import IdempotencyStore
+
+EMAIL = {"to": "a@example.com", "subject": "Invoice", "body": "Attached.", "idempotency_key": "invoice-42"}
+
+
+class Gmail:
+    """messages.send that can lose its response, and a Message-ID search."""
+
+    def __init__(self):
+        self.sent = []
+        self.delivered = {}
+        self.lose_next = False
+        self.delay = 0.0
+
+    def send(self, body, **kwargs):
+        time.sleep(self.delay)
+        self.sent.append(body["raw"])
+        message = {"id": f"sent-{len(self.sent)}", "threadId": "t-1"}
+        self.delivered[self.message_id(body["raw"])] = message
+        if self.lose_next:
+            self.lose_next = False
+            raise HttpError(503)
+        return message
+
+    def search(self, q, **kwargs):
+        found = self.delivered.get(f"<{q.split(':', 1)[1]}>")
+        return {"messages": [found]} if found else {}
+
+    @staticmethod
+    def message_id(raw):
+        for line in base64.urlsafe_b64decode(raw).decode().splitlines():
+            if line.startswith("Message-ID: "):
+                return line.split(": ", 1)[1]
+        return None
+
+
+class IdempotencyTest(unittest.TestCase):
+    def setUp(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        self.path = Path(tmp.name) / "idempotency.json"
+        self.gmail = Gmail()
+        self.module = self.make()
+
+    def make(self, **store_kwargs):
+        module = make_module(FakeGmailService({"messages.send": self.gmail.send,
+                                               "messages.list": self.gmail.search}))
+        module.idempotency = IdempotencyStore(self.path, **store_kwargs)
+        return module
+
+    def test_repeat_returns_the_first_result(self):
+        first = self.module.dispatch("gmail.send", EMAIL)
+        again = self.module.dispatch("gmail.send", EMAIL)
+        self.assertEqual(len(self.gmail.sent), 1)
+        self.assertEqual((first["message_id"], first["duplicate"]), ("sent-1", False))
+        self.assertEqual((again["message_id"], again["duplicate"]), ("sent-1", True))
+
+        # The record outlives the daemon, and is per account
+        self.assertTrue(self.make().dispatch("gmail.send", EMAIL)["duplicate"])
+        self.module.dispatch("gmail.send", dict(EMAIL, idempotency_key="invoice-43"))
+        self.assertNotIn("duplicate", self.module.dispatch("gmail.send", dict(EMAIL, idempotency_key=None)))
+        self.assertEqual(len(self.gmail.sent), 3)
+
+    def test_key_reused_for_another_message(self):
+        self.module.dispatch("gmail.send", EMAIL)
+        with self.assertRaisesRegex(ValueError, "already used for a different message"):
+            self.module.dispatch("gmail.send", dict(EMAIL, subject="Other"))
+        self.assertEqual(len(self.gmail.sent), 1)
+
+    def test_lost_response_is_found_on_retry(self):
+        self.gmail.lose_next = True
+        with self.assertRaises(HttpError):
+            self.module.dispatch("gmail.send", EMAIL)
+        again = self.make().dispatch("gmail.send", EMAIL)
+        self.assertEqual((again["message_id"], again["duplicate"]), ("sent-1", True))
+        self.assertEqual(len(self.gmail.sent), 1)
+
+    def test_unsent_retry_keeps_its_message_id(self):
+        self.gmail.lose_next = True
+        with self.assertRaises(HttpError):
+            self.module.dispatch("gmail.send", EMAIL)
+        self.gmail.delivered.clear()  # Gmail didn't keep it after all
+        self.assertFalse(self.module.dispatch("gmail.send", EMAIL)["duplicate"])
+        first, second = (Gmail.message_id(raw) for raw in self.gmail.sent)
+        self.assertEqual(first, second)
+
+    def test_invalid_send_leaves_the_key_free(self):
+        with self.assertRaisesRegex(ValueError, "required"):
+            self.module.dispatch("gmail.send", dict(EMAIL, subject=""))
+        self.assertFalse(self.module.dispatch("gmail.send", EMAIL)["duplicate"])
+
+    def test_concurrent_repeats_wait_for_the_first(self):
+        self.gmail.delay = 0.1
+        results = []
+        calls = [threading.Thread(target=lambda: results.append(self.module.dispatch("gmail.send", EMAIL)))
+                 for _ in range(3)]
+        for call in calls:
+            call.start()
+        for call in calls:
+            call.join(5)
+        self.assertEqual(len(self.gmail.sent), 1)
+        self.assertEqual(sorted(result["duplicate"] for result in results), [False, True, True])
+
+    def test_keys_expire(self):
+        now = [1000.0]
+        module = self.make(ttl=60, clock=lambda: now[0])
+        module.dispatch("gmail.send", EMAIL)
+        now[0] += 61
+        self.assertFalse(module.dispatch("gmail.send", EMAIL)["duplicate"])
+        self.assertEqual(len(self.gmail.sent), 2)
+        self.assertEqual(module.dispatch("gmail.config", {})["idempotency"]["keys"], 1)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,103 @@
+import base64
+import copy
+import unittest
+
+from helpers import FakeGmailService, load_fixture, make_module
+
+from gmail_lib.restricted import CONFIDENTIAL_MODE, SMIME_ENCRYPTED
+
+FULL = load_fixture("types/message_with_attachments.json")
+ENVELOPE = {k: v for k, v in FULL.items() if k != "payload"}
+METADATA = {**ENVELOPE, "payload": {"mimeType": FULL["payload"]["mimeType"], "headers": FULL["payload"]["headers"]}}
+
+EML = (
+    b"From: Billing <billing@example.com>\r\n"
+    b"To: me@example.com\r\n"
+    b"Date: Sat, 17 Jan 2026 09:00:00 +0000\r\n"
+    b"Subject: January invoice\r\n"
+    b"MIME-Version: 1.0\r\n"
+    b"Content-Type: multipart/mixed; boundary=b1\r\n"
+    b"\r\n"
+    b"--b1\r\n"
+    b"Content-Type: text/plain; charset=utf-8\r\n"
+    b"\r\n"
+    b"Invoice attached.\r\n"
+    b"--b1\r\n"
+    b"Content-Type: application/pdf\r\n"
+    b"Content-Disposition: attachment; filename=invoice.pdf\r\n"
+    b"Content-Transfer-Encoding: base64\r\n"
+    b"\r\n"
+    b"JVBERi0=\r\n"
+    b"--b1--\r\n"
+)
+CONFIDENTIAL_EML = EML.replace(b"MIME-Version", b"X-Gm-Confidential-Mode: true\r\nMIME-Version")
+SMIME_EML = (
+    b"From: Billing <billing@example.com>\r\n"
+    b"Subject: Encrypted\r\n"
+    b"Content-Type: application/pkcs7-mime; smime-type=enveloped-data; name=smime.p7m\r\n"
+    b"Content-Transfer-Encoding: base64\r\n"
+    b"\r\n"
+    b"MIAGCSqGSIb3DQEHA6CAMIACAQAxggE=\r\n"
+)
+
+
+def raw(data):
+    return {**ENVELOPE, "raw": base64.urlsafe_b64encode(data).decode().rstrip("=")}
+
+
+class MessageTest(unittest.TestCase):
+    def setUp(self):
+        self.responses = {"full": FULL, "metadata": METADATA, "raw": raw(EML)}
+        self.service = FakeGmailService({
+            "messages.get": lambda format, **kwargs: copy.deepcopy(self.responses[format]),
+        })
+        self.module = make_module(self.service)
+
+    def message(self, **params):
+        return self.module.dispatch("gmail.message", {"message_id": FULL["id"], **params})
+
+    def test_each_format(self):
+        for fmt, body in (("full", "Invoice and receipt for January attached."),
+                          ("raw", "Invoice attached."),
+                          ("metadata", None)):
+            with self.subTest(format=fmt):
+                result = self.message(format=fmt)
+                self.assertEqual(self.service.calls[-1][1]["format"], fmt)
+                self.assertEqual(result["format"], fmt)
+                self.assertEqual(result["headers"]["Subject"], "January invoice")
+                self.assertEqual((result["body_text"] or "").strip() or None, body)
+                self.assertIsNone(result["content_restricted"])
+        self.assertEqual(self.message()["format"], "full")
+
+    def test_attachments_with_full_and_raw(self):
+        for fmt, names in (("full", ["invoice-2026-01.pdf", "receipt.png"]), ("raw", ["invoice.pdf"])):
+            with self.subTest(format=fmt):
+                result = self.message(format=fmt, include_attachments=True)
+                self.assertEqual([a["filename"] for a in result["attachments"]], names)
+                self.assertEqual(result["attachment_count"], len(names))
+
+    def test_invalid_format(self):
+        with self.assertRaisesRegex(ValueError, r"format must be one of: full, metadata, raw \(got 'minimal'\)"):
+            self.message(format="minimal")
+        self.assertEqual(self.service.calls, [])
+
+    def test_include_attachments_needs_full_or_raw(self):
+        with self.assertRaisesRegex(ValueError, r"include_attachments needs format full or raw \(got 'metadata'\)"):
+            self.message(format="metadata", include_attachments=True)
+        self.assertEqual(self.service.calls, [])
+        self.assertNotIn("attachment_count", self.message(format="metadata", include_attachments=False))
+
+    def test_restricted_raw_message(self):
+        for data, restriction in ((CONFIDENTIAL_EML, CONFIDENTIAL_MODE), (SMIME_EML, SMIME_ENCRYPTED)):
+            self.responses["raw"] = raw(data)
+            with self.subTest(restriction=restriction):
+                result = self.message(format="raw", include_attachments=True, fresh=True)
+                self.assertEqual(result["content_restricted"], restriction)
+                self.assertIsNotNone(result["content_note"])
+                self.assertEqual((result["body_text"], result["body_html"], result["snippet"]), (None, None, ""))
+                self.assertIsNone(result["attachments"])
+                self.assertNotIn("attachment_count", result)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,71 @@
+import threading
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.metrics import Metrics, percentile
+
+
+class MetricsTest(unittest.TestCase):
+    def test_percentile_nearest_rank(self):
+        values = sorted(float(n) for n in range(1, 101))
+        self.assertEqual(percentile(values, 0.50), 50.0)
+        self.assertEqual(percentile(values, 0.95), 95.0)
+        self.assertEqual(percentile([7.0], 0.95), 7.0)
+        self.assertIsNone(percentile([], 0.5))
+
+    def test_window_bounds_percentiles_but_not_counters(self):
+        metrics = Metrics(window=3)
+        for total in (1000, 10, 20, 30):
+            metrics.record("gmail.inbox", total, total / 2)
+        metrics.record("gmail.inbox", 40, 0, error="ValueError: bad")
+        stats = metrics.snapshot()["gmail.inbox"]
+        self.assertEqual(stats["count"], 5)
+        self.assertEqual(stats["errors"], 1)
+        self.assertEqual(stats["window"], 3)
+        self.assertEqual(stats["max_ms"], 40)
+        self.assertEqual(stats["api_max_ms"], 15)
+        self.assertAlmostEqual(stats["window_error_rate"], 1 / 3)
+        self.assertEqual(stats["last_error"], "ValueError: bad")
+
+    def test_concurrent_records(self):
+        metrics = Metrics()
+
+        def worker():
+            for _ in range(500):
+                metrics.record("gmail.search", 1.0, 0.5)
+
+        threads = [threading.Thread(target=worker) for _ in range(8)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertEqual(metrics.snapshot()["gmail.search"]["count"], 4000)
+
+
+class ModuleStatsTest(unittest.TestCase):
+    def test_dispatch_records_calls_and_errors(self):
+        module = make_module(FakeGmailService({"messages.list": {"messages": []}}))
+        module.dispatch("gmail.inbox", {})
+        with self.assertRaises(ValueError):
+            module.dispatch("gmail.search", {})
+
+        stats = module.dispatch("gmail.stats", {})["methods"]
+        self.assertEqual(stats["gmail.inbox"]["count"], 1)
+        self.assertIsNotNone(stats["gmail.inbox"]["api_p50_ms"])
+        self.assertEqual(stats["gmail.search"]["errors"], 1)
+        self.assertNotIn("gmail.stats", stats)
+
+        health = module.health_check()
+        self.assertIn("1 calls, 0 errors", health["method.inbox"]["message"])
+        self.assertEqual(health["method.inbox"]["latency_ms"], stats["gmail.inbox"]["p95_ms"])
+
+    def test_reset(self):
+        module = make_module(FakeGmailService({"messages.list": {"messages": []}}))
+        module.dispatch("gmail.inbox", {})
+        self.assertTrue(module.dispatch("gmail.stats", {"reset": True})["reset"])
+        self.assertEqual(module.dispatch("gmail.stats", {})["methods"], {})
+
+
+if __name__ == "__main__":
+    unittest.main()
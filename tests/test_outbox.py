@@ -0,0 +1,168 @@
+import base64
+import json
+import shutil
+import tempfile
+import time
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, HttpError, make_module
+
+from gmail_lib.outbox import Outbox
+from gmail_lib.ratelimit import RateLimiter
+
+
+class Network:
+    """messages.send that fails with each queued status, then succeeds."""
+
+    def __init__(self, *failures):
+        self.failures = list(failures)
+        self.sent = []
+        self.delivered = set()
+
+    def send(self, body, **kwargs):
+        if self.failures:
+            failure = self.failures.pop(0)
+            if failure == "lost":
+                # Gmail took it, but the response never arrived
+                self.delivered.add(self.message_id(body["raw"]))
+                raise HttpError(503)
+            raise HttpError(failure)
+        self.sent.append(body["raw"])
+        return {"id": f"sent-{len(self.sent)}", "threadId": "t-1"}
+
+    def search(self, q, **kwargs):
+        wanted = q.split(":", 1)[1]
+        return {"messages": [{"id": "delivered"}]} if f"<{wanted}>" in self.delivered else {}
+
+    @staticmethod
+    def message_id(raw):
+        for line in base64.urlsafe_b64decode(raw).decode().splitlines():
+            if line.startswith("Message-ID: "):
+                return line.split(": ", 1)[1]
+        return None
+
+
+class OutboxTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = Path(tempfile.mkdtemp())
+        self.addCleanup(shutil.rmtree, self.dir)
+
+    def module(self, *failures, max_attempts=3):
+        self.network = Network(*failures)
+        self.service = FakeGmailService({"messages.send": self.network.send, "messages.list": self.network.search})
+        module = make_module(self.service)
+        module._outbox = Outbox(self.dir, module._outbox_send, module._outbox_find,
+                                max_attempts=max_attempts, fsync=False)
+        self.addCleanup(module._outbox.journal.close)
+        return module
+
+    def send(self, module, **params):
+        return module.dispatch("gmail.send", dict(
+            {"to": "a@example.com", "subject": "Hi", "body": "Hello", "queue_on_failure": True}, **params))
+
+    def test_failed_send_is_queued(self):
+        module = self.module(503)
+        queued = self.send(module)
+        self.assertEqual((queued["sent"], queued["queued"]), (False, True))
+
+        items = module.dispatch("gmail.outbox", {})["items"]
+        self.assertEqual([(i["id"], i["state"], i["attempts"]) for i in items], [(queued["outbox_id"], "pending", 1)])
+        self.assertEqual(items[0]["last_error"], "HTTP 503")
+        self.assertNotIn("raw", items[0])
+
+    def test_send_without_queueing_still_fails(self):
+        module = self.module(503)
+        with self.assertRaises(HttpError):
+            self.send(module, queue_on_failure=False)
+        self.assertEqual(module.dispatch("gmail.outbox", {})["count"], 0)
+
+    def test_permanent_errors_are_not_queued(self):
+        with self.assertRaises(HttpError):
+            self.send(self.module(400))
+
+    def test_retry_sends_and_archives(self):
+        module = self.module(503)
+        item_id = self.send(module)["outbox_id"]
+        result = module.dispatch("gmail.send_queued", {})
+        self.assertEqual(result["sent"], 1)
+        self.assertEqual(result["results"][0]["message_id"], "sent-1")
+        self.assertEqual(module.dispatch("gmail.outbox", {})["count"], 0)
+
+        record = json.loads((self.dir / "sent" / f"{item_id}.json").read_text())
+        self.assertEqual((record["message_id"], record["attempts"], record["reconciled"]), ("sent-1", 2, False))
+
+    def test_queued_send_clears_the_cache(self):
+        module = self.module(503)
+        self.service.handlers["threads.get"] = {"id": "t-1", "messages": []}
+        self.send(module)
+        module.dispatch("gmail.thread", {"thread_id": "t-1"})
+        module.dispatch("gmail.send_queued", {})
+        module.dispatch("gmail.thread", {"thread_id": "t-1"})
+        self.assertEqual([name for name, _ in self.service.calls].count("threads.get"), 2)
+
+    def test_lost_response_is_not_sent_twice(self):
+        module = self.module("lost")
+        self.send(module)
+        result = module.dispatch("gmail.send_queued", {})["results"][0]
+        self.assertEqual((result["state"], result["message_id"], result["reconciled"]), ("sent", "delivered", True))
+        self.assertEqual(self.network.sent, [])
+
+    def test_queued_sends_take_rate_limit_tokens(self):
+        module = self.module(503, 503)
+        self.send(module)
+        self.send(module)
+        # Room for one send, and no waiting for the refill
+        module.rate_limiter = RateLimiter(1, max_wait_secs=0)
+
+        first, second = module.dispatch("gmail.send_queued", {})["results"]
+        self.assertEqual(first["state"], "sent")
+        self.assertEqual(second["state"], "pending")
+        self.assertIn("gmail.send is rate limited", second["error"])
+        self.assertEqual(len(self.network.sent), 1)
+
+    def test_backoff_and_giving_up(self):
+        module = self.module(503, 503, 503, max_attempts=3)
+        item_id = self.send(module)["outbox_id"]
+        self.assertEqual(module._outbox.run_due(), [])
+
+        self.assertEqual(module.dispatch("gmail.send_queued", {"id": item_id})["results"][0]["state"], "pending")
+        self.assertEqual(module.dispatch("gmail.send_queued", {"id": item_id})["results"][0]["state"], "gave_up")
+        item = module.dispatch("gmail.outbox", {})["items"][0]
+        self.assertEqual((item["state"], item["attempts"]), ("gave_up", 3))
+
+        # A given-up item can be retried by hand under the same id
+        result = module.dispatch("gmail.send_queued", {"id": item_id})["results"][0]
+        self.assertEqual((result["id"], result["state"]), (item_id, "sent"))
+        self.assertEqual(module.dispatch("gmail.outbox", {})["count"], 0)
+
+    def test_background_pass_retries_due_items(self):
+        module = self.module(503)
+        module._outbox.initial_backoff_secs = 0
+        self.send(module)
+        self.assertEqual([r["state"] for r in module._outbox.run_due()], ["sent"])
+
+    def test_recovery_after_crash_mid_send(self):
+        module = self.module(503, 503)
+        done = self.send(module)["outbox_id"]
+        undone = self.send(module)["outbox_id"]
+        for item_id in (done, undone):
+            module._outbox.journal.claim(item_id)
+        (self.dir / "sent").mkdir()
+        (self.dir / "sent" / f"{done}.json").write_text("{}")
+        module._outbox.journal.close()
+
+        reopened = Outbox(self.dir, module._outbox_send, module._outbox_find, fsync=False)
+        self.addCleanup(reopened.journal.close)
+        items = reopened.items()
+        self.assertEqual([(i["id"], i["state"]) for i in items], [(undone, "pending")])
+        self.assertLessEqual(items[0]["next_attempt_at"], time.time())
+
+    def test_unknown_id(self):
+        module = self.module()
+        with self.assertRaisesRegex(Exception, "No queued message"):
+            module.dispatch("gmail.send_queued", {"id": "nope"})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,81 @@
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.params import age_terms, compose_query, parse_limit
+
+
+class ParseLimitTest(unittest.TestCase):
+    def test_default_and_valid(self):
+        self.assertEqual(parse_limit(None), 10)
+        self.assertEqual(parse_limit(25), 25)
+
+    def test_rejects_malformed(self):
+        for value in (0, -5, 2.5, "10", True):
+            with self.subTest(value=value):
+                with self.assertRaisesRegex(ValueError, "limit must be a positive integer"):
+                    parse_limit(value)
+
+    def test_clamps_to_max(self):
+        with self.assertLogs("fgp_gmail", "WARNING") as logs:
+            self.assertEqual(parse_limit(5000, max_limit=100), 100)
+        self.assertIn("Clamping limit 5000 to max 100", logs.output[0])
+
+    def test_inbox_and_search_share_validation(self):
+        service = FakeGmailService({"messages.list": {"messages": []}})
+        module = make_module(service)
+        module.max_limit = 50
+        module.dispatch("gmail.inbox", {"limit": 500})
+        module.dispatch("gmail.search", {"query": "x", "limit": 500})
+        self.assertEqual([kwargs["maxResults"] for _, kwargs in service.calls], [50, 50])
+        with self.assertRaisesRegex(ValueError, "positive integer"):
+            module.dispatch("gmail.search", {"query": "x", "limit": -1})
+
+
+
+class ComposeQueryTest(unittest.TestCase):
+    def test_structured_params(self):
+        self.assertEqual(
+            compose_query({"from": "alice@example.com", "subject": "weekly report",
+                           "after": "2026-01-05", "has_attachment": True, "is_unread": False}),
+            'from:alice@example.com subject:"weekly report" after:2026/01/05 has:attachment -is:unread',
+        )
+
+    def test_anded_with_raw_query(self):
+        self.assertEqual(compose_query({"query": "invoice OR receipt", "label": "Finance"}),
+                         "(invoice OR receipt) label:Finance")
+        self.assertEqual(compose_query({"query": "invoice"}), "invoice")
+        self.assertIsNone(compose_query({}))
+
+    def test_rejects_bad_dates(self):
+        for value in ("2026/01/05", "01-05-2026", "2026-1-5", "2026-02-30", 20260105):
+            with self.subTest(value=value):
+                with self.assertRaisesRegex(ValueError, "before must be a date in YYYY-MM-DD"):
+                    compose_query({"before": value})
+
+    def test_search_uses_composed_query(self):
+        service = FakeGmailService({"messages.list": {"messages": []}})
+        result = make_module(service).dispatch("gmail.search", {"from": "bob@example.com", "is_unread": True})
+        self.assertEqual(result["query"], "from:bob@example.com is:unread")
+        self.assertEqual(service.calls[0][1]["q"], "from:bob@example.com is:unread")
+        with self.assertRaisesRegex(ValueError, "query or at least one of"):
+            make_module(service).dispatch("gmail.search", {})
+
+
+class AgeTermsTest(unittest.TestCase):
+    def test_days_and_longer_use_gmail_operators(self):
+        self.assertEqual(age_terms({"older_than": "7d", "newer_than": "1y"}), ["older_than:7d", "newer_than:1y"])
+
+    def test_hours_and_weeks_become_timestamps(self):
+        self.assertEqual(age_terms({"newer_than": "1h"}, now=100000), ["after:96400"])
+        self.assertEqual(age_terms({"older_than": "2w"}, now=2000000), ["before:790400"])
+
+    def test_rejects_malformed(self):
+        for value in ("7", "d", "1.5d", "0d", "7 days", 7):
+            with self.subTest(value=value):
+                with self.assertRaisesRegex(ValueError, "older_than must be an age"):
+                    age_terms({"older_than": value})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,109 @@
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.concurrency import CallSlots
+from gmail_lib.prefetch import Prefetcher
+from gmail_lib.ratelimit import RateLimiter
+
+PAGES = {
+    None: {"messages": [{"id": "m1"}], "nextPageToken": "p2"},
+    "p2": {"messages": [{"id": "m2"}], "nextPageToken": "p3"},
+    "p3": {"messages": [{"id": "m3"}]},
+}
+
+
+def paged_service():
+    return FakeGmailService({
+        "messages.list": lambda pageToken=None, **kwargs: PAGES[pageToken],
+        "messages.get": lambda id, **kwargs: {"id": id, "threadId": id},
+    })
+
+
+def list_calls(service):
+    return [kwargs.get("pageToken") for name, kwargs in service.calls if name == "messages.list"]
+
+
+def prefetching_module(service, pages=1, gate=lambda: None):
+    module = make_module(service)
+    module.prefetch = Prefetcher(enabled=True, pages=pages, gate=gate)
+    return module
+
+
+class PrefetchTest(unittest.TestCase):
+    def test_next_page_is_served_from_cache(self):
+        service = paged_service()
+        module = prefetching_module(service)
+
+        first = module.dispatch("gmail.search", {"query": "invoice"})
+        self.assertEqual(first["next_page_token"], "p2")
+        module.prefetch.drain()
+        self.assertEqual(list_calls(service), [None, "p2"])
+
+        second = module.dispatch("gmail.search", {"query": "invoice", "page_token": "p2"})
+        self.assertEqual([e["id"] for e in second["emails"]], ["m2"])
+        # The follow-up was a cache hit; it didn't prefetch p3 either
+        self.assertEqual(list_calls(service), [None, "p2"])
+
+        stats = module.dispatch("gmail.stats", {})["prefetch"]
+        self.assertEqual((stats["completed"], stats["hits"], stats["hit_rate"]), (1, 1, 1.0))
+
+    def test_pages_ahead_chain(self):
+        service = paged_service()
+        module = prefetching_module(service, pages=2)
+        module.dispatch("gmail.inbox", {})
+        module.prefetch.drain()
+        self.assertEqual(list_calls(service), [None, "p2", "p3"])
+
+    def test_gate_vetoes_prefetch(self):
+        service = paged_service()
+        module = prefetching_module(service, gate=lambda: "quota_low")
+        module.dispatch("gmail.search", {"query": "invoice"})
+        module.prefetch.drain()
+        self.assertEqual(list_calls(service), [None])
+        self.assertEqual(module.prefetch.stats()["skipped"], {"quota_low": 1})
+
+    def test_prefetch_takes_rate_limit_tokens(self):
+        module = prefetching_module(paged_service())
+        module.rate_limiter = RateLimiter(1, clock=lambda: 0.0)
+        module.dispatch("gmail.search", {"query": "invoice"})
+        module.prefetch.drain()
+        # Two pages, one token each, out of a bucket of five
+        self.assertEqual(module.rate_limiter.headroom(), 0.6)
+
+    def test_prefetch_is_skipped_when_rate_limited(self):
+        service = paged_service()
+        module = prefetching_module(service)
+        module.rate_limiter = RateLimiter(1, clock=lambda: 0.0, sleep=lambda secs: None)
+        module.rate_limiter.acquire("gmail.send")
+        module.dispatch("gmail.search", {"query": "invoice"})
+        module.prefetch.drain()
+        self.assertEqual(list_calls(service), [None])
+        self.assertEqual(module.prefetch.stats()["skipped"], {"rate_limited": 1})
+
+    def test_prefetch_is_skipped_when_no_slot_is_free(self):
+        service = paged_service()
+        held = []
+
+        def take_the_only_slot():
+            held.append(module.call_slots.slot("gmail.inbox"))
+            held[-1].__enter__()
+
+        module = prefetching_module(service, gate=take_the_only_slot)
+        module.call_slots = CallSlots(max_concurrent=1)
+        module.dispatch("gmail.search", {"query": "invoice"})
+        module.prefetch.drain()
+        held[-1].__exit__(None, None, None)
+        self.assertEqual(list_calls(service), [None])
+        self.assertEqual(module.prefetch.stats()["skipped"], {"busy": 1})
+
+    def test_disabled_by_default(self):
+        service = paged_service()
+        module = make_module(service)
+        module.dispatch("gmail.search", {"query": "invoice"})
+        self.assertFalse(module.prefetch.enabled)
+        self.assertEqual(list_calls(service), [None])
+
+
+if __name__ == "__main__":
+    unittest.main()
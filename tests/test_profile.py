@@ -0,0 +1,57 @@
+import unittest
+from unittest import mock
+
+from helpers import FakeGmailService, HttpError, load_gmail_module, make_module
+
+from gmail_lib.accounts import AuthExpired
+from gmail_lib.types import UnexpectedOutput
+
+RefreshError = load_gmail_module().RefreshError
+
+PROFILE = {"emailAddress": "me@example.com", "messagesTotal": 1200, "threadsTotal": 800, "historyId": "98765"}
+
+
+def failing(error):
+    def call(**kwargs):
+        raise error
+    return call
+
+
+class ProfileTest(unittest.TestCase):
+    def test_profile(self):
+        result = make_module(FakeGmailService({"getProfile": PROFILE})).dispatch("gmail.profile", {})
+        self.assertEqual(result, {
+            "account": None,
+            "email_address": "me@example.com",
+            "messages_total": 1200,
+            "threads_total": 800,
+            "history_id": "98765",
+            "storage_used_bytes": None,
+            "request_id": mock.ANY,
+        })
+
+    def test_rejected_token_is_an_auth_error(self):
+        for error in (HttpError(401), RefreshError("invalid_grant")):
+            module = make_module(FakeGmailService({"getProfile": failing(error)}))
+            with self.subTest(error=error), self.assertRaisesRegex(AuthExpired, "needs to sign in"):
+                module.dispatch("gmail.profile", {})
+
+    def test_other_failures_pass_through(self):
+        module = make_module(FakeGmailService({"getProfile": failing(HttpError(403))}))
+        with self.assertRaises(HttpError):
+            module.dispatch("gmail.profile", {})
+
+        module = make_module(FakeGmailService({"getProfile": {"messagesTotal": 1}}))
+        with self.assertRaisesRegex(UnexpectedOutput, "emailAddress"):
+            module.dispatch("gmail.profile", {})
+
+    def test_health_names_the_account_or_the_auth_problem(self):
+        module = make_module(FakeGmailService({"getProfile": PROFILE}))
+        self.assertEqual(module.health_check()["gmail_api"]["message"], "Gmail API reachable as me@example.com")
+
+        module = make_module(FakeGmailService({"getProfile": failing(HttpError(401))}))
+        self.assertEqual(module.dispatch("gmail.health", {})["subsystems"]["gmail_api"]["reason"], "auth_expired")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,105 @@
+import os
+import unittest
+from unittest import mock
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.ratelimit import RateLimited, RateLimiter
+
+
+class FakeClock:
+    def __init__(self):
+        self.now = 0.0
+        self.slept = []
+
+    def __call__(self):
+        return self.now
+
+    def sleep(self, secs):
+        self.slept.append(secs)
+        self.now += secs
+
+
+def limiter(rate, **kwargs):
+    clock = FakeClock()
+    return RateLimiter(rate, clock=clock, sleep=clock.sleep, **kwargs), clock
+
+
+class RateLimiterTest(unittest.TestCase):
+    def test_disabled_by_default(self):
+        limit = RateLimiter()
+        self.assertFalse(limit.enabled)
+        for _ in range(1000):
+            self.assertEqual(limit.acquire("gmail.send"), 0.0)
+
+    def test_burst_then_wait_for_refill(self):
+        limit, clock = limiter(10)
+        for _ in range(10):
+            limit.acquire("gmail.search")
+        self.assertEqual(clock.slept, [])
+        self.assertAlmostEqual(limit.acquire("gmail.search"), 0.1)
+
+    def test_writes_cost_more_than_reads(self):
+        limit, clock = limiter(10)
+        limit.acquire("gmail.send")
+        limit.acquire("gmail.send")
+        self.assertAlmostEqual(limit.acquire("gmail.send"), 0.5)
+
+    def test_local_methods_are_free(self):
+        limit, clock = limiter(1)
+        for _ in range(20):
+            limit.acquire("gmail.watch_status")
+        self.assertEqual(clock.slept, [])
+
+    def test_too_long_a_wait_fails_without_taking_tokens(self):
+        limit, clock = limiter(1, max_wait_secs=2)
+        for _ in range(5):
+            limit.acquire("gmail.search")
+        limit.acquire("gmail.search")  # waits 1s
+        with self.assertRaisesRegex(RateLimited, r"gmail.send is rate limited; retry in 5.0s"):
+            limit.acquire("gmail.send")
+        self.assertAlmostEqual(limit.acquire("gmail.search"), 1.0)
+
+    def test_wait_is_bounded_by_the_deadline(self):
+        limit, clock = limiter(1)
+        for _ in range(5):
+            limit.acquire("gmail.search")
+        with self.assertRaises(RateLimited):
+            limit.acquire("gmail.search", deadline=clock.now + 0.5)
+
+    def test_accounts_have_their_own_buckets(self):
+        limit, clock = limiter(5)
+        limit.acquire("gmail.send", "work")
+        self.assertEqual(limit.acquire("gmail.send", "personal"), 0.0)
+        self.assertEqual(limit.headroom(), 0.0)
+
+
+class ModuleRateLimitTest(unittest.TestCase):
+    def test_calls_are_limited_but_cache_hits_are_not(self):
+        service = FakeGmailService({"messages.list": {"messages": []}, "messages.send": {"id": "m", "threadId": "t"}})
+        with mock.patch.dict(os.environ, {"FGP_GMAIL_RATE_LIMIT": "1"}):
+            module = make_module(service)
+        module.rate_limiter.max_wait_secs = 0
+        module.dispatch("gmail.send", {"to": "a@example.com", "subject": "s", "body": "b"})
+        with self.assertRaises(RateLimited):
+            module.dispatch("gmail.inbox", {})
+        self.assertEqual([name for name, _ in service.calls], ["messages.send"])
+
+        module.rate_limiter = RateLimiter(1, max_wait_secs=0)
+        for _ in range(4):
+            module.rate_limiter.acquire("gmail.search")
+        module.dispatch("gmail.inbox", {})
+        module.dispatch("gmail.inbox", {})  # served from the cache, so no token needed
+        self.assertEqual(module.dispatch("gmail.config", {})["rate_limit"]["rate_per_sec"], 1.0)
+
+    def test_prefetch_backs_off_when_headroom_is_low(self):
+        module = make_module(FakeGmailService({}))
+        module.rate_limiter = RateLimiter(10)
+        self.assertIsNone(module._prefetch_gate())
+        for _ in range(6):
+            module.rate_limiter.acquire("gmail.search")
+        self.assertEqual(module._prefetch_gate(), "rate_limited")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,116 @@
+import base64
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, make_module
+
+EML = (
+    b"From: Alice <alice@example.com>\r\n"
+    b"To: me@example.com\r\n"
+    b"Date: Mon, 13 Jan 2026 10:00:00 -0800\r\n"
+    b"Subject: Caf\xc3\xa9\r\n"
+    b"\r\n"
+    b"Exact bytes, \xff included.\r\n"
+)
+
+LABELS = {"labels": [{"id": "INBOX", "name": "INBOX"}, {"id": "Label_7", "name": "Imported"}]}
+
+
+def raw_message(data=EML, labels=("INBOX",)):
+    return {
+        "id": "m1",
+        "threadId": "t1",
+        "labelIds": list(labels),
+        "raw": base64.urlsafe_b64encode(data).decode().rstrip("="),
+    }
+
+
+def imported(body, **kwargs):
+    return {"id": "new-1", "threadId": "new-t", "labelIds": body["labelIds"]}
+
+
+class RawTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = Path(tempfile.mkdtemp())
+        self.addCleanup(shutil.rmtree, self.dir)
+
+    def test_export_writes_exact_bytes(self):
+        module = make_module(FakeGmailService({"messages.get": raw_message()}))
+        result = module.dispatch("gmail.export_raw", {"message_id": "m1", "output_path": str(self.dir)})
+        path = self.dir / "m1.eml"
+        self.assertEqual((result["saved"], result["path"], result["size"]), (True, str(path), len(EML)))
+        self.assertEqual(path.read_bytes(), EML)
+        self.assertIsNone(result["content_restricted"])
+
+    def test_export_inline(self):
+        service = FakeGmailService({"messages.get": raw_message()})
+        result = make_module(service).dispatch("gmail.export_raw", {"message_id": "m1", "inline": True})
+        self.assertEqual(base64.b64decode(result["data"]), EML)
+        self.assertEqual(service.calls[0][1]["format"], "raw")
+
+    def test_large_messages_are_not_inlined(self):
+        big = EML + b"x" * (1024 * 1024)
+        module = make_module(FakeGmailService({"messages.get": raw_message(big)}))
+        with self.assertRaisesRegex(ValueError, "pass output_path"):
+            module.dispatch("gmail.export_raw", {"message_id": "m1", "inline": True})
+
+    def test_export_checks_destination_first(self):
+        service = FakeGmailService({})
+        with self.assertRaises(FileNotFoundError):
+            make_module(service).dispatch("gmail.export_raw", {
+                "message_id": "m1", "output_path": str(self.dir / "missing" / "m1.eml")})
+        self.assertEqual(service.calls, [])
+
+    def test_import(self):
+        path = self.dir / "m.eml"
+        path.write_bytes(EML)
+        service = FakeGmailService({"labels.list": LABELS, "messages.import_": imported})
+        result = make_module(service).dispatch("gmail.import_raw", {
+            "path": str(path), "labels": ["inbox", "Imported"], "internal_date_from_header": True})
+        self.assertEqual((result["message_id"], result["labels"]), ("new-1", ["INBOX", "Label_7"]))
+
+        name, kwargs = service.calls[-1]
+        self.assertEqual((name, kwargs["internalDateSource"]), ("messages.import_", "dateHeader"))
+        self.assertEqual(base64.urlsafe_b64decode(kwargs["body"]["raw"]), EML)
+
+    def test_round_trip(self):
+        service = FakeGmailService({"messages.get": raw_message(), "messages.import_": imported})
+        module = make_module(service)
+        exported = module.dispatch("gmail.export_raw", {"message_id": "m1", "output_path": str(self.dir)})
+        module.dispatch("gmail.import_raw", {"path": exported["path"]})
+        name, kwargs = service.calls[-1]
+        self.assertEqual(base64.urlsafe_b64decode(kwargs["body"]["raw"]), EML)
+        self.assertEqual((kwargs["body"]["labelIds"], kwargs["internalDateSource"]), ([], "receivedTime"))
+
+    def test_import_rejects_invalid_files_before_upload(self):
+        cases = {
+            "empty.eml": (b"", "file is empty"),
+            "text.eml": (b"just some notes\nnothing more\n", "no valid header block"),
+            "nofrom.eml": (b"Subject: hi\r\n\r\nbody\r\n", "missing From header"),
+        }
+        service = FakeGmailService({})
+        module = make_module(service)
+        for name, (data, error) in cases.items():
+            (self.dir / name).write_bytes(data)
+            with self.subTest(name=name), self.assertRaisesRegex(ValueError, error):
+                module.dispatch("gmail.import_raw", {"path": str(self.dir / name)})
+
+        (self.dir / "nodate.eml").write_bytes(b"From: a@example.com\r\n\r\nbody\r\n")
+        with self.assertRaisesRegex(ValueError, "no parseable Date header"):
+            module.dispatch("gmail.import_raw", {
+                "path": str(self.dir / "nodate.eml"), "internal_date_from_header": True})
+        self.assertEqual(service.calls, [])
+
+    def test_import_rejects_oversized_files(self):
+        path = self.dir / "big.eml"
+        with open(path, "wb") as f:
+            f.write(EML)
+            f.truncate(36_700_161)
+        with self.assertRaisesRegex(ValueError, "up to 36700160 bytes"):
+            make_module(FakeGmailService({})).dispatch("gmail.import_raw", {"path": str(path)})
+
+
+if __name__ == "__main__":
+    unittest.main()
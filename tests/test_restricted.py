@@ -0,0 +1,82 @@
+import unittest
+
+from helpers import FakeGmailService, load_fixture, make_module
+
+from gmail_lib.restricted import (
+    CONFIDENTIAL_MODE,
+    SMIME_ENCRYPTED,
+    detect_restriction,
+    filter_restricted,
+)
+
+
+class DetectRestrictionTest(unittest.TestCase):
+    def test_confidential_mode_header(self):
+        msg = load_fixture("message_confidential_mode.json")
+        self.assertEqual(detect_restriction(msg), CONFIDENTIAL_MODE)
+
+    def test_confidential_mode_label(self):
+        msg = {"labelIds": ["SENT", "CONFIDENTIAL"], "payload": {"mimeType": "text/plain"}}
+        self.assertEqual(detect_restriction(msg), CONFIDENTIAL_MODE)
+
+    def test_smime_encrypted(self):
+        msg = load_fixture("message_smime_encrypted.json")
+        self.assertEqual(detect_restriction(msg), SMIME_ENCRYPTED)
+
+    def test_smime_nested_part(self):
+        msg = {"payload": {"mimeType": "multipart/mixed", "parts": [
+            {"mimeType": "text/plain"},
+            {"mimeType": "application/x-pkcs7-mime"},
+        ]}}
+        self.assertEqual(detect_restriction(msg), SMIME_ENCRYPTED)
+
+    def test_signed_only_is_not_restricted(self):
+        msg = load_fixture("message_smime_signed.json")
+        self.assertIsNone(detect_restriction(msg))
+
+    def test_filter_restricted(self):
+        emails = [{"id": "a", "content_restricted": None}, {"id": "b", "content_restricted": SMIME_ENCRYPTED}]
+        self.assertEqual([e["id"] for e in filter_restricted(emails, False)], ["a"])
+        self.assertEqual(len(filter_restricted(emails, True)), 2)
+
+
+class ReadRestrictedTest(unittest.TestCase):
+    def read(self, fixture):
+        msg = load_fixture(fixture)
+        module = make_module(FakeGmailService({"messages.get": msg}))
+        return module.dispatch("gmail.read", {"message_id": msg["id"]})
+
+    def test_confidential_body_is_skipped(self):
+        result = self.read("message_confidential_mode.json")
+        self.assertEqual(result["content_restricted"], CONFIDENTIAL_MODE)
+        self.assertIsNone(result["body_text"])
+        self.assertIsNone(result["body_html"])
+        self.assertEqual(result["snippet"], "")
+        self.assertIn("confidential mode", result["content_note"])
+
+    def test_smime_body_is_skipped(self):
+        result = self.read("message_smime_encrypted.json")
+        self.assertEqual(result["content_restricted"], SMIME_ENCRYPTED)
+        self.assertIsNone(result["body_text"])
+        self.assertIn("S/MIME", result["content_note"])
+
+    def test_signed_message_keeps_body(self):
+        result = self.read("message_smime_signed.json")
+        self.assertIsNone(result["content_restricted"])
+        self.assertIsNone(result["content_note"])
+        self.assertTrue(result["body_text"].startswith("Quarterly numbers"))
+
+    def test_inbox_annotates_summaries(self):
+        confidential = load_fixture("message_confidential_mode.json")
+        service = FakeGmailService({
+            "messages.list": {"messages": [{"id": confidential["id"]}]},
+            "messages.get": confidential,
+        })
+        result = make_module(service).dispatch("gmail.inbox", {"limit": 1})
+        email = result["emails"][0]
+        self.assertEqual(email["content_restricted"], CONFIDENTIAL_MODE)
+        self.assertEqual(email["snippet"], "")
+
+
+if __name__ == "__main__":
+    unittest.main()
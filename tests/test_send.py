@@ -0,0 +1,80 @@
+import base64
+import email
+import unittest
+from email import policy
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.markdown import render
+
+
+def sent_message(service):
+    """The email.message.Message passed to the last messages.send call."""
+    raw = service.calls[-1][1]["body"]["raw"]
+    return email.message_from_bytes(base64.urlsafe_b64decode(raw), policy=policy.default)
+
+
+class SendBodyTest(unittest.TestCase):
+    def send(self, **params):
+        service = FakeGmailService({"messages.send": {"id": "m", "threadId": "t"}})
+        make_module(service).dispatch("gmail.send", dict({"to": "a@example.com", "subject": "Hi"}, **params))
+        return sent_message(service)
+
+    def test_plain_text_by_default(self):
+        message = self.send(body="Hello")
+        self.assertEqual(message.get_content_type(), "text/plain")
+
+    def test_html_alternative(self):
+        message = self.send(body="Hello", body_html="<p>Hello</p>")
+        self.assertEqual(message.get_content_type(), "multipart/alternative")
+        self.assertEqual([part.get_content_type() for part in message.iter_parts()], ["text/plain", "text/html"])
+        self.assertEqual(message.get_body(("html",)).get_content().strip(), "<p>Hello</p>")
+
+    def test_markdown_is_rendered_and_kept_as_plain_text(self):
+        message = self.send(body_markdown="**Ship it**")
+        self.assertEqual(message.get_body(("plain",)).get_content().strip(), "**Ship it**")
+        self.assertIn("<p><strong>Ship it</strong></p>", message.get_body(("html",)).get_content())
+
+    def test_html_with_attachments_nests_the_alternative(self):
+        attachment = {"filename": "a.txt", "data": base64.b64encode(b"hi").decode()}
+        message = self.send(body="Hello", body_html="<p>Hello</p>", attachments=[attachment])
+        self.assertEqual(message.get_content_type(), "multipart/mixed")
+        self.assertEqual([part.get_content_type() for part in message.iter_parts()],
+                         ["multipart/alternative", "text/plain"])
+        self.assertEqual(message.get_body(("html",)).get_content().strip(), "<p>Hello</p>")
+
+    def test_rejects_html_and_markdown_together(self):
+        with self.assertRaisesRegex(ValueError, "not both"):
+            self.send(body="x", body_html="<p>x</p>", body_markdown="x")
+
+    def test_requires_a_body(self):
+        with self.assertRaisesRegex(ValueError, "body"):
+            self.send(body_html="<p>x</p>")
+
+
+class MarkdownTest(unittest.TestCase):
+    def test_blocks(self):
+        source = "# Agenda\n\n- one\n- two\n\n1. first\n\n> quoted\n\n```\nx < y\n```\n\n---"
+        self.assertEqual(render(source), "\n".join([
+            "<h1>Agenda</h1>",
+            "<ul><li>one</li><li>two</li></ul>",
+            "<ol><li>first</li></ol>",
+            "<blockquote><p>quoted</p></blockquote>",
+            "<pre><code>x &lt; y</code></pre>",
+            "<hr>",
+        ]))
+
+    def test_inline(self):
+        self.assertEqual(
+            render("**b** *i* `a*b*` snake_case [site](https://example.com/?a=1&b=2)\nnext line"),
+            '<p><strong>b</strong> <em>i</em> <code>a*b*</code> snake_case '
+            '<a href="https://example.com/?a=1&amp;b=2">site</a><br>next line</p>',
+        )
+
+    def test_html_is_escaped(self):
+        self.assertEqual(render('<script>alert("x")</script> [x](javascript:alert(1))'),
+                         '<p>&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; [x](javascript:alert(1))</p>')
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,87 @@
+import base64
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.settings import build_vacation, parse_instant
+
+SEND_AS = {"sendAs": [
+    {"sendAsEmail": "me@example.com", "displayName": "Me", "isPrimary": True, "isDefault": True},
+    {"sendAsEmail": "support@example.com", "displayName": "Support", "verificationStatus": "accepted"},
+    {"sendAsEmail": "pending@example.com", "verificationStatus": "pending"},
+]}
+
+CURRENT = {"enableAutoReply": False, "responseSubject": "Away", "responseBodyHtml": "<p>Away</p>",
+           "restrictToContacts": True}
+
+
+class VacationTest(unittest.TestCase):
+    def test_parse_instant(self):
+        self.assertEqual(parse_instant("2026-01-02", "start"), 1767312000000)
+        self.assertEqual(parse_instant("2026-01-02T01:00:00+01:00", "start"), 1767312000000)
+        self.assertEqual(parse_instant("2026-01-02T00:00:00Z", "start"), 1767312000000)
+        with self.assertRaisesRegex(ValueError, "start must be an ISO 8601"):
+            parse_instant("next tuesday", "start")
+
+    def test_build_keeps_unpassed_fields(self):
+        body = build_vacation({"enabled": True, "body": "Back Monday", "end": "2026-01-09"}, CURRENT)
+        self.assertEqual(body, {"enableAutoReply": True, "responseSubject": "Away",
+                                "responseBodyPlainText": "Back Monday", "restrictToContacts": True,
+                                "endTime": "1767916800000"})
+
+    def test_set_and_get(self):
+        service = FakeGmailService({"settings.getVacation": CURRENT,
+                                    "settings.updateVacation": lambda body, **kwargs: body})
+        module = make_module(service)
+        result = module.dispatch("gmail.vacation_set", {
+            "enabled": True, "subject": "Out", "start": "2026-01-02", "end": "2026-01-09T17:00:00Z",
+            "restrict_to_domain": True,
+        })
+        self.assertEqual([name for name, _ in service.calls], ["settings.getVacation", "settings.updateVacation"])
+        self.assertEqual(service.calls[1][1]["body"]["startTime"], "1767312000000")
+        self.assertEqual((result["start"], result["end"]), ("2026-01-02T00:00:00Z", "2026-01-09T17:00:00Z"))
+        self.assertTrue(result["restrict_to_domain"])
+        self.assertEqual(module.dispatch("gmail.vacation_get", {})["subject"], "Away")
+
+    def test_end_before_start_fails_locally(self):
+        service = FakeGmailService({})
+        module = make_module(service)
+        for params, error in (({"enabled": True, "start": "2026-01-09", "end": "2026-01-02"}, "must be after start"),
+                              ({"subject": "Out"}, "enabled parameter is required"),
+                              ({"enabled": "yes"}, "enabled must be a boolean")):
+            with self.subTest(params=params):
+                with self.assertRaisesRegex(ValueError, error):
+                    module.dispatch("gmail.vacation_set", params)
+        self.assertEqual(service.calls, [])
+
+
+class SendAsTest(unittest.TestCase):
+    def setUp(self):
+        self.service = FakeGmailService({"settings.sendAs.list": SEND_AS, "messages.send": {"id": "s1", "threadId": "t1"}})
+        self.module = make_module(self.service)
+
+    def sent_message(self) -> str:
+        raw = self.service.calls[-1][1]["body"]["raw"]
+        return base64.urlsafe_b64decode(raw).decode()
+
+    def test_list(self):
+        result = self.module.dispatch("gmail.sendas_list", {})
+        self.assertEqual(result["count"], 3)
+        self.assertEqual([alias["verified"] for alias in result["aliases"]], [True, True, False])
+
+    def test_send_from_alias(self):
+        self.module.dispatch("gmail.send", {"to": "a@example.com", "subject": "Hi", "body": "x",
+                                            "from": "SUPPORT@example.com"})
+        self.assertIn("from: Support <support@example.com>", self.sent_message())
+
+    def test_unknown_alias_lists_valid_ones(self):
+        for sender in ("someone@example.com", "pending@example.com"):
+            with self.subTest(sender=sender):
+                with self.assertRaisesRegex(ValueError, "me@example.com, support@example.com"):
+                    self.module.dispatch("gmail.send", {"to": "a@example.com", "subject": "Hi", "body": "x",
+                                                        "from": sender})
+        self.assertNotIn("messages.send", [name for name, _ in self.service.calls])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,91 @@
+import io
+import logging
+import unittest
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.spans import RequestIdFilter, configure_logging, level_from_filter, summarize_params
+
+
+class CaptureLogs:
+    """Attach a handler to `fgp_gmail` that formats records with their request id."""
+
+    def __init__(self, test):
+        self.stream = io.StringIO()
+        handler = logging.StreamHandler(self.stream)
+        handler.setFormatter(logging.Formatter("[%(request_id)s] %(levelname)s %(message)s"))
+        handler.addFilter(RequestIdFilter())
+        logger = logging.getLogger("fgp_gmail")
+        old_level = logger.level
+        logger.addHandler(handler)
+        logger.setLevel(logging.INFO)
+        test.addCleanup(logger.removeHandler, handler)
+        test.addCleanup(logger.setLevel, old_level)
+
+    def lines(self):
+        return self.stream.getvalue().splitlines()
+
+
+class SpanTest(unittest.TestCase):
+    def test_response_and_log_lines_share_the_request_id(self):
+        logs = CaptureLogs(self)
+        module = make_module(FakeGmailService({"messages.list": {"messages": []}}))
+        first = module.dispatch("gmail.inbox", {"limit": 5})
+        second = module.dispatch("gmail.inbox", {"limit": 5})  # cache hit, new id
+        self.assertNotEqual(first["request_id"], second["request_id"])
+
+        lines = [line for line in logs.lines() if first["request_id"] in line]
+        self.assertEqual(len(lines), 2)
+        self.assertIn('gmail.inbox started params={"limit": 5}', lines[0])
+        self.assertRegex(lines[1], r"INFO gmail.inbox ok in [\d.]+ms")
+
+    def test_failures_are_logged_with_the_error(self):
+        logs = CaptureLogs(self)
+        module = make_module(FakeGmailService({}))
+        with self.assertRaises(ValueError):
+            module.dispatch("gmail.nope", {})
+        self.assertRegex(logs.lines()[-1], r"^\[\w{16}\] WARNING gmail.nope failed in [\d.]+ms: ValueError: Unknown method")
+
+    def test_batch_operations_get_their_own_ids(self):
+        module = make_module(FakeGmailService({"messages.list": {"messages": []}}))
+        result = module.dispatch("gmail.batch", {"operations": [{"method": "gmail.inbox"}, {"method": "gmail.inbox"}]})
+        ids = {result["request_id"]} | {op["result"]["request_id"] for op in result["results"]}
+        self.assertEqual(len(ids), 3)
+
+    def test_params_are_summarized(self):
+        summary = summarize_params({
+            "to": "a@example.com, b@example.com",
+            "subject": "Dinner with c@example.com",
+            "body": "secret plans",
+            "attachments": [{"path": "/tmp/x.pdf"}],
+            "operations": [{"method": "gmail.send", "params": {"body": "hidden"}}],
+            "limit": 5,
+        })
+        self.assertEqual(summary["to"], "<2 recipients>")
+        self.assertNotIn("c@example.com", summary["subject"])
+        self.assertEqual((summary["body"], summary["attachments"]), ("<12 chars>", "<1 items>"))
+        self.assertEqual((summary["operations"], summary["limit"]), (["gmail.send"], 5))
+
+
+class LoggingSetupTest(unittest.TestCase):
+    def test_level_from_filter(self):
+        self.assertEqual(level_from_filter("fgp_gmail=info,fgp_daemon=debug"), logging.INFO)
+        self.assertEqual(level_from_filter("warn"), logging.WARNING)
+        self.assertIsNone(level_from_filter("fgp_daemon=debug"))
+
+    def test_configure_logging(self):
+        logger = logging.getLogger("fgp_gmail")
+        self.addCleanup(setattr, logger, "propagate", True)
+        self.addCleanup(setattr, logger, "handlers", list(logger.handlers))
+        self.addCleanup(logger.setLevel, logger.level)
+        logger.handlers = []
+
+        stream = io.StringIO()
+        configure_logging("fgp_gmail=warn", stream=stream)
+        logging.getLogger("fgp_gmail.test").info("quiet")
+        logging.getLogger("fgp_gmail.test").warning("loud")
+        self.assertRegex(stream.getvalue(), r"WARNING fgp_gmail.test \[-\] loud\n$")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,126 @@
+import os
+import pickle
+import subprocess
+import sys
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, make_module
+
+from gmail_lib.accounts import AccountRegistry
+from gmail_lib.tokens import TokenLockTimeout, lock_path, token_lock, write_token
+
+HOLD_LOCK = """
+import fcntl, os, sys, time
+fd = os.open(sys.argv[1], os.O_RDWR | os.O_CREAT)
+fcntl.flock(fd, fcntl.LOCK_EX)
+print("locked", flush=True)
+time.sleep(60)
+"""
+
+
+class SlowCreds:
+    refreshes = 0
+
+    def __init__(self):
+        self.valid = False
+        self.expired = True
+        self.refresh_token = "r"
+
+    def refresh(self, request):
+        type(self).refreshes += 1
+        time.sleep(0.05)
+        self.valid = True
+        self.expired = False
+
+
+class TokenLockTest(unittest.TestCase):
+    def setUp(self):
+        self._tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(self._tmp.cleanup)
+        self.token_file = Path(self._tmp.name) / "gmail_token.pickle"
+
+    def test_concurrent_writes_never_interleave(self):
+        payloads = [bytes([i]) * 200_000 for i in range(16)]
+        threads = [threading.Thread(target=write_token, args=(self.token_file, data)) for data in payloads]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertIn(self.token_file.read_bytes(), payloads)
+        self.assertEqual(sorted(p.name for p in self.token_file.parent.iterdir()),
+                         [lock_path(self.token_file).name, self.token_file.name])
+
+    @unittest.skipIf(sys.platform == "win32", "POSIX file modes")
+    def test_written_token_is_private(self):
+        old_umask = os.umask(0o022)
+        try:
+            write_token(self.token_file, b"secret")
+            write_token(self.token_file, b"secret")
+        finally:
+            os.umask(old_umask)
+        self.assertEqual(self.token_file.stat().st_mode & 0o777, 0o600)
+
+    def test_reentrant_and_released_on_error(self):
+        with self.assertRaises(RuntimeError):
+            with token_lock(self.token_file):
+                with token_lock(self.token_file):
+                    write_token(self.token_file, b"x")
+                raise RuntimeError("boom")
+        acquired = []
+
+        def other_thread():
+            with token_lock(self.token_file, timeout=1):
+                acquired.append(True)
+
+        thread = threading.Thread(target=other_thread)
+        thread.start()
+        thread.join()
+        self.assertEqual(acquired, [True])
+
+    @unittest.skipIf(sys.platform == "win32", "flock is POSIX-only")
+    def test_other_process_holds_then_dies(self):
+        child = subprocess.Popen([sys.executable, "-c", HOLD_LOCK, str(lock_path(self.token_file))],
+                                 stdout=subprocess.PIPE, text=True)
+        self.addCleanup(child.stdout.close)
+        self.addCleanup(child.kill)
+        self.assertEqual(child.stdout.readline().strip(), "locked")
+        with self.assertRaises(TokenLockTimeout):
+            with token_lock(self.token_file, timeout=0.2):
+                pass
+        child.kill()
+        child.wait()
+        with token_lock(self.token_file, timeout=1):
+            pass
+
+
+class RefreshSerializationTest(unittest.TestCase):
+    def test_concurrent_calls_refresh_once(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        auth_dir = Path(tmp.name)
+        (auth_dir / "credentials.json").write_text("{}")
+        module = make_module(FakeGmailService({}), accounts=AccountRegistry(auth_dir))
+        account = module.accounts.resolve(None)
+        with open(account.token_file, "wb") as f:
+            pickle.dump(SlowCreds(), f)
+        SlowCreds.refreshes = 0
+
+        results = []
+        threads = [threading.Thread(target=lambda: results.append(module._get_credentials(account)))
+                   for _ in range(8)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertEqual(len(results), 8)
+        self.assertTrue(all(creds.valid for creds in results))
+        self.assertEqual(SlowCreds.refreshes, 1)
+        self.assertFalse([p for p in os.listdir(auth_dir) if p.endswith(".tmp")])
+
+
+if __name__ == "__main__":
+    unittest.main()
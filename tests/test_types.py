@@ -0,0 +1,292 @@
+import base64
+import copy
+import unittest
+from unittest import mock
+
+from helpers import FakeGmailService, load_fixture, make_module
+
+from gmail_lib.types import EmailSummary, SendResult, Thread, UnexpectedOutput, rfc3339_date
+
+
+class EmailSummaryTest(unittest.TestCase):
+    def test_from_api(self):
+        summary = EmailSummary.from_api(load_fixture("types/message_metadata.json"))
+        self.assertEqual(summary.id, "18d2b0000000b001")
+        self.assertEqual(summary.from_, "CI Bot <ci@builds.example.com>")
+        self.assertEqual(summary.to, "me@example.com")
+        self.assertEqual(summary.subject, "Build #4821 passed")
+        self.assertTrue(summary.unread)
+        self.assertEqual(len(summary.snippet), 100)
+
+    def test_snippet_limit(self):
+        summary = EmailSummary.from_api(load_fixture("types/message_metadata.json"), snippet_limit=80)
+        self.assertEqual(len(summary.snippet), 80)
+
+    def test_message_without_subject(self):
+        summary = EmailSummary.from_api(load_fixture("types/message_no_subject.json"))
+        self.assertEqual(summary.subject, "")
+        self.assertFalse(summary.unread)
+        self.assertEqual(summary.to_dict()["subject"], "")
+
+    def test_date_is_rfc3339(self):
+        summary = EmailSummary.from_api(load_fixture("types/message_metadata.json"))
+        self.assertEqual(summary.date, "2026-01-16T14:03:21Z")
+        self.assertEqual(summary.extra, {"date_header": "Fri, 16 Jan 2026 14:03:21 +0000"})
+        self.assertEqual(rfc3339_date("Mon, 13 Jan 2026 10:00:00 -0800"), "2026-01-13T10:00:00-08:00")
+
+    def test_date_falls_back_to_internal_date(self):
+        msg = load_fixture("types/message_metadata.json")
+        msg["payload"]["headers"][-1]["value"] = "sometime last week"
+        msg["internalDate"] = "1768572201000"
+        self.assertEqual(EmailSummary.from_api(msg).date, "2026-01-16T14:03:21Z")
+        del msg["internalDate"]
+        self.assertEqual(EmailSummary.from_api(msg).date, "")
+
+    def test_round_trip(self):
+        summary = EmailSummary.from_api(load_fixture("types/message_metadata.json"))
+        self.assertEqual(EmailSummary.from_dict(summary.to_dict()), summary)
+
+    def test_missing_id(self):
+        msg = load_fixture("types/message_metadata.json")
+        del msg["id"]
+        with self.assertRaisesRegex(UnexpectedOutput, "missing field `id`"):
+            EmailSummary.from_api(msg)
+
+    def test_missing_thread_id(self):
+        msg = load_fixture("types/message_metadata.json")
+        del msg["threadId"]
+        with self.assertRaisesRegex(UnexpectedOutput, "missing field `threadId` in message 18d2b0000000b001"):
+            EmailSummary.from_api(msg)
+
+    def test_malformed_headers(self):
+        msg = load_fixture("types/message_metadata.json")
+        msg["payload"]["headers"] = {"From": "x"}
+        with self.assertRaisesRegex(UnexpectedOutput, "field `headers`.*should be array"):
+            EmailSummary.from_api(msg)
+
+    def test_serialized_summary_missing_subject(self):
+        data = EmailSummary.from_api(load_fixture("types/message_metadata.json")).to_dict()
+        del data["subject"]
+        with self.assertRaisesRegex(UnexpectedOutput, "missing field `subject`"):
+            EmailSummary.from_dict(data)
+
+
+class ListingShapeTest(unittest.TestCase):
+    def test_inbox_unread_and_search_share_the_summary_shape(self):
+        msg = load_fixture("types/message_metadata.json")
+        service = FakeGmailService({
+            "messages.list": {"messages": [{"id": msg["id"]}]},
+            "messages.get": msg,
+            "labels.get": {"messagesUnread": 1},
+        })
+        module = make_module(service)
+        emails = [module.dispatch(method, params)["emails"][0] for method, params in [
+            ("gmail.inbox", {}), ("gmail.unread", {}), ("gmail.search", {"query": "build"})]]
+        self.assertEqual(emails[1], emails[0])
+        self.assertEqual(emails[2], emails[0])
+        self.assertEqual(EmailSummary.from_dict(emails[0]).date, "2026-01-16T14:03:21Z")
+
+
+class AttachmentListingTest(unittest.TestCase):
+    ATTACHMENTS = [
+        {"filename": "invoice-2026-01.pdf", "mime_type": "application/pdf", "size": 48213,
+         "attachment_id": "ANGjdJ_inv"},
+        {"filename": "receipt.png", "mime_type": "image/png", "size": 9120, "attachment_id": "ANGjdJ_rcpt"},
+    ]
+
+    def setUp(self):
+        msg = load_fixture("types/message_with_attachments.json")
+        self.service = FakeGmailService({
+            "messages.list": {"messages": [{"id": msg["id"]}]},
+            "messages.get": msg,
+            "threads.get": {"id": msg["threadId"], "messages": [msg]},
+        })
+        self.module = make_module(self.service)
+
+    def test_opt_in(self):
+        email = self.module.dispatch("gmail.inbox", {})["emails"][0]
+        self.assertNotIn("has_attachments", email)
+        self.assertEqual(self.service.calls[-1][1]["format"], "metadata")
+
+        email = self.module.dispatch("gmail.search", {"query": "invoice", "include_attachments": True})["emails"][0]
+        self.assertEqual(self.service.calls[-1][1]["format"], "full")
+        self.assertEqual((email["has_attachments"], email["attachment_count"], email["attachments"]),
+                         (True, 2, self.ATTACHMENTS))
+        self.assertEqual(EmailSummary.from_dict(email).to_dict(), email)
+
+    def test_thread_and_message(self):
+        thread = self.module.dispatch("gmail.thread", {"thread_id": "18d2b0000000b002", "include_bodies": False,
+                                                        "include_attachments": True})
+        self.assertEqual(self.service.calls[-1][1]["format"], "full")
+        self.assertEqual(thread["messages"][0]["attachments"], self.ATTACHMENTS)
+        self.assertIsNone(thread["messages"][0]["body"])
+
+        message = self.module.dispatch("gmail.message", {"message_id": "18d2b0000000b002",
+                                                          "include_attachments": True})
+        self.assertEqual((message["attachments"], message["attachment_count"]), (self.ATTACHMENTS, 2))
+        with self.assertRaisesRegex(ValueError, "needs format full or raw"):
+            self.module.dispatch("gmail.message", {"message_id": "18d2b0000000b002", "format": "metadata",
+                                                   "include_attachments": True})
+
+
+class ThreadTest(unittest.TestCase):
+    def test_nested_replies(self):
+        thread = Thread.from_api(load_fixture("types/thread_nested_replies.json"))
+        self.assertEqual(thread.id, "18d2c0000000c001")
+        self.assertEqual(
+            [m.from_ for m in thread.messages],
+            ["Priya Shah <priya@example.com>", "Marco Ruiz <marco@example.com>", "Lee Park <lee@example.com>"],
+        )
+        self.assertEqual({m.thread_id for m in thread.messages}, {"18d2c0000000c001"})
+        self.assertEqual([m.unread for m in thread.messages], [False, False, True])
+
+        data = thread.to_dict()
+        self.assertEqual(data["count"], 3)
+        self.assertEqual(Thread.from_dict(data), thread)
+
+    def test_shuffled_messages_are_sorted(self):
+        thread = Thread.from_api(load_fixture("types/thread_shuffled.json"))
+        # e003 and e004 share an internalDate; the lower Gmail id goes first
+        self.assertEqual(
+            [m.id for m in thread.messages],
+            ["18d2e0000000e001", "18d2e0000000e002", "18d2e0000000e003", "18d2e0000000e004"],
+        )
+        self.assertEqual(
+            {m.id: m.parent_id for m in thread.messages},
+            {
+                "18d2e0000000e001": None,
+                "18d2e0000000e002": "18d2e0000000e001",
+                "18d2e0000000e003": "18d2e0000000e001",
+                "18d2e0000000e004": "18d2e0000000e002",
+            },
+        )
+        self.assertEqual(thread.gaps, [])
+        self.assertEqual(Thread.from_dict(thread.to_dict()), thread)
+
+    def test_gaps_from_missing_references(self):
+        thread = Thread.from_api(load_fixture("types/thread_gappy.json"))
+        self.assertEqual([m.id for m in thread.messages],
+                         ["18d2f0000000f001", "18d2f0000000f003", "18d2f0000000f004"])
+        self.assertEqual([gap.to_dict() for gap in thread.gaps], [{
+            "missing_message_id": "<offsite-2@example.com>",
+            "after": "18d2f0000000f001",
+            "before": "18d2f0000000f003",
+            "referenced_by": ["18d2f0000000f003", "18d2f0000000f004"],
+        }])
+        # The reply to the deleted message has no parent in the thread
+        self.assertIsNone(thread.messages[1].parent_id)
+        self.assertEqual(thread.messages[2].parent_id, "18d2f0000000f003")
+        self.assertEqual(Thread.from_dict(thread.to_dict()), thread)
+
+    def test_malformed_nested_message(self):
+        raw = load_fixture("types/thread_nested_replies.json")
+        broken = copy.deepcopy(raw)
+        broken["messages"][2]["labelIds"] = "INBOX"
+        with self.assertRaisesRegex(UnexpectedOutput, "field `labelIds` in message 18d2c0000000c003"):
+            Thread.from_api(broken)
+
+    def test_participants_are_deduplicated(self):
+        thread = Thread.from_api(load_fixture("types/thread_nested_replies.json"))
+        self.assertEqual([p.to_dict() for p in thread.participants], [
+            {"name": "Priya Shah", "email": "priya@example.com"},
+            {"name": "", "email": "team@example.com"},
+            {"name": "Marco Ruiz", "email": "marco@example.com"},
+            {"name": "Lee Park", "email": "lee@example.com"},
+        ])
+
+    def test_bodies_are_dequoted(self):
+        raw = load_fixture("types/thread_nested_replies.json")
+        reply = "Thursday works for me.\n\nOn Mon, Jan 19, 2026 Priya Shah wrote:\n> Can we move the launch review\n"
+        raw["messages"][1]["payload"]["body"] = {"data": base64.urlsafe_b64encode(reply.encode()).decode()}
+        thread = Thread.from_api(raw, include_bodies=True)
+        self.assertEqual([m.body for m in thread.messages], [None, "Thursday works for me.", None])
+        self.assertIsNone(Thread.from_api(raw).messages[1].body)
+        self.assertEqual(Thread.from_dict(thread.to_dict()), thread)
+
+    def test_max_messages_keeps_the_newest(self):
+        thread = Thread.from_api(load_fixture("types/thread_gappy.json"), max_messages=2)
+        self.assertEqual([m.id for m in thread.messages], ["18d2f0000000f003", "18d2f0000000f004"])
+        self.assertEqual((thread.total_count, thread.truncated), (3, True))
+        self.assertEqual(len(thread.gaps), 1)
+        self.assertEqual(thread.to_dict()["count"], 2)
+        self.assertFalse(Thread.from_api(load_fixture("types/thread_gappy.json"), max_messages=3).truncated)
+
+    def test_thread_method_fetches_bodies_by_default(self):
+        service = FakeGmailService({"threads.get": load_fixture("types/thread_nested_replies.json")})
+        module = make_module(service)
+        module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001", "include_bodies": False})
+        self.assertEqual([kw["format"] for _, kw in service.calls], ["full", "metadata"])
+
+    def test_missing_messages(self):
+        with self.assertRaisesRegex(UnexpectedOutput, "missing field `messages`"):
+            Thread.from_api({"id": "t1"})
+
+
+class LargeBodyTest(unittest.TestCase):
+    def setUp(self):
+        raw = load_fixture("types/thread_nested_replies.json")
+        body = "Thursday works for me. " * 100
+        raw["messages"][1]["payload"]["body"] = {"data": base64.urlsafe_b64encode(body.encode()).decode()}
+        self.service = FakeGmailService({"threads.get": raw, "messages.get": raw["messages"][1]})
+        self.module = make_module(self.service)
+
+    def test_truncate_body(self):
+        thread = self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001", "truncate_body": 22})
+        self.assertEqual([m["body"] for m in thread["messages"]], [None, "Thursday works for me.", None])
+        self.assertEqual([m["body_truncated"] for m in thread["messages"]], [False, True, False])
+        self.assertEqual(Thread.from_dict(thread).to_dict()["messages"], thread["messages"])
+        self.assertNotIn("body_truncated", self.module.dispatch("gmail.thread", {"thread_id": "x"})["messages"][1])
+
+        message = self.module.dispatch("gmail.message", {"message_id": "18d2c0000000c002", "truncate_body": 8})
+        self.assertEqual((message["body_text"], message["body_truncated"]), ("Thursday", True))
+        with self.assertRaisesRegex(ValueError, "truncate_body must be a positive integer"):
+            self.module.dispatch("gmail.message", {"message_id": "18d2c0000000c002", "truncate_body": True})
+
+    def test_oversized_response_fails(self):
+        self.module.max_response_bytes = 3000
+        with self.assertRaisesRegex(ValueError, "gmail.thread response exceeded max_response_bytes"):
+            self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        self.assertEqual(self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001",
+                                                                "truncate_body": 100})["count"], 3)
+        self.module.max_response_bytes = 0
+        self.assertEqual(self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})["count"], 3)
+
+    def test_oversized_response_is_not_cached(self):
+        self.module.max_response_bytes = 3000
+        with self.assertRaisesRegex(ValueError, "gmail.thread response exceeded max_response_bytes"):
+            self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        self.module.max_response_bytes = 0
+        self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        self.assertEqual([name for name, _ in self.service.calls], ["threads.get", "threads.get"])
+
+    def test_bodies_are_counted_as_they_are_decoded(self):
+        raw = self.service.handlers["threads.get"]
+        for msg in raw["messages"]:
+            msg["payload"]["body"] = raw["messages"][1]["payload"]["body"]
+        self.module.max_response_bytes = 3000
+        with mock.patch("gmail_lib.types.dequote", side_effect=lambda text: text) as dequote, \
+                self.assertRaisesRegex(ValueError, "gmail.thread response exceeded max_response_bytes"):
+            self.module.dispatch("gmail.thread", {"thread_id": "18d2c0000000c001"})
+        # The second body passed the limit, so the third was never decoded
+        self.assertEqual(dequote.call_count, 2)
+
+
+class SendResultTest(unittest.TestCase):
+    def test_from_api(self):
+        result = SendResult.from_api(load_fixture("types/send_result.json"))
+        self.assertEqual(result.to_dict(), {
+            "sent": True,
+            "message_id": "18d2d0000000d001",
+            "thread_id": "18d2d0000000d001",
+            "attachments": None,
+        })
+        self.assertEqual(SendResult.from_dict(result.to_dict()), result)
+
+    def test_missing_thread_id(self):
+        with self.assertRaisesRegex(UnexpectedOutput, "missing field `threadId`"):
+            SendResult.from_api({"id": "m1"})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,173 @@
+import json
+import shutil
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+
+from helpers import FakeGmailService, HttpError, make_module
+
+from gmail_lib.backend import ApiBackend
+from gmail_lib.watch import CommandSink, QueueSink, Watch
+
+
+def message(message_id, subject):
+    return {
+        "id": message_id,
+        "threadId": f"t-{message_id}",
+        "labelIds": ["INBOX", "UNREAD"],
+        "payload": {"headers": [{"name": "Subject", "value": subject}]},
+    }
+
+
+class ListSink:
+    kind = "list"
+    target = "memory"
+
+    def __init__(self):
+        self.events = []
+
+    def emit(self, event):
+        self.events.append(event)
+
+
+class MailboxFake:
+    """A mailbox whose history advances as messages arrive."""
+
+    def __init__(self):
+        self.history_id = 100
+        self.history = []
+        self.messages = {}
+        self.fail_next = None
+
+    def deliver(self, message_id, subject):
+        self.history_id += 1
+        self.messages[message_id] = message(message_id, subject)
+        self.history.append({"id": str(self.history_id),
+                             "messagesAdded": [{"message": {"id": message_id}}]})
+
+    def service(self):
+        def history_list(startHistoryId, **kwargs):
+            if self.fail_next:
+                error, self.fail_next = self.fail_next, None
+                raise error
+            records = [r for r in self.history if int(r["id"]) > int(startHistoryId)]
+            return {"history": records, "historyId": str(self.history_id)}
+
+        return FakeGmailService({
+            "getProfile": lambda **kwargs: {"historyId": str(self.history_id)},
+            "history.list": history_list,
+            "messages.get": lambda id, **kwargs: self.messages[id],
+        })
+
+
+def make_watch(mailbox, sink):
+    backend = ApiBackend(mailbox.service())
+    return Watch("default", lambda name, **kw: backend.call(name, userId="me", **kw), sink)
+
+
+class WatchTest(unittest.TestCase):
+    def test_only_mail_after_baseline_fires(self):
+        mailbox = MailboxFake()
+        mailbox.deliver("old", "Already here")
+        sink = ListSink()
+        watch = make_watch(mailbox, sink)
+
+        self.assertEqual(watch.poll_once(), 0)
+        mailbox.deliver("m1", "Hello")
+        mailbox.deliver("m2", "Again")
+        self.assertEqual(watch.poll_once(), 2)
+        self.assertEqual(watch.poll_once(), 0)
+
+        self.assertEqual([e["message"]["subject"] for e in sink.events], ["Hello", "Again"])
+        self.assertEqual(sink.events[0]["type"], "new_message")
+        self.assertEqual(watch.status()["events_fired"], 2)
+
+    def test_failures_are_counted_and_polling_continues(self):
+        mailbox = MailboxFake()
+        sink = ListSink()
+        watch = make_watch(mailbox, sink)
+        watch.poll_once()
+
+        mailbox.deliver("m1", "Hello")
+        mailbox.fail_next = RuntimeError("connection reset")
+        self.assertEqual(watch.poll_once(), 0)
+        self.assertEqual(watch.consecutive_failures, 1)
+        self.assertEqual(watch.last_error, "connection reset")
+
+        # Nothing is lost: the retry starts from the last processed history id
+        self.assertEqual(watch.poll_once(), 1)
+        self.assertEqual(watch.consecutive_failures, 0)
+
+    def test_expired_history_id_resyncs(self):
+        mailbox = MailboxFake()
+        watch = make_watch(mailbox, ListSink())
+        watch.poll_once()
+        mailbox.deliver("m1", "Hello")
+        mailbox.fail_next = HttpError(404)
+        self.assertEqual(watch.poll_once(), 0)
+        self.assertIsNone(watch.last_error)
+        self.assertEqual(watch.history_id, str(mailbox.history_id))
+
+    def test_queue_sink_writes_one_file_per_event(self):
+        directory = Path(tempfile.mkdtemp())
+        self.addCleanup(shutil.rmtree, directory)
+        mailbox = MailboxFake()
+        watch = make_watch(mailbox, QueueSink(directory / "default"))
+        watch.poll_once()
+        mailbox.deliver("m1", "Hello")
+        watch.poll_once()
+
+        files = list((directory / "default").iterdir())
+        self.assertEqual(len(files), 1)
+        self.assertTrue(files[0].name.endswith("-m1.json"))
+        self.assertEqual(json.loads(files[0].read_text())["message"]["id"], "m1")
+
+
+    def test_closing_a_command_sink_stops_its_command(self):
+        for command, grace in (("sleep 30", 5), ("trap '' TERM; sleep 30", 0.1)):
+            with self.subTest(command=command):
+                sink = CommandSink(command)
+                errors = []
+
+                def emit_event():
+                    try:
+                        sink.emit({})
+                    except RuntimeError as e:
+                        errors.append(e)
+
+                emit = threading.Thread(target=emit_event)
+                started = time.monotonic()
+                emit.start()
+                while sink._running is None:
+                    time.sleep(0.001)
+                time.sleep(0.05)  # let the shell set up its trap
+                sink.close(grace)
+                emit.join(5)
+                self.assertRegex(str(errors[0]), "command exited with -(15|9)")
+                self.assertLess(time.monotonic() - started, 5)
+                with self.assertRaisesRegex(RuntimeError, "closed"):
+                    sink.emit({})
+
+
+class ModuleWatchTest(unittest.TestCase):
+    def test_one_poller_per_account(self):
+        module = make_module(MailboxFake().service())
+        self.addCleanup(module.on_stop)
+
+        first = module.dispatch("gmail.watch", {"webhook": "https://example.com/hook", "interval": 3600})
+        second = module.dispatch("gmail.watch", {"interval": 3600})
+        self.assertTrue(first["started"])
+        self.assertFalse(second["started"])
+        self.assertEqual(second["sink"], "webhook")
+        self.assertTrue(module.dispatch("gmail.watch_status", {})["active"])
+        self.assertIn("watch.default", module.dispatch("gmail.health", {})["subsystems"])
+
+        self.assertTrue(module.dispatch("gmail.unwatch", {})["stopped"])
+        self.assertFalse(module.dispatch("gmail.watch_status", {})["active"])
+        self.assertFalse(module.dispatch("gmail.unwatch", {})["stopped"])
+
+
+if __name__ == "__main__":
+    unittest.main()